@@ -192,8 +192,19 @@ impl CanvasRenderingContext2D {
         let paint = self.current_state.resolve_paint(&self.current_state.stroke_paint);
         let paint_id = self.scene.push_paint(&paint);
 
+        let (mut outline, stroke_style) = self.prepare_stroke(path);
+        let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, stroke_style);
+        stroke_to_fill.offset();
+        outline = stroke_to_fill.into_outline();
+
+        outline.transform(&self.current_state.transform);
+        self.push_path(outline, paint_id, FillRule::Winding);
+    }
+
+    // Returns the dashed outline of the path and the style to stroke it with, untransformed.
+    fn prepare_stroke(&self, path: Path2D) -> (Outline, StrokeStyle) {
         let mut stroke_style = self.current_state.resolve_stroke_style();
-        
+
         // The smaller scale is relevant here, as we multiply by it and want to ensure it is always
         // bigger than `HAIRLINE_STROKE_WIDTH`.
         let transform_scale = f32::min(self.current_state.transform.m11(),
@@ -212,12 +223,7 @@ impl CanvasRenderingContext2D {
             outline = dash.into_outline();
         }
 
-        let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, stroke_style);
-        stroke_to_fill.offset();
-        outline = stroke_to_fill.into_outline();
-
-        outline.transform(&self.current_state.transform);
-        self.push_path(outline, paint_id, FillRule::Winding);
+        (outline, stroke_style)
     }
 
     pub fn clip_path(&mut self, path: Path2D, fill_rule: FillRule) {
@@ -230,6 +236,25 @@ impl CanvasRenderingContext2D {
         self.current_state.clip_path = Some(clip_path_id);
     }
 
+    // Hit testing
+
+    /// Returns true if `point`, in canvas coordinates, would be filled by `fill_path()` with this
+    /// path under the current transform.
+    pub fn is_point_in_path(&self, path: &Path2D, point: Vector2F, fill_rule: FillRule) -> bool {
+        let mut outline = path.clone().into_outline();
+        outline.transform(&self.current_state.transform);
+        outline.contains_point(point, fill_rule)
+    }
+
+    /// Returns true if `point`, in canvas coordinates, would be covered by `stroke_path()` with
+    /// this path under the current transform and line styles.
+    pub fn is_point_in_stroke(&self, path: &Path2D, point: Vector2F) -> bool {
+        // The stroke is built before the transform is applied, so test it in the same space.
+        let point = self.current_state.transform.inverse() * point;
+        let (outline, stroke_style) = self.prepare_stroke(path.clone());
+        outline.stroke_contains_point(point, stroke_style)
+    }
+
     fn push_path(&mut self, outline: Outline, paint_id: PaintId, fill_rule: FillRule) {
         let clip_path = self.current_state.clip_path;
         let blend_mode = self.current_state.global_composite_operation.to_blend_mode();
@@ -0,0 +1,765 @@
+// pathfinder/content/src/boolean.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Boolean operations on outlines.
+//!
+//! Curves are flattened in order to find where the outlines cross, and the edges that bound the
+//! result are selected by their winding numbers. The surviving pieces of each curve are then
+//! mapped back onto the original curve, so that the result keeps its lines, quadratics and cubics.
+//!
+//! The result has no overlaps and is oriented consistently, so it can be filled with either fill
+//! rule. To remove the overlaps from a single outline, such as a stroke, take its union with an
+//! empty outline.
+
+use crate::fill::FillRule;
+use crate::outline::{Contour, Outline};
+use crate::segment::Segment;
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::util::lerp;
+use pathfinder_geometry::vector::Vector2F;
+use std::collections::HashMap;
+
+// The maximum distance between a curve and the polyline it's flattened to while searching for
+// intersections.
+const FLATTENING_TOLERANCE: f32 = 0.01;
+const MAX_FLATTENING_STEPS: u32 = 256;
+
+// Intersections this close to the end of an edge, as a fraction of its length, are snapped to it.
+const PARAMETER_EPSILON: f32 = 0.00001;
+// Points this close together are treated as the same vertex.
+const SNAP_DISTANCE: f32 = 0.0001;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BooleanOp {
+    /// Areas inside either outline.
+    Union,
+    /// Areas inside both outlines.
+    Intersection,
+    /// Areas inside the first outline but not the second.
+    Difference,
+    /// Areas inside exactly one of the outlines.
+    Xor,
+}
+
+impl BooleanOp {
+    fn includes(self, in_first: bool, in_second: bool) -> bool {
+        match self {
+            BooleanOp::Union => in_first || in_second,
+            BooleanOp::Intersection => in_first && in_second,
+            BooleanOp::Difference => in_first && !in_second,
+            BooleanOp::Xor => in_first != in_second,
+        }
+    }
+}
+
+pub struct OutlineBoolean<'a> {
+    operands: [(&'a Outline, FillRule); 2],
+    op: BooleanOp,
+    output: Outline,
+}
+
+impl<'a> OutlineBoolean<'a> {
+    #[inline]
+    pub fn new(first: &'a Outline,
+               first_fill_rule: FillRule,
+               second: &'a Outline,
+               second_fill_rule: FillRule,
+               op: BooleanOp)
+               -> OutlineBoolean<'a> {
+        OutlineBoolean {
+            operands: [(first, first_fill_rule), (second, second_fill_rule)],
+            op,
+            output: Outline::new(),
+        }
+    }
+
+    pub fn combine(&mut self) {
+        let mut graph = EdgeGraph::new();
+        for (operand, &(outline, _)) in self.operands.iter().enumerate() {
+            graph.add_outline(outline, operand);
+        }
+        graph.split_at_intersections();
+
+        let fill_rules = [self.operands[0].1, self.operands[1].1];
+        let boundary = graph.into_boundary(self.op, fill_rules);
+        self.output = boundary.into_outline();
+    }
+
+    #[inline]
+    pub fn into_outline(self) -> Outline {
+        self.output
+    }
+}
+
+// The part of one of the original segments between two times, which may run backward.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Piece {
+    source: u32,
+    from_t: f32,
+    to_t: f32,
+}
+
+impl Piece {
+    #[inline]
+    fn reversed(self) -> Piece {
+        Piece { source: self.source, from_t: self.to_t, to_t: self.from_t }
+    }
+
+    #[inline]
+    fn continues_with(self, next: Piece) -> bool {
+        self.source == next.source && self.to_t == next.from_t
+    }
+}
+
+// A flattened edge, along with the points where other edges cross it.
+struct FlatEdge {
+    line: LineSegment2F,
+    operand: usize,
+    piece: Piece,
+    splits: Vec<(f32, Vector2F)>,
+}
+
+struct EdgeGraph {
+    sources: Vec<Segment>,
+    edges: Vec<FlatEdge>,
+}
+
+impl EdgeGraph {
+    fn new() -> EdgeGraph {
+        EdgeGraph { sources: vec![], edges: vec![] }
+    }
+
+    // Open contours are treated as closed, as they are when filled.
+    fn add_outline(&mut self, outline: &Outline, operand: usize) {
+        for contour in outline.contours() {
+            for segment in contour.iter() {
+                self.add_segment(segment, operand);
+            }
+            if !contour.is_closed() && !contour.is_empty() {
+                let first = contour.position_of(0);
+                let last = contour.position_of(contour.len() - 1);
+                self.add_segment(Segment::line(LineSegment2F::new(last, first)), operand);
+            }
+        }
+    }
+
+    fn add_segment(&mut self, segment: Segment, operand: usize) {
+        if segment.is_none() {
+            return;
+        }
+
+        let source = self.sources.len() as u32;
+        self.sources.push(segment);
+
+        let step_count = segment.flattening_step_count(FLATTENING_TOLERANCE)
+                                .min(MAX_FLATTENING_STEPS);
+        let mut from = segment.baseline.from();
+        for step in 0..step_count {
+            let (from_t, to_t) = (step as f32 / step_count as f32,
+                                  (step + 1) as f32 / step_count as f32);
+            let to = if step + 1 == step_count {
+                segment.baseline.to()
+            } else {
+                sample(&segment, to_t)
+            };
+            if from != to {
+                self.edges.push(FlatEdge {
+                    line: LineSegment2F::new(from, to),
+                    operand,
+                    piece: Piece { source, from_t, to_t },
+                    splits: vec![],
+                });
+            }
+            from = to;
+        }
+    }
+
+    // Finds every pair of edges that cross or overlap, sweeping from left to right.
+    fn split_at_intersections(&mut self) {
+        let mut order: Vec<usize> = (0..self.edges.len()).collect();
+        order.sort_by(|&a, &b| {
+            self.edges[a].line.min_x().partial_cmp(&self.edges[b].line.min_x()).unwrap()
+        });
+
+        for (order_index, &index) in order.iter().enumerate() {
+            let line = self.edges[index].line;
+            for &other_index in &order[(order_index + 1)..] {
+                let other_line = self.edges[other_index].line;
+                if other_line.min_x() > line.max_x() {
+                    break;
+                }
+                if other_line.min_y() > line.max_y() || other_line.max_y() < line.min_y() {
+                    continue;
+                }
+                self.intersect(index, other_index);
+            }
+        }
+    }
+
+    fn intersect(&mut self, index: usize, other_index: usize) {
+        let (line, other_line) = (self.edges[index].line, self.edges[other_index].line);
+        let (p, r) = (line.from(), line.vector());
+        let (q, s) = (other_line.from(), other_line.vector());
+
+        let denominator = r.det(s);
+        if denominator.abs() <= PARAMETER_EPSILON * r.length() * s.length() {
+            // Parallel edges only matter if they're collinear, in which case each one is split
+            // where the other one ends.
+            if (q - p).det(r).abs() > SNAP_DISTANCE * r.length() {
+                return;
+            }
+            for &point in &[q, q + s] {
+                let t = (point - p).dot(r) / r.square_length();
+                self.push_split(index, t, point);
+            }
+            for &point in &[p, p + r] {
+                let u = (point - q).dot(s) / s.square_length();
+                self.push_split(other_index, u, point);
+            }
+            return;
+        }
+
+        let t = (q - p).det(s) / denominator;
+        let u = (q - p).det(r) / denominator;
+        let range = -PARAMETER_EPSILON..=(1.0 + PARAMETER_EPSILON);
+        if !range.contains(&t) || !range.contains(&u) {
+            return;
+        }
+
+        // If the intersection is at the end of either edge, use that exact point for both.
+        let point = if t <= PARAMETER_EPSILON {
+            p
+        } else if t >= 1.0 - PARAMETER_EPSILON {
+            p + r
+        } else if u <= PARAMETER_EPSILON {
+            q
+        } else if u >= 1.0 - PARAMETER_EPSILON {
+            q + s
+        } else {
+            p + r.scale(t)
+        };
+        self.push_split(index, t, point);
+        self.push_split(other_index, u, point);
+    }
+
+    fn push_split(&mut self, index: usize, t: f32, point: Vector2F) {
+        if t > PARAMETER_EPSILON && t < 1.0 - PARAMETER_EPSILON {
+            self.edges[index].splits.push((t, point));
+        }
+    }
+
+    // Merges the split edges into groups that connect the same two vertices, and keeps those
+    // that separate the inside of the result from the outside.
+    fn into_boundary(self, op: BooleanOp, fill_rules: [FillRule; 2]) -> Boundary {
+        let mut vertices = VertexTable::new();
+        let mut groups: Vec<EdgeGroup> = vec![];
+        let mut group_indices: HashMap<(u32, u32), usize> = HashMap::new();
+
+        for mut edge in self.edges {
+            edge.splits.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            let mut points = vec![(0.0, edge.line.from())];
+            points.extend(edge.splits.iter().cloned());
+            points.push((1.0, edge.line.to()));
+
+            for pair in points.windows(2) {
+                let ((from_t, from_point), (to_t, to_point)) = (pair[0], pair[1]);
+                let (from, to) = (vertices.get_or_insert(from_point),
+                                  vertices.get_or_insert(to_point));
+                if from == to {
+                    continue;
+                }
+
+                let piece = Piece {
+                    source: edge.piece.source,
+                    from_t: lerp(edge.piece.from_t, edge.piece.to_t, from_t),
+                    to_t: lerp(edge.piece.from_t, edge.piece.to_t, to_t),
+                };
+
+                // Groups always run from their lower-numbered vertex.
+                let (key, direction, piece) = if from < to {
+                    ((from, to), 1, piece)
+                } else {
+                    ((to, from), -1, piece.reversed())
+                };
+                let group_index = *group_indices.entry(key).or_insert_with(|| {
+                    groups.push(EdgeGroup {
+                        from: key.0,
+                        to: key.1,
+                        windings: [0; 2],
+                        pieces: vec![],
+                    });
+                    groups.len() - 1
+                });
+                let group = &mut groups[group_index];
+                group.windings[edge.operand] += direction;
+                group.pieces.push(piece);
+            }
+        }
+
+        // Edges that cancel each other out don't affect winding numbers.
+        groups.retain(|group| group.windings != [0, 0]);
+
+        let lines: Vec<_> = groups.iter().map(|group| {
+            LineSegment2F::new(vertices.positions[group.from as usize],
+                               vertices.positions[group.to as usize])
+        }).collect();
+        let index = WindingIndex::new(&lines);
+
+        let mut edges = vec![];
+        for (group_index, group) in groups.iter().enumerate() {
+            let line = lines[group_index];
+            let axis = Axis::for_line(line);
+            let midpoint = line.midpoint();
+
+            // The winding numbers on the side of the group that the ray points toward don't
+            // include the group itself, while those on the other side do.
+            let mut ray_side = [0; 2];
+            for other_index in index.candidates(axis, midpoint) {
+                if other_index == group_index {
+                    continue;
+                }
+                let crossing = axis.crossing(lines[other_index], midpoint);
+                if crossing != 0 {
+                    for (winding, other_winding) in
+                            ray_side.iter_mut().zip(groups[other_index].windings.iter()) {
+                        *winding += crossing * other_winding;
+                    }
+                }
+            }
+            let crossing = axis.crossing_from_behind(line);
+            let other_side = [ray_side[0] + crossing * group.windings[0],
+                              ray_side[1] + crossing * group.windings[1]];
+
+            let is_inside = |windings: [i32; 2]| {
+                op.includes(fill_rules[0].includes_winding_number(windings[0]),
+                            fill_rules[1].includes_winding_number(windings[1]))
+            };
+            let (inside_on_ray_side, inside_on_other_side) =
+                (is_inside(ray_side), is_inside(other_side));
+            if inside_on_ray_side == inside_on_other_side {
+                continue;
+            }
+
+            // Orient the edge so that the inside has a winding number one greater than the
+            // outside. Only edges that come from a single piece of a curve remember it.
+            let forward = inside_on_other_side == (crossing > 0);
+            let piece = if group.pieces.len() == 1 { Some(group.pieces[0]) } else { None };
+            edges.push(if forward {
+                DirectedEdge { from: group.from, to: group.to, piece }
+            } else {
+                DirectedEdge { from: group.to, to: group.from, piece: piece.map(Piece::reversed) }
+            });
+        }
+
+        Boundary { sources: self.sources, positions: vertices.positions, edges }
+    }
+}
+
+// Flattened edges that connect the same two vertices. `windings` counts the edges from each
+// operand that run forward, minus those that run backward.
+struct EdgeGroup {
+    from: u32,
+    to: u32,
+    windings: [i32; 2],
+    pieces: Vec<Piece>,
+}
+
+struct VertexTable {
+    positions: Vec<Vector2F>,
+    indices: HashMap<(i64, i64), u32>,
+}
+
+impl VertexTable {
+    fn new() -> VertexTable {
+        VertexTable { positions: vec![], indices: HashMap::new() }
+    }
+
+    fn get_or_insert(&mut self, point: Vector2F) -> u32 {
+        let key = ((point.x() / SNAP_DISTANCE).round() as i64,
+                   (point.y() / SNAP_DISTANCE).round() as i64);
+        let positions = &mut self.positions;
+        *self.indices.entry(key).or_insert_with(|| {
+            positions.push(point);
+            positions.len() as u32 - 1
+        })
+    }
+}
+
+// The direction of the ray that winding numbers are measured along. Rays run perpendicular to
+// the edge as closely as possible, so that the sides of the edge are easy to tell apart.
+#[derive(Clone, Copy, PartialEq)]
+enum Axis {
+    // Along +x.
+    Horizontal,
+    // Along +y.
+    Vertical,
+}
+
+impl Axis {
+    fn for_line(line: LineSegment2F) -> Axis {
+        let vector = line.vector();
+        if vector.y().abs() >= vector.x().abs() {
+            Axis::Horizontal
+        } else {
+            Axis::Vertical
+        }
+    }
+
+    // Returns the contribution of `line` to the winding number of `point`, measured along a ray
+    // from the point. Lines that end exactly on the ray count on one side only.
+    fn crossing(self, line: LineSegment2F, point: Vector2F) -> i32 {
+        let (from, to) = (line.from(), line.to());
+        match self {
+            Axis::Horizontal => {
+                if (from.y() <= point.y()) == (to.y() <= point.y()) {
+                    return 0;
+                }
+                let x = from.x() + (point.y() - from.y()) * (to.x() - from.x()) /
+                    (to.y() - from.y());
+                if x > point.x() { self.crossing_from_behind(line) } else { 0 }
+            }
+            Axis::Vertical => {
+                if (from.x() <= point.x()) == (to.x() <= point.x()) {
+                    return 0;
+                }
+                let y = from.y() + (point.x() - from.x()) * (to.y() - from.y()) /
+                    (to.x() - from.x());
+                if y > point.y() { self.crossing_from_behind(line) } else { 0 }
+            }
+        }
+    }
+
+    // Returns the contribution of `line` to the winding number of a point just behind it, whose
+    // ray crosses it.
+    fn crossing_from_behind(self, line: LineSegment2F) -> i32 {
+        let vector = line.vector();
+        match self {
+            Axis::Horizontal => if vector.y() > 0.0 { 1 } else { -1 },
+            Axis::Vertical => if vector.x() > 0.0 { -1 } else { 1 },
+        }
+    }
+}
+
+// Buckets lines into bands across each axis, so that only the lines in the band of a point need
+// to be checked against its ray.
+struct WindingIndex {
+    bands: [Bands; 2],
+}
+
+struct Bands {
+    min: f32,
+    band_size: f32,
+    lines: Vec<Vec<usize>>,
+}
+
+impl WindingIndex {
+    fn new(lines: &[LineSegment2F]) -> WindingIndex {
+        let band_count = ((lines.len() as f32).sqrt().ceil() as usize).clamp(1, 1024);
+        WindingIndex {
+            bands: [
+                Bands::new(lines, band_count, |line| (line.min_y(), line.max_y())),
+                Bands::new(lines, band_count, |line| (line.min_x(), line.max_x())),
+            ],
+        }
+    }
+
+    fn candidates(&self, axis: Axis, point: Vector2F) -> impl Iterator<Item = usize> + '_ {
+        match axis {
+            Axis::Horizontal => self.bands[0].lines_at(point.y()),
+            Axis::Vertical => self.bands[1].lines_at(point.x()),
+        }.iter().cloned()
+    }
+}
+
+impl Bands {
+    fn new<F>(lines: &[LineSegment2F], band_count: usize, range: F) -> Bands
+              where F: Fn(LineSegment2F) -> (f32, f32) {
+        let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+        for &line in lines {
+            let (line_min, line_max) = range(line);
+            min = min.min(line_min);
+            max = max.max(line_max);
+        }
+        let band_size = ((max - min) / band_count as f32).max(SNAP_DISTANCE);
+
+        let mut bands = Bands { min, band_size, lines: vec![vec![]; band_count] };
+        for (index, &line) in lines.iter().enumerate() {
+            let (line_min, line_max) = range(line);
+            for band in bands.band_of(line_min)..=bands.band_of(line_max) {
+                bands.lines[band].push(index);
+            }
+        }
+        bands
+    }
+
+    fn band_of(&self, value: f32) -> usize {
+        let band = ((value - self.min) / self.band_size).floor();
+        (band.max(0.0) as usize).min(self.lines.len() - 1)
+    }
+
+    fn lines_at(&self, value: f32) -> &[usize] {
+        if self.lines.is_empty() { &[] } else { &self.lines[self.band_of(value)] }
+    }
+}
+
+struct DirectedEdge {
+    from: u32,
+    to: u32,
+    piece: Option<Piece>,
+}
+
+// The oriented edges of the result, which are linked up into contours.
+struct Boundary {
+    sources: Vec<Segment>,
+    positions: Vec<Vector2F>,
+    edges: Vec<DirectedEdge>,
+}
+
+impl Boundary {
+    fn into_outline(self) -> Outline {
+        let mut outgoing = vec![vec![]; self.positions.len()];
+        for (index, edge) in self.edges.iter().enumerate() {
+            outgoing[edge.from as usize].push(index);
+        }
+
+        let mut used = vec![false; self.edges.len()];
+        let mut outline = Outline::new();
+        for start_index in 0..self.edges.len() {
+            if used[start_index] {
+                continue;
+            }
+
+            // Where contours touch at a vertex, turn as sharply as possible so that they stay
+            // separate.
+            let mut contour_edges = vec![start_index];
+            used[start_index] = true;
+            let mut current = start_index;
+            while self.edges[current].to != self.edges[start_index].from {
+                let vector = self.edge_vector(current);
+                let candidates = outgoing[self.edges[current].to as usize].iter().cloned();
+                let next = candidates.filter(|&index| !used[index]).min_by(|&a, &b| {
+                    let turn = |index| {
+                        let next_vector = self.edge_vector(index);
+                        vector.det(next_vector).atan2(vector.dot(next_vector))
+                    };
+                    turn(a).partial_cmp(&turn(b)).unwrap()
+                });
+                match next {
+                    None => break,
+                    Some(next) => {
+                        used[next] = true;
+                        contour_edges.push(next);
+                        current = next;
+                    }
+                }
+            }
+
+            outline.push_contour(self.build_contour(contour_edges));
+        }
+        outline
+    }
+
+    fn edge_vector(&self, index: usize) -> Vector2F {
+        let edge = &self.edges[index];
+        self.positions[edge.to as usize] - self.positions[edge.from as usize]
+    }
+
+    // Merges runs of edges that come from consecutive pieces of the same curve back into one
+    // segment.
+    fn build_contour(&self, mut edges: Vec<usize>) -> Contour {
+        let continues = |from: usize, to: usize| {
+            match (self.edges[from].piece, self.edges[to].piece) {
+                (Some(from), Some(to)) => from.continues_with(to),
+                _ => false,
+            }
+        };
+
+        // Start at the beginning of a run.
+        if let Some(start) = (0..edges.len()).find(|&index| {
+            !continues(edges[(index + edges.len() - 1) % edges.len()], edges[index])
+        }) {
+            edges.rotate_left(start);
+        }
+
+        let mut contour = Contour::new();
+        contour.push_endpoint(self.positions[self.edges[edges[0]].from as usize]);
+
+        let mut run_start = 0;
+        for index in 0..edges.len() {
+            if index + 1 < edges.len() && continues(edges[index], edges[index + 1]) {
+                continue;
+            }
+
+            let (first, last) = (&self.edges[edges[run_start]], &self.edges[edges[index]]);
+            let to = self.positions[last.to as usize];
+            match (first.piece, last.piece) {
+                (Some(first_piece), Some(last_piece)) if
+                        !self.sources[first_piece.source as usize].is_line() => {
+                    let source = &self.sources[first_piece.source as usize];
+                    push_curve_piece(&mut contour,
+                                     source,
+                                     first_piece.from_t,
+                                     last_piece.to_t,
+                                     to);
+                }
+                // Closing the contour adds the last line.
+                _ if index + 1 == edges.len() => {}
+                _ => contour.push_endpoint(to),
+            }
+            run_start = index + 1;
+        }
+
+        contour.close();
+        contour
+    }
+}
+
+// Appends the part of the curve `source` between two times, which may run backward, ending at
+// `to`.
+fn push_curve_piece(contour: &mut Contour,
+                    source: &Segment,
+                    from_t: f32,
+                    to_t: f32,
+                    to: Vector2F) {
+    if source.is_quadratic() {
+        let points = [source.baseline.from(), source.ctrl.from(), source.baseline.to()];
+        contour.push_quadratic(blossom_quadratic(&points, from_t, to_t), to);
+    } else {
+        let points = [source.baseline.from(), source.ctrl.from(), source.ctrl.to(),
+                      source.baseline.to()];
+        contour.push_cubic(blossom_cubic(&points, from_t, from_t, to_t),
+                           blossom_cubic(&points, from_t, to_t, to_t),
+                           to);
+    }
+}
+
+fn sample(segment: &Segment, t: f32) -> Vector2F {
+    if segment.is_quadratic() {
+        let points = [segment.baseline.from(), segment.ctrl.from(), segment.baseline.to()];
+        blossom_quadratic(&points, t, t)
+    } else if segment.is_cubic() {
+        let points = [segment.baseline.from(), segment.ctrl.from(), segment.ctrl.to(),
+                      segment.baseline.to()];
+        blossom_cubic(&points, t, t, t)
+    } else {
+        segment.baseline.sample(t)
+    }
+}
+
+// Blossoms give the control points of the part of a curve between two times directly: the
+// quadratic between `u` and `v` has the control point `b(u, v)`, and the cubic has `b(u, u, v)`
+// and `b(u, v, v)`.
+fn blossom_quadratic(points: &[Vector2F; 3], u: f32, v: f32) -> Vector2F {
+    let (p0, p1) = (points[0].lerp(points[1], u), points[1].lerp(points[2], u));
+    p0.lerp(p1, v)
+}
+
+fn blossom_cubic(points: &[Vector2F; 4], u: f32, v: f32, w: f32) -> Vector2F {
+    let (p0, p1, p2) = (points[0].lerp(points[1], u),
+                        points[1].lerp(points[2], u),
+                        points[2].lerp(points[3], u));
+    let (p0, p1) = (p0.lerp(p1, v), p1.lerp(p2, v));
+    p0.lerp(p1, w)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fill::FillRule;
+    use crate::outline::{Contour, Outline};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::Vector2F;
+    use super::{BooleanOp, OutlineBoolean};
+
+    fn rect(origin: Vector2F, size: Vector2F) -> Outline {
+        Outline::from_rect(RectF::new(origin, size))
+    }
+
+    fn circle(center: Vector2F, radius: f32) -> Outline {
+        let mut contour = Contour::new();
+        let transform = Transform2F::from_translation(center) *
+            Transform2F::from_scale(Vector2F::splat(radius));
+        contour.push_ellipse(&transform);
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        outline
+    }
+
+    fn combine(first: &Outline, second: &Outline, op: BooleanOp) -> Outline {
+        let mut boolean = OutlineBoolean::new(first, FillRule::Winding, second, FillRule::Winding,
+                                              op);
+        boolean.combine();
+        boolean.into_outline()
+    }
+
+    // The signed area, measured on a fine polyline.
+    fn area(outline: &Outline) -> f32 {
+        let mut area = 0.0;
+        for contour in outline.contours() {
+            for segment in contour.iter() {
+                let mut from = segment.baseline.from();
+                for step in 1..=64 {
+                    let to = segment.sample(step as f32 / 64.0);
+                    area += from.det(to) * 0.5;
+                    from = to;
+                }
+            }
+        }
+        area
+    }
+
+    #[test]
+    fn test_rect_operations() {
+        let first = rect(Vector2F::new(0.0, 0.0), Vector2F::splat(10.0));
+        let second = rect(Vector2F::new(5.0, 5.0), Vector2F::splat(10.0));
+
+        let expected = [
+            (BooleanOp::Union, 175.0),
+            (BooleanOp::Intersection, 25.0),
+            (BooleanOp::Difference, 75.0),
+            (BooleanOp::Xor, 150.0),
+        ];
+        for &(op, expected_area) in &expected {
+            let result = combine(&first, &second, op);
+            assert!((area(&result).abs() - expected_area).abs() < 0.01,
+                    "{:?}: {}", op, area(&result));
+        }
+
+        // Rectangles stay rectangles.
+        let union = combine(&first, &second, BooleanOp::Union);
+        assert_eq!(union.contours().len(), 1);
+        assert!(union.contours()[0].iter().all(|segment| segment.is_line()));
+    }
+
+    #[test]
+    fn test_curves_are_preserved() {
+        let first = circle(Vector2F::new(0.0, 0.0), 10.0);
+        let second = rect(Vector2F::new(0.0, -20.0), Vector2F::splat(40.0));
+
+        // Half of the circle is left. Its arcs start at 45°, so it's made of three pieces of them
+        // and a line.
+        let result = combine(&first, &second, BooleanOp::Difference);
+        let expected_area = std::f32::consts::PI * 100.0 * 0.5;
+        assert!((area(&result).abs() - expected_area).abs() < 0.1, "{}", area(&result));
+        let segments: Vec<_> = result.contours()[0].iter().collect();
+        assert_eq!(segments.len(), 4);
+        assert_eq!(segments.iter().filter(|segment| segment.is_cubic()).count(), 3);
+    }
+
+    #[test]
+    fn test_remove_overlaps() {
+        let mut outline = rect(Vector2F::new(0.0, 0.0), Vector2F::splat(10.0));
+        outline.push_contour(rect(Vector2F::new(5.0, 0.0), Vector2F::splat(10.0)).contours()[0]
+                                                                               .clone());
+        let result = combine(&outline, &Outline::new(), BooleanOp::Union);
+        assert_eq!(result.contours().len(), 1);
+        assert!((area(&result).abs() - 150.0).abs() < 0.01);
+    }
+}
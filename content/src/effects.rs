@@ -10,6 +10,7 @@
 
 //! Special effects that can be applied to layers.
 
+use crate::pattern::RenderTargetId;
 use pathfinder_color::ColorF;
 
 /// This intentionally does not precisely match what Core Graphics does (a
@@ -40,6 +41,16 @@ pub struct Effects {
 pub enum Filter {
     /// A compositing operation.
     Composite(CompositeOp),    
+    /// Multiplies this layer by a constant opacity and, optionally, by a mask, and then composites
+    /// it with source-over.
+    Opacity {
+        /// The constant opacity, from 0.0 to 1.0.
+        opacity: f32,
+        /// The render target that supplies the mask, if any.
+        mask: Option<RenderTargetId>,
+        /// How coverage is derived from the contents of the mask.
+        mask_mode: MaskMode,
+    },
     /// Performs postprocessing operations useful for monochrome text.
     Text {
         /// The foreground color of the text.
@@ -75,6 +86,20 @@ pub enum BlendMode {
     Darken,
 }
 
+/// How a mask layer is converted to coverage.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MaskMode {
+    /// Coverage is the luminance of the mask, multiplied by its alpha.
+    Luminance,
+    /// Coverage is the alpha of the mask.
+    Alpha,
+    /// Coverage is one minus the luminance of the mask multiplied by its alpha, so transparent
+    /// areas of the mask are fully covered.
+    InvertedLuminance,
+    /// Coverage is one minus the alpha of the mask.
+    InvertedAlpha,
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct DefringingKernel(pub [f32; 4]);
 
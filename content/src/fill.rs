@@ -15,3 +15,14 @@ pub enum FillRule {
     Winding,
     EvenOdd,
 }
+
+impl FillRule {
+    /// Returns true if a point with the given winding number is filled under this rule.
+    #[inline]
+    pub fn includes_winding_number(self, winding_number: i32) -> bool {
+        match self {
+            FillRule::Winding => winding_number != 0,
+            FillRule::EvenOdd => winding_number % 2 != 0,
+        }
+    }
+}
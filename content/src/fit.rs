@@ -0,0 +1,310 @@
+// pathfinder/content/src/fit.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Fitting of cubic Bézier curves to sequences of points.
+//!
+//! This follows Philip J. Schneider, "An Algorithm for Automatically Fitting Digitized Curves",
+//! Graphics Gems, 1990. The points are first split at corners, and each run between corners is
+//! fitted with a single curve, which is split at the point of greatest error until every point is
+//! close enough.
+
+use crate::outline::Contour;
+use crate::segment::Segment;
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::vector::Vector2F;
+
+// Points where the path turns by more than 60° are corners, where the curves don't have to be
+// smooth.
+const CORNER_ANGLE_COSINE: f32 = 0.5;
+
+const MAX_REPARAMETERIZATION_ITERATIONS: u32 = 4;
+// Fits whose error is within this multiple of the maximum are improved before being split.
+const REPARAMETERIZATION_ERROR_FACTOR: f32 = 4.0;
+
+/// Fits lines and cubic curves through `points`, such that each point is within `max_error` of
+/// the result.
+///
+/// If `closed` is true, the result is closed, and the first point also follows the last.
+pub fn fit_cubics(points: &[Vector2F], max_error: f32, closed: bool) -> Contour {
+    let mut points: Vec<Vector2F> = points.to_vec();
+    points.dedup();
+    if closed && points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+
+    let mut contour = Contour::new();
+    if points.len() < 2 {
+        if let Some(&point) = points.first() {
+            contour.push_endpoint(point);
+        }
+        if closed {
+            contour.close();
+        }
+        return contour;
+    }
+
+    let mut corners = find_corners(&points, closed);
+    if closed {
+        // Start at a corner if there is one, and return to the start.
+        let start = corners.first().cloned().unwrap_or(0);
+        points.rotate_left(start);
+        for corner in &mut corners {
+            *corner -= start;
+        }
+        if corners.first() != Some(&0) {
+            corners.insert(0, 0);
+        }
+        points.push(points[0]);
+    } else {
+        corners.insert(0, 0);
+    }
+    corners.push(points.len() - 1);
+
+    let mut fitter = CurveFitter { contour: &mut contour, max_error };
+    fitter.contour.push_endpoint(points[0]);
+    for run in corners.windows(2) {
+        let run_points = &points[run[0]..=run[1]];
+        let start_tangent = (run_points[1] - run_points[0]).normalize();
+        let end_tangent = (run_points[run_points.len() - 2] - run_points[run_points.len() - 1])
+            .normalize();
+        fitter.fit(run_points, start_tangent, end_tangent);
+    }
+
+    if closed {
+        // Closing the contour adds the last line back.
+        let last_index = contour.len() - 1;
+        if contour.position_of(last_index) == contour.position_of(0) &&
+                contour.point_is_endpoint(last_index - 1) {
+            contour.points.pop();
+            contour.flags.pop();
+        }
+        contour.close();
+    }
+    contour
+}
+
+fn find_corners(points: &[Vector2F], closed: bool) -> Vec<usize> {
+    let point_count = points.len();
+    let indices = if closed { 0..point_count } else { 1..(point_count - 1) };
+    indices.filter(|&index| {
+        let prev = points[(index + point_count - 1) % point_count];
+        let next = points[(index + 1) % point_count];
+        let (incoming, outgoing) = (points[index] - prev, next - points[index]);
+        let lengths = incoming.length() * outgoing.length();
+        lengths > 0.0 && incoming.dot(outgoing) < CORNER_ANGLE_COSINE * lengths
+    }).collect()
+}
+
+struct CurveFitter<'a> {
+    contour: &'a mut Contour,
+    max_error: f32,
+}
+
+impl<'a> CurveFitter<'a> {
+    // Appends curves through `points`, starting from the last point of the contour. The start
+    // tangent points forward along the curve, and the end tangent points backward.
+    fn fit(&mut self, points: &[Vector2F], start_tangent: Vector2F, end_tangent: Vector2F) {
+        let (first, last) = (points[0], points[points.len() - 1]);
+        if points.len() == 2 || self.is_within_line(points) {
+            self.contour.push_endpoint(last);
+            return;
+        }
+
+        let mut times = chord_length_parameterize(points);
+        let mut curve = generate_curve(points, &times, start_tangent, end_tangent);
+        let (mut error, mut split_index) = max_error_of(points, &times, &curve);
+        let square_max_error = self.max_error * self.max_error;
+
+        if error > square_max_error &&
+                error <= square_max_error * REPARAMETERIZATION_ERROR_FACTOR.powi(2) {
+            for _ in 0..MAX_REPARAMETERIZATION_ITERATIONS {
+                reparameterize(points, &mut times, &curve);
+                curve = generate_curve(points, &times, start_tangent, end_tangent);
+                let (new_error, new_split_index) = max_error_of(points, &times, &curve);
+                error = new_error;
+                split_index = new_split_index;
+                if error <= square_max_error {
+                    break;
+                }
+            }
+        }
+
+        if error <= square_max_error {
+            self.contour.push_cubic(curve.ctrl.from(), curve.ctrl.to(), last);
+            return;
+        }
+
+        // Split where the fit is worst, keeping the curve smooth across the split.
+        let split_index = split_index.max(1).min(points.len() - 2);
+        let mut center_tangent = points[split_index - 1] - points[split_index + 1];
+        if center_tangent.square_length() == 0.0 {
+            center_tangent = first - last;
+        }
+        let center_tangent = center_tangent.normalize();
+        self.fit(&points[0..=split_index], start_tangent, center_tangent);
+        self.fit(&points[split_index..], -center_tangent, end_tangent);
+    }
+
+    fn is_within_line(&self, points: &[Vector2F]) -> bool {
+        let (first, last) = (points[0], points[points.len() - 1]);
+        let chord = LineSegment2F::new(first, last);
+        let chord_length = chord.vector().length();
+        if chord_length == 0.0 {
+            return false;
+        }
+        points[1..(points.len() - 1)].iter().all(|&point| {
+            let t = (point - first).dot(chord.vector()) / (chord_length * chord_length);
+            (0.0..=1.0).contains(&t) &&
+                ((point - first).det(chord.vector()) / chord_length).abs() <= self.max_error
+        })
+    }
+}
+
+// Assigns each point a time in proportion to its distance along the polyline.
+fn chord_length_parameterize(points: &[Vector2F]) -> Vec<f32> {
+    let mut times = Vec::with_capacity(points.len());
+    let mut distance = 0.0;
+    times.push(0.0);
+    for pair in points.windows(2) {
+        distance += (pair[1] - pair[0]).length();
+        times.push(distance);
+    }
+    for time in &mut times {
+        *time /= distance;
+    }
+    times
+}
+
+// Finds the control points along the given tangents that fit the points best, by least squares.
+fn generate_curve(points: &[Vector2F],
+                  times: &[f32],
+                  start_tangent: Vector2F,
+                  end_tangent: Vector2F)
+                  -> Segment {
+    let (first, last) = (points[0], points[points.len() - 1]);
+
+    let (mut c00, mut c01, mut c11, mut x0, mut x1) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for (&point, &t) in points.iter().zip(times.iter()) {
+        let (b0, b1, b2, b3) = bernstein(t);
+        let (a0, a1) = (start_tangent.scale(b1), end_tangent.scale(b2));
+        c00 += a0.dot(a0);
+        c01 += a0.dot(a1);
+        c11 += a1.dot(a1);
+        let remainder = point - (first.scale(b0 + b1) + last.scale(b2 + b3));
+        x0 += a0.dot(remainder);
+        x1 += a1.dot(remainder);
+    }
+
+    let det_c0_c1 = c00 * c11 - c01 * c01;
+    let (mut start_alpha, mut end_alpha) = if det_c0_c1 == 0.0 {
+        (0.0, 0.0)
+    } else {
+        ((x0 * c11 - x1 * c01) / det_c0_c1, (c00 * x1 - c01 * x0) / det_c0_c1)
+    };
+
+    // If the least squares solution is degenerate, fall back to placing the control points a
+    // third of the way along the chord.
+    let chord_length = (last - first).length();
+    let epsilon = 0.000001 * chord_length;
+    if start_alpha < epsilon || end_alpha < epsilon {
+        start_alpha = chord_length / 3.0;
+        end_alpha = chord_length / 3.0;
+    }
+
+    Segment::cubic(LineSegment2F::new(first, last),
+                   LineSegment2F::new(first + start_tangent.scale(start_alpha),
+                                      last + end_tangent.scale(end_alpha)))
+}
+
+// Moves each time closer to the point on the curve nearest its point, by one step of Newton's
+// method.
+fn reparameterize(points: &[Vector2F], times: &mut [f32], curve: &Segment) {
+    let (p0, p3) = (curve.baseline.from(), curve.baseline.to());
+    let (p1, p2) = (curve.ctrl.from(), curve.ctrl.to());
+    for (&point, time) in points.iter().zip(times.iter_mut()) {
+        let t = *time;
+        let offset = curve.sample(t) - point;
+        let derivative = curve.derivative(t);
+        let second_derivative = (p2 - p1.scale(2.0) + p0).lerp(p3 - p2.scale(2.0) + p1, t)
+                                                         .scale(6.0);
+        let denominator = derivative.dot(derivative) + offset.dot(second_derivative);
+        if denominator != 0.0 {
+            *time = (t - offset.dot(derivative) / denominator).clamp(0.0, 1.0);
+        }
+    }
+}
+
+// Returns the greatest squared distance from a point to the curve, and the index of that point.
+fn max_error_of(points: &[Vector2F], times: &[f32], curve: &Segment) -> (f32, usize) {
+    let (mut max_error, mut max_index) = (0.0, points.len() / 2);
+    for (index, (&point, &t)) in points.iter().zip(times.iter()).enumerate() {
+        let error = (curve.sample(t) - point).square_length();
+        if error > max_error {
+            max_error = error;
+            max_index = index;
+        }
+    }
+    (max_error, max_index)
+}
+
+fn bernstein(t: f32) -> (f32, f32, f32, f32) {
+    let s = 1.0 - t;
+    (s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::outline::Contour;
+    use pathfinder_geometry::vector::Vector2F;
+    use std::f32::consts::PI;
+    use super::fit_cubics;
+
+    fn distance_to_contour(contour: &Contour, point: Vector2F) -> f32 {
+        let mut distance = f32::INFINITY;
+        for segment in contour.iter() {
+            for step in 0..=4096 {
+                let sample = segment.sample(step as f32 / 4096.0);
+                distance = distance.min((sample - point).length());
+            }
+        }
+        distance
+    }
+
+    #[test]
+    fn test_fit_arc() {
+        let points: Vec<_> = (0..=100).map(|index| {
+            let angle = index as f32 / 100.0 * PI;
+            Vector2F::new(angle.cos(), angle.sin()).scale(50.0)
+        }).collect();
+
+        let contour = fit_cubics(&points, 0.1, false);
+        assert!(contour.len() < 20, "{:?}", contour);
+        for &point in &points {
+            assert!(distance_to_contour(&contour, point) <= 0.11);
+        }
+    }
+
+    #[test]
+    fn test_fit_keeps_corners() {
+        let points = [
+            Vector2F::new(0.0, 0.0),
+            Vector2F::new(5.0, 0.0),
+            Vector2F::new(10.0, 0.0),
+            Vector2F::new(10.0, 5.0),
+            Vector2F::new(10.0, 10.0),
+            Vector2F::new(0.0, 10.0),
+        ];
+        let contour = fit_cubics(&points, 0.01, true);
+        assert!(contour.is_closed());
+        let segments: Vec<_> = contour.iter().collect();
+        assert!(segments.iter().all(|segment| segment.is_line()));
+        assert_eq!(segments.len(), 4);
+    }
+}
@@ -11,13 +11,23 @@
 use crate::sorted_vector::SortedVector;
 use pathfinder_color::ColorU;
 use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
 use pathfinder_geometry::util;
+use pathfinder_geometry::vector::Vector2F;
 use pathfinder_simd::default::F32x4;
 use std::cmp::{Ordering, PartialOrd};
 use std::convert;
 use std::hash::{Hash, Hasher};
 use std::mem;
 
+// The maximum number of times a repeating or reflecting gradient is replicated in either
+// direction.
+const MAX_GRADIENT_PERIODS: i32 = 32;
+// Nudges the first stop of each repeated period past the last stop of the previous one, so that
+// the two don't sort ambiguously.
+const GRADIENT_PERIOD_EPSILON: f32 = 0.00001;
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Gradient {
     geometry: GradientGeometry,
@@ -40,6 +50,17 @@ pub struct ColorStop {
     pub color: ColorU,
 }
 
+/// How a gradient continues past the ends of its line, or outside its end circle.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SpreadMethod {
+    /// The colors of the first and last stops extend outward.
+    Pad,
+    /// The gradient repeats, alternating direction with each period.
+    Reflect,
+    /// The gradient repeats in the same direction.
+    Repeat,
+}
+
 impl Eq for Gradient {}
 
 impl Hash for Gradient {
@@ -154,6 +175,87 @@ impl Gradient {
             stop.color.a = (stop.color.a as f32 * alpha).round() as u8;
         }
     }
+
+    /// Extends the gradient past its ends according to `spread_method`, far enough to cover
+    /// `bounds`.
+    ///
+    /// The renderer only supports padding, so repeating and reflecting are emulated by stretching
+    /// the gradient to cover the bounds and replicating the stops once per period.
+    pub fn apply_spread_method(&mut self, spread_method: SpreadMethod, bounds: RectF) {
+        let (first_period, last_period) = match spread_method {
+            SpreadMethod::Pad => return,
+            SpreadMethod::Reflect | SpreadMethod::Repeat => {
+                gradient_periods(&self.geometry, bounds)
+            }
+        };
+        if (first_period, last_period) == (0, 1) {
+            return;
+        }
+
+        match self.geometry {
+            GradientGeometry::Linear(ref mut line) => {
+                let (from, vector) = (line.from(), line.vector());
+                *line = LineSegment2F::new(from + vector.scale(first_period as f32),
+                                           from + vector.scale(last_period as f32));
+            }
+            GradientGeometry::Radial { ref mut end_radius, .. } => {
+                // Radial gradients have no periods inside their start circle.
+                debug_assert_eq!(first_period, 0);
+                *end_radius *= last_period as f32;
+            }
+        }
+
+        // Each period must begin and end with a stop so that it doesn't bleed into its neighbors.
+        let mut period_stops = mem::replace(&mut self.stops, SortedVector::new()).array;
+        if let Some(&first_stop) = period_stops.first() {
+            if first_stop.offset > 0.0 {
+                period_stops.insert(0, ColorStop::new(first_stop.color, 0.0));
+            }
+        }
+        if let Some(&last_stop) = period_stops.last() {
+            if last_stop.offset < 1.0 {
+                period_stops.push(ColorStop::new(last_stop.color, 1.0));
+            }
+        }
+
+        let period_count = (last_period - first_period) as f32;
+        for period in first_period..last_period {
+            let reflected = spread_method == SpreadMethod::Reflect && period.rem_euclid(2) == 1;
+            for stop in &period_stops {
+                let mut offset = if reflected { 1.0 - stop.offset } else { stop.offset };
+                if spread_method == SpreadMethod::Repeat && period > first_period &&
+                        offset == 0.0 {
+                    offset += GRADIENT_PERIOD_EPSILON;
+                }
+                let offset = ((period - first_period) as f32 + offset) / period_count;
+                self.add_color_stop(ColorStop::new(stop.color, offset));
+            }
+        }
+    }
+}
+
+impl GradientGeometry {
+    /// Returns this geometry with `transform` applied, or `None` if it's a linear gradient whose
+    /// line is degenerate before or after transformation.
+    ///
+    /// Lines of constant color in a linear gradient stay parallel to their transformed selves,
+    /// even under skews and non-uniform scales.
+    pub fn transform(&self, transform: &Transform2F) -> Option<GradientGeometry> {
+        match *self {
+            GradientGeometry::Linear(line) => {
+                transform_linear_gradient_line(line, transform).map(GradientGeometry::Linear)
+            }
+            GradientGeometry::Radial { line, start_radius, end_radius } => {
+                // FIXME: Skews and non-uniform scales should make the gradient elliptical.
+                let scale = f32::sqrt(f32::abs(transform.matrix.det()));
+                Some(GradientGeometry::Radial {
+                    line: *transform * line,
+                    start_radius: start_radius * scale,
+                    end_radius: end_radius * scale,
+                })
+            }
+        }
+    }
 }
 
 impl ColorStop {
@@ -162,3 +264,115 @@ impl ColorStop {
         ColorStop { color, offset }
     }
 }
+
+// Transforming both endpoints of a linear gradient line isn't enough under skews and non-uniform
+// scales, because the lines of constant color must stay parallel to their transformed selves
+// rather than perpendicular to the transformed gradient vector. Instead, we find the transformed
+// line whose projection coefficient matches the untransformed one at every point.
+fn transform_linear_gradient_line(line: LineSegment2F, transform: &Transform2F)
+                                  -> Option<LineSegment2F> {
+    let vector = line.vector();
+    let square_length = vector.square_length();
+    let det = transform.matrix.det();
+    if square_length == 0.0 || det == 0.0 {
+        return None;
+    }
+
+    // The gradient of `t` in transformed space is `M⁻ᵀ · v / |v|²`.
+    let inverse = transform.matrix.inverse();
+    let t_gradient = Vector2F::new(inverse.m11() * vector.x() + inverse.m21() * vector.y(),
+                                   inverse.m12() * vector.x() + inverse.m22() * vector.y())
+        .scale(1.0 / square_length);
+    let t_gradient_square_length = t_gradient.square_length();
+    if t_gradient_square_length == 0.0 {
+        return None;
+    }
+
+    let from = *transform * line.from();
+    Some(LineSegment2F::new(from, from + t_gradient.scale(1.0 / t_gradient_square_length)))
+}
+
+// Returns the range of gradient periods, relative to the unspread gradient, that the given bounds
+// cover.
+fn gradient_periods(geometry: &GradientGeometry, bounds: RectF) -> (i32, i32) {
+    let corners = [
+        bounds.origin(),
+        bounds.upper_right(),
+        bounds.lower_left(),
+        bounds.lower_right(),
+    ];
+    let (mut t_min, mut t_max) = (0.0, 1.0);
+    match *geometry {
+        GradientGeometry::Linear(line) => {
+            for &corner in &corners {
+                let t = line.vector().projection_coefficient(corner - line.from());
+                t_min = f32::min(t_min, t);
+                t_max = f32::max(t_max, t);
+            }
+        }
+        GradientGeometry::Radial { line, end_radius, .. } => {
+            if end_radius <= 0.0 {
+                return (0, 1);
+            }
+            for &corner in &corners {
+                let distance = f32::max((corner - line.from()).length(),
+                                        (corner - line.to()).length());
+                t_max = f32::max(t_max, distance / end_radius);
+            }
+        }
+    }
+
+    let first_period = f32::max(t_min.floor(), -MAX_GRADIENT_PERIODS as f32) as i32;
+    let last_period = f32::min(t_max.ceil(), MAX_GRADIENT_PERIODS as f32 + 1.0) as i32;
+    (first_period, last_period)
+}
+
+#[cfg(test)]
+mod test {
+    use pathfinder_color::ColorU;
+    use pathfinder_geometry::line_segment::LineSegment2F;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::Vector2F;
+    use super::{ColorStop, Gradient, GradientGeometry, SpreadMethod};
+
+    fn black_to_white(spread_method: SpreadMethod) -> Gradient {
+        let line = LineSegment2F::new(Vector2F::default(), Vector2F::new(10.0, 0.0));
+        let mut gradient = Gradient::linear(line);
+        gradient.add_color_stop(ColorStop::new(ColorU::black(), 0.0));
+        gradient.add_color_stop(ColorStop::new(ColorU::white(), 1.0));
+        let bounds = RectF::new(Vector2F::new(-5.0, 0.0), Vector2F::new(30.0, 10.0));
+        gradient.apply_spread_method(spread_method, bounds);
+        gradient
+    }
+
+    #[test]
+    fn test_pad_leaves_gradient_alone() {
+        let gradient = black_to_white(SpreadMethod::Pad);
+        assert_eq!(gradient.stops().len(), 2);
+        let line = LineSegment2F::new(Vector2F::default(), Vector2F::new(10.0, 0.0));
+        assert_eq!(*gradient.geometry(), GradientGeometry::Linear(line));
+    }
+
+    #[test]
+    fn test_repeat_replicates_stops_over_bounds() {
+        // The bounds cover periods -1 through 2.
+        let gradient = black_to_white(SpreadMethod::Repeat);
+        let line = LineSegment2F::new(Vector2F::new(-10.0, 0.0), Vector2F::new(30.0, 0.0));
+        assert_eq!(*gradient.geometry(), GradientGeometry::Linear(line));
+        assert_eq!(gradient.stops().len(), 8);
+        assert_eq!(gradient.sample(0.0), ColorU::black());
+        assert_eq!(gradient.sample(0.25), ColorU::white());
+        // Each period restarts from black rather than fading back from white.
+        assert!(gradient.sample(0.26).r < 32);
+    }
+
+    #[test]
+    fn test_reflect_alternates_direction() {
+        let gradient = black_to_white(SpreadMethod::Reflect);
+        assert_eq!(gradient.stops().len(), 8);
+        // Period -1 is reflected, so it runs from white to black.
+        assert_eq!(gradient.sample(0.0), ColorU::white());
+        assert_eq!(gradient.sample(0.25), ColorU::black());
+        assert_eq!(gradient.sample(0.5), ColorU::white());
+    }
+}
@@ -0,0 +1,286 @@
+// pathfinder/content/src/measure.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Measurement of distances along paths.
+//!
+//! Distances along an outline run through its contours in order, as if they were joined end to
+//! end. Closed contours include the line that closes them.
+
+use crate::outline::{Contour, Outline, PushSegmentFlags};
+use crate::segment::Segment;
+use pathfinder_geometry::vector::Vector2F;
+
+// Times this close to the end of a segment are treated as the end when computing tangents.
+const TANGENT_EPSILON: f32 = 0.0001;
+
+pub struct OutlineMeasure {
+    contours: Vec<ContourMeasure>,
+    length: f32,
+}
+
+pub struct ContourMeasure {
+    segments: Vec<Segment>,
+    // The distance from the start of the contour to the end of each segment.
+    end_distances: Vec<f32>,
+    closed: bool,
+}
+
+impl OutlineMeasure {
+    pub fn new(outline: &Outline) -> OutlineMeasure {
+        let contours: Vec<_> = outline.contours().iter().map(ContourMeasure::new).collect();
+        let length = contours.iter().map(|contour| contour.length()).sum();
+        OutlineMeasure { contours, length }
+    }
+
+    #[inline]
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+
+    #[inline]
+    pub fn contours(&self) -> &[ContourMeasure] {
+        &self.contours
+    }
+
+    /// Returns the point at the given distance along the outline, clamped to its ends.
+    pub fn position_at(&self, distance: f32) -> Option<Vector2F> {
+        let (contour, distance) = self.contour_at(distance)?;
+        contour.position_at(distance)
+    }
+
+    /// Returns the unit tangent at the given distance along the outline, clamped to its ends.
+    pub fn tangent_at(&self, distance: f32) -> Option<Vector2F> {
+        let (contour, distance) = self.contour_at(distance)?;
+        contour.tangent_at(distance)
+    }
+
+    /// Returns the part of the outline between two distances along it.
+    pub fn extract(&self, from_distance: f32, to_distance: f32) -> Outline {
+        let mut outline = Outline::new();
+        let mut contour_start = 0.0;
+        for contour in &self.contours {
+            let contour_end = contour_start + contour.length();
+            if from_distance <= contour_end && to_distance >= contour_start {
+                let sub_contour = contour.extract(from_distance - contour_start,
+                                                  to_distance - contour_start);
+                if !sub_contour.is_empty() {
+                    outline.push_contour(sub_contour);
+                }
+            }
+            contour_start = contour_end;
+        }
+        outline
+    }
+
+    fn contour_at(&self, mut distance: f32) -> Option<(&ContourMeasure, f32)> {
+        let mut contours = self.contours.iter().filter(|contour| !contour.segments.is_empty());
+        let mut contour = contours.next()?;
+        for next_contour in contours {
+            if distance <= contour.length() {
+                break;
+            }
+            distance -= contour.length();
+            contour = next_contour;
+        }
+        Some((contour, distance))
+    }
+}
+
+impl ContourMeasure {
+    pub fn new(contour: &Contour) -> ContourMeasure {
+        let segments: Vec<_> = contour.iter().filter(|segment| !segment.is_none()).collect();
+        let mut end_distances = Vec::with_capacity(segments.len());
+        let mut distance = 0.0;
+        for segment in &segments {
+            distance += segment.arc_length();
+            end_distances.push(distance);
+        }
+        ContourMeasure { segments, end_distances, closed: contour.is_closed() }
+    }
+
+    #[inline]
+    pub fn length(&self) -> f32 {
+        self.end_distances.last().cloned().unwrap_or(0.0)
+    }
+
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Returns the index of the segment at the given distance along the contour, and the time
+    /// within that segment. The distance is clamped to the ends of the contour.
+    pub fn segment_at(&self, distance: f32) -> Option<(usize, f32)> {
+        if self.segments.is_empty() {
+            return None;
+        }
+
+        let distance = distance.max(0.0).min(self.length());
+        let index = self.end_distances
+                        .iter()
+                        .position(|&end_distance| distance <= end_distance)
+                        .unwrap_or(self.segments.len() - 1);
+        let start_distance = if index == 0 { 0.0 } else { self.end_distances[index - 1] };
+        Some((index, self.segments[index].time_for_distance(distance - start_distance)))
+    }
+
+    pub fn position_at(&self, distance: f32) -> Option<Vector2F> {
+        let (index, t) = self.segment_at(distance)?;
+        Some(self.segments[index].sample(t))
+    }
+
+    /// Returns the unit tangent at the given distance along the contour, pointing in the
+    /// direction of travel.
+    pub fn tangent_at(&self, distance: f32) -> Option<Vector2F> {
+        let (index, t) = self.segment_at(distance)?;
+        let segment = &self.segments[index];
+        let mut tangent = segment.derivative(t);
+
+        // Where control points coincide with endpoints, the derivative vanishes, so look a
+        // little further along the curve instead.
+        if tangent.square_length() == 0.0 {
+            let (from_t, to_t) = if t < 0.5 {
+                (t, t + TANGENT_EPSILON)
+            } else {
+                (t - TANGENT_EPSILON, t)
+            };
+            tangent = segment.sample(to_t) - segment.sample(from_t);
+        }
+        if tangent.square_length() == 0.0 {
+            tangent = segment.baseline.vector();
+        }
+        if tangent.square_length() == 0.0 {
+            return None;
+        }
+        Some(tangent.normalize())
+    }
+
+    /// Returns the part of the contour between two distances along it. Extracting all of a
+    /// closed contour returns a closed contour.
+    pub fn extract(&self, from_distance: f32, to_distance: f32) -> Contour {
+        let mut contour = Contour::new();
+        let (from_distance, to_distance) = (from_distance.max(0.0),
+                                            to_distance.min(self.length()));
+        if self.segments.is_empty() || from_distance > to_distance {
+            return contour;
+        }
+
+        if self.closed && from_distance == 0.0 && to_distance == self.length() {
+            // Leave off the closing line, since closing the contour adds it back.
+            for (index, segment) in self.segments[0..(self.segments.len() - 1)].iter().enumerate() {
+                contour.push_segment(segment, push_segment_flags(index == 0));
+            }
+            if self.segments.len() == 1 {
+                contour.push_endpoint(self.segments[0].baseline.from());
+            }
+            contour.close();
+            return contour;
+        }
+
+        let (from_index, from_t) = self.segment_at(from_distance).unwrap();
+        let (to_index, to_t) = self.segment_at(to_distance).unwrap();
+        for index in from_index..=to_index {
+            let segment_from_t = if index == from_index { from_t } else { 0.0 };
+            let segment_to_t = if index == to_index { to_t } else { 1.0 };
+            let segment = self.segments[index].subsegment(segment_from_t, segment_to_t);
+            contour.push_segment(&segment, push_segment_flags(index == from_index));
+        }
+        contour
+    }
+}
+
+fn push_segment_flags(first: bool) -> PushSegmentFlags {
+    if first {
+        PushSegmentFlags::UPDATE_BOUNDS | PushSegmentFlags::INCLUDE_FROM_POINT
+    } else {
+        PushSegmentFlags::UPDATE_BOUNDS
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::outline::{Contour, Outline};
+    use crate::segment::Segment;
+    use pathfinder_geometry::line_segment::LineSegment2F;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::Vector2F;
+    use std::f32::consts::PI;
+    use super::OutlineMeasure;
+
+    fn approx_eq(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn test_circle_length() {
+        let mut contour = Contour::new();
+        contour.push_ellipse(&Transform2F::from_scale(Vector2F::splat(10.0)));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        let measure = OutlineMeasure::new(&outline);
+        // The cubic approximation of the circle is very slightly longer than the circle.
+        assert!((measure.length() - 20.0 * PI).abs() < 0.02, "{}", measure.length());
+
+        // The circle starts at -45°, so a quarter of the way around is at 45°.
+        let diagonal = 0.5f32.sqrt();
+        let position = measure.position_at(measure.length() * 0.25).unwrap();
+        let expected_position = Vector2F::splat(diagonal * 10.0);
+        assert!((position - expected_position).length() < 0.01, "{:?}", position);
+        let tangent = measure.tangent_at(measure.length() * 0.25).unwrap();
+        assert!(approx_eq(tangent.x(), -diagonal) && approx_eq(tangent.y(), diagonal),
+                "{:?}", tangent);
+
+        // Half of the circle is half as long.
+        let half = OutlineMeasure::new(&measure.extract(0.0, measure.length() * 0.5));
+        assert!(approx_eq(half.length(), measure.length() * 0.5), "{}", half.length());
+    }
+
+    #[test]
+    fn test_polyline_across_contours() {
+        let mut outline = Outline::new();
+        for &y in &[0.0, 10.0] {
+            let mut contour = Contour::new();
+            contour.push_endpoint(Vector2F::new(0.0, y));
+            contour.push_endpoint(Vector2F::new(4.0, y));
+            contour.push_endpoint(Vector2F::new(4.0, y + 3.0));
+            outline.push_contour(contour);
+        }
+
+        let measure = OutlineMeasure::new(&outline);
+        assert!(approx_eq(measure.length(), 14.0));
+        assert_eq!(measure.position_at(9.0), Some(Vector2F::new(2.0, 10.0)));
+        assert_eq!(measure.tangent_at(5.0), Some(Vector2F::new(0.0, 1.0)));
+
+        let extracted = measure.extract(2.0, 9.0);
+        assert_eq!(extracted.contours().len(), 2);
+        assert!(approx_eq(OutlineMeasure::new(&extracted).length(), 7.0));
+    }
+
+    #[test]
+    fn test_time_for_distance() {
+        // The control points are bunched up at the start, so the speed varies a lot along the
+        // curve and the time isn't proportional to the distance.
+        let segment = Segment::cubic(LineSegment2F::new(Vector2F::new(0.0, 0.0),
+                                                        Vector2F::new(100.0, 0.0)),
+                                     LineSegment2F::new(Vector2F::new(1.0, 30.0),
+                                                        Vector2F::new(2.0, -30.0)));
+        let length = segment.arc_length();
+        for step in 1..20 {
+            let distance = length * step as f32 / 20.0;
+            let t = segment.time_for_distance(distance);
+            let reached = segment.arc_length_between(0.0, t);
+            assert!((reached - distance).abs() < 0.01, "{} at {}: {}", distance, t, reached);
+        }
+        assert_eq!(segment.time_for_distance(-1.0), 0.0);
+        assert_eq!(segment.time_for_distance(length + 1.0), 1.0);
+    }
+}
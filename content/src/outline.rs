@@ -12,6 +12,8 @@
 
 use crate::clip::{self, ContourPolygonClipper, ContourRectClipper};
 use crate::dilation::ContourDilator;
+use crate::fill::FillRule;
+use crate::fit;
 use crate::orientation::Orientation;
 use crate::segment::{Segment, SegmentFlags, SegmentKind};
 use pathfinder_geometry::line_segment::LineSegment2F;
@@ -110,6 +112,14 @@ impl Outline {
         outline
     }
 
+    /// Returns an outline consisting of a single closed rectangle.
+    #[inline]
+    pub fn from_rect(rect: RectF) -> Outline {
+        let mut outline = Outline::new();
+        outline.push_contour(Contour::from_rect(rect));
+        outline
+    }
+
     #[inline]
     pub fn bounds(&self) -> RectF {
         self.bounds
@@ -202,6 +212,31 @@ impl Outline {
             self.push_contour(ContourRectClipper::new(clip_rect, contour).clip());
         }
     }
+
+    /// Replaces all curves with lines that stay within `tolerance` of them.
+    pub fn flatten(&mut self, tolerance: f32) {
+        self.contours.iter_mut().for_each(|contour| contour.flatten(tolerance));
+    }
+
+    /// Reduces the number of points in the outline, by flattening it and then fitting curves to
+    /// the result, such that it stays within `tolerance` of the original.
+    pub fn simplify(&mut self, tolerance: f32) {
+        self.contours.iter_mut().for_each(|contour| contour.simplify(tolerance));
+    }
+
+    /// Returns the winding number of the outline around `point`. Open contours are treated as
+    /// closed, as they are when filled.
+    pub fn winding_number_at(&self, point: Vector2F) -> i32 {
+        if !self.bounds.contains_point(point) {
+            return 0;
+        }
+        self.contours.iter().map(|contour| contour.winding_number_at(point)).sum()
+    }
+
+    #[inline]
+    pub fn contains_point(&self, point: Vector2F, fill_rule: FillRule) -> bool {
+        fill_rule.includes_winding_number(self.winding_number_at(point))
+    }
 }
 
 impl Debug for Outline {
@@ -227,6 +262,19 @@ impl Contour {
         }
     }
 
+    /// Returns a closed contour that goes around the given rectangle, starting from its origin and
+    /// moving along the X axis first.
+    #[inline]
+    pub fn from_rect(rect: RectF) -> Contour {
+        let mut contour = Contour::new();
+        contour.push_endpoint(rect.origin());
+        contour.push_endpoint(rect.upper_right());
+        contour.push_endpoint(rect.lower_right());
+        contour.push_endpoint(rect.lower_left());
+        contour.close();
+        contour
+    }
+
     // Replaces this contour with a new one, with arrays preallocated to match `self`.
     #[inline]
     pub(crate) fn take(&mut self) -> Contour {
@@ -275,6 +323,60 @@ impl Contour {
         self.points[index as usize]
     }
 
+    /// Replaces all curves with lines that stay within `tolerance` of them.
+    pub fn flatten(&mut self, tolerance: f32) {
+        // Contours without control points are already flat.
+        if self.flags.iter().all(|flags| flags.is_empty()) {
+            return;
+        }
+
+        let mut segments: Vec<_> = self.iter().collect();
+        if self.closed {
+            segments.pop();
+        }
+
+        let mut contour = Contour::new();
+        contour.push_endpoint(self.position_of(0));
+        for segment in segments {
+            let step_count = segment.flattening_step_count(tolerance);
+            for step in 1..step_count {
+                contour.push_endpoint(segment.sample(step as f32 / step_count as f32));
+            }
+            contour.push_endpoint(segment.baseline.to());
+        }
+        contour.closed = self.closed;
+        *self = contour;
+    }
+
+    /// Reduces the number of points in the contour, by flattening it and then fitting curves to
+    /// the result, such that it stays within `tolerance` of the original.
+    pub fn simplify(&mut self, tolerance: f32) {
+        // Split the tolerance between the flattening and the fitting.
+        let mut flattened = self.clone();
+        flattened.flatten(tolerance * 0.25);
+        *self = fit::fit_cubics(&flattened.points, tolerance * 0.75, self.closed);
+    }
+
+    /// Returns the winding number of the contour around `point`. Open contours are treated as
+    /// closed, as they are when filled.
+    pub fn winding_number_at(&self, point: Vector2F) -> i32 {
+        if self.is_empty() || !self.bounds.contains_point(point) {
+            return 0;
+        }
+
+        let mut winding = self.iter().map(|segment| segment.winding_number_at(point)).sum();
+        if !self.closed {
+            let closing_line = LineSegment2F::new(self.position_of_last(1), self.position_of(0));
+            winding += Segment::line(closing_line).winding_number_at(point);
+        }
+        winding
+    }
+
+    #[inline]
+    pub fn contains_point(&self, point: Vector2F, fill_rule: FillRule) -> bool {
+        fill_rule.includes_winding_number(self.winding_number_at(point))
+    }
+
     #[inline]
     pub fn last_position(&self) -> Option<Vector2F> {
         self.points.last().cloned()
@@ -387,6 +489,10 @@ impl Contour {
             if !last {
                 sweep_vector = UnitVector(Vector2F::new(0.0, 1.0));
                 segment = Segment::quarter_circle_arc();
+            } else if sweep_vector.0.x() >= 1.0 {
+                // The chord is degenerate, and the arc approximation would divide by zero.
+                sweep_vector = UnitVector(Vector2F::new(1.0, 0.0));
+                segment = Segment::line(LineSegment2F::new(sweep_vector.0, sweep_vector.0));
             } else {
                 segment = Segment::arc_from_cos(sweep_vector.0.x());
             }
@@ -843,3 +949,123 @@ pub(crate) fn union_rect(bounds: &mut RectF, new_point: Vector2F, first: bool) {
         *bounds = bounds.union_point(new_point)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::fill::FillRule;
+    use crate::stroke::{LineCap, LineJoin, StrokeStyle};
+    use pathfinder_geometry::line_segment::LineSegment2F;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::Vector2F;
+    use super::{ArcDirection, Contour, Outline};
+
+    fn circle(radius: f32) -> Contour {
+        let mut contour = Contour::new();
+        contour.push_ellipse(&Transform2F::from_scale(Vector2F::splat(radius)));
+        contour.close();
+        contour
+    }
+
+    #[test]
+    fn test_contains_point_on_curves() {
+        let mut outline = Outline::new();
+        outline.push_contour(circle(10.0));
+
+        // Close to the curve on either side, where a polygon with a few points would be wrong.
+        let diagonal = Vector2F::splat(0.5f32.sqrt());
+        assert!(outline.contains_point(diagonal.scale(9.99), FillRule::Winding));
+        assert!(!outline.contains_point(diagonal.scale(10.01), FillRule::Winding));
+        assert!(outline.contains_point(Vector2F::new(0.0, -9.99), FillRule::Winding));
+        assert!(!outline.contains_point(Vector2F::new(-10.01, 0.0), FillRule::Winding));
+    }
+
+    #[test]
+    fn test_contains_point_fill_rules() {
+        let mut outline = Outline::new();
+        outline.push_contour(circle(10.0));
+        outline.push_contour(circle(5.0));
+
+        let center = Vector2F::default();
+        assert_eq!(outline.winding_number_at(center).abs(), 2);
+        assert!(outline.contains_point(center, FillRule::Winding));
+        assert!(!outline.contains_point(center, FillRule::EvenOdd));
+        assert!(outline.contains_point(Vector2F::new(7.0, 0.0), FillRule::EvenOdd));
+    }
+
+    #[test]
+    fn test_open_contours_are_closed() {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.0, 0.0));
+        contour.push_endpoint(Vector2F::new(10.0, 0.0));
+        contour.push_endpoint(Vector2F::new(0.0, 10.0));
+        assert!(contour.contains_point(Vector2F::new(2.0, 2.0), FillRule::Winding));
+        assert!(!contour.contains_point(Vector2F::new(8.0, 8.0), FillRule::Winding));
+    }
+
+    #[test]
+    fn test_flatten_and_simplify() {
+        let mut outline = Outline::new();
+        outline.push_contour(circle(10.0));
+
+        let mut flattened = outline.clone();
+        flattened.flatten(0.01);
+        let contour = &flattened.contours()[0];
+        assert!(contour.is_closed());
+        assert!(contour.iter().all(|segment| segment.is_line()));
+        for index in 0..contour.len() {
+            let distance = contour.position_of(index).length();
+            assert!((distance - 10.0).abs() < 0.01, "{}", distance);
+        }
+
+        // Fitting curves to the flattened circle gets back to a few curves.
+        let mut simplified = flattened.clone();
+        simplified.simplify(0.05);
+        let contour = &simplified.contours()[0];
+        assert!(contour.is_closed());
+        assert!(contour.len() < flattened.contours()[0].len() / 4);
+        for segment in contour.iter() {
+            for step in 0..=16 {
+                let distance = segment.sample(step as f32 / 16.0).length();
+                assert!((distance - 10.0).abs() < 0.06, "{}", distance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stroke_contains_point() {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.0, 0.0));
+        contour.push_endpoint(Vector2F::new(10.0, 0.0));
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        let style = StrokeStyle {
+            line_width: 2.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter(10.0),
+        };
+        assert!(outline.stroke_contains_point(Vector2F::new(5.0, 0.9), style));
+        assert!(!outline.stroke_contains_point(Vector2F::new(5.0, 1.1), style));
+        assert!(!outline.stroke_contains_point(Vector2F::new(-0.5, 0.0), style));
+
+        let style = StrokeStyle { line_cap: LineCap::Square, ..style };
+        assert!(outline.stroke_contains_point(Vector2F::new(-0.5, 0.0), style));
+    }
+
+    #[test]
+    fn test_arc_with_degenerate_chord() {
+        // These endpoints are a rounding error apart, as in a round join between two nearly
+        // parallel stroke segments.
+        let chord = LineSegment2F::new(Vector2F::new(-0.69295895, 0.72097707),
+                                       Vector2F::new(-0.692959, 0.72097695));
+        let mut contour = Contour::new();
+        contour.push_arc_from_unit_chord(&Transform2F::from_scale(Vector2F::splat(2.0)),
+                                         chord,
+                                         ArcDirection::CW);
+        assert!(contour.len() > 0);
+        for point_index in 0..contour.len() {
+            let point = contour.position_of(point_index);
+            assert!(!point.x().is_nan() && !point.y().is_nan());
+        }
+    }
+}
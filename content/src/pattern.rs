@@ -11,12 +11,18 @@
 //! Raster image patterns.
 
 use pathfinder_color::{self as color, ColorU};
-use pathfinder_geometry::vector::Vector2I;
+use pathfinder_geometry::rect::{RectF, RectI};
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, Vector2I};
 use std::fmt::{self, Debug, Formatter};
 
 #[cfg(feature = "pf-image")]
 use image::RgbaImage;
 
+// The maximum width and height of the images and render targets that patterns covering a path are
+// drawn into.
+const MAX_PATTERN_LENGTH: i32 = 4096;
+
 /// A raster image pattern.
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Pattern {
@@ -97,6 +103,78 @@ impl Image {
         self.pixels.iter_mut().for_each(|pixel| pixel.a = (pixel.a as f32 * alpha).round() as u8);
         self.is_opaque = false;
     }
+
+    /// Returns a pattern image that draws this image with `transform` applied, filling in only
+    /// the pixels inside `bounds`.
+    ///
+    /// Patterns are drawn untransformed from the scene origin, so this is how images are placed
+    /// anywhere else. Outside the image, it wraps around along the axes in `repeat` and extends
+    /// its edge pixels along the others. `smooth` selects bilinear rather than nearest-neighbor
+    /// filtering.
+    pub fn resample(&self, transform: &Transform2F, bounds: RectI, repeat: Repeat, smooth: bool)
+                    -> Image {
+        let size = bounds.lower_right();
+        let mut pixels = vec![ColorU::transparent_black(); size.x() as usize * size.y() as usize];
+
+        let transform_inv = transform.inverse();
+        for y in bounds.min_y()..bounds.max_y() {
+            for x in bounds.min_x()..bounds.max_x() {
+                let point = transform_inv * (Vector2I::new(x, y).to_f32() + Vector2F::splat(0.5));
+                let color = self.sample(point, repeat, smooth);
+                pixels[y as usize * size.x() as usize + x as usize] = color;
+            }
+        }
+
+        Image::new(size, pixels)
+    }
+
+    fn sample(&self, point: Vector2F, repeat: Repeat, smooth: bool) -> ColorU {
+        let size = self.size;
+        let wrap = |position: i32, length: i32, repeats: bool| {
+            if repeats {
+                position.rem_euclid(length)
+            } else {
+                position.max(0).min(length - 1)
+            }
+        };
+        let texel = |position: Vector2I| {
+            let x = wrap(position.x(), size.x(), repeat.contains(Repeat::X));
+            let y = wrap(position.y(), size.y(), repeat.contains(Repeat::Y));
+            self.pixels[y as usize * size.x() as usize + x as usize]
+        };
+
+        if !smooth {
+            return texel(point.floor().to_i32());
+        }
+
+        let point = point - Vector2F::splat(0.5);
+        let upper_left = point.floor();
+        let fract = point - upper_left;
+        let upper_left = upper_left.to_i32();
+
+        let upper_right = upper_left + Vector2I::new(1, 0);
+        let lower_left = upper_left + Vector2I::new(0, 1);
+        let lower_right = upper_left + Vector2I::splat(1);
+
+        let upper = texel(upper_left).to_f32().lerp(texel(upper_right).to_f32(), fract.x());
+        let lower = texel(lower_left).to_f32().lerp(texel(lower_right).to_f32(), fract.x());
+        upper.lerp(lower, fract.y()).to_u8()
+    }
+}
+
+/// Returns the pixels that a pattern must cover in order to paint a path with the given bounds,
+/// or `None` if the path covers no pixels.
+///
+/// Because patterns start at the scene origin, only the lower right corner is meaningful for
+/// sizing the image or render target behind the pattern. It's limited to 4096×4096 pixels.
+pub fn pattern_bounds(device_bounds: RectF) -> Option<RectI> {
+    let max_point = Vector2I::splat(MAX_PATTERN_LENGTH);
+    let upper_left = device_bounds.origin().floor().to_i32().max(Vector2I::default());
+    let lower_right = device_bounds.lower_right().ceil().to_i32().min(max_point);
+    if lower_right.x() <= upper_left.x() || lower_right.y() <= upper_left.y() {
+        return None;
+    }
+    Some(RectI::from_points(upper_left, lower_right))
 }
 
 impl PatternSource {
@@ -130,3 +208,43 @@ impl Debug for Image {
         write!(formatter, "(image {}×{} px)", self.size.x(), self.size.y())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use pathfinder_color::ColorU;
+    use pathfinder_geometry::rect::{RectF, RectI};
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::{Vector2F, Vector2I};
+    use super::{Image, Repeat};
+
+    // A 2×1 image with a black pixel on the left and a white one on the right.
+    fn black_and_white() -> Image {
+        Image::new(Vector2I::new(2, 1), vec![ColorU::black(), ColorU::white()])
+    }
+
+    #[test]
+    fn test_pattern_bounds() {
+        let bounds = RectF::from_points(Vector2F::new(-3.5, 1.5), Vector2F::new(8.25, 5000.0));
+        assert_eq!(super::pattern_bounds(bounds),
+                   Some(RectI::from_points(Vector2I::new(0, 1), Vector2I::new(9, 4096))));
+        let bounds = RectF::from_points(Vector2F::new(-8.0, -8.0), Vector2F::new(-1.0, -1.0));
+        assert_eq!(super::pattern_bounds(bounds), None);
+    }
+
+    #[test]
+    fn test_resample_clamps_or_wraps() {
+        let image = black_and_white();
+        let transform = Transform2F::from_translation(Vector2F::new(1.0, 0.0));
+        let bounds = RectI::new(Vector2I::new(0, 0), Vector2I::new(4, 1));
+
+        // Only the pixels inside the bounds are filled in.
+        let bounds_2 = RectI::new(Vector2I::new(1, 0), Vector2I::new(3, 1));
+        let clamped = image.resample(&transform, bounds_2, Repeat::empty(), false);
+        assert_eq!(clamped.size(), Vector2I::new(4, 1));
+        let (black, white) = (ColorU::black(), ColorU::white());
+        assert_eq!(clamped.pixels(), &[ColorU::transparent_black(), black, white, white]);
+
+        let repeated = image.resample(&transform, bounds, Repeat::X, false);
+        assert_eq!(repeated.pixels(), &[white, black, white, black]);
+    }
+}
@@ -19,6 +19,13 @@ use std::f32::consts::SQRT_2;
 
 const MAX_NEWTON_ITERATIONS: u32 = 32;
 
+// The maximum error in arc length computations, in path units.
+const ARC_LENGTH_TOLERANCE: f32 = 0.001;
+const MAX_ARC_LENGTH_SUBDIVISIONS: u32 = 8;
+
+// Keeps flattening from producing unbounded numbers of points for tiny tolerances.
+const MAX_FLATTENING_STEPS: u32 = 4096;
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Segment {
     pub baseline: LineSegment2F,
@@ -224,14 +231,287 @@ impl Segment {
         }
     }
 
+    // Chooses enough steps that each is within `tolerance` of the curve when the segment is split
+    // uniformly, from the bound on its second derivative.
+    pub(crate) fn flattening_step_count(&self, tolerance: f32) -> u32 {
+        let (from, to) = (self.baseline.from(), self.baseline.to());
+        let error = match self.kind {
+            SegmentKind::None | SegmentKind::Line => return 1,
+            SegmentKind::Quadratic => {
+                let ctrl = self.ctrl.from();
+                (from - ctrl.scale(2.0) + to).length() * 0.25
+            }
+            SegmentKind::Cubic => {
+                let (ctrl0, ctrl1) = (self.ctrl.from(), self.ctrl.to());
+                f32::max((from - ctrl0.scale(2.0) + ctrl1).length(),
+                         (ctrl0 - ctrl1.scale(2.0) + to).length()) * 0.75
+            }
+        };
+        let step_count = (error / tolerance).sqrt().ceil();
+        if step_count.is_nan() {
+            return 1;
+        }
+        (step_count as u32).clamp(1, MAX_FLATTENING_STEPS)
+    }
+
+    /// Returns the derivative of the segment with respect to time.
+    pub fn derivative(&self, t: f32) -> Vector2F {
+        let (p0, p3) = (self.baseline.from(), self.baseline.to());
+        match self.kind {
+            SegmentKind::None => Vector2F::default(),
+            SegmentKind::Line => p3 - p0,
+            SegmentKind::Quadratic => {
+                let p1 = self.ctrl.from();
+                (p1 - p0).lerp(p3 - p1, t).scale(2.0)
+            }
+            SegmentKind::Cubic => {
+                let (p1, p2) = (self.ctrl.from(), self.ctrl.to());
+                let (v0, v1) = ((p1 - p0).lerp(p2 - p1, t), (p2 - p1).lerp(p3 - p2, t));
+                v0.lerp(v1, t).scale(3.0)
+            }
+        }
+    }
+
+    /// Returns the part of the segment between two times, without changing its kind.
+    pub fn subsegment(&self, from_t: f32, to_t: f32) -> Segment {
+        let (p0, p3) = (self.baseline.from(), self.baseline.to());
+        let baseline = LineSegment2F::new(self.sample(from_t), self.sample(to_t));
+        match self.kind {
+            SegmentKind::None => *self,
+            SegmentKind::Line => Segment::line(baseline),
+            SegmentKind::Quadratic => {
+                // The control point is the blossom of the curve at (from_t, to_t).
+                let p1 = self.ctrl.from();
+                let ctrl = p0.lerp(p1, from_t).lerp(p1.lerp(p3, from_t), to_t);
+                Segment::quadratic(baseline, ctrl)
+            }
+            SegmentKind::Cubic => {
+                // The control points are the blossoms of the curve at (from_t, from_t, to_t) and
+                // (from_t, to_t, to_t).
+                let (p1, p2) = (self.ctrl.from(), self.ctrl.to());
+                let blossom = |u: f32, v: f32, w: f32| {
+                    let (p01, p12, p23) = (p0.lerp(p1, u), p1.lerp(p2, u), p2.lerp(p3, u));
+                    let (p012, p123) = (p01.lerp(p12, v), p12.lerp(p23, v));
+                    p012.lerp(p123, w)
+                };
+                let ctrl = LineSegment2F::new(blossom(from_t, from_t, to_t),
+                                              blossom(from_t, to_t, to_t));
+                Segment::cubic(baseline, ctrl)
+            }
+        }
+    }
+
     pub fn arc_length(&self) -> f32 {
-        // FIXME(pcwalton)
-        self.baseline.vector().length()
+        self.arc_length_between(0.0, 1.0)
     }
 
+    /// Returns the length of the segment between two times.
+    pub fn arc_length_between(&self, from_t: f32, to_t: f32) -> f32 {
+        match self.kind {
+            SegmentKind::None => 0.0,
+            SegmentKind::Line => self.baseline.vector().length() * (to_t - from_t),
+            SegmentKind::Quadratic | SegmentKind::Cubic => {
+                let estimate = self.gauss_legendre_arc_length(from_t, to_t);
+                self.adaptive_arc_length(from_t, to_t, estimate, ARC_LENGTH_TOLERANCE, 0)
+            }
+        }
+    }
+
+    /// Returns the time at which the segment reaches the given length along it, clamped to
+    /// [0, 1].
     pub fn time_for_distance(&self, distance: f32) -> f32 {
-        // FIXME(pcwalton)
-        distance / self.arc_length()
+        let length = self.arc_length();
+        if distance >= length {
+            return 1.0;
+        }
+        if distance <= 0.0 {
+            return 0.0;
+        }
+        if self.is_line() {
+            return distance / length;
+        }
+
+        // Newton's method, falling back to bisection when a step would leave the bracket. The
+        // length up to `t` is kept up to date by integrating over each step alone, which is
+        // negative when the step goes backward.
+        let (mut min_t, mut max_t) = (0.0, 1.0);
+        let mut t = distance / length;
+        let mut length_to_t = self.arc_length_between(0.0, t);
+        for _ in 0..MAX_NEWTON_ITERATIONS {
+            let error = length_to_t - distance;
+            if error.abs() <= ARC_LENGTH_TOLERANCE {
+                break;
+            }
+            if error > 0.0 {
+                max_t = t;
+            } else {
+                min_t = t;
+            }
+
+            let speed = self.derivative(t).length();
+            let next_t = if speed > EPSILON { t - error / speed } else { -1.0 };
+            let next_t = if next_t > min_t && next_t < max_t {
+                next_t
+            } else {
+                (min_t + max_t) * 0.5
+            };
+            length_to_t += self.arc_length_between(t, next_t);
+            t = next_t;
+        }
+        t
+    }
+
+    // Integrates the speed of the curve, splitting in half until the halves agree with the
+    // whole.
+    fn adaptive_arc_length(&self, from_t: f32, to_t: f32, estimate: f32, tolerance: f32, depth: u32)
+                           -> f32 {
+        let mid_t = (from_t + to_t) * 0.5;
+        let (left, right) = (self.gauss_legendre_arc_length(from_t, mid_t),
+                             self.gauss_legendre_arc_length(mid_t, to_t));
+        if depth == MAX_ARC_LENGTH_SUBDIVISIONS || (left + right - estimate).abs() <= tolerance {
+            return left + right;
+        }
+        self.adaptive_arc_length(from_t, mid_t, left, tolerance * 0.5, depth + 1) +
+            self.adaptive_arc_length(mid_t, to_t, right, tolerance * 0.5, depth + 1)
+    }
+
+    fn gauss_legendre_arc_length(&self, from_t: f32, to_t: f32) -> f32 {
+        // Five-point Gauss-Legendre quadrature.
+        const ABSCISSAE: [f32; 5] = [
+            0.0, -0.538_469_3, 0.538_469_3, -0.906_179_8, 0.906_179_8,
+        ];
+        const WEIGHTS: [f32; 5] = [
+            0.568_888_9, 0.478_628_67, 0.478_628_67, 0.236_926_88, 0.236_926_88,
+        ];
+
+        let (half_range, mid_t) = ((to_t - from_t) * 0.5, (from_t + to_t) * 0.5);
+        let mut length = 0.0;
+        for (&abscissa, &weight) in ABSCISSAE.iter().zip(WEIGHTS.iter()) {
+            length += weight * self.derivative(mid_t + half_range * abscissa).length();
+        }
+        length * half_range
+    }
+
+    /// Returns the contribution of this segment to the winding number of `point`.
+    ///
+    /// This counts the crossings of a ray cast from the point toward +x. Curves are split into
+    /// pieces that are monotonic in y, each of which crosses the ray at most once, and the
+    /// crossings are found on the curve itself rather than on a flattened approximation of it.
+    pub fn winding_number_at(&self, point: Vector2F) -> i32 {
+        if self.is_none() {
+            return 0;
+        }
+
+        if self.is_line() {
+            let (from, to) = (self.baseline.from(), self.baseline.to());
+            if (from.y() <= point.y()) == (to.y() <= point.y()) {
+                return 0;
+            }
+            let x = from.x() + (point.y() - from.y()) * (to.x() - from.x()) / (to.y() - from.y());
+            return if x <= point.x() { 0 } else if to.y() > from.y() { 1 } else { -1 };
+        }
+
+        // The curve lies within the bounds of its control points. Quadratics only use the first
+        // one.
+        let ctrl = if self.is_cubic() {
+            self.ctrl
+        } else {
+            LineSegment2F::new(self.ctrl.from(), self.ctrl.from())
+        };
+        let bounds_min_y = f32::min(self.baseline.min_y(), ctrl.min_y());
+        let bounds_max_y = f32::max(self.baseline.max_y(), ctrl.max_y());
+        let bounds_max_x = f32::max(self.baseline.max_x(), ctrl.max_x());
+        if point.y() < bounds_min_y || point.y() > bounds_max_y || point.x() >= bounds_max_x {
+            return 0;
+        }
+
+        let mut times = [0.0; 4];
+        let mut time_count = 1;
+        for &t in self.y_turning_points().iter() {
+            if let Some(t) = t {
+                times[time_count] = t;
+                time_count += 1;
+            }
+        }
+        times[time_count] = 1.0;
+        time_count += 1;
+
+        let mut winding = 0;
+        let mut from_y = self.baseline.from_y();
+        for index in 1..time_count {
+            let (from_t, to_t) = (times[index - 1], times[index]);
+            let to_y = if index + 1 == time_count {
+                self.baseline.to_y()
+            } else {
+                self.sample(to_t).y()
+            };
+
+            // Count pieces that end exactly on the ray on one side only, so that a ray through
+            // the point where two pieces meet crosses just one of them.
+            if (from_y <= point.y()) != (to_y <= point.y()) {
+                let t = self.solve_t_for_y(from_t, to_t, from_y < to_y, point.y());
+                if self.sample(t).x() > point.x() {
+                    winding += if to_y > from_y { 1 } else { -1 };
+                }
+            }
+            from_y = to_y;
+        }
+        winding
+    }
+
+    // Returns the times at which a curve turns around in y, in order.
+    fn y_turning_points(&self) -> [Option<f32>; 2] {
+        let (p0, p1) = (self.baseline.from_y(), self.ctrl.from_y());
+        if self.is_quadratic() {
+            let p2 = self.baseline.to_y();
+            let denom = p0 - 2.0 * p1 + p2;
+            if denom == 0.0 {
+                return [None, None];
+            }
+            return [Some((p0 - p1) / denom).filter(|&t| t > 0.0 && t < 1.0), None];
+        }
+
+        // Solve `a t² + b t + c = 0` for the roots of the derivative of the cubic.
+        let (p2, p3) = (self.ctrl.to_y(), self.baseline.to_y());
+        let (v0, v1, v2) = (p1 - p0, p2 - p1, p3 - p2);
+        let (a, b, c) = (v0 - 2.0 * v1 + v2, 2.0 * (v1 - v0), v0);
+        let mut roots = if a.abs() <= EPSILON * (b.abs() + c.abs()) {
+            if b == 0.0 { [None, None] } else { [Some(-c / b), None] }
+        } else {
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                [None, None]
+            } else {
+                let sqrt_discriminant = discriminant.sqrt();
+                let (t0, t1) = ((-b - sqrt_discriminant) / (2.0 * a),
+                                (-b + sqrt_discriminant) / (2.0 * a));
+                [Some(f32::min(t0, t1)), Some(f32::max(t0, t1))]
+            }
+        };
+        for root in &mut roots {
+            *root = root.filter(|&t| t > 0.0 && t < 1.0);
+        }
+        match roots {
+            [None, Some(t)] => [Some(t), None],
+            roots => roots,
+        }
+    }
+
+    // Finds the time at which a piece of the curve that's monotonic in y reaches `y`.
+    fn solve_t_for_y(&self, mut from_t: f32, mut to_t: f32, increasing: bool, y: f32) -> f32 {
+        const MAX_BISECTION_ITERATIONS: u32 = 32;
+        for _ in 0..MAX_BISECTION_ITERATIONS {
+            let mid_t = util::lerp(from_t, to_t, 0.5);
+            if mid_t <= from_t || mid_t >= to_t {
+                break;
+            }
+            if (self.sample(mid_t).y() <= y) == increasing {
+                from_t = mid_t;
+            } else {
+                to_t = mid_t;
+            }
+        }
+        util::lerp(from_t, to_t, 0.5)
     }
 }
 
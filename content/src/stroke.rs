@@ -10,20 +10,24 @@
 
 //! Utilities for converting path strokes to fills.
 
+use crate::fill::FillRule;
 use crate::outline::{ArcDirection, Contour, Outline, PushSegmentFlags};
 use crate::segment::Segment;
 use pathfinder_geometry::line_segment::LineSegment2F;
 use pathfinder_geometry::rect::RectF;
 use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::util::lerp;
 use pathfinder_geometry::vector::Vector2F;
 use std::f32;
 
 const TOLERANCE: f32 = 0.01;
+const MAX_WIDTH_SUBDIVISIONS: u32 = 8;
 
 pub struct OutlineStrokeToFill<'a> {
     input: &'a Outline,
     output: Outline,
     style: StrokeStyle,
+    variable_width: Option<VariableWidth<'a>>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -47,35 +51,74 @@ pub enum LineJoin {
     Round,
 }
 
+/// Describes a stroke whose width changes along each contour.
+///
+/// The widths that the profile gives are multiplied by the `line_width` of the stroke style, so
+/// that values such as pen pressure can be used directly.
+#[derive(Clone, Copy)]
+pub struct VariableWidth<'a> {
+    pub profile: WidthProfile<'a>,
+    /// The distance over which open contours narrow to a point at their start.
+    pub start_taper: f32,
+    /// The distance over which open contours narrow to a point at their end.
+    pub end_taper: f32,
+}
+
+#[derive(Clone, Copy)]
+pub enum WidthProfile<'a> {
+    /// A function of the index of the contour and the distance along it.
+    Distance(&'a dyn Fn(usize, f32) -> f32),
+    /// A width for each endpoint of each contour, interpolated linearly along the segments in
+    /// between. Missing widths are 1.0.
+    Points(&'a [Vec<f32>]),
+}
+
 impl<'a> OutlineStrokeToFill<'a> {
     #[inline]
     pub fn new(input: &Outline, style: StrokeStyle) -> OutlineStrokeToFill {
-        OutlineStrokeToFill { input, output: Outline::new(), style }
+        OutlineStrokeToFill { input, output: Outline::new(), style, variable_width: None }
+    }
+
+    #[inline]
+    pub fn with_variable_width(input: &'a Outline,
+                               style: StrokeStyle,
+                               variable_width: VariableWidth<'a>)
+                               -> OutlineStrokeToFill<'a> {
+        OutlineStrokeToFill {
+            input,
+            output: Outline::new(),
+            style,
+            variable_width: Some(variable_width),
+        }
     }
 
     pub fn offset(&mut self) {
         let mut new_contours = vec![];
-        for input in &self.input.contours {
+        for (contour_index, input) in self.input.contours.iter().enumerate() {
             let closed = input.closed;
-            let mut stroker = ContourStrokeToFill::new(input,
+            let segments = self.offset_segments(contour_index, input);
+            let (start_width, end_width) = match (segments.first(), segments.last()) {
+                (Some(first), Some(last)) => (first.distance.from * 2.0, last.distance.to * 2.0),
+                _ => (self.style.line_width, self.style.line_width),
+            };
+
+            let mut stroker = ContourStrokeToFill::new(&segments,
                                                        Contour::new(),
-                                                       self.style.line_width * 0.5,
                                                        self.style.line_join);
 
             stroker.offset_forward();
             if closed {
                 self.push_stroked_contour(&mut new_contours, stroker, true);
-                stroker = ContourStrokeToFill::new(input,
+                stroker = ContourStrokeToFill::new(&segments,
                                                    Contour::new(),
-                                                   self.style.line_width * 0.5,
                                                    self.style.line_join);
             } else {
-                self.add_cap(&mut stroker.output);
+                self.add_cap(&mut stroker.output, end_width);
             }
 
             stroker.offset_backward();
             if !closed {
-                self.add_cap(&mut stroker.output);
+                self.add_cap(&mut stroker.output, start_width);
             }
 
             self.push_stroked_contour(&mut new_contours, stroker, closed);
@@ -93,6 +136,72 @@ impl<'a> OutlineStrokeToFill<'a> {
         self.output
     }
 
+    // Splits the contour into segments along which the stroke width changes linearly.
+    fn offset_segments(&self, contour_index: usize, contour: &Contour) -> Vec<OffsetSegment> {
+        let radius = self.style.line_width * 0.5;
+        let variable_width = match self.variable_width {
+            None => {
+                let distance = OffsetDistance { from: radius, to: radius };
+                return contour.iter().map(|segment| OffsetSegment { segment, distance }).collect();
+            }
+            Some(ref variable_width) => variable_width,
+        };
+
+        let segments: Vec<_> = contour.iter().collect();
+        let lengths: Vec<_> = segments.iter().map(|segment| segment.arc_length()).collect();
+        let contour_length: f32 = lengths.iter().sum();
+        let endpoint_count = if contour.closed { segments.len() } else { segments.len() + 1 };
+
+        let mut offset_segments = vec![];
+        let mut start_distance = 0.0;
+        for (segment_index, segment) in segments.iter().enumerate() {
+            let length = lengths[segment_index];
+            let radius_at = |distance: f32| {
+                let scale = match variable_width.profile {
+                    WidthProfile::Distance(width) => width(contour_index, distance),
+                    WidthProfile::Points(widths) => {
+                        let width_at = |endpoint_index: usize| {
+                            widths.get(contour_index)
+                                  .and_then(|widths| widths.get(endpoint_index % endpoint_count))
+                                  .cloned()
+                                  .unwrap_or(1.0)
+                        };
+                        let t = if length > 0.0 {
+                            (distance - start_distance) / length
+                        } else {
+                            0.0
+                        };
+                        lerp(width_at(segment_index), width_at(segment_index + 1), t)
+                    }
+                };
+
+                let mut taper = 1.0;
+                if !contour.closed {
+                    if variable_width.start_taper > 0.0 {
+                        taper = f32::min(taper, distance / variable_width.start_taper);
+                    }
+                    if variable_width.end_taper > 0.0 {
+                        taper = f32::min(taper,
+                                         (contour_length - distance) / variable_width.end_taper);
+                    }
+                }
+                radius * scale * taper.max(0.0)
+            };
+
+            let mut subdivider = WidthSubdivider {
+                segment,
+                start_distance,
+                radius_at: &radius_at,
+                output: &mut offset_segments,
+            };
+            let (from_radius, to_radius) = (radius_at(start_distance),
+                                            radius_at(start_distance + length));
+            subdivider.subdivide(0.0, 1.0, from_radius, to_radius, 0);
+            start_distance += length;
+        }
+        offset_segments
+    }
+
     fn push_stroked_contour(&mut self,
                             new_contours: &mut Vec<Contour>,
                             mut stroker: ContourStrokeToFill,
@@ -101,9 +210,10 @@ impl<'a> OutlineStrokeToFill<'a> {
         if closed && stroker.output.might_need_join(self.style.line_join) {
             let (p1, p0) = (stroker.output.position_of(1), stroker.output.position_of(0));
             let final_segment = LineSegment2F::new(p1, p0);
-            stroker.output.add_join(self.style.line_width * 0.5,
+            let first_segment = &stroker.input[0];
+            stroker.output.add_join(first_segment.distance.from,
                                     self.style.line_join,
-                                    stroker.input.position_of(0),
+                                    first_segment.segment.baseline.from(),
                                     final_segment);
         }
 
@@ -111,13 +221,19 @@ impl<'a> OutlineStrokeToFill<'a> {
         new_contours.push(stroker.output);
     }
 
-    fn add_cap(&mut self, contour: &mut Contour) {
-        if self.style.line_cap == LineCap::Butt || contour.len() < 2 {
+    fn add_cap(&mut self, contour: &mut Contour, width: f32) {
+        // Tapered ends come to a point, which needs no cap.
+        if self.style.line_cap == LineCap::Butt || contour.len() < 2 || width == 0.0 {
             return
         }
 
-        let width = self.style.line_width;
         let (p0, p1) = (contour.position_of_last(2), contour.position_of_last(1));
+
+        // Degenerate segments, such as those that dashing can leave behind, have no direction to
+        // cap in.
+        if p0 == p1 {
+            return;
+        }
         let gradient = (p1 - p0).normalize();
 
         match self.style.line_cap {
@@ -147,17 +263,80 @@ impl<'a> OutlineStrokeToFill<'a> {
     }
 }
 
+// A segment of a contour, along with the distance from it to the edges of its stroke.
+struct OffsetSegment {
+    segment: Segment,
+    distance: OffsetDistance,
+}
+
+// The distance from a segment to the edge of its stroke at its start and end, which is
+// interpolated linearly in between.
+#[derive(Clone, Copy, Debug)]
+struct OffsetDistance {
+    from: f32,
+    to: f32,
+}
+
+impl OffsetDistance {
+    #[inline]
+    fn at(self, t: f32) -> f32 {
+        lerp(self.from, self.to, t)
+    }
+
+    #[inline]
+    fn split(self, t: f32) -> (OffsetDistance, OffsetDistance) {
+        let mid = self.at(t);
+        (OffsetDistance { from: self.from, to: mid }, OffsetDistance { from: mid, to: self.to })
+    }
+
+    #[inline]
+    fn reversed(self) -> OffsetDistance {
+        OffsetDistance { from: self.to, to: self.from }
+    }
+
+    #[inline]
+    fn negated(self) -> OffsetDistance {
+        OffsetDistance { from: -self.from, to: -self.to }
+    }
+}
+
+// Splits a segment until the stroke width is close enough to linear along each piece.
+struct WidthSubdivider<'a, 'b> {
+    segment: &'a Segment,
+    start_distance: f32,
+    radius_at: &'a dyn Fn(f32) -> f32,
+    output: &'b mut Vec<OffsetSegment>,
+}
+
+impl<'a, 'b> WidthSubdivider<'a, 'b> {
+    fn subdivide(&mut self, from_t: f32, to_t: f32, from_radius: f32, to_radius: f32, depth: u32) {
+        let mid_t = lerp(from_t, to_t, 0.5);
+        let mid_distance = self.start_distance + self.segment.arc_length_between(0.0, mid_t);
+        let mid_radius = (self.radius_at)(mid_distance);
+        if depth < MAX_WIDTH_SUBDIVISIONS &&
+                (mid_radius - lerp(from_radius, to_radius, 0.5)).abs() > TOLERANCE {
+            self.subdivide(from_t, mid_t, from_radius, mid_radius, depth + 1);
+            self.subdivide(mid_t, to_t, mid_radius, to_radius, depth + 1);
+            return;
+        }
+
+        self.output.push(OffsetSegment {
+            segment: self.segment.subsegment(from_t, to_t),
+            distance: OffsetDistance { from: from_radius, to: to_radius },
+        });
+    }
+}
+
 struct ContourStrokeToFill<'a> {
-    input: &'a Contour,
+    input: &'a [OffsetSegment],
     output: Contour,
-    radius: f32,
     join: LineJoin,
 }
 
 impl<'a> ContourStrokeToFill<'a> {
     #[inline]
-    fn new(input: &Contour, output: Contour, radius: f32, join: LineJoin) -> ContourStrokeToFill {
-        ContourStrokeToFill { input, output, radius, join }
+    fn new(input: &[OffsetSegment], output: Contour, join: LineJoin) -> ContourStrokeToFill {
+        ContourStrokeToFill { input, output, join }
     }
 
     fn offset_forward(&mut self) {
@@ -165,39 +344,35 @@ impl<'a> ContourStrokeToFill<'a> {
             // FIXME(pcwalton): We negate the radius here so that round end caps can be drawn
             // clockwise. Of course, we should just implement anticlockwise arcs to begin with...
             let join = if segment_index == 0 { LineJoin::Bevel } else { self.join };
-            segment.offset(-self.radius, join, &mut self.output);
+            segment.segment.offset(segment.distance.negated(), join, &mut self.output);
         }
     }
 
     fn offset_backward(&mut self) {
-        let mut segments: Vec<_> = self
-            .input
-            .iter()
-            .map(|segment| segment.reversed())
-            .collect();
-        segments.reverse();
-        for (segment_index, segment) in segments.iter().enumerate() {
+        for (segment_index, segment) in self.input.iter().rev().enumerate() {
             // FIXME(pcwalton): We negate the radius here so that round end caps can be drawn
             // clockwise. Of course, we should just implement anticlockwise arcs to begin with...
             let join = if segment_index == 0 { LineJoin::Bevel } else { self.join };
-            segment.offset(-self.radius, join, &mut self.output);
+            segment.segment.reversed().offset(segment.distance.reversed().negated(),
+                                              join,
+                                              &mut self.output);
         }
     }
 }
 
 trait Offset {
-    fn offset(&self, distance: f32, join: LineJoin, contour: &mut Contour);
+    fn offset(&self, distance: OffsetDistance, join: LineJoin, contour: &mut Contour);
     fn add_to_contour(&self,
-                      distance: f32,
+                      distance: OffsetDistance,
                       join: LineJoin,
                       join_point: Vector2F,
                       contour: &mut Contour);
-    fn offset_once(&self, distance: f32) -> Self;
-    fn error_is_within_tolerance(&self, other: &Segment, distance: f32) -> bool;
+    fn offset_once(&self, distance: OffsetDistance) -> Self;
+    fn error_is_within_tolerance(&self, other: &Segment, distance: OffsetDistance) -> bool;
 }
 
 impl Offset for Segment {
-    fn offset(&self, distance: f32, join: LineJoin, contour: &mut Contour) {
+    fn offset(&self, distance: OffsetDistance, join: LineJoin, contour: &mut Contour) {
         let join_point = self.baseline.from();
         if self.baseline.square_length() < TOLERANCE * TOLERANCE {
             self.add_to_contour(distance, join, join_point, contour);
@@ -214,12 +389,13 @@ impl Offset for Segment {
         debug!("... PRE-SPLIT: {:?}", self);
         let (before, after) = self.split(0.5);
         debug!("... AFTER-SPLIT: {:?} {:?}", before, after);
-        before.offset(distance, join, contour);
-        after.offset(distance, join, contour);
+        let (before_distance, after_distance) = distance.split(0.5);
+        before.offset(before_distance, join, contour);
+        after.offset(after_distance, join, contour);
     }
 
     fn add_to_contour(&self,
-                      distance: f32,
+                      distance: OffsetDistance,
                       join: LineJoin,
                       join_point: Vector2F,
                       contour: &mut Contour) {
@@ -234,7 +410,7 @@ impl Offset for Segment {
                 self.ctrl.from()
             };
 
-            contour.add_join(distance, join, join_point, LineSegment2F::new(p4, p3));
+            contour.add_join(distance.from, join, join_point, LineSegment2F::new(p4, p3));
         }
 
         // Push segment.
@@ -242,16 +418,19 @@ impl Offset for Segment {
         contour.push_segment(self, flags);
     }
 
-    fn offset_once(&self, distance: f32) -> Segment {
+    // The sides of the control polygon are offset by the distance at the times that their
+    // control points correspond to.
+    fn offset_once(&self, distance: OffsetDistance) -> Segment {
         if self.is_line() {
-            return Segment::line(self.baseline.offset(distance));
+            return Segment::line(offset_line(self.baseline, distance.from, distance.to));
         }
 
         if self.is_quadratic() {
             let mut segment_0 = LineSegment2F::new(self.baseline.from(), self.ctrl.from());
             let mut segment_1 = LineSegment2F::new(self.ctrl.from(), self.baseline.to());
-            segment_0 = segment_0.offset(distance);
-            segment_1 = segment_1.offset(distance);
+            let mid_distance = distance.at(0.5);
+            segment_0 = offset_line(segment_0, distance.from, mid_distance);
+            segment_1 = offset_line(segment_1, mid_distance, distance.to);
             let ctrl = match segment_0.intersection_t(segment_1) {
                 Some(t) => segment_0.sample(t),
                 None => segment_0.to().lerp(segment_1.from(), 0.5),
@@ -262,11 +441,13 @@ impl Offset for Segment {
 
         debug_assert!(self.is_cubic());
 
+        let (distance_1, distance_2) = (distance.at(1.0 / 3.0), distance.at(2.0 / 3.0));
+
         if self.baseline.from() == self.ctrl.from() {
             let mut segment_0 = LineSegment2F::new(self.baseline.from(), self.ctrl.to());
             let mut segment_1 = LineSegment2F::new(self.ctrl.to(), self.baseline.to());
-            segment_0 = segment_0.offset(distance);
-            segment_1 = segment_1.offset(distance);
+            segment_0 = offset_line(segment_0, distance.from, distance_2);
+            segment_1 = offset_line(segment_1, distance_2, distance.to);
             let ctrl = match segment_0.intersection_t(segment_1) {
                 Some(t) => segment_0.sample(t),
                 None => segment_0.to().lerp(segment_1.from(), 0.5),
@@ -279,8 +460,8 @@ impl Offset for Segment {
         if self.ctrl.to() == self.baseline.to() {
             let mut segment_0 = LineSegment2F::new(self.baseline.from(), self.ctrl.from());
             let mut segment_1 = LineSegment2F::new(self.ctrl.from(), self.baseline.to());
-            segment_0 = segment_0.offset(distance);
-            segment_1 = segment_1.offset(distance);
+            segment_0 = offset_line(segment_0, distance.from, distance_1);
+            segment_1 = offset_line(segment_1, distance_1, distance.to);
             let ctrl = match segment_0.intersection_t(segment_1) {
                 Some(t) => segment_0.sample(t),
                 None => segment_0.to().lerp(segment_1.from(), 0.5),
@@ -293,9 +474,9 @@ impl Offset for Segment {
         let mut segment_0 = LineSegment2F::new(self.baseline.from(), self.ctrl.from());
         let mut segment_1 = LineSegment2F::new(self.ctrl.from(), self.ctrl.to());
         let mut segment_2 = LineSegment2F::new(self.ctrl.to(), self.baseline.to());
-        segment_0 = segment_0.offset(distance);
-        segment_1 = segment_1.offset(distance);
-        segment_2 = segment_2.offset(distance);
+        segment_0 = offset_line(segment_0, distance.from, distance_1);
+        segment_1 = offset_line(segment_1, distance_1, distance_2);
+        segment_2 = offset_line(segment_2, distance_2, distance.to);
         let (ctrl_0, ctrl_1) = match (
             segment_0.intersection_t(segment_1),
             segment_1.intersection_t(segment_2),
@@ -311,16 +492,16 @@ impl Offset for Segment {
         Segment::cubic(baseline, ctrl)
     }
 
-    fn error_is_within_tolerance(&self, other: &Segment, distance: f32) -> bool {
-        let (mut min, mut max) = (
-            f32::abs(distance) - TOLERANCE,
-            f32::abs(distance) + TOLERANCE,
-        );
-        min = if min <= 0.0 { 0.0 } else { min * min };
-        max = if max <= 0.0 { 0.0 } else { max * max };
-
+    fn error_is_within_tolerance(&self, other: &Segment, distance: OffsetDistance) -> bool {
         for t_num in 0..(SAMPLE_COUNT + 1) {
             let t = t_num as f32 / SAMPLE_COUNT as f32;
+            let (mut min, mut max) = (
+                f32::abs(distance.at(t)) - TOLERANCE,
+                f32::abs(distance.at(t)) + TOLERANCE,
+            );
+            min = if min <= 0.0 { 0.0 } else { min * min };
+            max = if max <= 0.0 { 0.0 } else { max * max };
+
             // FIXME(pcwalton): Use signed distance!
             let (this_p, other_p) = (self.sample(t), other.sample(t));
             let vector = this_p - other_p;
@@ -340,6 +521,38 @@ impl Offset for Segment {
     }
 }
 
+// Offsets a line by distances that change linearly from one end to the other.
+fn offset_line(line: LineSegment2F, from_distance: f32, to_distance: f32) -> LineSegment2F {
+    if from_distance == to_distance || line.is_zero_length() {
+        return line.offset(from_distance);
+    }
+    let normal = line.vector().yx().normalize().scale_xy(Vector2F::new(-1.0, 1.0));
+    LineSegment2F::new(line.from() + normal.scale(from_distance),
+                       line.to() + normal.scale(to_distance))
+}
+
+impl Outline {
+    /// Returns true if `point` lies within the stroke of this outline.
+    ///
+    /// The stroke is converted to a fill first, so that caps and joins are hit exactly where
+    /// they're drawn.
+    pub fn stroke_contains_point(&self, point: Vector2F, style: StrokeStyle) -> bool {
+        // Square caps and miter joins reach furthest from the outline.
+        let half_width = style.line_width * 0.5;
+        let reach = match style.line_join {
+            LineJoin::Miter(miter_limit) => half_width * f32::max(miter_limit, f32::consts::SQRT_2),
+            LineJoin::Bevel | LineJoin::Round => half_width * f32::consts::SQRT_2,
+        };
+        if !self.bounds.dilate(Vector2F::splat(reach)).contains_point(point) {
+            return false;
+        }
+
+        let mut stroke_to_fill = OutlineStrokeToFill::new(self, style);
+        stroke_to_fill.offset();
+        stroke_to_fill.into_outline().contains_point(point, FillRule::Winding)
+    }
+}
+
 impl Contour {
     fn might_need_join(&self, join: LineJoin) -> bool {
         if self.len() < 2 {
@@ -403,3 +616,100 @@ impl Default for LineJoin {
     #[inline]
     fn default() -> LineJoin { LineJoin::Miter(10.0) }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::dash::OutlineDash;
+    use crate::fill::FillRule;
+    use crate::outline::{Contour, Outline};
+    use pathfinder_geometry::vector::Vector2F;
+    use super::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle, VariableWidth, WidthProfile};
+
+    fn horizontal_line(length: f32) -> Outline {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.0, 0.0));
+        contour.push_endpoint(Vector2F::new(length, 0.0));
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        outline
+    }
+
+    fn style() -> StrokeStyle {
+        StrokeStyle { line_width: 2.0, line_cap: LineCap::Butt, line_join: LineJoin::Miter(4.0) }
+    }
+
+    #[test]
+    fn test_width_from_points() {
+        let input = horizontal_line(10.0);
+        let widths = [vec![1.0, 3.0]];
+        let variable_width = VariableWidth {
+            profile: WidthProfile::Points(&widths),
+            start_taper: 0.0,
+            end_taper: 0.0,
+        };
+        let mut stroke_to_fill = OutlineStrokeToFill::with_variable_width(&input,
+                                                                          style(),
+                                                                          variable_width);
+        stroke_to_fill.offset();
+        let output = stroke_to_fill.into_outline();
+
+        // The half width grows from 1 to 3.
+        assert!(output.contains_point(Vector2F::new(0.5, 0.9), FillRule::Winding));
+        assert!(!output.contains_point(Vector2F::new(0.5, 1.3), FillRule::Winding));
+        assert!(output.contains_point(Vector2F::new(9.5, -2.8), FillRule::Winding));
+        assert!(!output.contains_point(Vector2F::new(9.5, -3.0), FillRule::Winding));
+    }
+
+    #[test]
+    fn test_taper() {
+        let input = horizontal_line(100.0);
+        let pressure = |_: usize, _: f32| 2.0;
+        let variable_width = VariableWidth {
+            profile: WidthProfile::Distance(&pressure),
+            start_taper: 20.0,
+            end_taper: 0.0,
+        };
+        let style = StrokeStyle { line_cap: LineCap::Round, ..style() };
+        let mut stroke_to_fill = OutlineStrokeToFill::with_variable_width(&input,
+                                                                          style,
+                                                                          variable_width);
+        stroke_to_fill.offset();
+        let output = stroke_to_fill.into_outline();
+
+        // The stroke comes to a point at the start, and is at its full half width of 2 after
+        // the taper, including its round cap at the end.
+        assert!(!output.contains_point(Vector2F::new(1.0, 0.5), FillRule::Winding));
+        assert!(output.contains_point(Vector2F::new(10.0, 0.9), FillRule::Winding));
+        assert!(!output.contains_point(Vector2F::new(10.0, 1.1), FillRule::Winding));
+        assert!(output.contains_point(Vector2F::new(50.0, 1.9), FillRule::Winding));
+        assert!(output.contains_point(Vector2F::new(101.5, 0.0), FillRule::Winding));
+    }
+
+    #[test]
+    fn test_dash_ending_in_degenerate_segment() {
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(0.0, 0.0));
+        contour.push_endpoint(Vector2F::new(10.0, 0.0));
+        contour.push_endpoint(Vector2F::new(10.0, 0.0));
+        let mut input = Outline::new();
+        input.push_contour(contour);
+
+        let dashes = [3.0, 1.0];
+        let mut dash = OutlineDash::new(&input, &dashes, 0.0);
+        dash.dash();
+        let dashed = dash.into_outline();
+
+        for &line_cap in &[LineCap::Square, LineCap::Round] {
+            let style = StrokeStyle { line_cap, ..style() };
+            let mut stroke_to_fill = OutlineStrokeToFill::new(&dashed, style);
+            stroke_to_fill.offset();
+            let output = stroke_to_fill.into_outline();
+            for contour in output.contours() {
+                for point_index in 0..contour.len() {
+                    let point = contour.position_of(point_index);
+                    assert!(!point.x().is_nan() && !point.y().is_nan());
+                }
+            }
+        }
+    }
+}
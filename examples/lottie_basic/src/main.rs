@@ -8,9 +8,22 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-//! Experimental example for reading Lottie animations. This is very incomplete.
+//! Experimental example for rendering Lottie animations. This is very incomplete.
 
-use pathfinder_lottie::Lottie;
+use pathfinder_color::ColorF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::{Vector2F, Vector2I};
+use pathfinder_gl::{GLDevice, GLVersion};
+use pathfinder_gpu::resources::FilesystemResourceLoader;
+use pathfinder_lottie::{BuiltLottie, Lottie};
+use pathfinder_renderer::concurrent::rayon::RayonExecutor;
+use pathfinder_renderer::concurrent::scene_proxy::SceneProxy;
+use pathfinder_renderer::gpu::options::{DestFramebuffer, RendererOptions};
+use pathfinder_renderer::gpu::renderer::Renderer;
+use pathfinder_renderer::options::{BuildOptions, RenderTransform};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::video::GLProfile;
 use std::env;
 use std::fs::File;
 use std::io::BufReader;
@@ -19,5 +32,60 @@ fn main() {
     let path = env::args().skip(1).next().unwrap();
     let file = BufReader::new(File::open(path).unwrap());
     let lottie = Lottie::from_reader(file).unwrap();
-    println!("{:#?}", lottie);
+
+    // Set up SDL2.
+    let sdl_context = sdl2::init().unwrap();
+    let video = sdl_context.video().unwrap();
+
+    // Make sure we have at least a GL 3.0 context. Pathfinder requires this.
+    let gl_attributes = video.gl_attr();
+    gl_attributes.set_context_profile(GLProfile::Core);
+    gl_attributes.set_context_version(3, 3);
+
+    // Open a window.
+    let window_size = Vector2I::new(lottie.width as i32, lottie.height as i32);
+    let window = video.window("Lottie example", window_size.x() as u32, window_size.y() as u32)
+        .opengl()
+        .allow_highdpi()
+        .build()
+        .unwrap();
+
+    let pixel_size = Vector2I::new(
+        window.drawable_size().0 as i32,
+        window.drawable_size().1 as i32
+    );
+    let device_pixel_ratio = pixel_size.x() as f32 / window_size.x() as f32;
+
+    // Create the GL context, and make it current.
+    let gl_context = window.gl_create_context().unwrap();
+    gl::load_with(|name| video.gl_get_proc_address(name) as *const _);
+    window.gl_make_current(&gl_context).unwrap();
+
+    // Create a Pathfinder renderer.
+    let mut renderer = Renderer::new(
+        GLDevice::new(GLVersion::GL3, 0),
+        &FilesystemResourceLoader::locate(),
+        DestFramebuffer::full_window(pixel_size),
+        RendererOptions { background_color: Some(ColorF::white()) }
+    );
+
+    // Build the first frame of the composition.
+    let BuiltLottie { scene, .. } = BuiltLottie::from_lottie(&lottie, lottie.in_point);
+
+    // Render the scene to the screen.
+    let scene = SceneProxy::from_scene(scene, RayonExecutor);
+    let mut build_options = BuildOptions::default();
+    let scale_transform = Transform2F::from_scale(Vector2F::splat(device_pixel_ratio));
+    build_options.transform = RenderTransform::Transform2D(scale_transform);
+    scene.build_and_render(&mut renderer, build_options);
+
+    window.gl_swap_window();
+    // Wait for a keypress.
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    loop {
+        match event_pump.wait_event() {
+            Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return,
+            _ => {}
+        }
+    }
 }
@@ -21,7 +21,7 @@ use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::video::GLProfile;
 use pathfinder_renderer::scene::Scene;
-use pathfinder_swf::{draw_paths_into_scene, process_swf_tags};
+use pathfinder_swf::{draw_frame_into_scene, process_swf_tags, BuiltSWF};
 use std::env;
 use std::fs::read;
 use pathfinder_geometry::transform2d::Transform2F;
@@ -77,7 +77,7 @@ fn main() {
     // process swf scene
     // TODO(jon): Since swf is a streaming format, this really wants to be a lazy iterator over
     // swf frames eventually.
-    let (library, stage) = process_swf_tags(&movie);
+    let BuiltSWF { library, stage, .. } = process_swf_tags(&movie).unwrap();
 
     // Open a window.
     let window_size = Vector2I::new(stage.width(), stage.height());
@@ -113,7 +113,7 @@ fn main() {
             stage.width() as f32 * device_pixel_ratio,
             stage.height() as f32 * device_pixel_ratio)
     ));
-    draw_paths_into_scene(&library, &mut scene);
+    draw_frame_into_scene(&library, &stage, 0, &mut scene);
 
     // Render the canvas to screen.
     let scene = SceneProxy::from_scene(scene, RayonExecutor);
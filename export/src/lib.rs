@@ -8,15 +8,29 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use pathfinder_content::effects::BlendMode;
+use pathfinder_content::fill::FillRule;
+use pathfinder_content::outline::Outline;
+use pathfinder_content::pattern::{PatternSource, Repeat};
 use pathfinder_content::segment::SegmentKind;
 use pathfinder_renderer::paint::Paint;
 use pathfinder_renderer::scene::Scene;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
 use pathfinder_geometry::vector::Vector2F;
+use std::collections::HashMap;
 use std::fmt;
 use std::io::{self, Write};
+use std::iter;
+use std::time::SystemTime;
 
 mod pdf;
+mod svg;
+
 use pdf::Pdf;
+use svg::export_svg;
+
+pub use pdf::PageLabelStyle;
 
 pub enum FileFormat {
     /// Scalable Vector Graphics
@@ -43,29 +57,75 @@ impl Export for Scene {
     }
 }
 
-fn export_svg<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
-    let view_box = scene.view_box();
-    writeln!(
-        writer,
-        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">",
-        view_box.origin().x(),
-        view_box.origin().y(),
-        view_box.size().x(),
-        view_box.size().y()
-    )?;
-    for (paint, outline, name) in scene.paths() {
-        write!(writer, "    <path")?;
-        if !name.is_empty() {
-            write!(writer, " id=\"{}\"", name)?;
+/// Writes a sequence of scenes as the pages of one PDF document.
+///
+/// Each page's media box is the view box of its scene.
+#[derive(Clone, Default, Debug)]
+pub struct PdfDocument {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub creation_date: Option<SystemTime>,
+    /// Ranges of page labels. Pages before the first range are numbered 1, 2, 3, ...
+    pub page_labels: Vec<PageLabels>,
+}
+
+/// Labels for the pages starting at `start_page` (counting from zero) and continuing up to the
+/// start of the next range.
+///
+/// Each label is `prefix` followed by the page's number in `style`, if any, where the first page
+/// of the range is number `first_number`.
+#[derive(Clone, Debug)]
+pub struct PageLabels {
+    pub start_page: usize,
+    pub style: Option<PageLabelStyle>,
+    pub prefix: String,
+    pub first_number: u32,
+}
+
+impl PdfDocument {
+    pub fn export<'a, I, W>(&self, scenes: I, writer: &mut W) -> io::Result<()>
+                            where I: IntoIterator<Item = &'a Scene>, W: Write {
+        let mut pdf = Pdf::new();
+        if let Some(ref title) = self.title {
+            pdf.set_info("Title", title);
+        }
+        if let Some(ref author) = self.author {
+            pdf.set_info("Author", author);
+        }
+        if let Some(creation_date) = self.creation_date {
+            pdf.set_info_date("CreationDate", creation_date);
+        }
+        for labels in &self.page_labels {
+            pdf.add_page_labels(labels.start_page,
+                                labels.style,
+                                &labels.prefix,
+                                labels.first_number);
+        }
+
+        for scene in scenes {
+            draw_pdf_page(&mut pdf, scene);
         }
-        writeln!(writer, " fill=\"{:?}\" d=\"{:?}\" />", paint, outline)?;
+        pdf.write_to(writer)
     }
-    writeln!(writer, "</svg>")?;
-    Ok(())
 }
-    
+
+impl Default for PageLabels {
+    #[inline]
+    fn default() -> PageLabels {
+        PageLabels {
+            start_page: 0,
+            style: Some(PageLabelStyle::Decimal),
+            prefix: String::new(),
+            first_number: 1,
+        }
+    }
+}
+
 fn export_pdf<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
-    let mut pdf = Pdf::new();
+    PdfDocument::default().export(iter::once(scene), writer)
+}
+
+fn draw_pdf_page(pdf: &mut Pdf, scene: &Scene) {
     let view_box = scene.view_box();
     pdf.add_page(view_box.size());
     
@@ -74,58 +134,125 @@ fn export_pdf<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
         let r = v - view_box.origin();
         Vector2F::new(r.x(), height - r.y())
     };
-    
-    for (paint, outline, _) in scene.paths() {
-        match paint {
-            Paint::Color(color) => pdf.set_fill_color(*color),
-            Paint::Gradient(_) => {
-                // TODO(pcwalton): Gradients.
+    // The same mapping as `tr`, for gradients and patterns.
+    let page_transform = Transform2F::row_major(1.0, 0.0, 0.0, -1.0,
+                                                -view_box.origin_x(),
+                                                height + view_box.origin_y());
+
+    let mut patterns = HashMap::new();
+    for draw_path in scene.paths() {
+        let paint = scene.get_paint(draw_path.paint());
+        let fill_pattern = match paint {
+            Paint::Color(_) => None,
+            Paint::Gradient(gradient) => {
+                // A gradient without stops is transparent.
+                if gradient.stops().is_empty() {
+                    continue;
+                }
+                Some(*patterns.entry(paint).or_insert_with(|| {
+                    pdf.add_gradient_pattern(gradient, page_transform)
+                }))
             }
-            Paint::Pattern(_) => {
-                // TODO(pcwalton): Patterns.
+            Paint::Pattern(pattern) => {
+                match pattern.source {
+                    PatternSource::Image(ref image) => {
+                        let size = image.size().to_f32();
+                        if size.x() <= 0.0 || size.y() <= 0.0 {
+                            continue;
+                        }
+                        let step = pattern_step(size, pattern.repeat, view_box);
+                        Some(*patterns.entry(paint).or_insert_with(|| {
+                            pdf.add_image_pattern(image, step, page_transform)
+                        }))
+                    }
+                    PatternSource::RenderTarget(_) => {
+                        // TODO: Render target patterns.
+                        continue;
+                    }
+                }
             }
+        };
+
+        pdf.save_state();
+        if let Some(clip_path_id) = draw_path.clip_path() {
+            let clip_path = scene.get_clip_path(clip_path_id);
+            write_pdf_outline(pdf, clip_path.outline(), &tr);
+            pdf.clip(clip_path.fill_rule());
+        }
+        if draw_path.blend_mode() != BlendMode::SrcOver {
+            pdf.set_blend_mode(draw_path.blend_mode());
+        }
+        match (paint, fill_pattern) {
+            (_, Some(pattern)) => pdf.set_fill_pattern(pattern),
+            (Paint::Color(color), None) => pdf.set_fill_color(*color),
+            _ => {}
         }
-        
-        for contour in outline.contours() {
-            for (segment_index, segment) in contour.iter().enumerate() {
-                if segment_index == 0 {
-                    pdf.move_to(tr(segment.baseline.from()));
-                }
 
-                match segment.kind {
-                    SegmentKind::None => {}
-                    SegmentKind::Line => pdf.line_to(tr(segment.baseline.to())),
-                    SegmentKind::Quadratic => {
-                        let current = segment.baseline.from();
-                        let c = segment.ctrl.from();
-                        let p = segment.baseline.to();
-                        let c1 = Vector2F::splat(2./3.) * c + Vector2F::splat(1./3.) * current;
-                        let c2 = Vector2F::splat(2./3.) * c + Vector2F::splat(1./3.) * p;
-                        pdf.cubic_to(c1, c2, p);
-                    }
-                    SegmentKind::Cubic => pdf.cubic_to(tr(segment.ctrl.from()), tr(segment.ctrl.to()), tr(segment.baseline.to()))
-                }
+        write_pdf_outline(pdf, draw_path.outline(), &tr);
+        // closes implicitly
+        pdf.fill(draw_path.fill_rule());
+        pdf.restore_state();
+    }
+}
+
+// Returns the distance between the tiles of an image pattern. In directions that don't repeat, the
+// tiles are spaced far enough apart that only the one at the origin touches the view box.
+fn pattern_step(image_size: Vector2F, repeat: Repeat, view_box: RectF) -> Vector2F {
+    let x = if repeat.contains(Repeat::X) {
+        image_size.x()
+    } else {
+        image_size.x() + view_box.min_x().abs() + view_box.max_x().abs()
+    };
+    let y = if repeat.contains(Repeat::Y) {
+        image_size.y()
+    } else {
+        image_size.y() + view_box.min_y().abs() + view_box.max_y().abs()
+    };
+    Vector2F::new(x, y)
+}
+
+fn write_pdf_outline<F>(pdf: &mut Pdf, outline: &Outline, tr: &F)
+                        where F: Fn(Vector2F) -> Vector2F {
+    for contour in outline.contours() {
+        for (segment_index, segment) in contour.iter().enumerate() {
+            if segment_index == 0 {
+                pdf.move_to(tr(segment.baseline.from()));
             }
 
-            if contour.is_closed() {
-                pdf.close();
+            match segment.kind {
+                SegmentKind::None => {}
+                SegmentKind::Line => pdf.line_to(tr(segment.baseline.to())),
+                SegmentKind::Quadratic => {
+                    let current = segment.baseline.from();
+                    let c = segment.ctrl.from();
+                    let p = segment.baseline.to();
+                    let c1 = Vector2F::splat(2./3.) * c + Vector2F::splat(1./3.) * current;
+                    let c2 = Vector2F::splat(2./3.) * c + Vector2F::splat(1./3.) * p;
+                    pdf.cubic_to(tr(c1), tr(c2), tr(p));
+                }
+                SegmentKind::Cubic => {
+                    pdf.cubic_to(tr(segment.ctrl.from()),
+                                 tr(segment.ctrl.to()),
+                                 tr(segment.baseline.to()))
+                }
             }
         }
-        
-        // closes implicitly
-        pdf.fill();
+
+        if contour.is_closed() {
+            pdf.close();
+        }
     }
-    pdf.write_to(writer)
 }
     
-fn export_ps<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
-    struct P(Vector2F);
-    impl fmt::Display for P {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            write!(f, "{} {}", self.0.x(), self.0.y())
-        }
+struct P(Vector2F);
+
+impl fmt::Display for P {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.0.x(), self.0.y())
     }
+}
 
+fn export_ps<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
     let view_box = scene.view_box();
     writeln!(writer, "%!PS-Adobe-3.0 EPSF-3.0")?;
     writeln!(writer, "%%BoundingBox: {:.0} {:.0}",
@@ -139,50 +266,49 @@ fn export_ps<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
     writeln!(writer, "0 {} translate", view_box.size().y())?;
     writeln!(writer, "1 -1 scale")?;
     
-    for (paint, outline, name) in scene.paths() {
-        if !name.is_empty() {
-            writeln!(writer, "newpath % {}", name)?;
-        } else {
+    for draw_path in scene.paths() {
+        let (paint, name) = (scene.get_paint(draw_path.paint()), draw_path.name());
+        writeln!(writer, "gsave")?;
+
+        if let Some(clip_path_id) = draw_path.clip_path() {
+            let clip_path = scene.get_clip_path(clip_path_id);
             writeln!(writer, "newpath")?;
+            write_ps_outline(writer, clip_path.outline())?;
+            match clip_path.fill_rule() {
+                FillRule::Winding => writeln!(writer, "clip")?,
+                FillRule::EvenOdd => writeln!(writer, "eoclip")?,
+            }
         }
 
-        for contour in outline.contours() {
-            for (segment_index, segment) in contour.iter().enumerate() {
-                if segment_index == 0 {
-                    writeln!(writer, "{} moveto", P(segment.baseline.from()))?;
-                }
-
-                match segment.kind {
-                    SegmentKind::None => {}
-                    SegmentKind::Line => {
-                        writeln!(writer, "{} lineto", P(segment.baseline.to()))?;
-                    }
-                    SegmentKind::Quadratic => {
-                        let current = segment.baseline.from();
-                        let c = segment.ctrl.from();
-                        let p = segment.baseline.to();
-                        let c1 = Vector2F::splat(2. / 3.) * c + Vector2F::splat(1. / 3.) * current;
-                        let c2 = Vector2F::splat(2. / 3.) * c + Vector2F::splat(1. / 3.) * p;
-                        writeln!(writer, "{} {} {} curveto", P(c1), P(c2), P(p))?;
-                    }
-                    SegmentKind::Cubic => {
-                        writeln!(writer, "{} {} {} curveto",
-                            P(segment.ctrl.from()),
-                            P(segment.ctrl.to()),
-                            P(segment.baseline.to())
-                        )?;
-                    }
-                }
+        // PostScript has no blend modes; it paints opaquely onto white paper. So modes that erase
+        // paint white, and the rest are drawn with source-over and noted in a comment.
+        let erases = match draw_path.blend_mode() {
+            BlendMode::SrcOver => false,
+            BlendMode::Clear | BlendMode::DestOut => true,
+            blend_mode => {
+                writeln!(writer,
+                         "% unsupported blend mode {:?}, drawn as source-over",
+                         blend_mode)?;
+                false
             }
+        };
 
-            if contour.is_closed() {
-                writeln!(writer, "closepath")?;
-            }
+        if !name.is_empty() {
+            writeln!(writer, "newpath % {}", name)?;
+        } else {
+            writeln!(writer, "newpath")?;
         }
+        write_ps_outline(writer, draw_path.outline())?;
 
         match paint {
+            _ if erases => writeln!(writer, "1 1 1 setrgbcolor")?,
             Paint::Color(color) => {
-                writeln!(writer, "{} {} {} setrgbcolor", color.r, color.g, color.b)?;
+                let norm = |channel| f32::from(channel) / 255.0;
+                writeln!(writer,
+                         "{} {} {} setrgbcolor",
+                         norm(color.r),
+                         norm(color.g),
+                         norm(color.b))?;
             }
             Paint::Gradient(_) => {
                 // TODO(pcwalton): Gradients.
@@ -192,10 +318,133 @@ fn export_ps<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
             }
         }
 
-        writeln!(writer, "fill")?;
+        match draw_path.fill_rule() {
+            FillRule::Winding => writeln!(writer, "fill")?,
+            FillRule::EvenOdd => writeln!(writer, "eofill")?,
+        }
+        writeln!(writer, "grestore")?;
     }
     writeln!(writer, "showpage")?;
     Ok(())
 }
 
+fn write_ps_outline<W: Write>(writer: &mut W, outline: &Outline) -> io::Result<()> {
+    for contour in outline.contours() {
+        for (segment_index, segment) in contour.iter().enumerate() {
+            if segment_index == 0 {
+                writeln!(writer, "{} moveto", P(segment.baseline.from()))?;
+            }
 
+            match segment.kind {
+                SegmentKind::None => {}
+                SegmentKind::Line => {
+                    writeln!(writer, "{} lineto", P(segment.baseline.to()))?;
+                }
+                SegmentKind::Quadratic => {
+                    let current = segment.baseline.from();
+                    let c = segment.ctrl.from();
+                    let p = segment.baseline.to();
+                    let c1 = Vector2F::splat(2. / 3.) * c + Vector2F::splat(1. / 3.) * current;
+                    let c2 = Vector2F::splat(2. / 3.) * c + Vector2F::splat(1. / 3.) * p;
+                    writeln!(writer, "{} {} {} curveto", P(c1), P(c2), P(p))?;
+                }
+                SegmentKind::Cubic => {
+                    writeln!(writer, "{} {} {} curveto",
+                        P(segment.ctrl.from()),
+                        P(segment.ctrl.to()),
+                        P(segment.baseline.to())
+                    )?;
+                }
+            }
+        }
+
+        if contour.is_closed() {
+            writeln!(writer, "closepath")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use pathfinder_color::ColorU;
+    use pathfinder_content::effects::BlendMode;
+    use pathfinder_content::fill::FillRule;
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::Vector2F;
+    use pathfinder_renderer::paint::Paint;
+    use pathfinder_renderer::scene::{ClipPath, DrawPath, Scene};
+    use super::export_ps;
+
+    fn export(scene: &Scene) -> String {
+        let mut output = vec![];
+        export_ps(scene, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn scene_with_path(clip_fill_rule: Option<FillRule>,
+                       fill_rule: FillRule,
+                       blend_mode: BlendMode)
+                       -> Scene {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(100.0)));
+
+        let clip_path = clip_fill_rule.map(|fill_rule| {
+            let outline = Outline::from_rect(RectF::new(Vector2F::splat(10.0),
+                                                        Vector2F::splat(50.0)));
+            scene.push_clip_path(ClipPath::new(outline, fill_rule, String::new()))
+        });
+        let paint = scene.push_paint(&Paint::Color(ColorU::new(255, 0, 51, 255)));
+        let outline = Outline::from_rect(RectF::new(Vector2F::splat(20.0), Vector2F::splat(60.0)));
+        scene.push_path(DrawPath::new(outline,
+                                      paint,
+                                      clip_path,
+                                      fill_rule,
+                                      blend_mode,
+                                      "square".to_owned()));
+        scene
+    }
+
+    #[test]
+    fn test_fill() {
+        let ps = export(&scene_with_path(None, FillRule::Winding, BlendMode::SrcOver));
+        assert!(ps.contains("gsave\nnewpath % square\n20 20 moveto\n"));
+        assert!(ps.contains("closepath\n1 0 0.2 setrgbcolor\nfill\ngrestore\n"));
+        assert!(!ps.contains("clip"));
+        assert!(!ps.contains("blend mode"));
+    }
+
+    #[test]
+    fn test_clip() {
+        let ps = export(&scene_with_path(Some(FillRule::Winding),
+                                         FillRule::Winding,
+                                         BlendMode::SrcOver));
+        assert!(ps.contains("gsave\nnewpath\n10 10 moveto\n60 10 lineto\n"));
+        assert!(ps.contains("closepath\nclip\nnewpath % square\n"));
+        assert!(!ps.contains("eoclip"));
+    }
+
+    #[test]
+    fn test_eoclip_and_eofill() {
+        let ps = export(&scene_with_path(Some(FillRule::EvenOdd),
+                                         FillRule::EvenOdd,
+                                         BlendMode::SrcOver));
+        assert!(ps.contains("closepath\neoclip\nnewpath % square\n"));
+        assert!(ps.contains("setrgbcolor\neofill\ngrestore\n"));
+        assert!(!ps.contains("\nclip\n"));
+        assert!(!ps.contains("\nfill\n"));
+    }
+
+    #[test]
+    fn test_blend_modes() {
+        // Erasing paints the white of the paper.
+        let ps = export(&scene_with_path(None, FillRule::Winding, BlendMode::DestOut));
+        assert!(ps.contains("closepath\n1 1 1 setrgbcolor\nfill\n"));
+        assert!(!ps.contains("blend mode"));
+
+        let ps = export(&scene_with_path(None, FillRule::Winding, BlendMode::Lighten));
+        assert!(ps.contains("gsave\n% unsupported blend mode Lighten, drawn as source-over\n"));
+        assert!(ps.contains("1 0 0.2 setrgbcolor\nfill\n"));
+    }
+}
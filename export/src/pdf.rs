@@ -13,8 +13,15 @@
 
 use deflate::Compression;
 use pathfinder_color::ColorU;
+use pathfinder_content::effects::BlendMode;
+use pathfinder_content::fill::FillRule;
+use pathfinder_content::gradient::{ColorStop, Gradient, GradientGeometry};
+use pathfinder_content::pattern::Image;
+use pathfinder_geometry::transform2d::Transform2F;
 use pathfinder_geometry::vector::Vector2F;
+use std::fmt;
 use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 struct Counter<T> {
     inner: T,
@@ -56,15 +63,26 @@ impl<W: Write> Write for Counter<W> {
 struct PdfObject {
     contents: Vec<u8>,
     is_page: bool,
-    is_xobject: bool,
     offset: Option<u64>,
 }
 
+/// A pattern object that can be used to fill paths
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PatternId(usize);
+
 /// The top-level struct that represents a (partially) in-memory PDF file
 pub struct Pdf {
     page_buffer: Vec<u8>,
     objects: Vec<PdfObject>,
     page_size: Option<Vector2F>,
+    page_patterns: Vec<PatternId>,
+    page_graphics_states: Vec<usize>,
+    graphics_states: Vec<(BlendMode, usize)>,
+    // The graphics states that apply the soft masks of translucent gradient patterns.
+    pattern_soft_masks: Vec<(PatternId, usize)>,
+    info: Vec<u8>,
+    // The starting page of each range of page labels, and its label dictionary.
+    page_labels: Vec<(usize, String)>,
     compression: Option<Compression>,
 }
 
@@ -84,31 +102,97 @@ impl Pdf {
                 PdfObject {
                     contents: Vec::new(),
                     is_page: false,
-                    is_xobject: false,
                     offset: None,
                 },
                 PdfObject {
                     contents: Vec::new(),
                     is_page: false,
-                    is_xobject: false,
                     offset: None,
                 },
             ],
             page_size: None,
+            page_patterns: Vec::new(),
+            page_graphics_states: Vec::new(),
+            graphics_states: Vec::new(),
+            pattern_soft_masks: Vec::new(),
+            info: Vec::new(),
+            page_labels: Vec::new(),
             compression: Some(Compression::Fast)
         }
     }
 
-    fn add_object(&mut self, data: Vec<u8>, is_page: bool, is_xobject: bool) -> usize {
+    fn add_object(&mut self, data: Vec<u8>, is_page: bool) -> usize {
         self.objects.push(PdfObject {
             contents: data,
             is_page,
-            is_xobject,
             offset: None,
         });
         self.objects.len()
     }
 
+    /// Build a stream object from its dictionary entries and (uncompressed) data
+    fn make_stream(&self, entries: &str, data: &[u8]) -> Vec<u8> {
+        let mut stream = if let Some(level) = self.compression {
+            let compressed = deflate::deflate_bytes_zlib_conf(data, level);
+            let mut stream = format!(
+                "<< {} /Length {} /Filter [/FlateDecode] >>\nstream\n",
+                entries,
+                compressed.len()
+            )
+            .into_bytes();
+            stream.extend_from_slice(&compressed);
+            stream
+        } else {
+            let mut stream = format!("<< {} /Length {} >>\nstream\n", entries, data.len())
+                .into_bytes();
+            stream.extend_from_slice(data);
+            stream
+        };
+        stream.extend(b"\nendstream\n");
+        stream
+    }
+
+    /// Set a text entry, such as `Title` or `Author`, in the document information dictionary
+    pub fn set_info(&mut self, key: &str, value: &str) {
+        writeln!(self.info, "/{} {}", key, PdfString(value)).unwrap();
+    }
+
+    /// Set a date entry, such as `CreationDate`, in the document information dictionary
+    pub fn set_info_date(&mut self, key: &str, date: SystemTime) {
+        writeln!(self.info, "/{} {}", key, PdfDate(date)).unwrap();
+    }
+
+    /// Label the pages starting at `start_page` (counting from zero) and continuing until the next
+    /// range
+    ///
+    /// Each label is `prefix` followed by the page's number in `style`, if any. Numbering starts
+    /// at `first_number`. Ranges may be added in any order, and a range replaces any earlier one
+    /// with the same `start_page`. Pages before the first range are numbered 1, 2, 3, ...
+    pub fn add_page_labels(&mut self,
+                           start_page: usize,
+                           style: Option<PageLabelStyle>,
+                           prefix: &str,
+                           first_number: u32) {
+        let mut label = String::from("<< ");
+        if let Some(style) = style {
+            let style = match style {
+                PageLabelStyle::Decimal => "D",
+                PageLabelStyle::UpperRoman => "R",
+                PageLabelStyle::LowerRoman => "r",
+                PageLabelStyle::UpperAlpha => "A",
+                PageLabelStyle::LowerAlpha => "a",
+            };
+            label.push_str(&format!("/S /{} ", style));
+        }
+        if !prefix.is_empty() {
+            label.push_str(&format!("/P {} ", PdfString(prefix)));
+        }
+        label.push_str(&format!("/St {} >>", first_number));
+
+        self.page_labels.retain(|&(other_start_page, _)| other_start_page != start_page);
+        self.page_labels.push((start_page, label));
+    }
+
     /// Set the color for all subsequent drawing operations
     #[inline]
     pub fn set_fill_color(&mut self, color: ColorU) {
@@ -120,6 +204,204 @@ impl Pdf {
         ).unwrap();
     }
 
+    /// Set the blend mode for all subsequent drawing operations
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        let existing = self.graphics_states.iter().find(|&&(mode, _)| mode == blend_mode);
+        let graphics_state_id = match existing {
+            Some(&(_, id)) => id,
+            None => {
+                let graphics_state = format!("<< /Type /ExtGState /BM /{} >>\n",
+                                             pdf_blend_mode(blend_mode));
+                let id = self.add_object(graphics_state.into_bytes(), false);
+                self.graphics_states.push((blend_mode, id));
+                id
+            }
+        };
+
+        if !self.page_graphics_states.contains(&graphics_state_id) {
+            self.page_graphics_states.push(graphics_state_id);
+        }
+        writeln!(self.page_buffer, "/GS{} gs", graphics_state_id).unwrap();
+    }
+
+    /// Set a pattern for all subsequent fill operations
+    #[inline]
+    pub fn set_fill_pattern(&mut self, pattern: PatternId) {
+        if !self.page_patterns.contains(&pattern) {
+            self.page_patterns.push(pattern);
+        }
+        let soft_mask = self.pattern_soft_masks.iter().find(|&&(id, _)| id == pattern);
+        if let Some(&(_, graphics_state_id)) = soft_mask {
+            if !self.page_graphics_states.contains(&graphics_state_id) {
+                self.page_graphics_states.push(graphics_state_id);
+            }
+            writeln!(self.page_buffer, "/GS{} gs", graphics_state_id).unwrap();
+        }
+        writeln!(self.page_buffer, "/Pattern cs /P{} scn", pattern.0).unwrap();
+    }
+
+    /// Add a shading pattern that paints an axial or radial gradient
+    ///
+    /// `transform` maps the gradient geometry to the default coordinate space of the page. The
+    /// colors of the first and last stops extend past the ends of the gradient.
+    ///
+    /// Shadings are opaque, so if any stop is translucent, filling with the pattern also sets a
+    /// soft mask that paints the alpha of the stops over the current page.
+    pub fn add_gradient_pattern(&mut self, gradient: &Gradient, transform: Transform2F)
+                                -> PatternId {
+        let function_id = self.add_gradient_function(gradient.stops(), |color| {
+            Color(color).to_string()
+        });
+        let shading_id = self.add_gradient_shading(gradient, "/DeviceRGB", function_id);
+
+        let pattern = format!("<< /Type /Pattern /PatternType 2 /Shading {} 0 R\n\
+                               /Matrix {} >>\n",
+                              shading_id, Matrix(transform));
+        let pattern_id = PatternId(self.add_object(pattern.into_bytes(), false));
+
+        if gradient.stops().iter().any(|stop| !stop.color.is_opaque()) {
+            let alpha_function_id = self.add_gradient_function(gradient.stops(), |color| {
+                (f32::from(color.a) / 255.0).to_string()
+            });
+            let alpha_shading_id = self.add_gradient_shading(gradient,
+                                                             "/DeviceGray",
+                                                             alpha_function_id);
+
+            // The mask is a transparency group in the coordinate space of the page, and its
+            // luminosity is the alpha of the gradient.
+            let size = self.page_size.unwrap_or_default();
+            let content = format!("{} {} {} {} {} {} cm /Sh{} sh\n",
+                                  transform.m11(),
+                                  transform.m21(),
+                                  transform.m12(),
+                                  transform.m22(),
+                                  transform.vector.x(),
+                                  transform.vector.y(),
+                                  alpha_shading_id);
+            let entries = format!("/Type /XObject /Subtype /Form /BBox [0 0 {} {}]\n\
+                                   /Group << /S /Transparency /CS /DeviceGray >>\n\
+                                   /Resources << /Shading << /Sh{} {} 0 R >> >>",
+                                  size.x(), size.y(),
+                                  alpha_shading_id, alpha_shading_id);
+            let group = self.make_stream(&entries, content.as_bytes());
+            let group_id = self.add_object(group, false);
+
+            let graphics_state = format!("<< /Type /ExtGState\n\
+                                          /SMask << /Type /Mask /S /Luminosity /G {} 0 R >> >>\n",
+                                         group_id);
+            let graphics_state_id = self.add_object(graphics_state.into_bytes(), false);
+            self.pattern_soft_masks.push((pattern_id, graphics_state_id));
+        }
+
+        pattern_id
+    }
+
+    /// Add an axial or radial shading whose colors, in `color_space`, come from a function
+    fn add_gradient_shading(&mut self, gradient: &Gradient, color_space: &str, function_id: usize)
+                            -> usize {
+        let mut shading = Vec::new();
+        match *gradient.geometry() {
+            GradientGeometry::Linear(line) => {
+                writeln!(shading, "<< /ShadingType 2 /ColorSpace {}\n\
+                                   /Coords [{} {} {} {}]",
+                         color_space,
+                         line.from_x(), line.from_y(), line.to_x(), line.to_y()).unwrap();
+            }
+            GradientGeometry::Radial { line, start_radius, end_radius } => {
+                writeln!(shading, "<< /ShadingType 3 /ColorSpace {}\n\
+                                   /Coords [{} {} {} {} {} {}]",
+                         color_space,
+                         line.from_x(), line.from_y(), start_radius,
+                         line.to_x(), line.to_y(), end_radius).unwrap();
+            }
+        }
+        writeln!(shading, "/Function {} 0 R /Extend [true true] >>", function_id).unwrap();
+        self.add_object(shading, false)
+    }
+
+    /// Build a stitching function that interpolates between each pair of adjacent stops
+    ///
+    /// `components` formats the color of a stop as the function's output.
+    fn add_gradient_function<F>(&mut self, stops: &[ColorStop], components: F) -> usize
+                                where F: Fn(ColorU) -> String {
+        // The function domain is [0, 1], so pad the stops out to both ends.
+        let mut stops = stops.to_vec();
+        if let Some(&first) = stops.first() {
+            if first.offset > 0.0 || stops.len() == 1 {
+                stops.insert(0, ColorStop { offset: 0.0, color: first.color });
+            }
+        }
+        if let Some(&last) = stops.last() {
+            if last.offset < 1.0 || stops.len() == 1 {
+                stops.push(ColorStop { offset: 1.0, color: last.color });
+            }
+        }
+
+        let mut function = b"<< /FunctionType 3 /Domain [0 1]\n/Functions [".to_vec();
+        for pair in stops.windows(2) {
+            write!(function, "<< /FunctionType 2 /Domain [0 1] /C0 [{}] /C1 [{}] /N 1 >> ",
+                   components(pair[0].color), components(pair[1].color)).unwrap();
+        }
+        function.extend(b"]\n/Bounds [");
+        for stop in &stops[1..(stops.len() - 1)] {
+            write!(function, "{} ", stop.offset).unwrap();
+        }
+        function.extend(b"]\n/Encode [");
+        for _ in 1..stops.len() {
+            function.extend(b"0 1 ");
+        }
+        function.extend(b"] >>\n");
+        self.add_object(function, false)
+    }
+
+    /// Add a tiling pattern that paints an image
+    ///
+    /// In pattern space, the image's top left corner is at the origin, each pixel is one unit
+    /// square, and the image repeats every `step` units. `transform` maps pattern space to the
+    /// default coordinate space of the page.
+    pub fn add_image_pattern(&mut self, image: &Image, step: Vector2F, transform: Transform2F)
+                             -> PatternId {
+        let image_id = self.add_image(image);
+        let size = image.size().to_f32();
+
+        // Image space has its origin at the bottom left, so flip the image vertically.
+        let content = format!("q {} 0 0 {} 0 {} cm /Im{} Do Q\n",
+                              size.x(), -size.y(), size.y(), image_id);
+        let entries = format!("/Type /Pattern /PatternType 1 /PaintType 1 /TilingType 1\n\
+                               /BBox [0 0 {} {}] /XStep {} /YStep {}\n\
+                               /Matrix {}\n\
+                               /Resources << /XObject << /Im{} {} 0 R >> >>",
+                              size.x(), size.y(), step.x(), step.y(),
+                              Matrix(transform),
+                              image_id, image_id);
+        let pattern = self.make_stream(&entries, content.as_bytes());
+        PatternId(self.add_object(pattern, false))
+    }
+
+    /// Add an image XObject, with a soft mask if the image has any transparency
+    fn add_image(&mut self, image: &Image) -> usize {
+        let size = image.size();
+        let mut entries = format!("/Type /XObject /Subtype /Image /Width {} /Height {} \
+                                   /ColorSpace /DeviceRGB /BitsPerComponent 8",
+                                  size.x(), size.y());
+
+        if !image.is_opaque() {
+            let alpha: Vec<u8> = image.pixels().iter().map(|pixel| pixel.a).collect();
+            let mask_entries = format!("/Type /XObject /Subtype /Image /Width {} /Height {} \
+                                        /ColorSpace /DeviceGray /BitsPerComponent 8",
+                                       size.x(), size.y());
+            let mask = self.make_stream(&mask_entries, &alpha);
+            let mask_id = self.add_object(mask, false);
+            entries.push_str(&format!(" /SMask {} 0 R", mask_id));
+        }
+
+        let rgb: Vec<u8> = image.pixels().iter().flat_map(|pixel| {
+            vec![pixel.r, pixel.g, pixel.b]
+        }).collect();
+        let image = self.make_stream(&entries, &rgb);
+        self.add_object(image, false)
+    }
+
     /// Move to a new page in the PDF document
     #[inline]
     pub fn add_page(&mut self, size: Vector2F) {
@@ -145,8 +427,29 @@ impl Pdf {
     pub fn cubic_to(&mut self, c1: Vector2F, c2: Vector2F, p: Vector2F) {
         writeln!(self.page_buffer, "{} {} {} {} {} {} c", c1.x(), c1.y(), c2.x(), c2.y(), p.x(), p.y()).unwrap();
     }
-    pub fn fill(&mut self) {
-        writeln!(self.page_buffer, "f").unwrap();
+    pub fn fill(&mut self, fill_rule: FillRule) {
+        match fill_rule {
+            FillRule::Winding => writeln!(self.page_buffer, "f").unwrap(),
+            FillRule::EvenOdd => writeln!(self.page_buffer, "f*").unwrap(),
+        }
+    }
+
+    /// Intersect the clipping region with the current path, and end the path
+    pub fn clip(&mut self, fill_rule: FillRule) {
+        match fill_rule {
+            FillRule::Winding => writeln!(self.page_buffer, "W n").unwrap(),
+            FillRule::EvenOdd => writeln!(self.page_buffer, "W* n").unwrap(),
+        }
+    }
+
+    /// Push a copy of the graphics state, including the clip path and blend mode
+    pub fn save_state(&mut self) {
+        writeln!(self.page_buffer, "q").unwrap();
+    }
+
+    /// Pop the graphics state saved by the matching `save_state()`
+    pub fn restore_state(&mut self) {
+        writeln!(self.page_buffer, "Q").unwrap();
     }
     
     pub fn close(&mut self) {
@@ -158,26 +461,10 @@ impl Pdf {
             Some(size) => size,
             None => return // no page started
         };
-        let page_stream = if let Some(level) = self.compression {
-            let compressed = deflate::deflate_bytes_zlib_conf(&self.page_buffer, level);
-            let mut page = format!(
-                "<< /Length {} /Filter [/FlateDecode] >>\nstream\n",
-                compressed.len()
-            )
-            .into_bytes();
-            page.extend_from_slice(&compressed);
-            page.extend(b"endstream\n");
-            page
-        } else {
-            let mut page = Vec::new();
-            page.extend(format!("<< /Length {} >>\nstream\n", self.page_buffer.len()).bytes());
-            page.extend(&self.page_buffer);
-            page.extend(b"endstream\n");
-            page
-        };
+        let page_stream = self.make_stream("", &self.page_buffer);
 
         // Create the stream object for this page
-        let stream_object_id = self.add_object(page_stream, false, false);
+        let stream_object_id = self.add_object(page_stream, false);
 
         // Create the page object, which describes settings for the whole page
         let mut page_object = b"<< /Type /Page\n \
@@ -185,8 +472,19 @@ impl Pdf {
             /Resources <<\n"
             .to_vec();
 
-        for (idx, _obj) in self.objects.iter().enumerate().filter(|&(_, o)| o.is_xobject) {
-            write!(page_object, "/XObject {} 0 R ", idx+1).unwrap();
+        if !self.page_patterns.is_empty() {
+            page_object.extend(b"/Pattern << ");
+            for pattern in self.page_patterns.drain(..) {
+                write!(page_object, "/P{} {} 0 R ", pattern.0, pattern.0).unwrap();
+            }
+            page_object.extend(b">>\n");
+        }
+        if !self.page_graphics_states.is_empty() {
+            page_object.extend(b"/ExtGState << ");
+            for graphics_state_id in self.page_graphics_states.drain(..) {
+                write!(page_object, "/GS{} {} 0 R ", graphics_state_id, graphics_state_id).unwrap();
+            }
+            page_object.extend(b">>\n");
         }
 
         write!(page_object,
@@ -196,7 +494,7 @@ impl Pdf {
                 >>\n",
             size.x(), size.y(), stream_object_id
         ).unwrap();
-        self.add_object(page_object, true, false);
+        self.add_object(page_object, true);
     }
 
     /// Write the in-memory PDF representation to disk
@@ -208,6 +506,15 @@ impl Pdf {
             self.end_page();
         }
 
+        let info_id = if self.info.is_empty() {
+            None
+        } else {
+            let mut info = b"<< ".to_vec();
+            info.extend_from_slice(&self.info);
+            info.extend(b">>\n");
+            Some(self.add_object(info, false))
+        };
+
         // Write out each object
         for (idx, obj) in self.objects.iter_mut().enumerate().skip(2) {
             obj.offset = Some(out.pos());
@@ -232,21 +539,37 @@ impl Pdf {
 
         // Write out the catalog dictionary object
         self.objects[0].offset = Some(out.pos());
-        out.write_all(b"1 0 obj\n<< /Type /Catalog\n/Pages 2 0 R >>\nendobj\n")?;
+        out.write_all(b"1 0 obj\n<< /Type /Catalog\n/Pages 2 0 R\n")?;
+        if !self.page_labels.is_empty() {
+            // The number tree must be sorted, and must start at the first page.
+            self.page_labels.sort_by_key(|&(start_page, _)| start_page);
+            out.write_all(b"/PageLabels << /Nums [\n")?;
+            if self.page_labels[0].0 != 0 {
+                out.write_all(b"0 << /S /D /St 1 >>\n")?;
+            }
+            for (start_page, label) in &self.page_labels {
+                writeln!(out, "{} {}", start_page, label)?;
+            }
+            out.write_all(b"] >>\n")?;
+        }
+        out.write_all(b">>\nendobj\n")?;
 
         // Write the cross-reference table
-        let startxref = out.pos() + 1; // NOTE: apparently there's some 1-based indexing??
+        let startxref = out.pos();
         out.write_all(b"xref\n")?;
         write!(out, "0 {}\n", self.objects.len() + 1)?;
         out.write_all(b"0000000000 65535 f \n")?;
 
         for obj in &self.objects {
-            write!(out, "{:010} 00000 f \n", obj.offset.unwrap())?;
+            write!(out, "{:010} 00000 n \n", obj.offset.unwrap())?;
         }
 
         // Write the document trailer
         out.write_all(b"trailer\n")?;
-        write!(out, "<< /Size {}\n", self.objects.len())?;
+        write!(out, "<< /Size {}\n", self.objects.len() + 1)?;
+        if let Some(info_id) = info_id {
+            writeln!(out, "/Info {} 0 R", info_id)?;
+        }
         out.write_all(b"/Root 1 0 R >>\n")?;
 
         // Write the offset to the xref table
@@ -258,3 +581,395 @@ impl Pdf {
         Ok(())
     }
 }
+
+// Returns the name of the PDF blend mode that matches `blend_mode`.
+fn pdf_blend_mode(blend_mode: BlendMode) -> &'static str {
+    match blend_mode {
+        BlendMode::SrcOver => "Normal",
+        BlendMode::Lighten => "Lighten",
+        BlendMode::Darken => "Darken",
+        BlendMode::Clear |
+        BlendMode::DestOver |
+        BlendMode::DestOut |
+        BlendMode::SrcAtop |
+        BlendMode::Xor |
+        BlendMode::Lighter => {
+            // FIXME: PDF has no equivalent of the Porter-Duff operators or of additive
+            // blending, so these draw normally.
+            "Normal"
+        }
+    }
+}
+
+/// How page numbers are written in page labels
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PageLabelStyle {
+    /// 1, 2, 3, ...
+    Decimal,
+    /// I, II, III, ...
+    UpperRoman,
+    /// i, ii, iii, ...
+    LowerRoman,
+    /// A, B, C, ..., Z, AA, BB, ...
+    UpperAlpha,
+    /// a, b, c, ..., z, aa, bb, ...
+    LowerAlpha,
+}
+
+/// Formats text as a PDF string, using UTF-16 if it isn't ASCII
+struct PdfString<'a>(&'a str);
+
+impl<'a> fmt::Display for PdfString<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_ascii() {
+            f.write_str("(")?;
+            for c in self.0.chars() {
+                match c {
+                    '(' | ')' | '\\' => write!(f, "\\{}", c)?,
+                    '\r' => f.write_str("\\r")?,
+                    _ => write!(f, "{}", c)?,
+                }
+            }
+            f.write_str(")")
+        } else {
+            f.write_str("<FEFF")?;
+            for unit in self.0.encode_utf16() {
+                write!(f, "{:04X}", unit)?;
+            }
+            f.write_str(">")
+        }
+    }
+}
+
+/// Formats a time as a PDF date in UTC
+struct PdfDate(SystemTime);
+
+impl fmt::Display for PdfDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.0.duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+        let (days, seconds_of_day) = ((seconds / 86400) as i64, seconds % 86400);
+
+        // Convert days since the epoch to a Gregorian date. This is Howard Hinnant's
+        // `civil_from_days` algorithm.
+        let z = days + 719468;
+        let era = z / 146097;
+        let day_of_era = z - era * 146097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 -
+                           day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        write!(f, "(D:{:04}{:02}{:02}{:02}{:02}{:02}Z)",
+            year,
+            month,
+            day,
+            seconds_of_day / 3600,
+            seconds_of_day / 60 % 60,
+            seconds_of_day % 60
+        )
+    }
+}
+
+/// Formats a transform as a PDF matrix array
+struct Matrix(Transform2F);
+
+impl fmt::Display for Matrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Matrix(transform) = *self;
+        write!(f, "[{} {} {} {} {} {}]",
+            transform.m11(),
+            transform.m21(),
+            transform.m12(),
+            transform.m22(),
+            transform.vector.x(),
+            transform.vector.y()
+        )
+    }
+}
+
+/// Formats a color as the components of a `/DeviceRGB` color
+struct Color(ColorU);
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let norm = |color| f32::from(color) / 255.0;
+        write!(f, "{} {} {}", norm(self.0.r), norm(self.0.g), norm(self.0.b))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pathfinder_color::ColorU;
+    use pathfinder_content::effects::BlendMode;
+    use pathfinder_content::fill::FillRule;
+    use pathfinder_content::gradient::{ColorStop, Gradient};
+    use pathfinder_content::outline::Outline;
+    use pathfinder_content::pattern::{Image, Pattern, PatternSource, Repeat};
+    use pathfinder_geometry::line_segment::LineSegment2F;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::{Vector2F, Vector2I};
+    use pathfinder_renderer::paint::Paint;
+    use pathfinder_renderer::scene::{ClipPath, DrawPath, Scene};
+    use std::str;
+    use std::time::{Duration, UNIX_EPOCH};
+    use crate::{PageLabelStyle, PageLabels, PdfDocument};
+    use super::Pdf;
+
+
+    // Exports the scene without compression, so that the content stream can be inspected.
+    fn export(scene: &Scene) -> Vec<u8> {
+        let mut pdf = Pdf::new();
+        pdf.compression = None;
+        crate::draw_pdf_page(&mut pdf, scene);
+        let mut output = vec![];
+        pdf.write_to(&mut output).unwrap();
+        output
+    }
+
+    fn contains<N>(haystack: &[u8], needle: N) -> bool where N: AsRef<[u8]> {
+        let needle = needle.as_ref();
+        haystack.windows(needle.len()).any(|window| window == needle)
+    }
+
+    fn test_scene() -> Scene {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(100.0)));
+
+        let mut gradient = Gradient::linear(LineSegment2F::new(Vector2F::new(0.0, 0.0),
+                                                               Vector2F::new(100.0, 0.0)));
+        gradient.add_color_stop(ColorStop::new(ColorU::new(255, 0, 0, 255), 0.0));
+        gradient.add_color_stop(ColorStop::new(ColorU::new(0, 255, 0, 255), 0.5));
+        gradient.add_color_stop(ColorStop::new(ColorU::new(0, 0, 255, 255), 1.0));
+        let gradient = scene.push_paint(&Paint::Gradient(gradient));
+        let black = scene.push_paint(&Paint::Color(ColorU::black()));
+
+        let clip_rect = RectF::new(Vector2F::splat(10.0), Vector2F::splat(50.0));
+        let clip_path = scene.push_clip_path(ClipPath::new(Outline::from_rect(clip_rect),
+                                                           FillRule::Winding,
+                                                           String::new()));
+        let rect = RectF::new(Vector2F::default(), Vector2F::splat(100.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect),
+                                      gradient,
+                                      Some(clip_path),
+                                      FillRule::Winding,
+                                      BlendMode::SrcOver,
+                                      String::new()));
+
+        // Two nested squares, which leave a hole with the even-odd rule.
+        let mut outline = Outline::from_rect(RectF::new(Vector2F::splat(20.0), Vector2F::splat(60.0)));
+        let inner = Outline::from_rect(RectF::new(Vector2F::splat(40.0), Vector2F::splat(20.0)));
+        outline.push_contour(inner.contours()[0].clone());
+        scene.push_path(DrawPath::new(outline,
+                                      black,
+                                      None,
+                                      FillRule::EvenOdd,
+                                      BlendMode::SrcOver,
+                                      String::new()));
+        scene
+    }
+
+    #[test]
+    fn test_gradient_clip_and_fill_rule() {
+        let output = export(&test_scene());
+        assert!(contains(&output, "/ShadingType 2"));
+        assert!(contains(&output, "/Coords [0 0 100 0]"));
+        assert!(contains(&output, "/FunctionType 3"));
+        assert!(contains(&output, "/Bounds [0.5 ]"));
+        assert!(contains(&output, "/Pattern cs /P"));
+        assert!(contains(&output, "\nW n\n"));
+        assert!(contains(&output, "\nf*\n"));
+    }
+
+    fn scene_with_paint(paint: Paint) -> Scene {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(100.0)));
+        let paint = scene.push_paint(&paint);
+        let rect = RectF::new(Vector2F::splat(10.0), Vector2F::splat(50.0));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect),
+                                      paint,
+                                      None,
+                                      FillRule::Winding,
+                                      BlendMode::SrcOver,
+                                      String::new()));
+        scene
+    }
+
+    fn linear_gradient(stops: &[ColorU]) -> Paint {
+        let mut gradient = Gradient::linear(LineSegment2F::new(Vector2F::new(0.0, 0.0),
+                                                               Vector2F::new(100.0, 0.0)));
+        for (index, &color) in stops.iter().enumerate() {
+            let offset = index as f32 / (stops.len() - 1) as f32;
+            gradient.add_color_stop(ColorStop::new(color, offset));
+        }
+        Paint::Gradient(gradient)
+    }
+
+    #[test]
+    fn test_gradient_stop_alpha() {
+        // Opaque gradients need no soft mask.
+        let paint = linear_gradient(&[ColorU::new(255, 0, 0, 255), ColorU::new(0, 0, 255, 255)]);
+        let output = export(&scene_with_paint(paint));
+        assert!(!contains(&output, "/SMask"));
+        assert!(!contains(&output, " gs\n"));
+
+        let paint = linear_gradient(&[ColorU::new(255, 0, 0, 255), ColorU::new(0, 0, 255, 51)]);
+        let output = export(&scene_with_paint(paint));
+        let text = String::from_utf8_lossy(&output);
+
+        // The colors are still painted by an RGB shading.
+        assert!(contains(&output, "/C0 [1 0 0] /C1 [0 0 1] /N 1"));
+        assert!(contains(&output, "<< /ShadingType 2 /ColorSpace /DeviceRGB\n"));
+
+        // The alpha is painted by a gray shading in a transparency group over the page.
+        assert!(contains(&output, "/C0 [1] /C1 [0.2] /N 1"));
+        assert!(contains(&output, "<< /ShadingType 2 /ColorSpace /DeviceGray\n"));
+        assert!(contains(&output, "/Subtype /Form /BBox [0 0 100 100]\n\
+                                   /Group << /S /Transparency /CS /DeviceGray >>"));
+        assert!(contains(&output, "1 0 0 -1 -0 100 cm /Sh"));
+
+        // Filling with the pattern sets the soft mask first.
+        let graphics_state_start = text.find("/SMask << /Type /Mask /S /Luminosity").unwrap();
+        let graphics_state_id = text[..graphics_state_start].rfind(" 0 obj").map(|end| {
+            let start = text[..end].rfind('\n').unwrap() + 1;
+            text[start..end].to_owned()
+        }).unwrap();
+        let set_state = format!("/GS{} gs\n/Pattern cs /P", graphics_state_id);
+        assert!(contains(&output, &set_state));
+        let resource = format!("/ExtGState << /GS{} {} 0 R", graphics_state_id, graphics_state_id);
+        assert!(contains(&output, &resource));
+    }
+
+    #[test]
+    fn test_image_pattern() {
+        let (red, clear) = (ColorU::new(255, 0, 0, 255), ColorU::transparent_black());
+        let opaque = Image::new(Vector2I::splat(2), vec![red; 4]);
+        let paint = Paint::Pattern(Pattern::new(PatternSource::Image(opaque), Repeat::all()));
+        let output = export(&scene_with_paint(paint));
+        assert!(contains(&output, "/PatternType 1 /PaintType 1 /TilingType 1\n\
+                                   /BBox [0 0 2 2] /XStep 2 /YStep 2\n\
+                                   /Matrix [1 0 0 -1 -0 100]"));
+        assert!(contains(&output, "q 2 0 0 -2 0 2 cm /Im"));
+        assert!(contains(&output, "/Width 2 /Height 2 /ColorSpace /DeviceRGB"));
+        assert!(contains(&output, b"\xff\x00\x00\xff\x00\x00\xff\x00\x00\xff\x00\x00"));
+        assert!(!contains(&output, "/SMask"));
+
+        // Tiles that don't repeat are spaced past the view box.
+        let translucent = Image::new(Vector2I::splat(2), vec![red, clear, clear, red]);
+        let pattern = Pattern::new(PatternSource::Image(translucent), Repeat::X);
+        let output = export(&scene_with_paint(Paint::Pattern(pattern)));
+        assert!(contains(&output, "/XStep 2 /YStep 102\n"));
+
+        // The alpha channel becomes a soft mask.
+        let text = String::from_utf8_lossy(&output);
+        let mask_start = text.find("/ColorSpace /DeviceGray /BitsPerComponent 8").unwrap();
+        let mask_id = text[..mask_start].rfind(" 0 obj").map(|end| {
+            let start = text[..end].rfind('\n').unwrap() + 1;
+            text[start..end].to_owned()
+        }).unwrap();
+        assert!(contains(&output, format!("/SMask {} 0 R", mask_id)));
+        assert!(contains(&output, b"/Length 4 >>\nstream\n\xff\x00\x00\xff\nendstream"));
+    }
+
+    #[test]
+    fn test_xref_offsets() {
+        let output = export(&test_scene());
+        let text = String::from_utf8_lossy(&output);
+
+        let startxref_index = text.rfind("startxref\n").unwrap();
+        let startxref_line = text[(startxref_index + 10)..].lines().next().unwrap();
+        let startxref: usize = startxref_line.parse().unwrap();
+        assert!(output[startxref..].starts_with(b"xref\n"));
+
+        let mut lines = str::from_utf8(&output[startxref..]).unwrap().lines().skip(1);
+        let header: Vec<usize> = lines.next().unwrap()
+                                      .split(' ')
+                                      .map(|field| field.parse().unwrap())
+                                      .collect();
+        assert_eq!(header[0], 0);
+        assert_eq!(lines.next(), Some("0000000000 65535 f "));
+        for object_number in 1..header[1] {
+            let entry = lines.next().unwrap();
+            assert!(entry.ends_with(" 00000 n "));
+            let offset: usize = entry[0..10].parse().unwrap();
+            let expected = format!("{} 0 obj\n", object_number);
+            assert!(output[offset..].starts_with(expected.as_bytes()));
+        }
+        assert_eq!(lines.next(), Some("trailer"));
+    }
+
+    fn export_document(document: &PdfDocument, scenes: &[Scene]) -> String {
+        let mut output = vec![];
+        document.export(scenes, &mut output).unwrap();
+        String::from_utf8_lossy(&output).into_owned()
+    }
+
+    fn page(size: Vector2F) -> Scene {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), size));
+        scene
+    }
+
+    #[test]
+    fn test_multiple_pages() {
+        let scenes = [page(Vector2F::new(100.0, 200.0)), page(Vector2F::new(300.0, 50.0))];
+        let output = export_document(&PdfDocument::default(), &scenes);
+        assert!(output.contains("/Count 2\n"));
+        let first = output.find("/MediaBox [0 0 100 200]").unwrap();
+        let second = output.find("/MediaBox [0 0 300 50]").unwrap();
+        assert!(first < second);
+
+        // Documents without metadata have no information dictionary or labels.
+        assert!(!output.contains("/Info"));
+        assert!(!output.contains("/PageLabels"));
+    }
+
+    #[test]
+    fn test_info_dictionary() {
+        let document = PdfDocument {
+            title: Some("Caf\u{e9} (draft)".to_owned()),
+            author: Some("A \\ B".to_owned()),
+            creation_date: Some(UNIX_EPOCH + Duration::from_secs(1_584_000_000)),
+            page_labels: vec![],
+        };
+        let output = export_document(&document, &[page(Vector2F::splat(100.0))]);
+
+        // Non-ASCII strings are written in UTF-16, and the rest are escaped.
+        let title = "<FEFF 0043 0061 0066 00E9 0020 0028 0064 0072 0061 0066 0074 0029>";
+        assert!(output.contains(&format!("/Title {}", title.replace(' ', ""))));
+        assert!(output.contains("/Author (A \\\\ B)"));
+        assert!(output.contains("/CreationDate (D:20200312080000Z)"));
+
+        let info_start = output.rfind("/Info ").unwrap() + 6;
+        let info_id = output[info_start..].split(' ').next().unwrap();
+        assert!(output.contains(&format!("\n{} 0 obj\n<< /Title", info_id)));
+    }
+
+    #[test]
+    fn test_page_labels() {
+        let labels = |start_page: usize, style: Option<PageLabelStyle>, prefix: &str| {
+            PageLabels { start_page, style, prefix: prefix.to_owned(), first_number: 1 }
+        };
+        let document = PdfDocument {
+            page_labels: vec![
+                labels(3, Some(PageLabelStyle::Decimal), ""),
+                labels(1, Some(PageLabelStyle::LowerRoman), ""),
+                labels(5, None, "Appendix"),
+                labels(3, Some(PageLabelStyle::UpperAlpha), "A-"),
+            ],
+            ..PdfDocument::default()
+        };
+        let scenes: Vec<Scene> = (0..6).map(|_| page(Vector2F::splat(100.0))).collect();
+        let output = export_document(&document, &scenes);
+
+        // Ranges are sorted, the last one for a page wins, and page 0 gets a default label.
+        assert!(output.contains("/PageLabels << /Nums [\n\
+                                 0 << /S /D /St 1 >>\n\
+                                 1 << /S /r /St 1 >>\n\
+                                 3 << /S /A /P (A-) /St 1 >>\n\
+                                 5 << /P (Appendix) /St 1 >>\n\
+                                 ] >>\n"));
+    }
+}
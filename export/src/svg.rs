@@ -0,0 +1,767 @@
+// pathfinder/export/src/svg.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Serializes a scene to SVG.
+
+use pathfinder_color::ColorU;
+use pathfinder_content::effects::{BlendMode, CompositeOp, Effects, Filter, MaskMode};
+use pathfinder_content::fill::FillRule;
+use pathfinder_content::gradient::{Gradient, GradientGeometry};
+use pathfinder_content::outline::Outline;
+use pathfinder_content::pattern::{Image, Pattern, PatternSource, RenderTargetId, Repeat};
+use pathfinder_content::segment::SegmentKind;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_renderer::paint::{Paint, PaintId};
+use pathfinder_renderer::scene::{ClipPathId, DisplayItem, DrawPath, Scene};
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, Write};
+
+pub fn export_svg<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
+    let view_box = scene.view_box();
+    writeln!(
+        writer,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" \
+         xmlns:xlink=\"http://www.w3.org/1999/xlink\" viewBox=\"{} {} {} {}\">",
+        view_box.origin().x(),
+        view_box.origin().y(),
+        view_box.size().x(),
+        view_box.size().y()
+    )?;
+    write_defs(scene, writer)?;
+
+    let mut ids = ElementIds::new(scene);
+    let mut depth = 1;
+    for (item_index, display_item) in scene.display_list().iter().enumerate() {
+        match *display_item {
+            DisplayItem::DrawPaths { start_index, end_index } => {
+                for path_index in start_index..end_index {
+                    let draw_path = scene.get_path(path_index);
+                    write_path(writer, scene, draw_path, &mut ids, depth)?;
+                }
+            }
+            // Render targets become groups that are only drawn where something refers to them.
+            DisplayItem::PushRenderTarget(render_target_id) => {
+                writeln!(writer,
+                         "{}<defs><g id=\"{}\">",
+                         Indent(depth),
+                         RenderTargetName(render_target_id))?;
+                depth += 1;
+            }
+            DisplayItem::PopRenderTarget => {
+                depth = depth.saturating_sub(1);
+                writeln!(writer, "{}</g></defs>", Indent(depth))?;
+            }
+            DisplayItem::DrawRenderTarget { render_target, effects } => {
+                write_render_target(writer, view_box, item_index, render_target, effects, depth)?;
+            }
+        }
+    }
+
+    writeln!(writer, "</svg>")?;
+    Ok(())
+}
+
+fn write_path<W: Write>(writer: &mut W,
+                        scene: &Scene,
+                        draw_path: &DrawPath,
+                        ids: &mut ElementIds,
+                        depth: usize)
+                        -> io::Result<()> {
+    write!(writer, "{}<path", Indent(depth))?;
+    if !draw_path.name().is_empty() {
+        write!(writer, " id=\"{}\"", Escaped(&ids.unique(draw_path.name())))?;
+    }
+
+    let paint_id = draw_path.paint();
+    match *scene.get_paint(paint_id) {
+        Paint::Color(color) => write_color(writer, "fill", color)?,
+        Paint::Gradient(_) | Paint::Pattern(_) => {
+            write!(writer, " fill=\"url(#{})\"", PaintName(paint_id))?
+        }
+    }
+
+    if draw_path.fill_rule() == FillRule::EvenOdd {
+        write!(writer, " fill-rule=\"evenodd\"")?;
+    }
+    if let Some(clip_path_id) = draw_path.clip_path() {
+        write!(writer, " clip-path=\"url(#{})\"", ClipPathName(clip_path_id))?;
+    }
+    if let Some(blend_mode) = css_blend_mode(draw_path.blend_mode()) {
+        write!(writer, " style=\"mix-blend-mode: {}\"", blend_mode)?;
+    }
+
+    writeln!(writer, " d=\"{}\" />", PathData(draw_path.outline()))
+}
+
+// Writes the gradients, patterns and clip paths that the paths refer to.
+fn write_defs<W: Write>(scene: &Scene, writer: &mut W) -> io::Result<()> {
+    let mut paint_ids = vec![];
+    let mut clip_path_ids = vec![];
+    for draw_path in scene.paths() {
+        if !paint_ids.contains(&draw_path.paint()) {
+            paint_ids.push(draw_path.paint());
+        }
+        if let Some(clip_path_id) = draw_path.clip_path() {
+            if !clip_path_ids.iter().any(|&id: &ClipPathId| id.0 == clip_path_id.0) {
+                clip_path_ids.push(clip_path_id);
+            }
+        }
+    }
+
+    writeln!(writer, "    <defs>")?;
+    for paint_id in paint_ids {
+        match *scene.get_paint(paint_id) {
+            Paint::Color(_) => {}
+            Paint::Gradient(ref gradient) => write_gradient(writer, paint_id, gradient)?,
+            Paint::Pattern(ref pattern) => write_pattern(writer, scene, paint_id, pattern)?,
+        }
+    }
+    for clip_path_id in clip_path_ids {
+        let clip_path = scene.get_clip_path(clip_path_id);
+        write!(writer, "        <clipPath id=\"{}\"><path", ClipPathName(clip_path_id))?;
+        if clip_path.fill_rule() == FillRule::EvenOdd {
+            write!(writer, " clip-rule=\"evenodd\"")?;
+        }
+        writeln!(writer, " d=\"{}\" /></clipPath>", PathData(clip_path.outline()))?;
+    }
+    writeln!(writer, "    </defs>")?;
+    Ok(())
+}
+
+fn write_gradient<W: Write>(writer: &mut W, paint_id: PaintId, gradient: &Gradient)
+                            -> io::Result<()> {
+    let element = match *gradient.geometry() {
+        GradientGeometry::Linear(line) => {
+            write!(writer,
+                   "        <linearGradient id=\"{}\" gradientUnits=\"userSpaceOnUse\" \
+                    x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\">",
+                   PaintName(paint_id),
+                   line.from_x(),
+                   line.from_y(),
+                   line.to_x(),
+                   line.to_y())?;
+            "linearGradient"
+        }
+        GradientGeometry::Radial { line, start_radius, end_radius } => {
+            // The start circle is the focal circle, and the end circle is the outer circle.
+            write!(writer,
+                   "        <radialGradient id=\"{}\" gradientUnits=\"userSpaceOnUse\" \
+                    fx=\"{}\" fy=\"{}\" cx=\"{}\" cy=\"{}\" r=\"{}\"",
+                   PaintName(paint_id),
+                   line.from_x(),
+                   line.from_y(),
+                   line.to_x(),
+                   line.to_y(),
+                   end_radius)?;
+            if start_radius != 0.0 {
+                write!(writer, " fr=\"{}\"", start_radius)?;
+            }
+            write!(writer, ">")?;
+            "radialGradient"
+        }
+    };
+
+    for stop in gradient.stops() {
+        write!(writer, "<stop offset=\"{}\"", stop.offset)?;
+        write_color(writer, "stop-color", stop.color)?;
+        write!(writer, " />")?;
+    }
+    writeln!(writer, "</{}>", element)
+}
+
+fn write_pattern<W: Write>(writer: &mut W, scene: &Scene, paint_id: PaintId, pattern: &Pattern)
+                           -> io::Result<()> {
+    let view_box = scene.view_box();
+    let size = match pattern.source {
+        PatternSource::Image(ref image) => image.size().to_f32(),
+        PatternSource::RenderTarget(render_target_id) => {
+            match scene.get_render_target(render_target_id).size() {
+                Some(size) => size.to_f32(),
+                None => view_box.size().ceil(),
+            }
+        }
+    };
+
+    // SVG patterns always repeat, so space the tiles far enough apart in directions that don't
+    // repeat that only the one at the origin touches the view box.
+    let mut tile_size = size;
+    if !pattern.repeat.contains(Repeat::X) {
+        tile_size.set_x(size.x() + view_box.min_x().abs() + view_box.max_x().abs());
+    }
+    if !pattern.repeat.contains(Repeat::Y) {
+        tile_size.set_y(size.y() + view_box.min_y().abs() + view_box.max_y().abs());
+    }
+
+    write!(writer,
+           "        <pattern id=\"{}\" patternUnits=\"userSpaceOnUse\" width=\"{}\" \
+            height=\"{}\">",
+           PaintName(paint_id),
+           tile_size.x(),
+           tile_size.y())?;
+    match pattern.source {
+        PatternSource::Image(ref image) => {
+            write!(writer,
+                   "<image width=\"{}\" height=\"{}\" \
+                    xlink:href=\"data:image/png;base64,{}\" />",
+                   size.x(),
+                   size.y(),
+                   Base64(&encode_png(image)))?;
+        }
+        PatternSource::RenderTarget(render_target_id) => {
+            // The render target's contents are clipped to its size, as they would be when
+            // rendered.
+            write!(writer,
+                   "<svg width=\"{}\" height=\"{}\"><use xlink:href=\"#{}\" /></svg>",
+                   size.x(),
+                   size.y(),
+                   RenderTargetName(render_target_id))?;
+        }
+    }
+    writeln!(writer, "</pattern>")
+}
+
+// Draws the contents of a render target with its effects, as a `<use>` of its group.
+fn write_render_target<W: Write>(writer: &mut W,
+                                 view_box: RectF,
+                                 item_index: usize,
+                                 render_target_id: RenderTargetId,
+                                 effects: Effects,
+                                 depth: usize)
+                                 -> io::Result<()> {
+    match effects.filter {
+        Filter::Composite(CompositeOp::SrcOver) => {}
+        Filter::Opacity { mask: None, .. } => {}
+        Filter::Opacity { mask: Some(mask), mask_mode, .. } => {
+            write_mask(writer, view_box, item_index, mask, mask_mode, depth)?;
+        }
+        Filter::Text { fg_color, .. } => {
+            // Text render targets hold coverage, so draw it in the foreground color. The
+            // background is whatever has already been drawn underneath.
+            write!(writer,
+                   "{}<defs><filter id=\"{}\"><feFlood",
+                   Indent(depth),
+                   FilterName(item_index))?;
+            write_color(writer, "flood-color", fg_color.to_u8())?;
+            writeln!(writer,
+                     " /><feComposite operator=\"in\" in2=\"SourceAlpha\" /></filter></defs>")?;
+        }
+    }
+
+    write!(writer, "{}<use xlink:href=\"#{}\"", Indent(depth), RenderTargetName(render_target_id))?;
+    match effects.filter {
+        Filter::Composite(CompositeOp::SrcOver) => {}
+        Filter::Opacity { opacity, mask, .. } => {
+            if opacity < 1.0 {
+                write!(writer, " opacity=\"{}\"", opacity)?;
+            }
+            if mask.is_some() {
+                write!(writer, " mask=\"url(#{})\"", MaskName(item_index))?;
+            }
+        }
+        Filter::Text { .. } => write!(writer, " filter=\"url(#{})\"", FilterName(item_index))?,
+    }
+    writeln!(writer, " />")
+}
+
+// Writes a mask whose coverage is derived from a render target as `mask_mode` says.
+//
+// SVG masks only come in luminance and alpha flavors, so the inverted modes recolor the render
+// target with a filter and draw it over white: the result is black wherever the mask is fully
+// covered.
+fn write_mask<W: Write>(writer: &mut W,
+                        view_box: RectF,
+                        item_index: usize,
+                        mask: RenderTargetId,
+                        mask_mode: MaskMode,
+                        depth: usize)
+                        -> io::Result<()> {
+    let color_matrix = match mask_mode {
+        MaskMode::Luminance | MaskMode::Alpha => None,
+        // Inverts the luminance, keeping the alpha, so that the result over white is
+        // 1 - luminance * alpha.
+        MaskMode::InvertedLuminance => {
+            Some("-0.2125 -0.7154 -0.0721 0 1 -0.2125 -0.7154 -0.0721 0 1 \
+                  -0.2125 -0.7154 -0.0721 0 1 0 0 0 1 0")
+        }
+        // Turns the render target black, keeping the alpha.
+        MaskMode::InvertedAlpha => Some("0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 1 0"),
+    };
+
+    write!(writer, "{}<defs>", Indent(depth))?;
+    if let Some(color_matrix) = color_matrix {
+        write!(writer,
+               "<filter id=\"{}\" color-interpolation-filters=\"sRGB\"><feColorMatrix \
+                type=\"matrix\" values=\"{}\" /></filter>",
+               FilterName(item_index),
+               color_matrix)?;
+    }
+    write!(writer, "<mask id=\"{}\"", MaskName(item_index))?;
+    if mask_mode == MaskMode::Alpha {
+        write!(writer, " style=\"mask-type: alpha\"")?;
+    }
+    write!(writer, ">")?;
+    if color_matrix.is_some() {
+        write!(writer,
+               "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#ffffff\" />\
+                <use xlink:href=\"#{}\" filter=\"url(#{})\" />",
+               view_box.origin().x(),
+               view_box.origin().y(),
+               view_box.size().x(),
+               view_box.size().y(),
+               RenderTargetName(mask),
+               FilterName(item_index))?;
+    } else {
+        write!(writer, "<use xlink:href=\"#{}\" />", RenderTargetName(mask))?;
+    }
+    writeln!(writer, "</mask></defs>")
+}
+
+fn write_color<W: Write>(writer: &mut W, attribute: &str, color: ColorU) -> io::Result<()> {
+    write!(writer, " {}=\"#{:02x}{:02x}{:02x}\"", attribute, color.r, color.g, color.b)?;
+    if !color.is_opaque() {
+        let opacity_attribute = match attribute {
+            "stop-color" => "stop-opacity",
+            "flood-color" => "flood-opacity",
+            _ => "fill-opacity",
+        };
+        write!(writer, " {}=\"{}\"", opacity_attribute, color.a as f32 / 255.0)?;
+    }
+    Ok(())
+}
+
+// Returns the CSS `mix-blend-mode` for a blend mode, or `None` if it's the default or has no
+// equivalent.
+fn css_blend_mode(blend_mode: BlendMode) -> Option<&'static str> {
+    match blend_mode {
+        BlendMode::SrcOver => None,
+        BlendMode::Lighten => Some("lighten"),
+        BlendMode::Darken => Some("darken"),
+        BlendMode::Lighter => Some("plus-lighter"),
+        BlendMode::Clear |
+        BlendMode::DestOver |
+        BlendMode::DestOut |
+        BlendMode::SrcAtop |
+        BlendMode::Xor => {
+            // FIXME: These are compositing operators, which SVG has no way to express
+            // on individual paths.
+            None
+        }
+    }
+}
+
+// Encodes an image as an 8-bit RGBA PNG.
+fn encode_png(image: &Image) -> Vec<u8> {
+    let size = image.size();
+
+    let mut header = vec![];
+    header.extend_from_slice(&(size.x() as u32).to_be_bytes());
+    header.extend_from_slice(&(size.y() as u32).to_be_bytes());
+    // 8 bits per channel, RGBA, default compression, filtering, and no interlacing.
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut scanlines = vec![];
+    for row in image.pixels().chunks(size.x().max(1) as usize) {
+        // Filter type: none.
+        scanlines.push(0);
+        for pixel in row {
+            scanlines.extend_from_slice(&[pixel.r, pixel.g, pixel.b, pixel.a]);
+        }
+    }
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_png_chunk(&mut png, b"IHDR", &header);
+    write_png_chunk(&mut png, b"IDAT", &deflate::deflate_bytes_zlib(&scanlines));
+    write_png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    let mut crc = !0u32;
+    for &byte in kind.iter().chain(data) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    png.extend_from_slice(&(!crc).to_be_bytes());
+}
+
+/// Formats an outline as SVG path data
+struct PathData<'a>(&'a Outline);
+
+impl<'a> fmt::Display for PathData<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for contour in self.0.contours() {
+            for (segment_index, segment) in contour.iter().enumerate() {
+                if segment_index == 0 {
+                    let from = segment.baseline.from();
+                    write!(f, "M {} {} ", from.x(), from.y())?;
+                }
+
+                let to = segment.baseline.to();
+                match segment.kind {
+                    SegmentKind::None => {}
+                    SegmentKind::Line => write!(f, "L {} {} ", to.x(), to.y())?,
+                    SegmentKind::Quadratic => {
+                        let ctrl = segment.ctrl.from();
+                        write!(f, "Q {} {} {} {} ", ctrl.x(), ctrl.y(), to.x(), to.y())?;
+                    }
+                    SegmentKind::Cubic => {
+                        let (ctrl0, ctrl1) = (segment.ctrl.from(), segment.ctrl.to());
+                        write!(f, "C {} {} {} {} {} {} ",
+                               ctrl0.x(), ctrl0.y(),
+                               ctrl1.x(), ctrl1.y(),
+                               to.x(), to.y())?;
+                    }
+                }
+            }
+
+            if contour.is_closed() {
+                write!(f, "Z ")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Formats text for use in an XML attribute value
+struct Escaped<'a>(&'a str);
+
+impl<'a> fmt::Display for Escaped<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '&' => f.write_str("&amp;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '"' => f.write_str("&quot;")?,
+                _ => write!(f, "{}", c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct PaintName(PaintId);
+
+impl fmt::Display for PaintName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "paint{}", (self.0).0)
+    }
+}
+
+struct ClipPathName(ClipPathId);
+
+impl fmt::Display for ClipPathName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "clip{}", (self.0).0)
+    }
+}
+
+struct RenderTargetName(RenderTargetId);
+
+impl fmt::Display for RenderTargetName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "layer{}", (self.0).0)
+    }
+}
+
+// Masks and filters are named after the index of the display item that uses them.
+struct MaskName(usize);
+
+impl fmt::Display for MaskName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "mask{}", self.0)
+    }
+}
+
+struct FilterName(usize);
+
+impl fmt::Display for FilterName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "filter{}", self.0)
+    }
+}
+
+/// Indents an element nested `depth` levels deep
+struct Indent(usize);
+
+impl fmt::Display for Indent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for _ in 0..self.0 {
+            f.write_str("    ")?;
+        }
+        Ok(())
+    }
+}
+
+// Hands out the ids of named paths. Path names needn't be unique, and they can collide with the
+// ids of the defs, so later duplicates get a numeric suffix.
+struct ElementIds {
+    used: HashSet<String>,
+}
+
+impl ElementIds {
+    fn new(scene: &Scene) -> ElementIds {
+        let mut used = HashSet::new();
+        for draw_path in scene.paths() {
+            used.insert(PaintName(draw_path.paint()).to_string());
+            if let Some(clip_path_id) = draw_path.clip_path() {
+                used.insert(ClipPathName(clip_path_id).to_string());
+            }
+        }
+        for (item_index, display_item) in scene.display_list().iter().enumerate() {
+            match *display_item {
+                DisplayItem::PushRenderTarget(render_target_id) => {
+                    used.insert(RenderTargetName(render_target_id).to_string());
+                }
+                DisplayItem::DrawRenderTarget { .. } => {
+                    used.insert(MaskName(item_index).to_string());
+                    used.insert(FilterName(item_index).to_string());
+                }
+                DisplayItem::DrawPaths { .. } | DisplayItem::PopRenderTarget => {}
+            }
+        }
+        ElementIds { used }
+    }
+
+    fn unique(&mut self, name: &str) -> String {
+        let mut id = name.to_owned();
+        let mut suffix = 2;
+        while !self.used.insert(id.clone()) {
+            id = format!("{}-{}", name, suffix);
+            suffix += 1;
+        }
+        id
+    }
+}
+
+/// Formats bytes as standard base64
+struct Base64<'a>(&'a [u8]);
+
+impl<'a> fmt::Display for Base64<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        for chunk in self.0.chunks(3) {
+            let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+            let bits = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+            for index in 0..4 {
+                if index <= chunk.len() {
+                    let sextet = (bits >> (18 - 6 * index)) & 0x3f;
+                    write!(f, "{}", ALPHABET[sextet as usize] as char)?;
+                } else {
+                    f.write_str("=")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pathfinder_color::ColorU;
+    use pathfinder_content::effects::{BlendMode, Effects, Filter, MaskMode};
+    use pathfinder_content::fill::FillRule;
+    use pathfinder_content::gradient::{ColorStop, Gradient};
+    use pathfinder_content::outline::{Contour, Outline};
+    use pathfinder_content::pattern::{Pattern, PatternSource, Repeat};
+    use pathfinder_geometry::line_segment::LineSegment2F;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::{Vector2F, Vector2I};
+    use pathfinder_renderer::paint::Paint;
+    use pathfinder_renderer::scene::{ClipPath, DrawPath, RenderTarget, Scene};
+    use super::export_svg;
+
+    fn export(scene: &Scene) -> String {
+        let mut output = vec![];
+        export_svg(scene, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn scene_with_path(paint: Paint, clip: bool, fill_rule: FillRule, blend_mode: BlendMode)
+                       -> Scene {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(100.0)));
+
+        let mut contour = Contour::new();
+        contour.push_endpoint(Vector2F::new(10.0, 10.0));
+        contour.push_endpoint(Vector2F::new(90.0, 10.0));
+        contour.push_quadratic(Vector2F::new(90.0, 50.0), Vector2F::new(50.0, 50.0));
+        contour.push_cubic(Vector2F::new(40.0, 60.0), Vector2F::new(20.0, 80.0),
+                           Vector2F::new(10.0, 90.0));
+        contour.close();
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+
+        let clip_path = if clip {
+            let outline = Outline::from_rect(RectF::new(Vector2F::splat(20.0),
+                                                        Vector2F::splat(50.0)));
+            Some(scene.push_clip_path(ClipPath::new(outline, FillRule::Winding, String::new())))
+        } else {
+            None
+        };
+
+        let paint = scene.push_paint(&paint);
+        scene.push_path(DrawPath::new(outline,
+                                      paint,
+                                      clip_path,
+                                      fill_rule,
+                                      blend_mode,
+                                      "shape".to_owned()));
+        scene
+    }
+
+    #[test]
+    fn test_path_data() {
+        let paint = Paint::Color(ColorU::new(255, 0, 0, 255));
+        let svg = export(&scene_with_path(paint, false, FillRule::Winding, BlendMode::SrcOver));
+        assert!(svg.contains(" id=\"shape\" fill=\"#ff0000\" d=\"M 10 10 L 90 10 Q 90 50 50 50 \
+                              C 40 60 20 80 10 90 L 10 10 Z \" />"));
+        assert!(!svg.contains("fill-rule"));
+        assert!(!svg.contains("clip-path"));
+        assert!(!svg.contains("mix-blend-mode"));
+    }
+
+    #[test]
+    fn test_fill_rule_and_blend_mode() {
+        let paint = Paint::Color(ColorU::new(0, 0, 255, 128));
+        let svg = export(&scene_with_path(paint, false, FillRule::EvenOdd, BlendMode::Lighten));
+        assert!(svg.contains(" fill=\"#0000ff\" fill-opacity=\""));
+        assert!(svg.contains(" fill-rule=\"evenodd\""));
+        assert!(svg.contains(" style=\"mix-blend-mode: lighten\""));
+    }
+
+    #[test]
+    fn test_gradient() {
+        let mut gradient = Gradient::linear(LineSegment2F::new(Vector2F::new(0.0, 0.0),
+                                                               Vector2F::new(100.0, 0.0)));
+        gradient.add_color_stop(ColorStop::new(ColorU::new(255, 0, 0, 255), 0.0));
+        gradient.add_color_stop(ColorStop::new(ColorU::new(0, 0, 255, 255), 1.0));
+        let paint = Paint::Gradient(gradient);
+        let svg = export(&scene_with_path(paint, false, FillRule::Winding, BlendMode::SrcOver));
+
+        assert!(svg.contains("<linearGradient id=\"paint0\" gradientUnits=\"userSpaceOnUse\" \
+                              x1=\"0\" y1=\"0\" x2=\"100\" y2=\"0\">"));
+        assert!(svg.contains("<stop offset=\"0\" stop-color=\"#ff0000\" />"));
+        assert!(svg.contains("<stop offset=\"1\" stop-color=\"#0000ff\" />"));
+        assert!(svg.contains(" fill=\"url(#paint0)\""));
+
+        // The definition comes before its use.
+        assert!(svg.find("<linearGradient").unwrap() < svg.find("url(#paint0)").unwrap());
+    }
+
+    #[test]
+    fn test_clip_path() {
+        let paint = Paint::Color(ColorU::black());
+        let svg = export(&scene_with_path(paint, true, FillRule::Winding, BlendMode::SrcOver));
+        assert!(svg.contains("<clipPath id=\"clip0\"><path d=\"M 20 20 L 70 20 L 70 70 L 20 70 \
+                              L 20 20 Z \" /></clipPath>"));
+        assert!(svg.contains(" clip-path=\"url(#clip0)\""));
+    }
+
+    fn push_rect(scene: &mut Scene, paint: Paint, name: &str) {
+        let paint = scene.push_paint(&paint);
+        let outline = Outline::from_rect(RectF::new(Vector2F::splat(10.0), Vector2F::splat(20.0)));
+        scene.push_path(DrawPath::new(outline,
+                                      paint,
+                                      None,
+                                      FillRule::Winding,
+                                      BlendMode::SrcOver,
+                                      name.to_owned()));
+    }
+
+    #[test]
+    fn test_duplicate_names() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(100.0)));
+        let mut gradient = Gradient::linear(LineSegment2F::new(Vector2F::new(0.0, 0.0),
+                                                               Vector2F::new(100.0, 0.0)));
+        gradient.add_color_stop(ColorStop::new(ColorU::black(), 0.0));
+        push_rect(&mut scene, Paint::Gradient(gradient), "paint0");
+        push_rect(&mut scene, Paint::Color(ColorU::black()), "shape");
+        push_rect(&mut scene, Paint::Color(ColorU::black()), "shape");
+        push_rect(&mut scene, Paint::Color(ColorU::black()), "shape");
+
+        let svg = export(&scene);
+        assert!(svg.contains("<linearGradient id=\"paint0\""));
+        assert!(svg.contains("<path id=\"paint0-2\""));
+        assert!(svg.contains("<path id=\"shape\""));
+        assert!(svg.contains("<path id=\"shape-2\""));
+        assert!(svg.contains("<path id=\"shape-3\""));
+    }
+
+    #[test]
+    fn test_render_target_pattern() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(100.0)));
+        let render_target_id = scene.push_render_target(RenderTarget::new(Vector2I::splat(40),
+                                                                          String::new()));
+        push_rect(&mut scene, Paint::Color(ColorU::new(255, 0, 0, 255)), "tile");
+        scene.pop_render_target();
+        let pattern = Pattern::new(PatternSource::RenderTarget(render_target_id), Repeat::all());
+        push_rect(&mut scene, Paint::Pattern(pattern), "");
+
+        let svg = export(&scene);
+        assert!(svg.contains("<pattern id=\"paint1\" patternUnits=\"userSpaceOnUse\" \
+                              width=\"40\" height=\"40\"><svg width=\"40\" height=\"40\">\
+                              <use xlink:href=\"#layer0\" /></svg></pattern>"));
+        assert!(svg.contains("    <defs><g id=\"layer0\">\n        <path id=\"tile\" \
+                              fill=\"#ff0000\""));
+        assert!(svg.contains("</g></defs>\n    <path fill=\"url(#paint1)\""));
+        assert!(!svg.contains("fill=\"none\""));
+    }
+
+    #[test]
+    fn test_render_target_opacity_and_mask() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(100.0)));
+        let mask = scene.push_render_target(RenderTarget::with_view_box_size(String::new()));
+        push_rect(&mut scene, Paint::Color(ColorU::white()), "");
+        scene.pop_render_target();
+        let layer = scene.push_render_target(RenderTarget::with_view_box_size(String::new()));
+        push_rect(&mut scene, Paint::Color(ColorU::new(0, 0, 255, 255)), "");
+        scene.pop_render_target();
+        scene.draw_render_target(layer, Effects {
+            filter: Filter::Opacity { opacity: 0.5, mask: None, mask_mode: MaskMode::Alpha },
+        });
+        for &mask_mode in &[MaskMode::Luminance, MaskMode::Alpha, MaskMode::InvertedAlpha] {
+            scene.draw_render_target(layer, Effects {
+                filter: Filter::Opacity { opacity: 1.0, mask: Some(mask), mask_mode },
+            });
+        }
+
+        let svg = export(&scene);
+        assert!(svg.contains("<defs><g id=\"layer0\">"));
+        assert!(svg.contains("<defs><g id=\"layer1\">"));
+        assert!(svg.contains("    <use xlink:href=\"#layer1\" opacity=\"0.5\" />"));
+        assert!(!svg.contains("mask=\"url(#mask6)\""));
+
+        assert!(svg.contains("<mask id=\"mask7\"><use xlink:href=\"#layer0\" /></mask>"));
+        assert!(svg.contains("<use xlink:href=\"#layer1\" mask=\"url(#mask7)\" />"));
+        assert!(svg.contains("<mask id=\"mask8\" style=\"mask-type: alpha\">\
+                              <use xlink:href=\"#layer0\" /></mask>"));
+
+        // Inverted masks draw the mask in black over white.
+        assert!(svg.contains("<filter id=\"filter9\" color-interpolation-filters=\"sRGB\">\
+                              <feColorMatrix type=\"matrix\" \
+                              values=\"0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 1 0\" /></filter>\
+                              <mask id=\"mask9\"><rect x=\"0\" y=\"0\" width=\"100\" \
+                              height=\"100\" fill=\"#ffffff\" /><use xlink:href=\"#layer0\" \
+                              filter=\"url(#filter9)\" /></mask>"));
+        assert!(svg.contains("<use xlink:href=\"#layer1\" mask=\"url(#mask9)\" />"));
+    }
+}
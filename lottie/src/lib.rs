@@ -10,20 +10,30 @@
 
 //! Experimental support for Lottie. This is very incomplete.
 
-use serde::{Deserialize, Serialize};
-use serde_json::Error as JSONError;
+#[macro_use]
+extern crate bitflags;
+
+use serde::de::Error as DeserializeError;
+use serde::ser::Error as SerializeError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Error as JSONError, Map, Value};
 use std::io::Read;
 
+pub use crate::render::{BuildResultFlags, BuiltLottie};
+
+mod property;
+mod render;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Lottie {
     #[serde(rename = "v")]
     pub version: String,
     #[serde(rename = "fr")]
-    pub frame_rate: i64,
+    pub frame_rate: f32,
     #[serde(rename = "ip")]
-    pub in_point: i64,
+    pub in_point: f32,
     #[serde(rename = "op")]
-    pub out_point: i64,
+    pub out_point: f32,
     #[serde(rename = "w")]
     pub width: f64,
     #[serde(rename = "h")]
@@ -34,42 +44,164 @@ pub struct Lottie {
     pub layers: Vec<Layer>,
 }
 
+// Precompositions have layers, while images have a path.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Asset {}
+pub struct Asset {
+    pub id: String,
+    #[serde(default)]
+    pub layers: Option<Vec<Layer>>,
+    #[serde(rename = "w")]
+    #[serde(default)]
+    pub width: Option<f64>,
+    #[serde(rename = "h")]
+    #[serde(default)]
+    pub height: Option<f64>,
+    #[serde(rename = "u")]
+    #[serde(default)]
+    pub directory: Option<String>,
+    #[serde(rename = "p")]
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(rename = "e")]
+    #[serde(default)]
+    pub embedded: Option<i64>,
+}
 
-// FIXME(pcwalton): Using an untagged enum is a botch here. There actually is a tag: it's just an
-// integer, which `serde_json` doesn't support natively.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum Layer {
-    Shape {
-        #[serde(rename = "ddd")]
-        three_d: i64,
-        #[serde(rename = "ind")]
-        index: i64,
-        #[serde(rename = "nm")]
-        name: String,
-        #[serde(rename = "ks")]
-        transform: Transform,
-        #[serde(rename = "ao")]
-        auto_orient: i64,
-        #[serde(rename = "ip")]
-        in_point: i64,
-        #[serde(rename = "op")]
-        out_point: i64,
-        #[serde(rename = "st")]
-        start_time: i64,
-        #[serde(rename = "bm")]
-        blend_mode: i64,
-        #[serde(rename = "sr")]
-        stretch: i64,
-        #[serde(rename = "ln")]
-        #[serde(default)]
-        layer_id: Option<String>,
-        shapes: Vec<Shape>,
-    },
+pub struct Layer {
+    #[serde(rename = "ddd")]
+    #[serde(default)]
+    pub three_d: i64,
+    #[serde(rename = "ind")]
+    #[serde(default)]
+    pub index: Option<i64>,
+    #[serde(rename = "nm")]
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "ks")]
+    pub transform: Transform,
+    #[serde(rename = "ao")]
+    #[serde(default)]
+    pub auto_orient: i64,
+    #[serde(rename = "ip")]
+    pub in_point: f32,
+    #[serde(rename = "op")]
+    pub out_point: f32,
+    #[serde(rename = "st")]
+    #[serde(default)]
+    pub start_time: f32,
+    #[serde(rename = "bm")]
+    #[serde(default)]
+    pub blend_mode: i64,
+    #[serde(rename = "sr")]
+    #[serde(default = "default_stretch")]
+    pub stretch: f32,
+    #[serde(rename = "ln")]
+    #[serde(default)]
+    pub layer_id: Option<String>,
+    // The `ind` of the layer whose transform this layer's transform is relative to.
+    #[serde(default)]
+    pub parent: Option<i64>,
+    #[serde(rename = "hasMask")]
+    #[serde(default)]
+    pub has_mask: Option<bool>,
+    #[serde(rename = "masksProperties")]
+    #[serde(default)]
+    pub masks: Vec<Mask>,
+    // Uses the layer before this one as a matte: 1 for alpha, 2 for inverted alpha, 3 for luma,
+    // and 4 for inverted luma.
+    #[serde(rename = "tt")]
+    #[serde(default)]
+    pub track_matte: Option<i64>,
+    // Set on layers that are only drawn as the track matte of the layer after them.
+    #[serde(rename = "td")]
+    #[serde(default)]
+    pub is_track_matte: Option<i64>,
+    #[serde(flatten)]
+    pub kind: LayerKind,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Mask {
+    #[serde(rename = "nm")]
+    #[serde(default)]
+    pub name: Option<String>,
+    // "a" to add, "s" to subtract, "i" to intersect, and "n" for none. "l" (lighten), "d" (darken)
+    // and "f" (difference) also exist.
+    #[serde(default = "default_mask_mode")]
+    pub mode: String,
+    #[serde(rename = "inv")]
+    #[serde(default)]
+    pub inverted: bool,
+    #[serde(rename = "pt")]
+    pub path: ShapeVertices,
+    #[serde(rename = "o")]
+    #[serde(default)]
+    pub opacity: Option<PropertyValue>,
+    #[serde(rename = "x")]
+    #[serde(default)]
+    pub expansion: Option<PropertyValue>,
+    #[serde(rename = "f")]
+    #[serde(default)]
+    pub feather: Option<MultidimensionalPropertyValue>,
+}
+
+/// The type-specific part of a layer, selected by its integer `ty` tag.
+#[derive(Clone, Debug)]
+pub enum LayerKind {
+    Precomp(PrecompLayer),
+    Solid(SolidLayer),
+    Image(ImageLayer),
+    Null,
+    Shape(ShapeLayer),
+    /// A layer of an unsupported type, such as text, along with its tag.
+    Unimplemented(i64),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PrecompLayer {
+    #[serde(rename = "refId")]
+    pub ref_id: String,
+    #[serde(rename = "w")]
+    #[serde(default)]
+    pub width: Option<f64>,
+    #[serde(rename = "h")]
+    #[serde(default)]
+    pub height: Option<f64>,
+    // Maps the time of the layer to the time of the precomposition, in seconds.
+    #[serde(rename = "tm")]
+    #[serde(default)]
+    pub time_remap: Option<PropertyValue>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SolidLayer {
+    // A CSS-style hex color, such as `#ff0000`.
+    #[serde(rename = "sc")]
+    pub color: String,
+    #[serde(rename = "sw")]
+    pub width: f64,
+    #[serde(rename = "sh")]
+    pub height: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImageLayer {
+    #[serde(rename = "refId")]
+    pub ref_id: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShapeLayer {
+    pub shapes: Vec<Shape>,
+}
+
+const PRECOMP_LAYER_TAG: i64 = 0;
+const SOLID_LAYER_TAG: i64 = 1;
+const IMAGE_LAYER_TAG: i64 = 2;
+const NULL_LAYER_TAG: i64 = 3;
+const SHAPE_LAYER_TAG: i64 = 4;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Transform {
     #[serde(rename = "p")]
@@ -130,11 +262,20 @@ pub struct KeyframeValue {
     #[serde(rename = "s")]
     #[serde(default)]
     pub start: Option<Vec<f32>>,
+    #[serde(rename = "e")]
+    #[serde(default)]
+    pub end: Option<Vec<f32>>,
     #[serde(rename = "t")]
-    pub time: i64,
+    pub time: f32,
     #[serde(rename = "i")]
     #[serde(default)]
-    pub interpolation: Option<OffsetInterpolation>,
+    pub in_value: Option<OffsetInterpolation>,
+    #[serde(rename = "o")]
+    #[serde(default)]
+    pub out_value: Option<OffsetInterpolation>,
+    #[serde(rename = "h")]
+    #[serde(default)]
+    pub hold: Option<i64>,
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
@@ -143,9 +284,13 @@ pub struct Interpolation {
     pub y: f32,
 }
 
+// The easing handles of a keyframe. Each dimension of a property can have its own handle, but
+// properties with a single handle for all dimensions often store it as a plain number.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct OffsetInterpolation {
+    #[serde(deserialize_with = "deserialize_number_or_vec")]
     pub x: Vec<f32>,
+    #[serde(deserialize_with = "deserialize_number_or_vec")]
     pub y: Vec<f32>,
 }
 
@@ -154,14 +299,27 @@ pub struct OffsetKeyframe {
     #[serde(rename = "s")]
     #[serde(default)]
     pub start: Option<Vec<f32>>,
+    #[serde(rename = "e")]
+    #[serde(default)]
+    pub end: Option<Vec<f32>>,
     #[serde(rename = "t")]
-    pub time: i64,
+    pub time: f32,
     #[serde(rename = "i")]
     #[serde(default)]
     pub in_value: Option<OffsetInterpolation>,
     #[serde(rename = "o")]
     #[serde(default)]
     pub out_value: Option<OffsetInterpolation>,
+    #[serde(rename = "h")]
+    #[serde(default)]
+    pub hold: Option<i64>,
+    // Spatial tangents of the motion path, relative to the start and end values.
+    #[serde(rename = "to")]
+    #[serde(default)]
+    pub out_tangent: Option<Vec<f32>>,
+    #[serde(rename = "ti")]
+    #[serde(default)]
+    pub in_tangent: Option<Vec<f32>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -213,6 +371,53 @@ pub enum Shape {
         #[serde(default)]
         direction: Option<i64>,
     },
+    #[serde(rename = "rc")]
+    Rect {
+        #[serde(rename = "p")]
+        position: MultidimensionalPropertyValue,
+        #[serde(rename = "s")]
+        size: MultidimensionalPropertyValue,
+        #[serde(rename = "r")]
+        roundness: PropertyValue,
+        #[serde(rename = "d")]
+        #[serde(default)]
+        direction: Option<i64>,
+    },
+    #[serde(rename = "el")]
+    Ellipse {
+        #[serde(rename = "p")]
+        position: MultidimensionalPropertyValue,
+        #[serde(rename = "s")]
+        size: MultidimensionalPropertyValue,
+        #[serde(rename = "d")]
+        #[serde(default)]
+        direction: Option<i64>,
+    },
+    // Stars have inner and outer vertices, while polygons only have outer ones.
+    #[serde(rename = "sr")]
+    Star {
+        #[serde(rename = "sy")]
+        star_type: i64,
+        #[serde(rename = "p")]
+        position: MultidimensionalPropertyValue,
+        #[serde(rename = "r")]
+        rotation: PropertyValue,
+        #[serde(rename = "pt")]
+        points: PropertyValue,
+        #[serde(rename = "or")]
+        outer_radius: PropertyValue,
+        #[serde(rename = "os")]
+        outer_roundness: PropertyValue,
+        #[serde(rename = "ir")]
+        #[serde(default)]
+        inner_radius: Option<PropertyValue>,
+        #[serde(rename = "is")]
+        #[serde(default)]
+        inner_roundness: Option<PropertyValue>,
+        #[serde(rename = "d")]
+        #[serde(default)]
+        direction: Option<i64>,
+    },
     #[serde(rename = "fl")]
     Fill {
         #[serde(rename = "nm")]
@@ -223,6 +428,108 @@ pub enum Shape {
         opacity: Option<PropertyValue>,
         #[serde(rename = "c")]
         color: MultidimensionalPropertyValue,
+        #[serde(rename = "r")]
+        #[serde(default)]
+        fill_rule: Option<i64>,
+    },
+    #[serde(rename = "st")]
+    Stroke {
+        #[serde(rename = "nm")]
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(rename = "o")]
+        #[serde(default)]
+        opacity: Option<PropertyValue>,
+        #[serde(rename = "c")]
+        color: MultidimensionalPropertyValue,
+        #[serde(rename = "w")]
+        width: PropertyValue,
+        #[serde(rename = "lc")]
+        #[serde(default)]
+        line_cap: Option<i64>,
+        #[serde(rename = "lj")]
+        #[serde(default)]
+        line_join: Option<i64>,
+        #[serde(rename = "ml")]
+        #[serde(default)]
+        miter_limit: Option<f32>,
+        #[serde(rename = "d")]
+        #[serde(default)]
+        dashes: Vec<StrokeDash>,
+    },
+    #[serde(rename = "gf")]
+    GradientFill {
+        #[serde(rename = "nm")]
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(rename = "o")]
+        #[serde(default)]
+        opacity: Option<PropertyValue>,
+        #[serde(flatten)]
+        gradient: GradientProperty,
+        #[serde(rename = "r")]
+        #[serde(default)]
+        fill_rule: Option<i64>,
+    },
+    #[serde(rename = "gs")]
+    GradientStroke {
+        #[serde(rename = "nm")]
+        #[serde(default)]
+        name: Option<String>,
+        #[serde(rename = "o")]
+        #[serde(default)]
+        opacity: Option<PropertyValue>,
+        #[serde(flatten)]
+        gradient: GradientProperty,
+        #[serde(rename = "w")]
+        width: PropertyValue,
+        #[serde(rename = "lc")]
+        #[serde(default)]
+        line_cap: Option<i64>,
+        #[serde(rename = "lj")]
+        #[serde(default)]
+        line_join: Option<i64>,
+        #[serde(rename = "ml")]
+        #[serde(default)]
+        miter_limit: Option<f32>,
+        #[serde(rename = "d")]
+        #[serde(default)]
+        dashes: Vec<StrokeDash>,
+    },
+    #[serde(rename = "tm")]
+    TrimPaths {
+        #[serde(rename = "s")]
+        start: PropertyValue,
+        #[serde(rename = "e")]
+        end: PropertyValue,
+        #[serde(rename = "o")]
+        offset: PropertyValue,
+        #[serde(rename = "m")]
+        #[serde(default)]
+        mode: Option<i64>,
+    },
+    #[serde(rename = "rp")]
+    Repeater {
+        #[serde(rename = "c")]
+        copies: PropertyValue,
+        #[serde(rename = "o")]
+        #[serde(default)]
+        offset: Option<PropertyValue>,
+        #[serde(rename = "m")]
+        #[serde(default)]
+        composite: Option<i64>,
+        #[serde(rename = "tr")]
+        transform: RepeaterTransform,
+    },
+    #[serde(rename = "rd")]
+    RoundedCorners {
+        #[serde(rename = "r")]
+        radius: PropertyValue,
+    },
+    #[serde(rename = "mm")]
+    MergePaths {
+        #[serde(rename = "mm")]
+        mode: i64,
     },
     #[serde(rename = "tr")]
     Transform {
@@ -238,11 +545,71 @@ pub enum Shape {
         anchor_point: MultidimensionalPropertyValue,
         #[serde(rename = "s")]
         scale: MultidimensionalPropertyValue,
+        #[serde(rename = "o")]
+        #[serde(default)]
+        opacity: Option<PropertyValue>,
     },
     #[serde(other)]
     Unimplemented,
 }
 
+// The color stops of a gradient are packed into a single property: first `count` groups of offset,
+// red, green and blue, and then optionally groups of offset and alpha.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GradientColors {
+    #[serde(rename = "p")]
+    pub count: usize,
+    #[serde(rename = "k")]
+    pub colors: MultidimensionalPropertyValue,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GradientProperty {
+    // 1 for linear gradients, and 2 for radial ones.
+    #[serde(rename = "t")]
+    pub gradient_type: i64,
+    #[serde(rename = "s")]
+    pub start_point: MultidimensionalPropertyValue,
+    #[serde(rename = "e")]
+    pub end_point: MultidimensionalPropertyValue,
+    // Moves the focal point of radial gradients toward the end point, as a percentage.
+    #[serde(rename = "h")]
+    #[serde(default)]
+    pub highlight_length: Option<PropertyValue>,
+    #[serde(rename = "a")]
+    #[serde(default)]
+    pub highlight_angle: Option<PropertyValue>,
+    #[serde(rename = "g")]
+    pub colors: GradientColors,
+}
+
+// `kind` is "d" for a dash, "g" for a gap and "o" for the offset of the pattern.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StrokeDash {
+    #[serde(rename = "n")]
+    pub kind: String,
+    #[serde(rename = "v")]
+    pub value: PropertyValue,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RepeaterTransform {
+    #[serde(rename = "a")]
+    pub anchor_point: MultidimensionalPropertyValue,
+    #[serde(rename = "p")]
+    pub position: MultidimensionalPropertyValue,
+    #[serde(rename = "s")]
+    pub scale: MultidimensionalPropertyValue,
+    #[serde(rename = "r")]
+    pub rotation: PropertyValue,
+    #[serde(rename = "so")]
+    #[serde(default)]
+    pub start_opacity: Option<PropertyValue>,
+    #[serde(rename = "eo")]
+    #[serde(default)]
+    pub end_opacity: Option<PropertyValue>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ShapeVertices {
@@ -295,14 +662,20 @@ pub struct ShapeKeyframeProperty {
     #[serde(rename = "s")]
     #[serde(default)]
     pub start: Vec<Option<ShapeProperty>>,
+    #[serde(rename = "e")]
+    #[serde(default)]
+    pub end: Vec<Option<ShapeProperty>>,
     #[serde(rename = "t")]
-    pub time: i64,
+    pub time: f32,
     #[serde(rename = "i")]
     #[serde(default)]
     pub in_value: Option<OffsetInterpolation>,
     #[serde(rename = "o")]
     #[serde(default)]
     pub out_value: Option<OffsetInterpolation>,
+    #[serde(rename = "h")]
+    #[serde(default)]
+    pub hold: Option<i64>,
 }
 
 impl Lottie {
@@ -311,3 +684,120 @@ impl Lottie {
         serde_json::from_reader(reader)
     }
 }
+
+impl<'de> Deserialize<'de> for LayerKind {
+    fn deserialize<D>(deserializer: D) -> Result<LayerKind, D::Error> where D: Deserializer<'de> {
+        let mut fields = Map::deserialize(deserializer)?;
+        let tag = match fields.remove("ty").and_then(|tag| tag.as_i64()) {
+            None => return Err(D::Error::missing_field("ty")),
+            Some(tag) => tag,
+        };
+        let fields = Value::Object(fields);
+        let kind = match tag {
+            PRECOMP_LAYER_TAG => PrecompLayer::deserialize(fields).map(LayerKind::Precomp),
+            SOLID_LAYER_TAG => SolidLayer::deserialize(fields).map(LayerKind::Solid),
+            IMAGE_LAYER_TAG => ImageLayer::deserialize(fields).map(LayerKind::Image),
+            NULL_LAYER_TAG => Ok(LayerKind::Null),
+            SHAPE_LAYER_TAG => ShapeLayer::deserialize(fields).map(LayerKind::Shape),
+            tag => Ok(LayerKind::Unimplemented(tag)),
+        };
+        kind.map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for LayerKind {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let (tag, fields) = match *self {
+            LayerKind::Precomp(ref layer) => (PRECOMP_LAYER_TAG, serde_json::to_value(layer)),
+            LayerKind::Solid(ref layer) => (SOLID_LAYER_TAG, serde_json::to_value(layer)),
+            LayerKind::Image(ref layer) => (IMAGE_LAYER_TAG, serde_json::to_value(layer)),
+            LayerKind::Null => (NULL_LAYER_TAG, Ok(Value::Object(Map::new()))),
+            LayerKind::Shape(ref layer) => (SHAPE_LAYER_TAG, serde_json::to_value(layer)),
+            LayerKind::Unimplemented(tag) => (tag, Ok(Value::Object(Map::new()))),
+        };
+        let mut fields = match fields.map_err(S::Error::custom)? {
+            Value::Object(fields) => fields,
+            _ => Map::new(),
+        };
+        fields.insert("ty".to_owned(), Value::from(tag));
+        fields.serialize(serializer)
+    }
+}
+
+fn default_stretch() -> f32 {
+    1.0
+}
+
+fn default_mask_mode() -> String {
+    "a".to_owned()
+}
+
+fn deserialize_number_or_vec<'de, D>(deserializer: D) -> Result<Vec<f32>, D::Error>
+                                     where D: Deserializer<'de> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrVec {
+        Number(f32),
+        Vec(Vec<f32>),
+    }
+
+    match NumberOrVec::deserialize(deserializer)? {
+        NumberOrVec::Number(number) => Ok(vec![number]),
+        NumberOrVec::Vec(vec) => Ok(vec),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::{self, Value};
+    use super::{Layer, LayerKind};
+
+    #[test]
+    fn test_layer_kind_round_trip() {
+        let kinds = [
+            r#"{"ty": 0, "refId": "comp_0", "w": 40.0, "h": 20.0, "tm": null}"#,
+            r##"{"ty": 1, "sc": "#ff0000", "sw": 40.0, "sh": 20.0}"##,
+            r#"{"ty": 2, "refId": "image_0"}"#,
+            r#"{"ty": 3}"#,
+            r#"{"ty": 4, "shapes": []}"#,
+            r#"{"ty": 5}"#,
+        ];
+        for (tag, json) in kinds.iter().enumerate() {
+            let value: Value = serde_json::from_str(json).unwrap();
+            let kind: LayerKind = serde_json::from_value(value.clone()).unwrap();
+            match (tag, &kind) {
+                (0, LayerKind::Precomp(layer)) => assert_eq!(layer.ref_id, "comp_0"),
+                (1, LayerKind::Solid(layer)) => assert_eq!(layer.color, "#ff0000"),
+                (2, LayerKind::Image(layer)) => assert_eq!(layer.ref_id, "image_0"),
+                (3, LayerKind::Null) | (4, LayerKind::Shape(_)) => {}
+                (5, LayerKind::Unimplemented(5)) => {}
+                _ => panic!("tag {} deserialized as {:?}", tag, kind),
+            }
+            assert_eq!(serde_json::to_value(&kind).unwrap(), value);
+        }
+
+        assert!(serde_json::from_str::<LayerKind>(r##"{"sc": "#ff0000"}"##).is_err());
+        assert!(serde_json::from_str::<LayerKind>(r#"{"ty": 1, "sw": 40.0}"#).is_err());
+    }
+
+    #[test]
+    fn test_layer_kind_is_flattened_into_layer() {
+        let layer: Layer = serde_json::from_str(r##"{
+            "ty": 1, "nm": "solid", "ip": 0, "op": 10,
+            "ks": {"p": {"k": [0, 0]}, "a": {"k": [0, 0]}, "s": {"k": [100, 100]}, "r": {"k": 0}},
+            "sc": "#00ff00", "sw": 40, "sh": 20
+        }"##).unwrap();
+        match layer.kind {
+            LayerKind::Solid(ref solid_layer) => assert_eq!(solid_layer.width, 40.0),
+            ref kind => panic!("deserialized as {:?}", kind),
+        }
+
+        let value = serde_json::to_value(&layer).unwrap();
+        assert_eq!(value["ty"], 1);
+        assert_eq!(value["sc"], "#00ff00");
+        assert_eq!(value["nm"], "solid");
+        let layer: Layer = serde_json::from_value(value).unwrap();
+        assert_eq!(layer.name, "solid");
+        assert!(matches!(layer.kind, LayerKind::Solid(_)));
+    }
+}
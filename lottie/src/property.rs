@@ -0,0 +1,411 @@
+// pathfinder/lottie/src/property.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Sampling of animatable properties at a given frame.
+//!
+//! Between keyframes, values are eased along the cubic Bézier curve given by the `o` (out) and
+//! `i` (in) handles of the keyframe that starts the span, as in After Effects.
+
+use crate::{KeyframeValue, MultidimensionalPropertyValue, OffsetInterpolation, OffsetKeyframe};
+use crate::{PropertyValue, ShapeKeyframeProperty, ShapeProperty, ShapeVertices};
+
+// The number of times the easing curve solver refines its guess.
+const EASING_ITERATIONS: u32 = 16;
+const EASING_EPSILON: f32 = 0.00001;
+
+// The number of line segments that motion paths are divided into to move along them at the
+// eased speed.
+const MOTION_PATH_SEGMENTS: usize = 64;
+
+impl PropertyValue {
+    /// Returns the value of this property at the given frame.
+    pub fn value_at(&self, frame: f32) -> f32 {
+        match *self {
+            PropertyValue::Value { value, .. } => value,
+            PropertyValue::KeyframedValue { ref keyframes, .. } => {
+                let value = sample(keyframes, frame, |_, start, end, easing| {
+                    lerp_values(start, end, easing)
+                });
+                value.and_then(|value| value.first().cloned()).unwrap_or(0.0)
+            }
+        }
+    }
+}
+
+impl MultidimensionalPropertyValue {
+    /// Returns the value of this property at the given frame.
+    ///
+    /// Properties with motion paths, such as positions, move along the path.
+    pub fn value_at(&self, frame: f32) -> Vec<f32> {
+        match *self {
+            MultidimensionalPropertyValue::Value { ref value, .. } => value.clone(),
+            MultidimensionalPropertyValue::KeyframedValue { ref keyframes, .. } => {
+                sample(keyframes, frame, |keyframe, start, end, easing| {
+                    match (&keyframe.out_tangent, &keyframe.in_tangent) {
+                        (Some(out_tangent), Some(in_tangent)) => {
+                            move_along_path(start, end, out_tangent, in_tangent, easing.value(0))
+                        }
+                        _ => lerp_values(start, end, easing),
+                    }
+                }).unwrap_or_default()
+            }
+        }
+    }
+}
+
+impl ShapeVertices {
+    /// Returns the path at the given frame, if there is one.
+    ///
+    /// Paths can only be interpolated if they have the same number of vertices. Otherwise, each
+    /// path holds until the next keyframe.
+    pub fn value_at(&self, frame: f32) -> Option<ShapeProperty> {
+        match *self {
+            ShapeVertices::Shape { ref value, .. } => Some(value.clone()),
+            ShapeVertices::ShapeKeyframed { ref value, .. } => {
+                sample(value, frame, |_, start, end, easing| {
+                    lerp_paths(start, end, easing.value(0))
+                })
+            }
+        }
+    }
+}
+
+// The parts of a keyframe that are needed to sample a property.
+trait Keyframe {
+    type Value: Clone;
+    fn time(&self) -> f32;
+    fn is_hold(&self) -> bool;
+    fn in_value(&self) -> Option<&OffsetInterpolation>;
+    fn out_value(&self) -> Option<&OffsetInterpolation>;
+    fn start(&self) -> Option<&Self::Value>;
+    fn end(&self) -> Option<&Self::Value>;
+}
+
+impl Keyframe for KeyframeValue {
+    type Value = Vec<f32>;
+    fn time(&self) -> f32 { self.time }
+    fn is_hold(&self) -> bool { self.hold.unwrap_or(0) != 0 }
+    fn in_value(&self) -> Option<&OffsetInterpolation> { self.in_value.as_ref() }
+    fn out_value(&self) -> Option<&OffsetInterpolation> { self.out_value.as_ref() }
+    fn start(&self) -> Option<&Vec<f32>> { self.start.as_ref() }
+    fn end(&self) -> Option<&Vec<f32>> { self.end.as_ref() }
+}
+
+impl Keyframe for OffsetKeyframe {
+    type Value = Vec<f32>;
+    fn time(&self) -> f32 { self.time }
+    fn is_hold(&self) -> bool { self.hold.unwrap_or(0) != 0 }
+    fn in_value(&self) -> Option<&OffsetInterpolation> { self.in_value.as_ref() }
+    fn out_value(&self) -> Option<&OffsetInterpolation> { self.out_value.as_ref() }
+    fn start(&self) -> Option<&Vec<f32>> { self.start.as_ref() }
+    fn end(&self) -> Option<&Vec<f32>> { self.end.as_ref() }
+}
+
+impl Keyframe for ShapeKeyframeProperty {
+    type Value = ShapeProperty;
+    fn time(&self) -> f32 { self.time }
+    fn is_hold(&self) -> bool { self.hold.unwrap_or(0) != 0 }
+    fn in_value(&self) -> Option<&OffsetInterpolation> { self.in_value.as_ref() }
+    fn out_value(&self) -> Option<&OffsetInterpolation> { self.out_value.as_ref() }
+    fn start(&self) -> Option<&ShapeProperty> {
+        self.start.first().and_then(|start| start.as_ref())
+    }
+    fn end(&self) -> Option<&ShapeProperty> {
+        self.end.first().and_then(|end| end.as_ref())
+    }
+}
+
+// How far along the span between two keyframes a frame is, before easing.
+struct Easing<'a> {
+    progress: f32,
+    out_value: Option<&'a OffsetInterpolation>,
+    in_value: Option<&'a OffsetInterpolation>,
+}
+
+impl<'a> Easing<'a> {
+    // Returns the eased progress of the given dimension. Dimensions without their own handles use
+    // those of the first dimension, and keyframes without handles ease linearly.
+    fn value(&self, dimension: usize) -> f32 {
+        let (out_value, in_value) = match (self.out_value, self.in_value) {
+            (Some(out_value), Some(in_value)) => (out_value, in_value),
+            _ => return self.progress,
+        };
+        let handle = |values: &[f32]| {
+            values.get(dimension).or_else(|| values.first()).cloned()
+        };
+        let (out_x, out_y) = (handle(&out_value.x), handle(&out_value.y));
+        let (in_x, in_y) = (handle(&in_value.x), handle(&in_value.y));
+        match (out_x, out_y, in_x, in_y) {
+            (Some(x1), Some(y1), Some(x2), Some(y2)) => {
+                cubic_bezier_easing(x1, y1, x2, y2, self.progress)
+            }
+            _ => self.progress,
+        }
+    }
+}
+
+// Samples keyframes at the given frame. Before the first keyframe and after the last one, values
+// hold. In between, `interpolate` blends the values of the surrounding keyframes.
+fn sample<K, F>(keyframes: &[K], frame: f32, interpolate: F) -> Option<K::Value>
+                where K: Keyframe, F: FnOnce(&K, &K::Value, &K::Value, &Easing) -> K::Value {
+    let next_index = keyframes.iter()
+                              .position(|keyframe| keyframe.time() > frame)
+                              .unwrap_or(keyframes.len());
+    if next_index == 0 {
+        return keyframes.iter().find_map(|keyframe| keyframe.start()).cloned();
+    }
+
+    let index = next_index - 1;
+    let keyframe = &keyframes[index];
+    let start = match keyframe.start() {
+        Some(start) => start,
+        // Keyframes without a value (such as the final keyframe in older files) hold the value
+        // that the previous keyframe ended on.
+        None => {
+            return keyframes[..index].last().and_then(|previous| {
+                previous.end().or_else(|| previous.start()).cloned()
+            });
+        }
+    };
+
+    let next_keyframe = match keyframes.get(next_index) {
+        Some(next_keyframe) if !keyframe.is_hold() => next_keyframe,
+        _ => return Some(start.clone()),
+    };
+    let end = match keyframe.end().or_else(|| next_keyframe.start()) {
+        Some(end) => end,
+        None => return Some(start.clone()),
+    };
+
+    let duration = next_keyframe.time() - keyframe.time();
+    let progress = if duration > 0.0 { (frame - keyframe.time()) / duration } else { 1.0 };
+    let easing = Easing {
+        progress: progress.clamp(0.0, 1.0),
+        out_value: keyframe.out_value(),
+        in_value: keyframe.in_value(),
+    };
+    Some(interpolate(keyframe, start, end, &easing))
+}
+
+fn lerp_values(start: &[f32], end: &[f32], easing: &Easing) -> Vec<f32> {
+    start.iter().zip(end.iter()).enumerate().map(|(dimension, (&start, &end))| {
+        lerp(start, end, easing.value(dimension))
+    }).collect()
+}
+
+// Moves along the cubic Bézier motion path from `start` to `end` by the given fraction of its
+// length, so that the speed along the path follows the easing curve.
+fn move_along_path(start: &[f32],
+                   end: &[f32],
+                   out_tangent: &[f32],
+                   in_tangent: &[f32],
+                   fraction: f32)
+                   -> Vec<f32> {
+    let dimensions = start.len().min(end.len());
+    let tangent = |tangent: &[f32], dimension: usize| {
+        tangent.get(dimension).cloned().unwrap_or(0.0)
+    };
+    let point_at = |t: f32| -> Vec<f32> {
+        (0..dimensions).map(|dimension| {
+            let (from, to) = (start[dimension], end[dimension]);
+            let ctrl0 = from + tangent(out_tangent, dimension);
+            let ctrl1 = to + tangent(in_tangent, dimension);
+            cubic_bezier(from, ctrl0, ctrl1, to, t)
+        }).collect()
+    };
+
+    if out_tangent.iter().chain(in_tangent.iter()).all(|&tangent| tangent == 0.0) {
+        return point_at(fraction);
+    }
+
+    // Approximate the path with line segments, and measure along them.
+    let points: Vec<_> = (0..=MOTION_PATH_SEGMENTS).map(|index| {
+        point_at(index as f32 / MOTION_PATH_SEGMENTS as f32)
+    }).collect();
+    let lengths: Vec<f32> = points.windows(2).map(|segment| distance(&segment[0], &segment[1]))
+                                             .collect();
+    let total_length: f32 = lengths.iter().sum();
+    if total_length <= 0.0 {
+        return point_at(fraction);
+    }
+
+    let mut remaining_length = fraction * total_length;
+    for (index, &length) in lengths.iter().enumerate() {
+        if remaining_length <= length || index + 1 == lengths.len() {
+            let segment_fraction = if length > 0.0 { remaining_length / length } else { 0.0 };
+            let t = (index as f32 + segment_fraction.clamp(0.0, 1.0)) /
+                MOTION_PATH_SEGMENTS as f32;
+            return point_at(t);
+        }
+        remaining_length -= length;
+    }
+    point_at(fraction)
+}
+
+fn lerp_paths(start: &ShapeProperty, end: &ShapeProperty, t: f32) -> ShapeProperty {
+    if start.vertices.len() != end.vertices.len() ||
+            start.in_points.len() != end.in_points.len() ||
+            start.out_points.len() != end.out_points.len() {
+        return start.clone();
+    }
+    let lerp_points = |start: &[[f32; 2]], end: &[[f32; 2]]| {
+        start.iter().zip(end.iter()).map(|(start, end)| {
+            [lerp(start[0], end[0], t), lerp(start[1], end[1], t)]
+        }).collect()
+    };
+    ShapeProperty {
+        closed: start.closed,
+        in_points: lerp_points(&start.in_points, &end.in_points),
+        out_points: lerp_points(&start.out_points, &end.out_points),
+        vertices: lerp_points(&start.vertices, &end.vertices),
+    }
+}
+
+// Evaluates the easing curve from (0, 0) to (1, 1) with control points (x1, y1) and (x2, y2) at
+// the given x, as CSS `cubic-bezier()` does.
+fn cubic_bezier_easing(x1: f32, y1: f32, x2: f32, y2: f32, x: f32) -> f32 {
+    let (x1, x2) = (x1.clamp(0.0, 1.0), x2.clamp(0.0, 1.0));
+    if x <= 0.0 || x >= 1.0 {
+        return x;
+    }
+
+    // Solve for t with Newton's method, falling back to bisection where the curve is flat. Since
+    // x is monotonic in t, the bisection bounds always contain the solution.
+    let (mut lower, mut upper) = (0.0, 1.0);
+    let mut t = x;
+    for _ in 0..EASING_ITERATIONS {
+        let error = cubic_bezier(0.0, x1, x2, 1.0, t) - x;
+        if error.abs() < EASING_EPSILON {
+            break;
+        }
+        if error > 0.0 {
+            upper = t;
+        } else {
+            lower = t;
+        }
+        let slope = cubic_bezier_derivative(0.0, x1, x2, 1.0, t);
+        let next_t = t - error / slope;
+        t = if slope.abs() > EASING_EPSILON && next_t > lower && next_t < upper {
+            next_t
+        } else {
+            (lower + upper) * 0.5
+        };
+    }
+    cubic_bezier(0.0, y1, y2, 1.0, t)
+}
+
+fn cubic_bezier(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let u = 1.0 - t;
+    u * u * u * p0 + 3.0 * u * u * t * p1 + 3.0 * u * t * t * p2 + t * t * t * p3
+}
+
+fn cubic_bezier_derivative(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let u = 1.0 - t;
+    3.0 * u * u * (p1 - p0) + 6.0 * u * t * (p2 - p1) + 3.0 * t * t * (p3 - p2)
+}
+
+fn distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(a, b)| (b - a) * (b - a)).sum::<f32>().sqrt()
+}
+
+#[inline]
+fn lerp(start: f32, end: f32, t: f32) -> f32 {
+    start + (end - start) * t
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{PropertyValue, ShapeProperty};
+    use super::{cubic_bezier_easing, lerp_paths, move_along_path};
+
+    const EPSILON: f32 = 0.001;
+
+    fn property(json: &str) -> PropertyValue {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn square(size: f32, vertex_count: usize) -> ShapeProperty {
+        let corners = [[0.0, 0.0], [size, 0.0], [size, size], [0.0, size]];
+        let vertices = corners.iter().cloned().cycle().take(vertex_count).collect();
+        ShapeProperty {
+            closed: true,
+            in_points: vec![[0.0, 0.0]; vertex_count],
+            out_points: vec![[0.0, 0.0]; vertex_count],
+            vertices,
+        }
+    }
+
+    #[test]
+    fn test_cubic_bezier_easing() {
+        for &x in &[0.0, 0.1, 0.25, 0.5, 0.9, 1.0] {
+            assert!((cubic_bezier_easing(0.0, 0.0, 1.0, 1.0, x) - x).abs() < EPSILON);
+            assert!((cubic_bezier_easing(0.25, 0.25, 0.75, 0.75, x) - x).abs() < EPSILON);
+        }
+
+        // Whatever the handles, the curve starts at 0 and ends at 1.
+        let handles = [(0.42, 0.0, 0.58, 1.0), (0.0, 1.0, 1.0, 0.0), (0.8, -0.5, 0.2, 1.5)];
+        for &(x1, y1, x2, y2) in &handles {
+            assert_eq!(cubic_bezier_easing(x1, y1, x2, y2, 0.0), 0.0);
+            assert_eq!(cubic_bezier_easing(x1, y1, x2, y2, 1.0), 1.0);
+        }
+
+        // Ease-in-out is symmetric about the midpoint.
+        assert!((cubic_bezier_easing(0.42, 0.0, 0.58, 1.0, 0.5) - 0.5).abs() < EPSILON);
+        assert!(cubic_bezier_easing(0.42, 0.0, 0.58, 1.0, 0.25) < 0.25);
+    }
+
+    #[test]
+    fn test_hold_outside_keyframes() {
+        let value = property(r#"{"k": [{"t": 10, "s": [0], "e": [100]}, {"t": 20, "s": [100]}]}"#);
+        assert_eq!(value.value_at(0.0), 0.0);
+        assert_eq!(value.value_at(10.0), 0.0);
+        assert!((value.value_at(15.0) - 50.0).abs() < EPSILON);
+        assert_eq!(value.value_at(20.0), 100.0);
+        assert_eq!(value.value_at(30.0), 100.0);
+    }
+
+    #[test]
+    fn test_hold_keyframe() {
+        let value = property(r#"{"k": [{"t": 0, "s": [5], "h": 1}, {"t": 10, "s": [20]}]}"#);
+        assert_eq!(value.value_at(0.0), 5.0);
+        assert_eq!(value.value_at(9.9), 5.0);
+        assert_eq!(value.value_at(10.0), 20.0);
+    }
+
+    #[test]
+    fn test_final_keyframe_without_value() {
+        let value = property(r#"{"k": [{"t": 0, "s": [0], "e": [10]}, {"t": 10}]}"#);
+        assert!((value.value_at(5.0) - 5.0).abs() < EPSILON);
+        assert_eq!(value.value_at(10.0), 10.0);
+        assert_eq!(value.value_at(15.0), 10.0);
+    }
+
+    #[test]
+    fn test_move_along_symmetric_path() {
+        // A symmetric arch from (0, 0) to (100, 0), whose halfway point by length is its apex.
+        let point = move_along_path(&[0.0, 0.0], &[100.0, 0.0], &[0.0, 50.0], &[0.0, 50.0], 0.5);
+        assert!((point[0] - 50.0).abs() < 0.1);
+        assert!((point[1] - 37.5).abs() < 0.1);
+
+        let end = move_along_path(&[0.0, 0.0], &[100.0, 0.0], &[0.0, 50.0], &[0.0, 50.0], 1.0);
+        assert!((end[0] - 100.0).abs() < EPSILON && end[1].abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_lerp_paths() {
+        let halfway = lerp_paths(&square(10.0, 4), &square(20.0, 4), 0.5);
+        assert_eq!(halfway.vertices[2], [15.0, 15.0]);
+
+        // Paths with different numbers of vertices can't be blended, so the start path holds.
+        let start = square(10.0, 4);
+        let held = lerp_paths(&start, &square(20.0, 5), 0.5);
+        assert_eq!(held.vertices, start.vertices);
+    }
+}
@@ -0,0 +1,1674 @@
+// pathfinder/lottie/src/render.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Converts a Lottie composition at a given frame into a Pathfinder scene.
+
+use crate::{GradientProperty, Layer, LayerKind, Lottie, Mask, MultidimensionalPropertyValue};
+use crate::{PropertyValue, RepeaterTransform, Shape, ShapeProperty, StrokeDash, Transform};
+
+use pathfinder_color::{ColorF, ColorU};
+use pathfinder_content::boolean::{BooleanOp, OutlineBoolean};
+use pathfinder_content::dash::OutlineDash;
+use pathfinder_content::effects::{BlendMode, Effects, Filter, MaskMode};
+use pathfinder_content::fill::FillRule;
+use pathfinder_content::gradient::{ColorStop, Gradient};
+use pathfinder_content::measure::{ContourMeasure, OutlineMeasure};
+use pathfinder_content::outline::{Contour, Outline};
+use pathfinder_content::segment::{Segment, SegmentKind};
+use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::Vector2F;
+use pathfinder_geometry::util;
+use pathfinder_renderer::paint::Paint;
+use pathfinder_content::pattern::RenderTargetId;
+use pathfinder_renderer::scene::{ClipPath, ClipPathId, DrawPath, RenderTarget, Scene};
+use std::f32::consts::PI;
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::mem;
+
+// The length of the handles of a cubic Bézier curve approximating a quarter circle, relative to
+// the radius.
+const KAPPA: f32 = 0.5522848;
+
+// Precompositions that (indirectly) contain themselves are cut off after this many levels.
+const MAX_PRECOMP_DEPTH: u32 = 16;
+
+const DEFAULT_MITER_LIMIT: f32 = 4.0;
+
+// The number of bands that the soft edge of a feathered mask is drawn with.
+const MASK_FEATHER_STEPS: u32 = 8;
+
+pub struct BuiltLottie {
+    pub scene: Scene,
+    pub result_flags: BuildResultFlags,
+}
+
+bitflags! {
+    // NB: If you change this, make sure to update the `Display`
+    // implementation as well.
+    pub struct BuildResultFlags: u16 {
+        const UNSUPPORTED_IMAGE_LAYER       = 0x0001;
+        const UNSUPPORTED_MASK_MODE         = 0x0002;
+        const UNSUPPORTED_MASK_EXPANSION    = 0x0004;
+    }
+}
+
+impl BuiltLottie {
+    /// Builds a scene showing the composition at the given frame.
+    ///
+    /// Frames are counted from the start of the composition, so `lottie.in_point` is the first
+    /// frame that's normally shown. Fractional frames are allowed.
+    #[inline]
+    pub fn from_lottie(lottie: &Lottie, frame: f32) -> BuiltLottie {
+        BuiltLottie::from_lottie_and_scene(lottie, frame, Scene::new())
+    }
+
+    pub fn from_lottie_and_scene(lottie: &Lottie, frame: f32, scene: Scene) -> BuiltLottie {
+        let mut built_lottie = BuiltLottie { scene, result_flags: BuildResultFlags::empty() };
+        built_lottie.scene.set_view_box(RectF::new(Vector2F::default(),
+                                                   Vector2F::new(lottie.width as f32,
+                                                                 lottie.height as f32)));
+
+        let state = State { transform: Transform2F::default(), clip_path: None };
+        built_lottie.process_layers(lottie, &lottie.layers, frame, &state, 0);
+        built_lottie
+    }
+
+    fn process_layers(&mut self,
+                      lottie: &Lottie,
+                      layers: &[Layer],
+                      frame: f32,
+                      state: &State,
+                      depth: u32) {
+        // Layers are listed front to back. Track mattes are only drawn through the layer after
+        // them.
+        for (index, layer) in layers.iter().enumerate().rev() {
+            if layer.is_track_matte.unwrap_or(0) != 0 || !layer.is_visible_at(frame) {
+                continue;
+            }
+
+            let matte = match (layer.track_matte.and_then(track_matte_mode), index.checked_sub(1)) {
+                (Some(mask_mode), Some(matte_index)) => Some((&layers[matte_index], mask_mode)),
+                _ => None,
+            };
+            match matte {
+                None => self.process_layer(lottie, layers, layer, frame, state, depth),
+                Some((matte_layer, mask_mode)) => {
+                    let matte = self.push_layer_render_target(format!("Matte({})",
+                                                                      matte_layer.name));
+                    self.process_layer(lottie, layers, matte_layer, frame, state, depth);
+                    self.scene.pop_render_target();
+
+                    let render_target = self.push_layer_render_target(layer.name.clone());
+                    self.process_layer(lottie, layers, layer, frame, state, depth);
+                    self.scene.pop_render_target();
+                    self.draw_masked_render_target(render_target, Some(matte), mask_mode, 1.0);
+                }
+            }
+        }
+    }
+
+    // Draws a layer, applying its masks. `layers` are its siblings, which may be its parents.
+    fn process_layer(&mut self,
+                     lottie: &Lottie,
+                     layers: &[Layer],
+                     layer: &Layer,
+                     frame: f32,
+                     state: &State,
+                     depth: u32) {
+        if !layer.is_visible_at(frame) {
+            return;
+        }
+
+        let local_frame = layer.local_frame(frame);
+        let opacity = opacity_at(layer.transform.opacity.as_ref(), local_frame);
+        if opacity == 0.0 {
+            return;
+        }
+        let mut state = State {
+            transform: state.transform * parented_transform(layers, layer, frame),
+            clip_path: state.clip_path,
+        };
+
+        let masks: Vec<&Mask> = layer.masks.iter().filter(|mask| mask.mode != "n").collect();
+        for mask in &masks {
+            self.check_mask_support(mask, local_frame);
+        }
+
+        // A single opaque mask that adds to the layer is just a clip path. Anything else is drawn
+        // into a render target and used as an alpha mask.
+        let mut mask_render_target = None;
+        match masks[..] {
+            [] => {}
+            [mask] if state.clip_path.is_none() && mask.mode == "a" &&
+                    opacity_at(mask.opacity.as_ref(), local_frame) == 1.0 &&
+                    mask_feather(mask, local_frame) == Vector2F::default() => {
+                let (outline, fill_rule) = mask_outline(mask,
+                                                        local_frame,
+                                                        &state.transform,
+                                                        mask.inverted,
+                                                        Vector2F::default(),
+                                                        self.scene.view_box());
+                let clip_path = ClipPath::new(outline, fill_rule, format!("Mask({})", layer.name));
+                state.clip_path = Some(self.scene.push_clip_path(clip_path));
+            }
+            _ => {
+                let mask = self.draw_masks(&masks, local_frame, &state.transform, &layer.name);
+                mask_render_target = Some(mask);
+            }
+        }
+
+        // The opacity of a layer applies to its contents as a whole, so where its paths overlap
+        // they don't show through each other.
+        if opacity == 1.0 && mask_render_target.is_none() {
+            self.process_layer_contents(lottie, layer, local_frame, &state, depth);
+            return;
+        }
+        let render_target = self.push_layer_render_target(layer.name.clone());
+        self.process_layer_contents(lottie, layer, local_frame, &state, depth);
+        self.scene.pop_render_target();
+        self.draw_masked_render_target(render_target, mask_render_target, MaskMode::Alpha, opacity);
+    }
+
+    // `frame` is relative to the start of the layer.
+    fn process_layer_contents(&mut self,
+                              lottie: &Lottie,
+                              layer: &Layer,
+                              frame: f32,
+                              state: &State,
+                              depth: u32) {
+        match layer.kind {
+            LayerKind::Shape(ref shape_layer) => {
+                let group = process_group(&shape_layer.shapes, frame, &layer.name);
+                self.push_paths(group.paths, state);
+            }
+            LayerKind::Solid(ref solid_layer) => {
+                let rect = RectF::new(Vector2F::default(),
+                                      Vector2F::new(solid_layer.width as f32,
+                                                    solid_layer.height as f32));
+                let path = PendingPath {
+                    outline: Outline::from_rect(rect),
+                    paint: Paint::Color(parse_hex_color(&solid_layer.color)),
+                    opacity: 1.0,
+                    fill_rule: FillRule::Winding,
+                    name: layer.name.clone(),
+                };
+                self.push_paths(vec![path], state);
+            }
+            LayerKind::Precomp(ref precomp_layer) => {
+                if depth >= MAX_PRECOMP_DEPTH {
+                    return;
+                }
+                let asset = lottie.assets.iter().find(|asset| asset.id == precomp_layer.ref_id);
+                let layers = match asset.and_then(|asset| asset.layers.as_ref()) {
+                    None => return,
+                    Some(layers) => layers,
+                };
+
+                // Time remapping gives the time of the precomposition in seconds.
+                let frame = match precomp_layer.time_remap {
+                    None => frame,
+                    Some(ref time_remap) => time_remap.value_at(frame) * lottie.frame_rate,
+                };
+
+                // The precomposition is cropped to its size.
+                //
+                // FIXME: Paths only have one clip path, so this replaces any clip of an enclosing
+                // precomposition or mask instead of intersecting with it.
+                let mut state = *state;
+                if let (Some(width), Some(height)) = (precomp_layer.width, precomp_layer.height) {
+                    let rect = RectF::new(Vector2F::default(),
+                                          Vector2F::new(width as f32, height as f32));
+                    let mut outline = Outline::from_rect(rect);
+                    outline.transform(&state.transform);
+                    let clip_path = ClipPath::new(outline, FillRule::Winding, layer.name.clone());
+                    state.clip_path = Some(self.scene.push_clip_path(clip_path));
+                }
+
+                self.process_layers(lottie, layers, frame, &state, depth + 1);
+            }
+            LayerKind::Image(_) => {
+                self.result_flags.insert(BuildResultFlags::UNSUPPORTED_IMAGE_LAYER);
+            }
+            LayerKind::Null | LayerKind::Unimplemented(_) => {}
+        }
+    }
+
+    // Draws the masks of a layer into a render target, for use as an alpha mask. Each mask is
+    // combined with the ones before it according to its mode.
+    fn draw_masks(&mut self,
+                  masks: &[&Mask],
+                  frame: f32,
+                  transform: &Transform2F,
+                  name: &str)
+                  -> RenderTargetId {
+        let render_target = self.push_layer_render_target(format!("Mask({})", name));
+        let view_box = self.scene.view_box();
+        for (index, mask) in masks.iter().enumerate() {
+            // Subtracting from or intersecting with nothing starts from the whole layer instead.
+            if index == 0 && (mask.mode == "s" || mask.mode == "i") {
+                self.push_mask_path(Outline::from_rect(view_box), FillRule::Winding, 1.0,
+                                    BlendMode::SrcOver);
+            }
+
+            // Intersecting removes everything outside the mask. Lighten, darken and difference
+            // masks are unsupported, and add to the mask instead.
+            let (inverted, blend_mode) = match &*mask.mode {
+                "s" => (mask.inverted, BlendMode::DestOut),
+                "i" => (!mask.inverted, BlendMode::DestOut),
+                _ => (mask.inverted, BlendMode::SrcOver),
+            };
+            let opacity = opacity_at(mask.opacity.as_ref(), frame);
+            let feather = mask_feather(mask, frame);
+            if feather == Vector2F::default() {
+                let (outline, fill_rule) =
+                    mask_outline(mask, frame, transform, inverted, Vector2F::default(), view_box);
+                self.push_mask_path(outline, fill_rule, opacity, blend_mode);
+                continue;
+            }
+
+            // The feather fades the mask out linearly, from half its radius inside the edge to
+            // half of it outside. This is approximated with bands from the widest to the
+            // narrowest, so that a point covered by `k` of them has `k / MASK_FEATHER_STEPS` of
+            // the opacity of the mask. Inverted masks widen as the mask shrinks.
+            let mut coverage = 0.0;
+            for step in 0..MASK_FEATHER_STEPS {
+                let t = (step as f32 + 0.5) / MASK_FEATHER_STEPS as f32;
+                let mut dilation = feather.scale(0.5 - t);
+                if inverted {
+                    dilation = -dilation;
+                }
+                let next_coverage = opacity * (step + 1) as f32 / MASK_FEATHER_STEPS as f32;
+                let band_opacity = 1.0 - (1.0 - next_coverage) / (1.0 - coverage);
+                coverage = next_coverage;
+
+                let (outline, fill_rule) =
+                    mask_outline(mask, frame, transform, inverted, dilation, view_box);
+                self.push_mask_path(outline, fill_rule, band_opacity, blend_mode);
+            }
+        }
+        self.scene.pop_render_target();
+        render_target
+    }
+
+    fn check_mask_support(&mut self, mask: &Mask, frame: f32) {
+        if !["a", "s", "i"].contains(&&*mask.mode) {
+            self.result_flags.insert(BuildResultFlags::UNSUPPORTED_MASK_MODE);
+        }
+        if let Some(ref expansion) = mask.expansion {
+            if expansion.value_at(frame) != 0.0 {
+                self.result_flags.insert(BuildResultFlags::UNSUPPORTED_MASK_EXPANSION);
+            }
+        }
+    }
+
+    fn push_mask_path(&mut self,
+                      outline: Outline,
+                      fill_rule: FillRule,
+                      opacity: f32,
+                      blend_mode: BlendMode) {
+        let color = ColorF::new(1.0, 1.0, 1.0, opacity).to_u8();
+        let paint_id = self.scene.push_paint(&Paint::Color(color));
+        self.scene.push_path(DrawPath::new(outline,
+                                           paint_id,
+                                           None,
+                                           fill_rule,
+                                           blend_mode,
+                                           String::new()));
+    }
+
+    // Render targets for masks and mattes cover the whole composition.
+    fn push_layer_render_target(&mut self, name: String) -> RenderTargetId {
+        self.scene.push_render_target(RenderTarget::with_view_box_size(name))
+    }
+
+    fn draw_masked_render_target(&mut self,
+                                 render_target: RenderTargetId,
+                                 mask: Option<RenderTargetId>,
+                                 mask_mode: MaskMode,
+                                 opacity: f32) {
+        let filter = Filter::Opacity { opacity, mask, mask_mode };
+        self.scene.draw_render_target(render_target, Effects { filter });
+    }
+
+    // Pushes paths, which are listed frontmost first, to the scene.
+    fn push_paths(&mut self, paths: Vec<PendingPath>, state: &State) {
+        for mut path in paths.into_iter().rev() {
+            path.outline.transform(&state.transform);
+            path.paint.apply_transform(&state.transform);
+            path.paint.set_opacity(path.opacity);
+            let paint_id = self.scene.push_paint(&path.paint);
+            self.scene.push_path(DrawPath::new(path.outline,
+                                               paint_id,
+                                               state.clip_path,
+                                               path.fill_rule,
+                                               BlendMode::SrcOver,
+                                               path.name));
+        }
+    }
+}
+
+impl Display for BuildResultFlags {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let mut first = true;
+        for (bit, name) in NAMES.iter().enumerate() {
+            if (self.bits() >> bit) & 1 == 0 {
+                continue;
+            }
+            if !first {
+                formatter.write_str(", ")?;
+            } else {
+                first = false;
+            }
+            formatter.write_str(name)?;
+        }
+
+        return Ok(());
+
+        // Must match the order in `BuildResultFlags`.
+        static NAMES: &[&str] = &[
+            "image layer",
+            "lighten, darken or difference mask",
+            "mask expansion",
+        ];
+    }
+}
+
+#[derive(Clone, Copy)]
+struct State {
+    transform: Transform2F,
+    clip_path: Option<ClipPathId>,
+}
+
+// The contents of a shape group, in its own coordinate space.
+struct Group {
+    // The outlines of its path items, so that fills later in the enclosing group can include them.
+    // Nested groups count as one shape, which matters when merging paths.
+    shapes: Vec<Outline>,
+    // The paths that fill and stroke its items, frontmost first.
+    paths: Vec<PendingPath>,
+}
+
+impl Group {
+    fn outline(&self) -> Outline {
+        let mut outline = Outline::new();
+        for shape in &self.shapes {
+            for contour in shape.contours() {
+                outline.push_contour(contour.clone());
+            }
+        }
+        outline
+    }
+}
+
+#[derive(Clone)]
+struct PendingPath {
+    outline: Outline,
+    paint: Paint,
+    opacity: f32,
+    fill_rule: FillRule,
+    name: String,
+}
+
+impl PendingPath {
+    fn transform(&mut self, transform: &Transform2F) {
+        self.outline.transform(transform);
+        self.paint.apply_transform(transform);
+    }
+}
+
+fn process_group(items: &[Shape], frame: f32, name: &str) -> Group {
+    let mut transform = Transform2F::default();
+    let mut opacity = 1.0;
+    for item in items {
+        if let Shape::Transform {
+            ref position,
+            ref anchor_point,
+            ref scale,
+            ref rotation,
+            ref skew,
+            ref skew_axis,
+            opacity: ref group_opacity,
+        } = *item {
+            transform *= compose_transform(anchor_point.value_at(frame),
+                                           position.value_at(frame),
+                                           scale.value_at(frame),
+                                           rotation.value_at(frame),
+                                           skew.value_at(frame),
+                                           skew_axis.value_at(frame));
+            opacity *= opacity_at(group_opacity.as_ref(), frame);
+        }
+    }
+
+    // Fills and strokes apply to all the paths before them in the group, including those in
+    // nested groups. Unnamed ones are named after the group.
+    let mut group = Group { shapes: vec![], paths: vec![] };
+    for item in items {
+        match *item {
+            Shape::Group { ref items, ref name } => {
+                let child = process_group(items, frame, name);
+                group.shapes.push(child.outline());
+                group.paths.extend(child.paths);
+            }
+            Shape::Shape { ref vertices, direction } => {
+                if let Some(path) = vertices.value_at(frame) {
+                    push_path(&mut group.shapes, path, direction);
+                }
+            }
+            Shape::Rect { ref position, ref size, ref roundness, direction } => {
+                let path = rect_path(vector_value(&position.value_at(frame), 0.0),
+                                     vector_value(&size.value_at(frame), 0.0),
+                                     roundness.value_at(frame));
+                push_path(&mut group.shapes, path, direction);
+            }
+            Shape::Ellipse { ref position, ref size, direction } => {
+                let path = ellipse_path(vector_value(&position.value_at(frame), 0.0),
+                                        vector_value(&size.value_at(frame), 0.0));
+                push_path(&mut group.shapes, path, direction);
+            }
+            Shape::Star {
+                star_type,
+                ref position,
+                ref rotation,
+                ref points,
+                ref outer_radius,
+                ref outer_roundness,
+                ref inner_radius,
+                ref inner_roundness,
+                direction,
+            } => {
+                let value_at = |property: Option<&PropertyValue>| {
+                    property.map_or(0.0, |property| property.value_at(frame))
+                };
+                let outer = StarRadius {
+                    radius: outer_radius.value_at(frame),
+                    roundness: outer_roundness.value_at(frame) * 0.01,
+                };
+                // Polygons have no inner vertices.
+                let inner = if star_type == 2 {
+                    None
+                } else {
+                    Some(StarRadius {
+                        radius: value_at(inner_radius.as_ref()),
+                        roundness: value_at(inner_roundness.as_ref()) * 0.01,
+                    })
+                };
+                let path = star_path(vector_value(&position.value_at(frame), 0.0),
+                                     rotation.value_at(frame),
+                                     points.value_at(frame),
+                                     outer,
+                                     inner);
+                push_path(&mut group.shapes, path, direction);
+            }
+            Shape::Fill { ref opacity, ref color, fill_rule, name: ref item_name } => {
+                group.paths.push(PendingPath {
+                    outline: group.outline(),
+                    paint: Paint::Color(color_value(color, frame)),
+                    opacity: opacity_at(opacity.as_ref(), frame),
+                    fill_rule: fill_rule_value(fill_rule),
+                    name: item_name.clone().unwrap_or_else(|| name.to_owned()),
+                });
+            }
+            Shape::GradientFill { ref opacity, ref gradient, fill_rule, name: ref item_name } => {
+                group.paths.push(PendingPath {
+                    outline: group.outline(),
+                    paint: gradient_paint(gradient, frame),
+                    opacity: opacity_at(opacity.as_ref(), frame),
+                    fill_rule: fill_rule_value(fill_rule),
+                    name: item_name.clone().unwrap_or_else(|| name.to_owned()),
+                });
+            }
+            Shape::Stroke {
+                ref opacity,
+                ref color,
+                ref width,
+                line_cap,
+                line_join,
+                miter_limit,
+                ref dashes,
+                name: ref item_name,
+            } => {
+                let style = stroke_style(width.value_at(frame), line_cap, line_join, miter_limit);
+                group.paths.push(PendingPath {
+                    outline: stroke_outline(&group.outline(), style, dashes, frame),
+                    paint: Paint::Color(color_value(color, frame)),
+                    opacity: opacity_at(opacity.as_ref(), frame),
+                    fill_rule: FillRule::Winding,
+                    name: item_name.clone().unwrap_or_else(|| name.to_owned()),
+                });
+            }
+            Shape::GradientStroke {
+                ref opacity,
+                ref gradient,
+                ref width,
+                line_cap,
+                line_join,
+                miter_limit,
+                ref dashes,
+                name: ref item_name,
+            } => {
+                let style = stroke_style(width.value_at(frame), line_cap, line_join, miter_limit);
+                group.paths.push(PendingPath {
+                    outline: stroke_outline(&group.outline(), style, dashes, frame),
+                    paint: gradient_paint(gradient, frame),
+                    opacity: opacity_at(opacity.as_ref(), frame),
+                    fill_rule: FillRule::Winding,
+                    name: item_name.clone().unwrap_or_else(|| name.to_owned()),
+                });
+            }
+            Shape::Repeater { ref copies, ref offset, composite, ref transform } => {
+                let offset = offset.as_ref().map_or(0.0, |offset| offset.value_at(frame));
+                repeat(&mut group, copies.value_at(frame), offset, composite, transform, frame);
+            }
+            Shape::TrimPaths { ref start, ref end, ref offset, mode } => {
+                trim(&mut group,
+                     start.value_at(frame),
+                     end.value_at(frame),
+                     offset.value_at(frame),
+                     mode);
+            }
+            Shape::RoundedCorners { ref radius } => {
+                round_corners(&mut group, radius.value_at(frame));
+            }
+            Shape::MergePaths { mode } => merge(&mut group, mode),
+            Shape::Transform { .. } | Shape::Unimplemented => {}
+        }
+    }
+
+    for shape in &mut group.shapes {
+        shape.transform(&transform);
+    }
+    for path in &mut group.paths {
+        path.transform(&transform);
+        path.opacity *= opacity;
+    }
+    group
+}
+
+fn push_path(shapes: &mut Vec<Outline>, path: ShapeProperty, direction: Option<i64>) {
+    // A direction of 3 reverses the path, which changes which areas the nonzero rule fills.
+    let path = if direction == Some(3) { reverse_path(path) } else { path };
+    let mut outline = Outline::new();
+    outline.push_contour(path_to_contour(&path));
+    shapes.push(outline);
+}
+
+// Trims the shapes of the group so far to the part between the start and end percentages of
+// their length, shifted by the offset in degrees, where a full turn is the whole length.
+//
+// By default, each contour is trimmed on its own. A mode of 2 trims all of them as if they were
+// joined end to end, so that they're drawn one after another.
+//
+// Fills and strokes that came before this item, including those of nested groups, are unaffected.
+fn trim(group: &mut Group, start: f32, end: f32, offset: f32, mode: Option<i64>) {
+    let ranges = match trim_ranges(start, end, offset) {
+        None => return,
+        Some(ranges) => ranges,
+    };
+
+    if mode == Some(2) {
+        let measures: Vec<_> = group.shapes.iter().map(OutlineMeasure::new).collect();
+        let total_length: f32 = measures.iter().map(|measure| measure.length()).sum();
+        let mut shape_start = 0.0;
+        for (shape, measure) in group.shapes.iter_mut().zip(measures.iter()) {
+            let mut trimmed = Outline::new();
+            for &(from, to) in &ranges {
+                let from = from * total_length - shape_start;
+                let to = to * total_length - shape_start;
+                if to > 0.0 && from < measure.length() {
+                    for contour in measure.extract(from, to).contours() {
+                        trimmed.push_contour(contour.clone());
+                    }
+                }
+            }
+            shape_start += measure.length();
+            *shape = trimmed;
+        }
+        return;
+    }
+
+    for shape in &mut group.shapes {
+        let mut trimmed = Outline::new();
+        for contour in shape.contours() {
+            let measure = ContourMeasure::new(contour);
+            let length = measure.length();
+            let mut pieces: Vec<_> = ranges.iter().map(|&(from, to)| {
+                measure.extract(from * length, to * length)
+            }).filter(|piece| !piece.is_empty()).collect();
+
+            // A range that wraps around the end of a closed contour is one piece.
+            if measure.is_closed() && ranges.len() == 2 && pieces.len() == 2 {
+                let last_piece = pieces.pop().unwrap();
+                append_contour(&mut pieces[0], &last_piece);
+            }
+            for piece in pieces {
+                trimmed.push_contour(piece);
+            }
+        }
+        *shape = trimmed;
+    }
+}
+
+// Rounds the corners of the shapes of the group so far where two straight edges meet, replacing
+// each with a circular arc of the given radius, as `rect_path` does. The radius is limited to half
+// the length of either edge so that neighbouring arcs don't overlap. The ends of open contours and
+// corners next to curves are left as they are.
+fn round_corners(group: &mut Group, radius: f32) {
+    if radius <= 0.0 {
+        return;
+    }
+    for shape in &mut group.shapes {
+        let mut rounded = Outline::new();
+        for contour in shape.contours() {
+            rounded.push_contour(round_contour_corners(contour, radius));
+        }
+        *shape = rounded;
+    }
+}
+
+fn round_contour_corners(contour: &Contour, radius: f32) -> Contour {
+    // Closed paths repeat their first point at the end, which leaves an empty closing line.
+    let segments: Vec<_> = contour.iter().filter(|segment| {
+        !segment.is_line() || segment.baseline.square_length() > 0.0
+    }).collect();
+    if segments.is_empty() {
+        return contour.clone();
+    }
+
+    let closed = contour.is_closed();
+    let count = segments.len();
+    // Whether the corner at the start of the segment with the given index is rounded.
+    let is_rounded = |index: usize| {
+        (closed || (index > 0 && index < count)) && segments[index % count].is_line() &&
+            segments[(index + count - 1) % count].is_line()
+    };
+    let cut = |segment: &Segment| radius.min(segment.baseline.vector().length() * 0.5);
+
+    let mut rounded = Contour::new();
+    let mut last_to = Vector2F::default();
+    for (index, segment) in segments.iter().enumerate() {
+        let (from, to) = (segment.baseline.from(), segment.baseline.to());
+        if !segment.is_line() {
+            if index == 0 {
+                rounded.push_endpoint(from);
+            }
+            match segment.kind {
+                SegmentKind::Quadratic => rounded.push_quadratic(segment.ctrl.from(), to),
+                _ => rounded.push_cubic(segment.ctrl.from(), segment.ctrl.to(), to),
+            }
+            last_to = to;
+            continue;
+        }
+
+        let direction = segment.baseline.vector().normalize();
+        let start = if is_rounded(index) { from + direction.scale(cut(segment)) } else { from };
+        if index == 0 {
+            rounded.push_endpoint(start);
+        } else if is_rounded(index) {
+            push_corner(&mut rounded, last_to, from, start);
+        }
+        last_to = if is_rounded(index + 1) { to - direction.scale(cut(segment)) } else { to };
+        // Nothing is left of edges that are cut back by half their length at both ends.
+        if last_to != start {
+            rounded.push_endpoint(last_to);
+        }
+    }
+
+    if closed {
+        if is_rounded(0) {
+            let (first, corner) = (&segments[0], segments[0].baseline.from());
+            let start = corner + first.baseline.vector().normalize().scale(cut(first));
+            push_corner(&mut rounded, last_to, corner, start);
+        }
+        rounded.close();
+    }
+    rounded
+}
+
+// Pushes the arc from the current end of the contour, around the corner, to the given point.
+fn push_corner(contour: &mut Contour, from: Vector2F, corner: Vector2F, to: Vector2F) {
+    contour.push_cubic(from + (corner - from).scale(KAPPA), to + (corner - to).scale(KAPPA), to);
+}
+
+// Returns the fractions of the length to keep, or `None` to keep all of it. A range that wraps
+// around the end is split into one that ends at 1 and one that starts at 0, in that order.
+fn trim_ranges(start: f32, end: f32, offset: f32) -> Option<Vec<(f32, f32)>> {
+    let (start, end) = ((start * 0.01).clamp(0.0, 1.0), (end * 0.01).clamp(0.0, 1.0));
+    let (start, end) = if start <= end { (start, end) } else { (end, start) };
+    if start == 0.0 && end == 1.0 {
+        return None;
+    }
+    if start == end {
+        return Some(vec![]);
+    }
+
+    let offset = offset / 360.0;
+    let from = start + offset - (start + offset).floor();
+    let to = from + (end - start);
+    if to <= 1.0 {
+        Some(vec![(from, to)])
+    } else {
+        Some(vec![(from, 1.0), (0.0, to - 1.0)])
+    }
+}
+
+// Extends a contour with the segments of one that starts where it ends.
+fn append_contour(contour: &mut Contour, next: &Contour) {
+    for segment in next.iter() {
+        let to = segment.baseline.to();
+        match segment.kind {
+            SegmentKind::None => {}
+            SegmentKind::Line => contour.push_endpoint(to),
+            SegmentKind::Quadratic => contour.push_quadratic(segment.ctrl.from(), to),
+            SegmentKind::Cubic => contour.push_cubic(segment.ctrl.from(), segment.ctrl.to(), to),
+        }
+    }
+}
+
+// Replaces the shapes of the group so far with the result of combining each with those before it.
+// Mode 1, the default, draws them together unchanged.
+fn merge(group: &mut Group, mode: i64) {
+    let op = match mode {
+        2 => BooleanOp::Union,
+        3 => BooleanOp::Difference,
+        4 => BooleanOp::Intersection,
+        5 => BooleanOp::Xor,
+        _ => return,
+    };
+    let mut shapes = mem::replace(&mut group.shapes, vec![]).into_iter();
+    let first = match shapes.next() {
+        None => return,
+        Some(first) => first,
+    };
+    let merged = shapes.fold(first, |merged, shape| {
+        let mut boolean = OutlineBoolean::new(&merged,
+                                              FillRule::Winding,
+                                              &shape,
+                                              FillRule::Winding,
+                                              op);
+        boolean.combine();
+        boolean.into_outline()
+    });
+    group.shapes.push(merged);
+}
+
+// Replaces the contents of the group so far with copies, each transformed by the repeater
+// transform one more time than the last.
+fn repeat(group: &mut Group,
+          copies: f32,
+          offset: f32,
+          composite: Option<i64>,
+          transform: &RepeaterTransform,
+          frame: f32) {
+    let copy_count = copies.max(0.0).floor() as u32;
+    let anchor_point = vector_value(&transform.anchor_point.value_at(frame), 0.0);
+    let position = vector_value(&transform.position.value_at(frame), 0.0);
+    let scale = vector_value(&transform.scale.value_at(frame), 100.0).scale(0.01);
+    let rotation = transform.rotation.value_at(frame).to_radians();
+    let start_opacity = opacity_at(transform.start_opacity.as_ref(), frame);
+    let end_opacity = opacity_at(transform.end_opacity.as_ref(), frame);
+
+    let mut shapes = vec![];
+    let mut copies = vec![];
+    for index in 0..copy_count {
+        let step = index as f32 + offset;
+        let copy_transform = Transform2F::from_translation(anchor_point + position.scale(step)) *
+            Transform2F::from_rotation(rotation * step) *
+            Transform2F::from_scale(Vector2F::new(scale.x().powf(step), scale.y().powf(step))) *
+            Transform2F::from_translation(-anchor_point);
+        let t = if copy_count > 1 { index as f32 / (copy_count - 1) as f32 } else { 0.0 };
+        let copy_opacity = util::lerp(start_opacity, end_opacity, t);
+
+        for shape in &group.shapes {
+            let mut copy_shape = shape.clone();
+            copy_shape.transform(&copy_transform);
+            shapes.push(copy_shape);
+        }
+
+        let mut paths = group.paths.clone();
+        for path in &mut paths {
+            path.transform(&copy_transform);
+            path.opacity *= copy_opacity;
+        }
+        copies.push(paths);
+    }
+
+    // By default, each copy is drawn above the one before it.
+    if composite != Some(2) {
+        copies.reverse();
+    }
+    group.shapes = shapes;
+    group.paths = copies.into_iter().flatten().collect();
+}
+
+impl Layer {
+    // Layers are shown from their in point up to, but not including, their out point.
+    fn is_visible_at(&self, frame: f32) -> bool {
+        frame >= self.in_point && frame < self.out_point
+    }
+
+    // Keyframes of the layer are relative to its start time.
+    fn local_frame(&self, frame: f32) -> f32 {
+        let stretch = if self.stretch == 0.0 { 1.0 } else { self.stretch };
+        (frame - self.start_time) / stretch
+    }
+}
+
+// Returns the transform of a layer, relative to those of its chain of parents. Parents are
+// looked up by index among the layer's siblings, and each is sampled at its own local time.
+fn parented_transform(layers: &[Layer], layer: &Layer, frame: f32) -> Transform2F {
+    let mut transform = layer_transform(&layer.transform, layer.local_frame(frame));
+    let mut parent_index = layer.parent;
+
+    // Cycles are cut off once the chain is longer than the number of layers.
+    for _ in 0..layers.len() {
+        let parent = match parent_index {
+            None => break,
+            Some(index) => layers.iter().find(|layer| layer.index == Some(index)),
+        };
+        let parent = match parent {
+            None => break,
+            Some(parent) => parent,
+        };
+        transform = layer_transform(&parent.transform, parent.local_frame(frame)) * transform;
+        parent_index = parent.parent;
+    }
+    transform
+}
+
+// Returns the horizontal and vertical feather radii of a mask, in layer space.
+fn mask_feather(mask: &Mask, frame: f32) -> Vector2F {
+    let radii = match mask.feather {
+        None => return Vector2F::default(),
+        Some(ref feather) => feather.value_at(frame),
+    };
+    match radii[..] {
+        [] => Vector2F::default(),
+        [radius] => Vector2F::splat(radius),
+        [x_radius, y_radius, ..] => Vector2F::new(x_radius, y_radius),
+    }
+}
+
+// Maps the `tt` property of a layer to how its matte is applied.
+fn track_matte_mode(track_matte: i64) -> Option<MaskMode> {
+    match track_matte {
+        1 => Some(MaskMode::Alpha),
+        2 => Some(MaskMode::InvertedAlpha),
+        3 => Some(MaskMode::Luminance),
+        4 => Some(MaskMode::InvertedLuminance),
+        _ => None,
+    }
+}
+
+// Returns the outline of a mask in scene space, grown by `dilation` in layer space. Inverted masks
+// cover the rest of the view box.
+//
+// Expansion is unsupported, so masks are never grown by it.
+fn mask_outline(mask: &Mask,
+                frame: f32,
+                transform: &Transform2F,
+                inverted: bool,
+                dilation: Vector2F,
+                view_box: RectF)
+                -> (Outline, FillRule) {
+    let mut outline = Outline::new();
+    if let Some(path) = mask.path.value_at(frame) {
+        outline.push_contour(path_to_contour(&path));
+        if dilation != Vector2F::default() {
+            outline.dilate(dilation);
+        }
+        outline.transform(transform);
+    }
+    if !inverted {
+        return (outline, FillRule::Winding);
+    }
+
+    let mut inverted_outline = Outline::from_rect(view_box);
+    for contour in outline.contours() {
+        inverted_outline.push_contour(contour.clone());
+    }
+    (inverted_outline, FillRule::EvenOdd)
+}
+
+fn layer_transform(transform: &Transform, frame: f32) -> Transform2F {
+    let value_at = |property: Option<&PropertyValue>| {
+        property.map_or(0.0, |property| property.value_at(frame))
+    };
+    compose_transform(transform.anchor_point.value_at(frame),
+                      transform.position.value_at(frame),
+                      transform.scale.value_at(frame),
+                      transform.rotation.value_at(frame),
+                      value_at(transform.skew.as_ref()),
+                      value_at(transform.skew_axis.as_ref()))
+}
+
+// Builds the transform of a layer or group. Angles are in degrees, clockwise, and scales are
+// percentages.
+fn compose_transform(anchor_point: Vec<f32>,
+                     position: Vec<f32>,
+                     scale: Vec<f32>,
+                     rotation: f32,
+                     skew: f32,
+                     skew_axis: f32)
+                     -> Transform2F {
+    let anchor_point = vector_value(&anchor_point, 0.0);
+    let position = vector_value(&position, 0.0);
+    let scale = vector_value(&scale, 100.0).scale(0.01);
+
+    // Skew shears along the skew axis.
+    let skew_axis = skew_axis.to_radians();
+    let shear = Transform2F::row_major(1.0, -skew.to_radians().tan(), 0.0, 1.0, 0.0, 0.0);
+    let skew = Transform2F::from_rotation(-skew_axis) * shear *
+        Transform2F::from_rotation(skew_axis);
+
+    Transform2F::from_translation(position) *
+        Transform2F::from_rotation(rotation.to_radians()) *
+        skew *
+        Transform2F::from_scale(scale) *
+        Transform2F::from_translation(-anchor_point)
+}
+
+// Returns an opacity property, which is a percentage, as a fraction.
+fn opacity_at(opacity: Option<&PropertyValue>, frame: f32) -> f32 {
+    opacity.map_or(1.0, |opacity| opacity.value_at(frame) * 0.01).clamp(0.0, 1.0)
+}
+
+fn vector_value(value: &[f32], default: f32) -> Vector2F {
+    Vector2F::new(value.first().cloned().unwrap_or(default),
+                  value.get(1).cloned().unwrap_or(default))
+}
+
+// Colors are stored as fractions, with an optional alpha channel.
+fn color_value(color: &MultidimensionalPropertyValue, frame: f32) -> ColorU {
+    let color = color.value_at(frame);
+    let channel = |index: usize, default: f32| color.get(index).cloned().unwrap_or(default);
+    ColorF::new(channel(0, 0.0), channel(1, 0.0), channel(2, 0.0), channel(3, 1.0)).to_u8()
+}
+
+// Parses a CSS-style hex color, such as `#ff8000`. Invalid colors are black.
+fn parse_hex_color(color: &str) -> ColorU {
+    let color = color.trim_start_matches('#');
+    let channel = |index: usize| {
+        color.get((index * 2)..(index * 2 + 2))
+             .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+             .unwrap_or(0)
+    };
+    ColorU::new(channel(0), channel(1), channel(2), 255)
+}
+
+
+fn fill_rule_value(fill_rule: Option<i64>) -> FillRule {
+    match fill_rule {
+        Some(2) => FillRule::EvenOdd,
+        _ => FillRule::Winding,
+    }
+}
+
+fn stroke_style(width: f32,
+                line_cap: Option<i64>,
+                line_join: Option<i64>,
+                miter_limit: Option<f32>)
+                -> StrokeStyle {
+    let line_cap = match line_cap {
+        Some(2) => LineCap::Round,
+        Some(3) => LineCap::Square,
+        _ => LineCap::Butt,
+    };
+    let line_join = match line_join {
+        Some(2) => LineJoin::Round,
+        Some(3) => LineJoin::Bevel,
+        _ => LineJoin::Miter(miter_limit.unwrap_or(DEFAULT_MITER_LIMIT)),
+    };
+    StrokeStyle { line_width: width, line_cap, line_join }
+}
+
+fn stroke_outline(outline: &Outline,
+                  style: StrokeStyle,
+                  dashes: &[StrokeDash],
+                  frame: f32)
+                  -> Outline {
+    let mut lengths = vec![];
+    let mut offset = 0.0;
+    for dash in dashes {
+        match &*dash.kind {
+            "o" => offset = dash.value.value_at(frame),
+            _ => lengths.push(dash.value.value_at(frame)),
+        }
+    }
+
+    // Dash patterns with no length would never end.
+    let dashed;
+    let outline = if lengths.iter().any(|&length| length > 0.0) {
+        let mut dash = OutlineDash::new(outline, &lengths, offset);
+        dash.dash();
+        dashed = dash.into_outline();
+        &dashed
+    } else {
+        outline
+    };
+
+    let mut stroke_to_fill = OutlineStrokeToFill::new(outline, style);
+    stroke_to_fill.offset();
+    stroke_to_fill.into_outline()
+}
+
+fn gradient_paint(gradient: &GradientProperty, frame: f32) -> Paint {
+    let start_point = vector_value(&gradient.start_point.value_at(frame), 0.0);
+    let end_point = vector_value(&gradient.end_point.value_at(frame), 0.0);
+
+    let mut result = if gradient.gradient_type == 2 {
+        // The highlight moves the focal point from the center toward the edge of the circle.
+        let value_at = |property: Option<&PropertyValue>| {
+            property.map_or(0.0, |property| property.value_at(frame))
+        };
+        let radius = (end_point - start_point).length();
+        let highlight_length = value_at(gradient.highlight_length.as_ref()).clamp(-99.0, 99.0);
+        let highlight_angle = value_at(gradient.highlight_angle.as_ref()).to_radians() +
+            (end_point - start_point).y().atan2((end_point - start_point).x());
+        let focal_point = start_point + Vector2F::new(highlight_angle.cos(),
+                                                      highlight_angle.sin())
+                                                      .scale(radius * highlight_length * 0.01);
+        Gradient::radial(LineSegment2F::new(focal_point, start_point), 0.0, radius)
+    } else {
+        Gradient::linear(LineSegment2F::new(start_point, end_point))
+    };
+
+    // Colors come first, as groups of offset, red, green and blue. Any alpha stops follow, as
+    // pairs of offset and alpha, and are sampled at the offsets of the color stops.
+    let values = gradient.colors.colors.value_at(frame);
+    let color_count = gradient.colors.count.min(values.len() / 4);
+    let (colors, alphas) = values.split_at(color_count * 4);
+    for color in colors.chunks(4) {
+        let offset = color[0];
+        let alpha = alpha_at(alphas, offset);
+        let color = ColorF::new(color[1], color[2], color[3], alpha).to_u8();
+        result.add_color_stop(ColorStop::new(color, offset));
+    }
+    Paint::Gradient(result)
+}
+
+fn alpha_at(alphas: &[f32], offset: f32) -> f32 {
+    let stops: Vec<_> = alphas.chunks_exact(2).map(|stop| (stop[0], stop[1])).collect();
+    let (first, last) = match (stops.first(), stops.last()) {
+        (Some(&first), Some(&last)) => (first, last),
+        _ => return 1.0,
+    };
+    if offset <= first.0 {
+        return first.1;
+    }
+    for pair in stops.windows(2) {
+        let ((from_offset, from_alpha), (to_offset, to_alpha)) = (pair[0], pair[1]);
+        if offset <= to_offset {
+            let t = if to_offset > from_offset {
+                (offset - from_offset) / (to_offset - from_offset)
+            } else {
+                1.0
+            };
+            return util::lerp(from_alpha, to_alpha, t);
+        }
+    }
+    last.1
+}
+
+// Rectangles run clockwise from the right end of their top edge.
+fn rect_path(center: Vector2F, size: Vector2F, roundness: f32) -> ShapeProperty {
+    let (min, max) = (center - size.scale(0.5), center + size.scale(0.5));
+    let radius = roundness.min(size.x() * 0.5).min(size.y() * 0.5).max(0.0);
+    let mut path = ShapeProperty::new(true);
+    if radius == 0.0 {
+        for &[x, y] in &[[max.x(), min.y()], [max.x(), max.y()], [min.x(), max.y()],
+                         [min.x(), min.y()]] {
+            path.push(Vector2F::new(x, y), Vector2F::default(), Vector2F::default());
+        }
+        return path;
+    }
+
+    let (zero, handle) = (Vector2F::default(), radius * KAPPA);
+    path.push(Vector2F::new(max.x() - radius, min.y()), zero, Vector2F::new(handle, 0.0));
+    path.push(Vector2F::new(max.x(), min.y() + radius), Vector2F::new(0.0, -handle), zero);
+    path.push(Vector2F::new(max.x(), max.y() - radius), zero, Vector2F::new(0.0, handle));
+    path.push(Vector2F::new(max.x() - radius, max.y()), Vector2F::new(handle, 0.0), zero);
+    path.push(Vector2F::new(min.x() + radius, max.y()), zero, Vector2F::new(-handle, 0.0));
+    path.push(Vector2F::new(min.x(), max.y() - radius), Vector2F::new(0.0, handle), zero);
+    path.push(Vector2F::new(min.x(), min.y() + radius), zero, Vector2F::new(0.0, -handle));
+    path.push(Vector2F::new(min.x() + radius, min.y()), Vector2F::new(-handle, 0.0), zero);
+    path
+}
+
+// Ellipses run clockwise from their top.
+fn ellipse_path(center: Vector2F, size: Vector2F) -> ShapeProperty {
+    let radii = size.scale(0.5);
+    let handles = radii.scale(KAPPA);
+    let mut path = ShapeProperty::new(true);
+    path.push(center + Vector2F::new(0.0, -radii.y()),
+              Vector2F::new(-handles.x(), 0.0),
+              Vector2F::new(handles.x(), 0.0));
+    path.push(center + Vector2F::new(radii.x(), 0.0),
+              Vector2F::new(0.0, -handles.y()),
+              Vector2F::new(0.0, handles.y()));
+    path.push(center + Vector2F::new(0.0, radii.y()),
+              Vector2F::new(handles.x(), 0.0),
+              Vector2F::new(-handles.x(), 0.0));
+    path.push(center + Vector2F::new(-radii.x(), 0.0),
+              Vector2F::new(0.0, handles.y()),
+              Vector2F::new(0.0, -handles.y()));
+    path
+}
+
+#[derive(Clone, Copy)]
+struct StarRadius {
+    radius: f32,
+    // As a fraction.
+    roundness: f32,
+}
+
+// Stars and polygons run clockwise from their top, alternating between outer and inner vertices
+// for stars. Roundness turns each vertex into a curve with handles perpendicular to its radius.
+fn star_path(center: Vector2F,
+             rotation: f32,
+             points: f32,
+             outer: StarRadius,
+             inner: Option<StarRadius>)
+             -> ShapeProperty {
+    let points = points.max(0.0).floor() as u32;
+    let vertex_count = if inner.is_some() { points * 2 } else { points };
+    let mut path = ShapeProperty::new(true);
+    if vertex_count == 0 {
+        return path;
+    }
+
+    let step = 2.0 * PI / vertex_count as f32;
+    let mut angle = rotation.to_radians() - PI * 0.5;
+    for index in 0..vertex_count {
+        let StarRadius { radius, roundness } = match inner {
+            Some(inner) if index % 2 == 1 => inner,
+            _ => outer,
+        };
+        // Handles are scaled by a quarter of the arc between outer vertices.
+        let perimeter_segment = 2.0 * PI * radius / (points * 4) as f32;
+        let offset = Vector2F::new(angle.cos(), angle.sin()).scale(radius);
+        let tangent = if offset == Vector2F::default() {
+            Vector2F::default()
+        } else {
+            Vector2F::new(offset.y(), -offset.x()).scale(1.0 / offset.length())
+        };
+        let handle = tangent.scale(perimeter_segment * roundness);
+        path.push(center + offset, handle, -handle);
+        angle += step;
+    }
+    path
+}
+
+fn reverse_path(path: ShapeProperty) -> ShapeProperty {
+    let ShapeProperty { closed, mut in_points, mut out_points, mut vertices } = path;
+    vertices.reverse();
+    in_points.reverse();
+    out_points.reverse();
+    ShapeProperty { closed, in_points: out_points, out_points: in_points, vertices }
+}
+
+impl ShapeProperty {
+    fn new(closed: bool) -> ShapeProperty {
+        ShapeProperty { closed, in_points: vec![], out_points: vec![], vertices: vec![] }
+    }
+
+    // Tangents are relative to the vertex.
+    fn push(&mut self, vertex: Vector2F, in_tangent: Vector2F, out_tangent: Vector2F) {
+        self.vertices.push([vertex.x(), vertex.y()]);
+        self.in_points.push([in_tangent.x(), in_tangent.y()]);
+        self.out_points.push([out_tangent.x(), out_tangent.y()]);
+    }
+}
+
+// Converts a path, whose tangents are relative to their vertices, to a contour of cubic curves.
+fn path_to_contour(path: &ShapeProperty) -> Contour {
+    let mut contour = Contour::new();
+    let vertex_count = path.vertices.len();
+    if vertex_count == 0 {
+        return contour;
+    }
+
+    let point = |points: &[[f32; 2]], index: usize| {
+        points.get(index).map_or(Vector2F::default(), |&[x, y]| Vector2F::new(x, y))
+    };
+    contour.push_endpoint(point(&path.vertices, 0));
+
+    let segment_count = if path.closed { vertex_count } else { vertex_count - 1 };
+    for index in 0..segment_count {
+        let next_index = (index + 1) % vertex_count;
+        let from = point(&path.vertices, index);
+        let to = point(&path.vertices, next_index);
+        let out_tangent = point(&path.out_points, index);
+        let in_tangent = point(&path.in_points, next_index);
+        if out_tangent == Vector2F::default() && in_tangent == Vector2F::default() {
+            contour.push_endpoint(to);
+        } else {
+            contour.push_cubic(from + out_tangent, to + in_tangent, to);
+        }
+    }
+
+    if path.closed {
+        contour.close();
+    }
+    contour
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Layer, Lottie, Mask, Shape, ShapeProperty};
+    use pathfinder_color::ColorU;
+    use pathfinder_content::outline::{Contour, Outline};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::Vector2F;
+    use pathfinder_renderer::options::BuildOptions;
+    use pathfinder_renderer::scene::Scene;
+    use super::{BuildResultFlags, BuiltLottie, Group, KAPPA, compose_transform, merge};
+    use super::{parented_transform, path_to_contour, process_group, trim, trim_ranges};
+
+    fn line(from: Vector2F, to: Vector2F) -> Outline {
+        let mut contour = Contour::new();
+        contour.push_endpoint(from);
+        contour.push_endpoint(to);
+        let mut outline = Outline::new();
+        outline.push_contour(contour);
+        outline
+    }
+
+    fn approx_eq(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn test_trim_ranges() {
+        assert_eq!(trim_ranges(0.0, 100.0, 0.0), None);
+        assert_eq!(trim_ranges(0.0, 0.0, 0.0), Some(vec![]));
+        assert_eq!(trim_ranges(50.0, 0.0, 0.0), Some(vec![(0.0, 0.5)]));
+        assert_eq!(trim_ranges(25.0, 75.0, 180.0), Some(vec![(0.75, 1.0), (0.0, 0.25)]));
+    }
+
+    #[test]
+    fn test_trim_simultaneously() {
+        let mut group = Group {
+            shapes: vec![line(Vector2F::new(0.0, 0.0), Vector2F::new(100.0, 0.0)),
+                         line(Vector2F::new(0.0, 10.0), Vector2F::new(50.0, 10.0))],
+            paths: vec![],
+        };
+        trim(&mut group, 0.0, 50.0, 0.0, None);
+        assert!(approx_eq(group.shapes[0].bounds().max_x(), 50.0));
+        assert!(approx_eq(group.shapes[1].bounds().max_x(), 25.0));
+    }
+
+    #[test]
+    fn test_trim_individually() {
+        let mut group = Group {
+            shapes: vec![line(Vector2F::new(0.0, 0.0), Vector2F::new(100.0, 0.0)),
+                         line(Vector2F::new(0.0, 10.0), Vector2F::new(100.0, 10.0))],
+            paths: vec![],
+        };
+        trim(&mut group, 0.0, 25.0, 0.0, Some(2));
+        assert!(approx_eq(group.shapes[0].bounds().max_x(), 50.0));
+        assert!(group.shapes[1].contours().is_empty());
+    }
+
+    #[test]
+    fn test_trim_wraps_around_closed_contour() {
+        let square = Outline::from_rect(RectF::new(Vector2F::default(), Vector2F::splat(10.0)));
+        let mut group = Group { shapes: vec![square], paths: vec![] };
+
+        // This keeps the left and top edges, which meet at the start of the contour.
+        trim(&mut group, 0.0, 50.0, 270.0, None);
+        assert_eq!(group.shapes[0].contours().len(), 1);
+        let bounds = group.shapes[0].bounds();
+        assert!(approx_eq(bounds.max_x(), 10.0) && approx_eq(bounds.max_y(), 10.0));
+        assert!(!group.shapes[0].contours()[0].is_closed());
+    }
+
+    #[test]
+    fn test_merge_paths() {
+        let first = Outline::from_rect(RectF::new(Vector2F::default(), Vector2F::splat(10.0)));
+        let second = Outline::from_rect(RectF::new(Vector2F::splat(5.0), Vector2F::splat(10.0)));
+
+        let mut group = Group { shapes: vec![first.clone(), second.clone()], paths: vec![] };
+        merge(&mut group, 1);
+        assert_eq!(group.shapes.len(), 2);
+
+        merge(&mut group, 4);
+        assert_eq!(group.shapes.len(), 1);
+        let bounds = group.shapes[0].bounds();
+        assert!(approx_eq(bounds.min_x(), 5.0) && approx_eq(bounds.max_x(), 10.0));
+
+        let mut group = Group { shapes: vec![first, second], paths: vec![] };
+        merge(&mut group, 2);
+        let bounds = group.shapes[0].bounds();
+        assert!(approx_eq(bounds.min_x(), 0.0) && approx_eq(bounds.max_x(), 15.0));
+    }
+
+    #[test]
+    fn test_rounded_corners() {
+        let items: Vec<Shape> = serde_json::from_str(r#"[
+            {"ty": "rc", "p": {"k": [0, 0]}, "s": {"k": [10, 10]}, "r": {"k": 0}},
+            {"ty": "rd", "r": {"k": 2}}
+        ]"#).unwrap();
+        let group = process_group(&items, 0.0, "");
+        let contour = &group.shapes[0].contours()[0];
+        assert!(contour.is_closed());
+        let segments: Vec<_> = contour.iter().collect();
+        assert_eq!(segments.iter().filter(|segment| segment.is_cubic()).count(), 4);
+
+        // Each corner is cut back by the radius along both edges, with handles like `rect_path`'s.
+        let corner = segments.iter().find(|segment| {
+            segment.is_cubic() && segment.baseline.from() == Vector2F::new(3.0, -5.0)
+        }).unwrap();
+        assert_eq!(corner.baseline.to(), Vector2F::new(5.0, -3.0));
+        assert!(approx_eq(corner.ctrl.from().x(), 3.0 + 2.0 * KAPPA));
+        assert!(approx_eq(corner.ctrl.to().y(), -3.0 - 2.0 * KAPPA));
+        let bounds = group.shapes[0].bounds();
+        assert!(approx_eq(bounds.min_x(), -5.0) && approx_eq(bounds.max_x(), 5.0));
+
+        // The radius is limited to half the length of the edges, which leaves a circle.
+        let items: Vec<Shape> = serde_json::from_str(r#"[
+            {"ty": "rc", "p": {"k": [0, 0]}, "s": {"k": [10, 10]}, "r": {"k": 0}},
+            {"ty": "rd", "r": {"k": 100}}
+        ]"#).unwrap();
+        let group = process_group(&items, 0.0, "");
+        let segments: Vec<_> = group.shapes[0].contours()[0].iter().collect();
+        assert!(segments.iter().all(|segment| {
+            segment.is_cubic() || segment.baseline.square_length() == 0.0
+        }));
+        assert_eq!(segments.iter().filter(|segment| segment.is_cubic()).count(), 4);
+
+        // The ends of open paths and corners next to curves are kept.
+        let items: Vec<Shape> = serde_json::from_str(r#"[
+            {"ty": "sh", "ks": {"k": {"c": false, "v": [[0, 0], [10, 0], [10, 10], [20, 10]],
+                                      "i": [[0, 0], [0, 0], [0, 0], [-5, 0]],
+                                      "o": [[0, 0], [0, 0], [5, 0], [0, 0]]}, "a": 0}},
+            {"ty": "rd", "r": {"k": 2}}
+        ]"#).unwrap();
+        let group = process_group(&items, 0.0, "");
+        let contour = &group.shapes[0].contours()[0];
+        assert_eq!(contour.position_of(0), Vector2F::default());
+        assert_eq!(contour.last_position(), Some(Vector2F::new(20.0, 10.0)));
+        let segments: Vec<_> = contour.iter().collect();
+        assert_eq!(segments.iter().filter(|segment| segment.is_cubic()).count(), 2);
+        assert!(segments.iter().any(|segment| segment.baseline.to() == Vector2F::new(8.0, 0.0)));
+        assert!(segments.iter().any(|segment| segment.baseline.to() == Vector2F::new(10.0, 10.0)));
+    }
+
+    #[test]
+    fn test_unsupported_masks_are_reported() {
+        let masks: Vec<Mask> = serde_json::from_str(r#"[
+            {"mode": "a", "pt": {"k": {"c": true, "i": [], "o": [], "v": []}, "a": 0},
+             "f": {"k": [4, 4]}, "x": {"k": 0}},
+            {"mode": "f", "pt": {"k": {"c": true, "i": [], "o": [], "v": []}, "a": 0}}
+        ]"#).unwrap();
+        let mut built_lottie = BuiltLottie {
+            scene: Scene::new(),
+            result_flags: BuildResultFlags::empty(),
+        };
+
+        built_lottie.check_mask_support(&masks[0], 0.0);
+        assert!(built_lottie.result_flags.is_empty());
+
+        built_lottie.check_mask_support(&masks[1], 0.0);
+        assert_eq!(built_lottie.result_flags, BuildResultFlags::UNSUPPORTED_MASK_MODE);
+    }
+
+    // A composition of the given layers, 40 by 20 pixels, at frame 0.
+    fn build(layers: &str) -> BuiltLottie {
+        let json = format!(r#"{{"v": "5.5.2", "fr": 30, "ip": 0, "op": 10, "w": 40, "h": 20,
+                                "ddd": 0, "assets": [], "layers": [{}]}}"#, layers);
+        let lottie: Lottie = serde_json::from_str(&json).unwrap();
+        BuiltLottie::from_lottie(&lottie, 0.0)
+    }
+
+    // A solid layer covering the whole composition, with extra properties appended.
+    fn solid_layer(index: i64, color: &str, extra: &str) -> String {
+        format!(r#"{{"ty": 1, "ind": {}, "nm": "{}", "ip": 0, "op": 10,
+                     "ks": {{"p": {{"k": [0, 0]}}, "a": {{"k": [0, 0]}}, "s": {{"k": [100, 100]}},
+                             "r": {{"k": 0}}}},
+                     "sc": "{}", "sw": 40, "sh": 20{}}}"#,
+                index,
+                color,
+                color,
+                extra)
+    }
+
+    // A closed mask path covering the given rectangle.
+    fn rect_mask(mode: &str, rect: RectF, extra: &str) -> String {
+        let corners: Vec<String> = [rect.origin(), rect.upper_right(), rect.lower_right(),
+                                    rect.lower_left()].iter().map(|point| {
+            format!("[{}, {}]", point.x(), point.y())
+        }).collect();
+        format!(r#"{{"mode": "{}", "pt": {{"k": {{"c": true,
+                                                 "i": [[0, 0], [0, 0], [0, 0], [0, 0]],
+                                                 "o": [[0, 0], [0, 0], [0, 0], [0, 0]],
+                                                 "v": [{}]}}, "a": 0}}{}}}"#,
+                mode,
+                corners.join(", "),
+                extra)
+    }
+
+    fn masks(masks: &[String]) -> String {
+        format!(r#", "hasMask": true, "masksProperties": [{}]"#, masks.join(", "))
+    }
+
+    // Renders the scene, and returns the pixels along its middle row.
+    fn middle_row(scene: &Scene) -> Vec<ColorU> {
+        let size = scene.view_box().size().to_i32();
+        let image = scene.render_to_image(BuildOptions::default(), size);
+        let row = (size.y() / 2 * size.x()) as usize;
+        image.pixels()[row..(row + size.x() as usize)].to_vec()
+    }
+
+    fn alpha_row(scene: &Scene) -> Vec<u8> {
+        middle_row(scene).iter().map(|pixel| pixel.a).collect()
+    }
+
+    // A shape layer covering the left half of the composition, with the given shape items after
+    // the rectangle.
+    fn shape_layer(opacity: f32, items: &str) -> String {
+        format!(r#"{{"ty": 4, "ind": 1, "nm": "shapes", "ip": 0, "op": 10,
+                     "ks": {{"p": {{"k": [0, 0]}}, "a": {{"k": [0, 0]}}, "s": {{"k": [100, 100]}},
+                             "r": {{"k": 0}}, "o": {{"k": {}}}}},
+                     "shapes": [{{"ty": "rc", "p": {{"k": [10, 10]}}, "s": {{"k": [20, 20]}},
+                                  "r": {{"k": 0}}}}, {}]}}"#,
+                opacity,
+                items)
+    }
+
+    fn fill(color: &str) -> String {
+        format!(r#"{{"ty": "fl", "c": {{"k": [{}]}}, "o": {{"k": 100}}}}"#, color)
+    }
+
+    fn rect(x: f32, width: f32) -> RectF {
+        RectF::new(Vector2F::new(x, 0.0), Vector2F::new(width, 20.0))
+    }
+
+    #[test]
+    fn test_transform_order() {
+        // The anchor point is moved to the position after scaling and rotating around it.
+        let transform = compose_transform(vec![10.0, 0.0],
+                                          vec![20.0, 10.0],
+                                          vec![200.0, 200.0],
+                                          90.0,
+                                          0.0,
+                                          0.0);
+        let point = transform * Vector2F::new(10.0, 0.0);
+        assert!(approx_eq(point.x(), 20.0) && approx_eq(point.y(), 10.0));
+        let point = transform * Vector2F::new(11.0, 0.0);
+        assert!(approx_eq(point.x(), 20.0) && approx_eq(point.y(), 12.0));
+
+        // Nested groups are transformed before the groups that contain them.
+        let items: Vec<Shape> = serde_json::from_str(r#"[
+            {"ty": "gr", "nm": "inner", "it": [
+                {"ty": "rc", "p": {"k": [0, 0]}, "s": {"k": [2, 2]}, "r": {"k": 0}},
+                {"ty": "tr", "p": {"k": [5, 0]}, "a": {"k": [0, 0]}, "s": {"k": [100, 100]},
+                 "r": {"k": 0}, "sk": {"k": 0}, "sa": {"k": 0}}
+            ]},
+            {"ty": "tr", "p": {"k": [0, 0]}, "a": {"k": [0, 0]}, "s": {"k": [200, 200]},
+             "r": {"k": 0}, "sk": {"k": 0}, "sa": {"k": 0}}
+        ]"#).unwrap();
+        let bounds = process_group(&items, 0.0, "").outline().bounds();
+        assert!(approx_eq(bounds.min_x(), 8.0) && approx_eq(bounds.max_x(), 12.0));
+        assert!(approx_eq(bounds.min_y(), -2.0) && approx_eq(bounds.max_y(), 2.0));
+    }
+
+    #[test]
+    fn test_path_to_contour() {
+        // Vertices whose tangents are both zero are joined by lines, and the rest by cubic
+        // curves whose control points are relative to the vertices.
+        let path: ShapeProperty = serde_json::from_str(r#"{
+            "c": true,
+            "v": [[0, 0], [10, 0], [10, 10]],
+            "i": [[1, -1], [0, 0], [0, -2]],
+            "o": [[0, 0], [2, 0], [-3, 0]]
+        }"#).unwrap();
+        let contour = path_to_contour(&path);
+        assert!(contour.is_closed());
+        let segments: Vec<_> = contour.iter().collect();
+        assert!(segments[0].is_line());
+        assert_eq!(segments[0].baseline.to(), Vector2F::new(10.0, 0.0));
+        assert!(segments[1].is_cubic());
+        assert_eq!(segments[1].ctrl.from(), Vector2F::new(12.0, 0.0));
+        assert_eq!(segments[1].ctrl.to(), Vector2F::new(10.0, 8.0));
+        assert_eq!(segments[1].baseline.to(), Vector2F::new(10.0, 10.0));
+
+        // Closed paths have a curve back to the first vertex.
+        assert!(segments[2].is_cubic());
+        assert_eq!(segments[2].ctrl.from(), Vector2F::new(7.0, 10.0));
+        assert_eq!(segments[2].ctrl.to(), Vector2F::new(1.0, -1.0));
+        assert_eq!(segments[2].baseline.to(), Vector2F::default());
+
+        let path = ShapeProperty { closed: false, ..path };
+        let contour = path_to_contour(&path);
+        assert!(!contour.is_closed());
+        assert_eq!(contour.iter().count(), 2);
+    }
+
+    #[test]
+    fn test_paint_stacking_order() {
+        // Earlier fills in a group are drawn in front of later ones.
+        let pixels = middle_row(&build(&shape_layer(100.0, &format!("{}, {}",
+                                                                    fill("1, 0, 0, 1"),
+                                                                    fill("0, 0, 1, 1")))).scene);
+        assert_eq!(pixels[5], ColorU::new(255, 0, 0, 255));
+
+        // Earlier layers are drawn in front of later ones.
+        let layers = format!("{}, {}",
+                             solid_layer(1, "#00ff00", ""),
+                             solid_layer(2, "#0000ff", ""));
+        let pixels = middle_row(&build(&layers).scene);
+        assert_eq!(pixels[5], ColorU::new(0, 255, 0, 255));
+    }
+
+    #[test]
+    fn test_layer_opacity() {
+        // The layer is made transparent as a whole, so the fill behind doesn't show through.
+        let layer = shape_layer(50.0, &format!("{}, {}", fill("1, 0, 0, 1"), fill("0, 0, 1, 1")));
+        let pixels = middle_row(&build(&layer).scene);
+        assert!((pixels[5].a as i32 - 128).abs() <= 1);
+        assert!(pixels[5].r > 0 && pixels[5].b == 0);
+        assert_eq!(pixels[30].a, 0);
+
+        let pixels = middle_row(&build(&shape_layer(0.0, &fill("1, 0, 0, 1"))).scene);
+        assert_eq!(pixels[5].a, 0);
+    }
+
+    #[test]
+    fn test_parented_transform() {
+        let layer = |index: i64, parent: Option<i64>, position: (f32, f32), scale: f32| {
+            let parent = parent.map_or(String::new(), |parent| format!(r#""parent": {},"#, parent));
+            format!(r#"{{"ty": 3, "ind": {}, {} "ip": 0, "op": 10,
+                         "ks": {{"p": {{"k": [{}, {}]}}, "a": {{"k": [0, 0]}},
+                                 "s": {{"k": [{}, {}]}}, "r": {{"k": 0}}}}}}"#,
+                    index, parent, position.0, position.1, scale, scale)
+        };
+        let chain = format!("[{}, {}, {}]",
+                            layer(1, Some(2), (1.0, 0.0), 100.0),
+                            layer(2, Some(3), (0.0, 0.0), 200.0),
+                            layer(3, None, (0.0, 10.0), 100.0));
+        let layers: Vec<Layer> = serde_json::from_str(&chain).unwrap();
+
+        // Parents apply their transforms after their children's.
+        let point = parented_transform(&layers, &layers[0], 0.0) * Vector2F::new(1.0, 1.0);
+        assert!(approx_eq(point.x(), 4.0) && approx_eq(point.y(), 12.0));
+
+        // Cycles are cut off rather than followed forever.
+        let cycle = format!("[{}, {}]",
+                            layer(1, Some(2), (1.0, 0.0), 100.0),
+                            layer(2, Some(1), (1.0, 0.0), 100.0));
+        let layers: Vec<Layer> = serde_json::from_str(&cycle).unwrap();
+        let point = parented_transform(&layers, &layers[0], 0.0) * Vector2F::default();
+        assert!(approx_eq(point.x(), 3.0));
+    }
+
+    #[test]
+    fn test_track_matte() {
+        // The matte covers the left quarter of the composition.
+        let matte = solid_layer(1, "#ffffff", &format!(r#", "td": 1{}"#,
+                                                       masks(&[rect_mask("a", rect(0.0, 10.0),
+                                                                         "")])));
+        let layer = |track_matte: i64| {
+            solid_layer(2, "#ff0000", &format!(r#", "tt": {}"#, track_matte))
+        };
+
+        // The matte isn't drawn itself. An alpha matte shows the layer where the matte is opaque,
+        // and an inverted alpha matte shows the rest of it.
+        let alpha = alpha_row(&build(&format!("{}, {}", matte, layer(1))).scene);
+        assert_eq!((alpha[5], alpha[30]), (255, 0));
+        let alpha = alpha_row(&build(&format!("{}, {}", matte, layer(2))).scene);
+        assert_eq!((alpha[5], alpha[30]), (0, 255));
+    }
+
+    #[test]
+    fn test_mask_modes_combine_in_order() {
+        // Add the left three quarters, subtract the second quarter, and intersect with the
+        // right half, which leaves only the third quarter.
+        let layer = solid_layer(1, "#ff0000", &masks(&[
+            rect_mask("a", rect(0.0, 30.0), ""),
+            rect_mask("s", rect(10.0, 10.0), ""),
+            rect_mask("i", rect(20.0, 20.0), ""),
+        ]));
+        let built_lottie = build(&layer);
+        assert!(built_lottie.result_flags.is_empty());
+        let alpha = alpha_row(&built_lottie.scene);
+        assert_eq!([alpha[5], alpha[15], alpha[25], alpha[35]], [0, 0, 255, 0]);
+    }
+
+    #[test]
+    fn test_mask_opacity_and_inversion() {
+        let layer = solid_layer(1, "#ff0000", &masks(&[
+            rect_mask("a", rect(0.0, 20.0), r#", "o": {"k": 50}, "inv": true"#),
+        ]));
+        let alpha = alpha_row(&build(&layer).scene);
+        assert_eq!(alpha[10], 0);
+        assert!((alpha[30] as i32 - 128).abs() <= 1);
+    }
+
+    #[test]
+    fn test_mask_feather() {
+        let layer = solid_layer(1, "#ff0000", &masks(&[
+            rect_mask("a", rect(0.0, 20.0), r#", "f": {"k": [8, 8]}"#),
+        ]));
+        let alpha = alpha_row(&build(&layer).scene);
+
+        // The edge fades out over the feather radius, centered on the mask outline.
+        assert_eq!(alpha[10], 255);
+        assert!((alpha[20] as i32 - 128).abs() <= 32);
+        assert!(alpha[17] > alpha[20] && alpha[20] > alpha[23]);
+        assert_eq!(alpha[30], 0);
+    }
+}
@@ -312,7 +312,8 @@ impl<'a> SceneBuilder<'a> {
                     let z_buffer = &mut z_buffers[*z_buffer_index_stack.last().unwrap()];
                     for (path_index, built_draw_path) in
                             built_draw_paths[start_index..end_index].iter().enumerate() {
-                        z_buffer.update(&built_draw_path.path.solid_tiles, path_index as u32);
+                        let path_index = (start_index + path_index) as u32;
+                        z_buffer.update(&built_draw_path.path.solid_tiles, path_index);
                     }
                 }
                 DisplayItem::DrawRenderTarget { .. } => {
@@ -726,3 +727,64 @@ impl CulledTiles {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::concurrent::executor::SequentialExecutor;
+    use crate::gpu_data::RenderCommand;
+    use crate::options::BuildOptions;
+    use crate::paint::Paint;
+    use crate::scene::{DrawPath, RenderTarget, Scene};
+    use pathfinder_color::ColorU;
+    use pathfinder_content::effects::BlendMode;
+    use pathfinder_content::fill::FillRule;
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::{Vector2F, Vector2I};
+    use std::sync::mpsc;
+
+    fn push_rect(scene: &mut Scene, rect: RectF, color: ColorU) {
+        let paint = scene.push_paint(&Paint::Color(color));
+        scene.push_path(DrawPath::new(Outline::from_rect(rect),
+                                      paint,
+                                      None,
+                                      FillRule::Winding,
+                                      BlendMode::SrcOver,
+                                      String::new()));
+    }
+
+    // Solid tiles must refer to the paths that cover them even when they're drawn into a render
+    // target, whose paths don't start at index 0.
+    #[test]
+    fn test_solid_tiles_in_render_target_use_scene_path_indices() {
+        let mut scene = Scene::new();
+        let view_box = RectF::new(Vector2F::default(), Vector2F::splat(64.0));
+        scene.set_view_box(view_box);
+        push_rect(&mut scene, view_box, ColorU::new(255, 0, 0, 255));
+        scene.push_render_target(RenderTarget::new(Vector2I::splat(64), String::new()));
+        push_rect(&mut scene, view_box, ColorU::new(0, 0, 255, 255));
+        scene.pop_render_target();
+
+        let (sender, receiver) = mpsc::channel();
+        let listener = Box::new(move |command| drop(sender.send(command)));
+        scene.build(BuildOptions::default(), listener, &SequentialExecutor);
+
+        let mut render_target_depth = 0;
+        let mut object_indices = [vec![], vec![]];
+        for command in receiver.try_iter() {
+            match command {
+                RenderCommand::PushRenderTarget(_) => render_target_depth += 1,
+                RenderCommand::PopRenderTarget => render_target_depth -= 1,
+                RenderCommand::DrawSolidTiles(batch) => {
+                    let indices = &mut object_indices[render_target_depth];
+                    indices.extend(batch.vertices.iter().map(|vertex| vertex.object_index));
+                }
+                _ => {}
+            }
+        }
+
+        assert!(!object_indices[0].is_empty() && !object_indices[1].is_empty());
+        assert!(object_indices[0].iter().all(|&object_index| object_index == 0));
+        assert!(object_indices[1].iter().all(|&object_index| object_index == 1));
+    }
+}
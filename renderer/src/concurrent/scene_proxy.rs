@@ -20,11 +20,15 @@
 //! You don't need to use this API to use Pathfinder; it's only a convenience.
 
 use crate::concurrent::executor::Executor;
+use crate::cpu::renderer::Renderer as CpuRenderer;
+use crate::gpu::options::RendererOptions;
 use crate::gpu::renderer::Renderer;
 use crate::gpu_data::RenderCommand;
 use crate::options::{BuildOptions, RenderCommandListener};
 use crate::scene::Scene;
+use pathfinder_content::pattern::Image;
 use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::vector::Vector2I;
 use pathfinder_gpu::Device;
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::thread;
@@ -92,6 +96,17 @@ impl SceneProxy {
         renderer.end_scene();
     }
 
+    /// A convenience method to build a scene and render it on the CPU to an image of the given
+    /// size, without a GPU.
+    pub fn render_to_image(&self, build_options: BuildOptions, size: Vector2I) -> Image {
+        let mut renderer = CpuRenderer::new(size, RendererOptions::default());
+        renderer.begin_scene();
+        for command in self.build_with_stream(build_options) {
+            renderer.render_command(&command);
+        }
+        renderer.read_image()
+    }
+
     #[inline]
     pub fn copy_scene(&self) -> Scene {
         let (sender, receiver) = mpsc::channel();
@@ -0,0 +1,810 @@
+// pathfinder/renderer/src/cpu/renderer.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Executes render commands on the CPU, producing an RGBA buffer.
+//!
+//! This mirrors the GPU renderer stage by stage: fills are accumulated into per-tile coverage
+//! buffers, mask tiles resolve them according to the fill rule, and solid and alpha tiles are
+//! composited into the render target on top of the stack.
+
+use crate::gpu::options::RendererOptions;
+use crate::gpu::renderer::{MASK_TILES_ACROSS, RenderStats};
+use crate::gpu_data::{AlphaTile, FillBatchPrimitive, MaskTile, PaintData, PaintPageContents};
+use crate::gpu_data::{PaintPageId, RenderCommand, SolidTileBatch};
+use crate::tiles::{TILE_HEIGHT, TILE_WIDTH};
+use pathfinder_color::{ColorF, ColorU};
+use pathfinder_content::effects::{BlendMode, CompositeOp, DefringingKernel, Effects, Filter};
+use pathfinder_content::effects::MaskMode;
+use pathfinder_content::fill::FillRule;
+use pathfinder_content::pattern::{Image, RenderTargetId};
+use pathfinder_geometry::vector::{Vector2F, Vector2I};
+use pathfinder_simd::default::F32x4;
+use std::mem;
+
+const TILE_AREA: usize = TILE_WIDTH as usize * TILE_HEIGHT as usize;
+
+pub struct Renderer {
+    // Core data
+    dest_framebuffer: Texture,
+    options: RendererOptions,
+    paint_textures: Vec<PaintTexture>,
+    render_targets: Vec<Texture>,
+    render_target_stack: Vec<RenderTargetId>,
+
+    // Rendering state
+    //
+    // These are indexed by alpha tile index and mask tile index respectively, `TILE_AREA` texels
+    // per tile. They play the role of the fill and mask framebuffers in the GPU renderer.
+    fill_tiles: Vec<f32>,
+    mask_tiles: Vec<f32>,
+
+    // Debug
+    pub stats: RenderStats,
+}
+
+impl Renderer {
+    pub fn new(framebuffer_size: Vector2I, options: RendererOptions) -> Renderer {
+        Renderer {
+            dest_framebuffer: Texture::new(framebuffer_size, ColorF::transparent_black()),
+            options,
+            paint_textures: vec![],
+            render_targets: vec![],
+            render_target_stack: vec![],
+
+            fill_tiles: vec![],
+            mask_tiles: vec![],
+
+            stats: RenderStats::default(),
+        }
+    }
+
+    pub fn begin_scene(&mut self) {
+        self.fill_tiles.clear();
+        self.mask_tiles.clear();
+        self.stats = RenderStats::default();
+
+        if let Some(background_color) = self.options.background_color {
+            self.dest_framebuffer.clear(background_color);
+        }
+    }
+
+    pub fn render_command(&mut self, command: &RenderCommand) {
+        match *command {
+            RenderCommand::Start { path_count, .. } => self.stats.path_count = path_count,
+            RenderCommand::AddPaintData(ref paint_data) => self.upload_paint_data(paint_data),
+            RenderCommand::AddFills(ref fills) => self.add_fills(fills),
+            RenderCommand::FlushFills => {}
+            RenderCommand::RenderMaskTiles { tiles: ref mask_tiles, fill_rule } => {
+                self.draw_mask_tiles(mask_tiles, fill_rule)
+            }
+            RenderCommand::PushRenderTarget(render_target_id) => {
+                self.render_target_stack.push(render_target_id)
+            }
+            RenderCommand::PopRenderTarget => {
+                self.render_target_stack.pop().expect("Render target stack underflow!");
+            }
+            RenderCommand::DrawRenderTarget { render_target, effects } => {
+                self.draw_entire_render_target(render_target, effects)
+            }
+            RenderCommand::DrawSolidTiles(ref batch) => self.draw_solid_tiles(batch),
+            RenderCommand::DrawAlphaTiles { tiles: ref alpha_tiles, paint_page, blend_mode } => {
+                self.draw_alpha_tiles(alpha_tiles, paint_page, blend_mode)
+            }
+            RenderCommand::Finish { .. } => {}
+        }
+    }
+
+    #[inline]
+    pub fn framebuffer_size(&self) -> Vector2I {
+        self.dest_framebuffer.size
+    }
+
+    /// Resizes the destination framebuffer. Its contents are discarded.
+    #[inline]
+    pub fn set_framebuffer_size(&mut self, new_framebuffer_size: Vector2I) {
+        self.dest_framebuffer = Texture::new(new_framebuffer_size, ColorF::transparent_black());
+    }
+
+    #[inline]
+    pub fn set_options(&mut self, new_options: RendererOptions) {
+        self.options = new_options
+    }
+
+    /// Returns the contents of the destination framebuffer as RGBA8 texels in row-major order,
+    /// top row first.
+    ///
+    /// As with `Device::read_pixels()` on the GPU, the color channels are premultiplied by alpha.
+    pub fn read_pixels(&self) -> Vec<ColorU> {
+        self.dest_framebuffer.texels.iter().map(|texel| texel.to_u8()).collect()
+    }
+
+    /// Returns the contents of the destination framebuffer as an image with non-premultiplied
+    /// alpha.
+    pub fn read_image(&self) -> Image {
+        let pixels = self.dest_framebuffer.texels.iter().map(|texel| {
+            let alpha = texel.a();
+            if alpha == 0.0 {
+                return ColorU::transparent_black();
+            }
+            let color = texel.0 * F32x4::new(1.0 / alpha, 1.0 / alpha, 1.0 / alpha, 1.0);
+            ColorF(color.min(F32x4::splat(1.0))).to_u8()
+        }).collect();
+        Image::new(self.dest_framebuffer.size, pixels)
+    }
+
+    fn upload_paint_data(&mut self, paint_data: &PaintData) {
+        self.paint_textures.clear();
+        self.render_targets.clear();
+
+        for paint_page_data in &paint_data.pages {
+            let paint_size = paint_page_data.size;
+            match paint_page_data.contents {
+                PaintPageContents::RenderTarget(render_target_id) => {
+                    self.render_targets.push(Texture::new(paint_size,
+                                                          ColorF::transparent_black()));
+                    self.paint_textures.push(PaintTexture::RenderTarget(render_target_id));
+                }
+                PaintPageContents::Texels(ref paint_texels) => {
+                    let texture = Texture::from_texels(paint_size, paint_texels);
+                    self.paint_textures.push(PaintTexture::Texture(texture));
+                }
+            }
+        }
+    }
+
+    fn add_fills(&mut self, fills: &[FillBatchPrimitive]) {
+        self.stats.fill_count += fills.len();
+
+        for fill in fills {
+            // Copy out of the packed structure before touching any fields.
+            let (px, subpx, alpha_tile_index) = (fill.px, fill.subpx, fill.alpha_tile_index);
+            let from = unpack_fill_point(px.from, subpx.from_x, subpx.from_y);
+            let to = unpack_fill_point(px.to, subpx.to_x, subpx.to_y);
+
+            let start_index = alpha_tile_index as usize * TILE_AREA;
+            if self.fill_tiles.len() < start_index + TILE_AREA {
+                self.fill_tiles.resize(start_index + TILE_AREA, 0.0);
+            }
+            let fill_tile = &mut self.fill_tiles[start_index..(start_index + TILE_AREA)];
+            accumulate_fill(fill_tile, from, to);
+        }
+    }
+
+    fn draw_mask_tiles(&mut self, mask_tiles: &[MaskTile], fill_rule: FillRule) {
+        for mask_tile in mask_tiles {
+            let vertex = &mask_tile.upper_left;
+            let fill_start_index = mask_uv_to_tile_index(vertex.fill_u, vertex.fill_v) * TILE_AREA;
+            let mask_start_index = mask_uv_to_tile_index(vertex.mask_u, vertex.mask_v) * TILE_AREA;
+            let backdrop = vertex.backdrop as f32;
+
+            // Like the mask framebuffer on the GPU, untouched mask texels start out fully
+            // covered, and each mask tile can only reduce coverage.
+            if self.mask_tiles.len() < mask_start_index + TILE_AREA {
+                self.mask_tiles.resize(mask_start_index + TILE_AREA, 1.0);
+            }
+
+            for texel_index in 0..TILE_AREA {
+                let area = self.fill_tiles
+                               .get(fill_start_index + texel_index)
+                               .cloned()
+                               .unwrap_or(0.0);
+                let winding = area + backdrop;
+                let coverage = match fill_rule {
+                    FillRule::Winding => f32::abs(winding),
+                    FillRule::EvenOdd => 1.0 - f32::abs(1.0 - winding.rem_euclid(2.0)),
+                };
+
+                let mask = &mut self.mask_tiles[mask_start_index + texel_index];
+                *mask = f32::min(*mask, f32::min(coverage, 1.0));
+            }
+        }
+    }
+
+    fn draw_solid_tiles(&mut self, batch: &SolidTileBatch) {
+        self.stats.solid_tile_count += batch.vertices.len() / 4;
+
+        let mut target = self.take_draw_target();
+
+        {
+            let paint_texture = self.paint_texture(batch.paint_page);
+            for vertices in batch.vertices.chunks(4) {
+                let tile_position = Vector2I::new(vertices[0].tile_x as i32,
+                                                  vertices[0].tile_y as i32);
+                let color_uvs = TileUVs::new([
+                    (vertices[0].color_u, vertices[0].color_v),
+                    (vertices[1].color_u, vertices[1].color_v),
+                    (vertices[2].color_u, vertices[2].color_v),
+                ]);
+
+                for_each_tile_texel(tile_position, target.size, |position, tile_offset| {
+                    let color = paint_texture.sample(color_uvs.sample(tile_offset));
+                    *target.texel_mut(position) = color;
+                });
+            }
+        }
+
+        self.restore_draw_target(target);
+    }
+
+    fn draw_alpha_tiles(&mut self,
+                        alpha_tiles: &[AlphaTile],
+                        paint_page: PaintPageId,
+                        blend_mode: BlendMode) {
+        self.stats.alpha_tile_count += alpha_tiles.len();
+
+        let mut target = self.take_draw_target();
+
+        {
+            let paint_texture = self.paint_texture(paint_page);
+            for alpha_tile in alpha_tiles {
+                let (upper_left, upper_right, lower_left) =
+                    (&alpha_tile.upper_left, &alpha_tile.upper_right, &alpha_tile.lower_left);
+
+                let mask_start_index = mask_uv_to_tile_index(upper_left.mask_u,
+                                                             upper_left.mask_v) * TILE_AREA;
+                let color_uvs = TileUVs::new([
+                    (upper_left.color_u, upper_left.color_v),
+                    (upper_right.color_u, upper_right.color_v),
+                    (lower_left.color_u, lower_left.color_v),
+                ]);
+
+                let tile_position = upper_left.tile_position();
+                let mask_tiles = &self.mask_tiles;
+                for_each_tile_texel(tile_position, target.size, |position, tile_offset| {
+                    let texel_index = tile_offset.y() as usize * TILE_WIDTH as usize +
+                        tile_offset.x() as usize;
+                    let coverage = mask_tiles.get(mask_start_index + texel_index)
+                                             .cloned()
+                                             .unwrap_or(0.0);
+                    if coverage == 0.0 {
+                        return;
+                    }
+
+                    let color = match blend_mode {
+                        // Use opaque black for the paint so that the transparent black paint
+                        // color doesn't zero out the mask, as the GPU renderer does.
+                        BlendMode::Clear => ColorF::black(),
+                        _ => paint_texture.sample(color_uvs.sample(tile_offset)),
+                    };
+                    let alpha = color.a() * coverage;
+                    let color = ColorF::new(color.r() * alpha,
+                                            color.g() * alpha,
+                                            color.b() * alpha,
+                                            alpha);
+
+                    let dest = target.texel_mut(position);
+                    *dest = blend_mode.blend(color, *dest);
+                });
+            }
+        }
+
+        self.restore_draw_target(target);
+    }
+
+    // FIXME: This is inefficient and should eventually go away.
+    fn draw_entire_render_target(&mut self, render_target_id: RenderTargetId, effects: Effects) {
+        let mut target = self.take_draw_target();
+
+        {
+            let source = &self.render_targets[render_target_id.0 as usize];
+            match effects.filter {
+                Filter::Composite(composite_op) => {
+                    composite_render_target(&mut target, source, composite_op)
+                }
+                Filter::Opacity { opacity, mask, mask_mode } => {
+                    let mask = mask.map(|mask| &self.render_targets[mask.0 as usize]);
+                    draw_opacity_render_target(&mut target, source, opacity, mask, mask_mode)
+                }
+                Filter::Text { fg_color, bg_color, defringing_kernel, gamma_correction } => {
+                    draw_text_render_target(&mut target,
+                                            source,
+                                            fg_color,
+                                            bg_color,
+                                            defringing_kernel,
+                                            gamma_correction)
+                }
+            }
+        }
+
+        self.restore_draw_target(target);
+    }
+
+    fn paint_texture(&self, paint_page: PaintPageId) -> &Texture {
+        match self.paint_textures[paint_page.0 as usize] {
+            PaintTexture::Texture(ref texture) => texture,
+            PaintTexture::RenderTarget(render_target_id) => {
+                &self.render_targets[render_target_id.0 as usize]
+            }
+        }
+    }
+
+    // Moves the render target on top of the stack out of the renderer so that it can be drawn to
+    // while paint textures are borrowed.
+    fn take_draw_target(&mut self) -> Texture {
+        match self.render_target_stack.last() {
+            Some(&render_target_id) => {
+                mem::take(&mut self.render_targets[render_target_id.0 as usize])
+            }
+            None => mem::take(&mut self.dest_framebuffer),
+        }
+    }
+
+    fn restore_draw_target(&mut self, target: Texture) {
+        match self.render_target_stack.last() {
+            Some(&render_target_id) => self.render_targets[render_target_id.0 as usize] = target,
+            None => self.dest_framebuffer = target,
+        }
+    }
+}
+
+fn composite_render_target(target: &mut Texture, source: &Texture, composite_op: CompositeOp) {
+    let target_size = target.size;
+    for_each_texel(target_size, |position| {
+        let color = source.sample(texel_center_uv(position, target_size));
+        let dest = target.texel_mut(position);
+        *dest = match composite_op {
+            CompositeOp::SrcOver => BlendMode::SrcOver.blend(color, *dest),
+        };
+    });
+}
+
+fn draw_opacity_render_target(target: &mut Texture,
+                              source: &Texture,
+                              opacity: f32,
+                              mask: Option<&Texture>,
+                              mask_mode: MaskMode) {
+    let target_size = target.size;
+    for_each_texel(target_size, |position| {
+        let uv = texel_center_uv(position, target_size);
+        let mut coverage = opacity;
+        if let Some(mask) = mask {
+            // The mask is premultiplied, so its luminance is already scaled by its alpha.
+            let mask_color = mask.sample(uv);
+            let luminance = || {
+                mask_color.r() * 0.2125 + mask_color.g() * 0.7154 + mask_color.b() * 0.0721
+            };
+            coverage *= match mask_mode {
+                MaskMode::Luminance => luminance(),
+                MaskMode::Alpha => mask_color.a(),
+                MaskMode::InvertedLuminance => 1.0 - luminance(),
+                MaskMode::InvertedAlpha => 1.0 - mask_color.a(),
+            };
+        }
+
+        let color = ColorF(source.sample(uv).0 * F32x4::splat(coverage));
+        let dest = target.texel_mut(position);
+        *dest = BlendMode::SrcOver.blend(color, *dest);
+    });
+}
+
+fn draw_text_render_target(target: &mut Texture,
+                           source: &Texture,
+                           fg_color: ColorF,
+                           bg_color: ColorF,
+                           defringing_kernel: Option<DefringingKernel>,
+                           _gamma_correction: bool) {
+    // FIXME: Support gamma correction. This requires the gamma LUT that the GPU
+    // renderer loads as a texture resource.
+    let kernel = match defringing_kernel {
+        Some(DefringingKernel(kernel)) if kernel[3] != 0.0 => Some(kernel),
+        _ => None,
+    };
+
+    let target_size = target.size;
+    let one_pixel = 1.0 / source.size.x() as f32;
+    for_each_texel(target_size, |position| {
+        let uv = texel_center_uv(position, target_size);
+        let sample_1_tap = |offset: i32| {
+            source.sample(uv + Vector2F::new(offset as f32 * one_pixel, 0.0)).r()
+        };
+
+        let alpha = match kernel {
+            None => F32x4::splat(sample_1_tap(0)),
+            Some(kernel) => {
+                // Convolve horizontally over 9 taps, offset by one tap per color channel.
+                let mut taps = [0.0; 9];
+                for (tap_index, tap) in taps.iter_mut().enumerate() {
+                    let offset = tap_index as i32 - 4;
+                    if (offset != -4 && offset != 4) || kernel[0] > 0.0 {
+                        *tap = sample_1_tap(offset);
+                    }
+                }
+
+                let mut alpha = [0.0; 4];
+                for (channel, alpha) in alpha.iter_mut().take(3).enumerate() {
+                    for kernel_index in 0..4 {
+                        *alpha += taps[channel + kernel_index] * kernel[kernel_index];
+                    }
+                    for kernel_index in 0..3 {
+                        *alpha += taps[channel + 4 + kernel_index] * kernel[2 - kernel_index];
+                    }
+                }
+                F32x4::new(alpha[0], alpha[1], alpha[2], 0.0)
+            }
+        };
+
+        let color = bg_color.0 + (fg_color.0 - bg_color.0) * alpha;
+        *target.texel_mut(position) = ColorF::new(color[0], color[1], color[2], 1.0);
+    });
+}
+
+// Texture storage
+
+#[derive(Clone, Default)]
+struct Texture {
+    size: Vector2I,
+    texels: Vec<ColorF>,
+}
+
+impl Texture {
+    fn new(size: Vector2I, color: ColorF) -> Texture {
+        let area = size.x() as usize * size.y() as usize;
+        Texture { size, texels: vec![color; area] }
+    }
+
+    fn from_texels(size: Vector2I, texels: &[ColorU]) -> Texture {
+        Texture { size, texels: texels.iter().map(|texel| texel.to_f32()).collect() }
+    }
+
+    fn clear(&mut self, color: ColorF) {
+        for texel in &mut self.texels {
+            *texel = color;
+        }
+    }
+
+    #[inline]
+    fn texel(&self, position: Vector2I) -> ColorF {
+        let position = position.max(Vector2I::default()).min(self.size - Vector2I::splat(1));
+        self.texels[position.y() as usize * self.size.x() as usize + position.x() as usize]
+    }
+
+    #[inline]
+    fn texel_mut(&mut self, position: Vector2I) -> &mut ColorF {
+        &mut self.texels[position.y() as usize * self.size.x() as usize + position.x() as usize]
+    }
+
+    // Bilinear filtering with clamp-to-edge addressing, like the GPU samplers.
+    fn sample(&self, uv: Vector2F) -> ColorF {
+        if self.texels.is_empty() {
+            return ColorF::transparent_black();
+        }
+
+        let position = uv.scale_xy(self.size.to_f32()) - Vector2F::splat(0.5);
+        let origin = position.floor();
+        let t = position - origin;
+        let origin = origin.to_i32();
+
+        let upper = self.texel(origin).lerp(self.texel(origin + Vector2I::new(1, 0)), t.x());
+        let lower = self.texel(origin + Vector2I::new(0, 1))
+                        .lerp(self.texel(origin + Vector2I::splat(1)), t.x());
+        upper.lerp(lower, t.y())
+    }
+}
+
+enum PaintTexture {
+    Texture(Texture),
+    RenderTarget(RenderTargetId),
+}
+
+// The color texture coordinates at three corners of a tile, normalized to [0, 1].
+struct TileUVs {
+    upper_left: Vector2F,
+    right: Vector2F,
+    down: Vector2F,
+}
+
+impl TileUVs {
+    fn new(corners: [(u16, u16); 3]) -> TileUVs {
+        let unpack = |(u, v): (u16, u16)| Vector2F::new(u as f32, v as f32).scale(1.0 / 65535.0);
+        let upper_left = unpack(corners[0]);
+        TileUVs {
+            upper_left,
+            right: unpack(corners[1]) - upper_left,
+            down: unpack(corners[2]) - upper_left,
+        }
+    }
+
+    // Interpolates the texture coordinates at the center of the given texel in the tile.
+    #[inline]
+    fn sample(&self, tile_offset: Vector2I) -> Vector2F {
+        let tile_size = Vector2F::new(TILE_WIDTH as f32, TILE_HEIGHT as f32);
+        let t = (tile_offset.to_f32() + Vector2F::splat(0.5)) / tile_size;
+        self.upper_left + self.right.scale(t.x()) + self.down.scale(t.y())
+    }
+}
+
+trait BlendModeExt {
+    fn blend(self, src: ColorF, dest: ColorF) -> ColorF;
+}
+
+impl BlendModeExt for BlendMode {
+    // These must match the blend states that the GPU renderer uses. Colors are premultiplied.
+    fn blend(self, src: ColorF, dest: ColorF) -> ColorF {
+        let one = F32x4::splat(1.0);
+        let (src_alpha, dest_alpha) = (F32x4::splat(src.a()), F32x4::splat(dest.a()));
+        let inv_src_alpha = 1.0 - src.a();
+        let inv_src_alpha_rgb = F32x4::new(inv_src_alpha, inv_src_alpha, inv_src_alpha, 1.0);
+        let dest_alpha_rgb = F32x4::new(dest.a(), dest.a(), dest.a(), 1.0);
+
+        let result = match self {
+            BlendMode::Clear | BlendMode::DestOut => dest.0 * (one - src_alpha),
+            BlendMode::SrcOver => src.0 + dest.0 * (one - src_alpha),
+            BlendMode::DestOver => {
+                src.0 * (one - dest_alpha) + dest.0 * dest_alpha_rgb
+            }
+            BlendMode::SrcAtop => src.0 * dest_alpha + dest.0 * (one - src_alpha),
+            BlendMode::Xor => src.0 * (one - dest_alpha) + dest.0 * (one - src_alpha),
+            BlendMode::Lighter => src.0 + dest.0,
+            BlendMode::Lighten => src.0.max(dest.0 * inv_src_alpha_rgb),
+            BlendMode::Darken => src.0.min(dest.0 * inv_src_alpha_rgb),
+        };
+
+        ColorF(result.clamp(F32x4::default(), one))
+    }
+}
+
+// Rasterization
+
+// Unpacks a fill endpoint into tile-local coordinates. See `ObjectBuilder::add_fill()`.
+#[inline]
+fn unpack_fill_point(px: u8, subpx_x: u8, subpx_y: u8) -> Vector2F {
+    let px = Vector2F::new((px & 0xf) as f32, (px >> 4) as f32);
+    px + Vector2F::new(subpx_x as f32, subpx_y as f32).scale(1.0 / 256.0)
+}
+
+// Accumulates the signed area between the line and the bottom of the tile into each texel.
+//
+// This is the exact equivalent of the area lookup table that the fill shader uses.
+fn accumulate_fill(fill_tile: &mut [f32], from: Vector2F, to: Vector2F) {
+    let (left, right, sign) = if from.x() < to.x() { (from, to, -1.0) } else { (to, from, 1.0) };
+    if left.x() == right.x() {
+        return;
+    }
+
+    let slope = (right.y() - left.y()) / (right.x() - left.x());
+    let min_x = f32::max(f32::floor(left.x()), 0.0) as usize;
+    let max_x = f32::min(f32::ceil(right.x()), TILE_WIDTH as f32) as usize;
+
+    for x in min_x..max_x {
+        let x0 = f32::max(left.x(), x as f32);
+        let x1 = f32::min(right.x(), (x + 1) as f32);
+        if x1 <= x0 {
+            continue;
+        }
+
+        let y0 = left.y() + (x0 - left.x()) * slope;
+        let y1 = left.y() + (x1 - left.x()) * slope;
+        let (y_min, y_max) = (f32::min(y0, y1), f32::max(y0, y1));
+        let width = x1 - x0;
+
+        let min_y = f32::max(f32::floor(y_min), 0.0) as usize;
+        for y in min_y..(TILE_HEIGHT as usize) {
+            let row_top = y as f32;
+            let area = if row_top >= y_max {
+                // The line is entirely above this row.
+                width
+            } else {
+                width * (row_top + 1.0 - average_clamped(y_min, y_max, row_top, row_top + 1.0))
+            };
+            fill_tile[y * TILE_WIDTH as usize + x] += sign * area;
+        }
+    }
+}
+
+// Returns the average of `clamp(y, lo, hi)` for `y` uniformly distributed in `[a, b]`.
+fn average_clamped(a: f32, b: f32, lo: f32, hi: f32) -> f32 {
+    if b - a < 1e-6 {
+        return f32::max(f32::min(a, hi), lo);
+    }
+
+    // Antiderivative of `clamp(y, lo, hi)`.
+    let integral = |y: f32| {
+        if y <= lo {
+            lo * y
+        } else if y <= hi {
+            lo * lo + 0.5 * (y * y - lo * lo)
+        } else {
+            lo * lo + 0.5 * (hi * hi - lo * lo) + hi * (y - hi)
+        }
+    };
+    (integral(b) - integral(a)) / (b - a)
+}
+
+// Inverts `calculate_mask_uv()` for the upper left corner of a tile.
+#[inline]
+fn mask_uv_to_tile_index(mask_u: u16, mask_v: u16) -> usize {
+    let scale = MASK_TILES_ACROSS as f32 / 65535.0;
+    let mask_u = f32::round(mask_u as f32 * scale) as usize;
+    let mask_v = f32::round(mask_v as f32 * scale) as usize;
+    mask_v * MASK_TILES_ACROSS as usize + mask_u
+}
+
+fn for_each_tile_texel<F>(tile_position: Vector2I, target_size: Vector2I, mut f: F)
+                          where F: FnMut(Vector2I, Vector2I) {
+    let tile_size = Vector2I::new(TILE_WIDTH as i32, TILE_HEIGHT as i32);
+    let tile_origin = tile_position.scale_xy(tile_size);
+    for y in 0..tile_size.y() {
+        for x in 0..tile_size.x() {
+            let tile_offset = Vector2I::new(x, y);
+            let position = tile_origin + tile_offset;
+            if position.x() < 0 || position.y() < 0 || position.x() >= target_size.x() ||
+                    position.y() >= target_size.y() {
+                continue;
+            }
+            f(position, tile_offset);
+        }
+    }
+}
+
+fn for_each_texel<F>(size: Vector2I, mut f: F) where F: FnMut(Vector2I) {
+    for y in 0..size.y() {
+        for x in 0..size.x() {
+            f(Vector2I::new(x, y));
+        }
+    }
+}
+
+#[inline]
+fn texel_center_uv(position: Vector2I, size: Vector2I) -> Vector2F {
+    (position.to_f32() + Vector2F::splat(0.5)) / size.to_f32()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::concurrent::executor::SequentialExecutor;
+    use crate::concurrent::scene_proxy::SceneProxy;
+    use crate::gpu::options::RendererOptions;
+    use crate::options::BuildOptions;
+    use crate::paint::{Paint, PaintId};
+    use crate::scene::{DrawPath, RenderTarget, Scene};
+    use pathfinder_color::{ColorF, ColorU};
+    use pathfinder_content::effects::{BlendMode, Effects, Filter, MaskMode};
+    use pathfinder_content::fill::FillRule;
+    use pathfinder_content::outline::Outline;
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::vector::{Vector2F, Vector2I};
+    use std::sync::mpsc;
+    use super::Renderer;
+
+
+    fn fill_rect(scene: &mut Scene, rect: RectF, paint: PaintId) {
+        scene.push_path(DrawPath::new(Outline::from_rect(rect),
+                                      paint,
+                                      None,
+                                      FillRule::Winding,
+                                      BlendMode::SrcOver,
+                                      String::new()));
+    }
+
+    fn render(scene: &Scene, size: Vector2I) -> Vec<ColorU> {
+        let (sender, receiver) = mpsc::channel();
+        let listener = Box::new(move |command| drop(sender.send(command)));
+        scene.build(BuildOptions::default(), listener, &SequentialExecutor);
+
+        let options = RendererOptions { background_color: Some(ColorF::white()) };
+        let mut renderer = Renderer::new(size, options);
+        renderer.begin_scene();
+        for command in receiver.try_iter() {
+            renderer.render_command(&command);
+        }
+        renderer.read_pixels()
+    }
+
+    #[test]
+    fn test_fill_rect() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(64.0)));
+
+        let paint = scene.push_paint(&Paint::Color(ColorU::new(255, 0, 0, 255)));
+        let rect = RectF::from_points(Vector2F::splat(8.0), Vector2F::new(40.5, 40.0));
+        fill_rect(&mut scene, rect, paint);
+
+        let pixels = render(&scene, Vector2I::splat(64));
+        let pixel = |x: usize, y: usize| pixels[y * 64 + x];
+        assert_eq!(pixel(4, 4), ColorU::white());
+        assert_eq!(pixel(8, 8), ColorU::new(255, 0, 0, 255));
+        assert_eq!(pixel(24, 24), ColorU::new(255, 0, 0, 255));
+        assert_eq!(pixel(39, 39), ColorU::new(255, 0, 0, 255));
+        assert_eq!(pixel(48, 48), ColorU::white());
+
+        // The right edge covers half of this column.
+        let edge = pixel(40, 20);
+        assert!(edge.r == 255 && edge.g > 120 && edge.g < 136);
+    }
+
+    #[test]
+    fn test_opacity_layer_with_mask() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(64.0)));
+
+        // The mask is white on the left half and black on the right half.
+        let white = scene.push_paint(&Paint::Color(ColorU::white()));
+        let black = scene.push_paint(&Paint::Color(ColorU::black()));
+        let mask = scene.push_render_target(RenderTarget::new(Vector2I::splat(64), String::new()));
+        for &(paint, x) in &[(white, 0.0), (black, 32.0)] {
+            let rect = RectF::new(Vector2F::new(x, 0.0), Vector2F::new(32.0, 64.0));
+            fill_rect(&mut scene, rect, paint);
+        }
+        scene.pop_render_target();
+
+        let red = scene.push_paint(&Paint::Color(ColorU::new(255, 0, 0, 255)));
+        let layer = scene.push_render_target(RenderTarget::new(Vector2I::splat(64),
+                                                               String::new()));
+        fill_rect(&mut scene, RectF::new(Vector2F::default(), Vector2F::splat(64.0)), red);
+        scene.pop_render_target();
+        scene.draw_render_target(layer, Effects {
+            filter: Filter::Opacity {
+                opacity: 0.5,
+                mask: Some(mask),
+                mask_mode: MaskMode::Luminance,
+            },
+        });
+
+        let pixels = render(&scene, Vector2I::splat(64));
+        let pixel = |x: usize, y: usize| pixels[y * 64 + x];
+        let left = pixel(16, 32);
+        assert!(left.r == 255 && left.g > 120 && left.g < 136 && left.b == left.g);
+        assert_eq!(pixel(48, 32), ColorU::white());
+    }
+
+    #[test]
+    fn test_inverted_alpha_mask() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::splat(64.0)));
+
+        // The mask is opaque on the left half only, so the layer shows through on the right.
+        let black = scene.push_paint(&Paint::Color(ColorU::black()));
+        let mask = scene.push_render_target(RenderTarget::new(Vector2I::splat(64), String::new()));
+        fill_rect(&mut scene, RectF::new(Vector2F::default(), Vector2F::new(32.0, 64.0)), black);
+        scene.pop_render_target();
+
+        let red = scene.push_paint(&Paint::Color(ColorU::new(255, 0, 0, 255)));
+        let layer = scene.push_render_target(RenderTarget::new(Vector2I::splat(64),
+                                                               String::new()));
+        fill_rect(&mut scene, RectF::new(Vector2F::default(), Vector2F::splat(64.0)), red);
+        scene.pop_render_target();
+        scene.draw_render_target(layer, Effects {
+            filter: Filter::Opacity {
+                opacity: 1.0,
+                mask: Some(mask),
+                mask_mode: MaskMode::InvertedAlpha,
+            },
+        });
+
+        let pixels = render(&scene, Vector2I::splat(64));
+        let pixel = |x: usize, y: usize| pixels[y * 64 + x];
+        assert_eq!(pixel(16, 32), ColorU::white());
+        assert_eq!(pixel(48, 32), ColorU::new(255, 0, 0, 255));
+    }
+
+    #[test]
+    fn test_render_to_image() {
+        let mut scene = Scene::new();
+        scene.set_view_box(RectF::new(Vector2F::default(), Vector2F::new(32.0, 16.0)));
+        let paint = scene.push_paint(&Paint::Color(ColorU::new(0, 255, 0, 128)));
+        fill_rect(&mut scene, RectF::new(Vector2F::default(), Vector2F::new(16.0, 16.0)), paint);
+
+        let image = scene.render_to_image(BuildOptions::default(), Vector2I::new(32, 16));
+        let proxy_image = SceneProxy::from_scene(scene, SequentialExecutor)
+            .render_to_image(BuildOptions::default(), Vector2I::new(32, 16));
+
+        for image in &[image, proxy_image] {
+            assert_eq!(image.size(), Vector2I::new(32, 16));
+            assert_eq!(image.pixels().len(), 32 * 16);
+
+            // Pixels come back with their alpha un-premultiplied, and the background is clear.
+            let pixel = |x: usize, y: usize| image.pixels()[y * 32 + x];
+            let inside = pixel(8, 8);
+            assert!(inside.r == 0 && inside.b == 0);
+            assert!(inside.g >= 254 && inside.a >= 127 && inside.a <= 129);
+            assert_eq!(pixel(24, 8), ColorU::transparent_black());
+        }
+    }
+}
@@ -11,8 +11,9 @@
 use crate::gpu::debug::DebugUIPresenter;
 use crate::gpu::options::{DestFramebuffer, RendererOptions};
 use crate::gpu::shaders::{AlphaTileProgram, AlphaTileVertexArray, FillProgram, FillVertexArray};
-use crate::gpu::shaders::{FilterBasicProgram, FilterBasicVertexArray, FilterTextProgram};
-use crate::gpu::shaders::{FilterTextVertexArray, MAX_FILLS_PER_BATCH, MaskTileProgram};
+use crate::gpu::shaders::{FilterBasicProgram, FilterBasicVertexArray, FilterOpacityProgram};
+use crate::gpu::shaders::{FilterOpacityVertexArray, FilterTextProgram, FilterTextVertexArray};
+use crate::gpu::shaders::{MAX_FILLS_PER_BATCH, MaskTileProgram};
 use crate::gpu::shaders::{MaskTileVertexArray, ReprojectionProgram, ReprojectionVertexArray};
 use crate::gpu::shaders::{SolidTileProgram, SolidTileVertexArray};
 use crate::gpu::shaders::{StencilProgram, StencilVertexArray};
@@ -21,6 +22,7 @@ use crate::gpu_data::{PaintPageId, RenderCommand, SolidTileVertex};
 use crate::tiles::{TILE_HEIGHT, TILE_WIDTH};
 use pathfinder_color::{self as color, ColorF};
 use pathfinder_content::effects::{BlendMode, CompositeOp, DefringingKernel, Effects, Filter};
+use pathfinder_content::effects::MaskMode;
 use pathfinder_content::fill::FillRule;
 use pathfinder_content::pattern::RenderTargetId;
 use pathfinder_geometry::vector::{Vector2I, Vector4F};
@@ -90,6 +92,8 @@ where
     // Filter shaders
     filter_basic_program: FilterBasicProgram<D>,
     filter_basic_vertex_array: FilterBasicVertexArray<D>,
+    filter_opacity_program: FilterOpacityProgram<D>,
+    filter_opacity_vertex_array: FilterOpacityVertexArray<D>,
     filter_text_program: FilterTextProgram<D>,
     filter_text_vertex_array: FilterTextVertexArray<D>,
     gamma_lut_texture: D::Texture,
@@ -137,6 +141,7 @@ where
         let solid_tile_program = SolidTileProgram::new(&device, resources);
         let alpha_tile_program = AlphaTileProgram::new(&device, resources);
         let filter_basic_program = FilterBasicProgram::new(&device, resources);
+        let filter_opacity_program = FilterOpacityProgram::new(&device, resources);
         let filter_text_program = FilterTextProgram::new(&device, resources);
         let stencil_program = StencilProgram::new(&device, resources);
         let reprojection_program = ReprojectionProgram::new(&device, resources);
@@ -192,6 +197,12 @@ where
             &quad_vertex_positions_buffer,
             &quad_vertex_indices_buffer,
         );
+        let filter_opacity_vertex_array = FilterOpacityVertexArray::new(
+            &device,
+            &filter_opacity_program,
+            &quad_vertex_positions_buffer,
+            &quad_vertex_indices_buffer,
+        );
         let filter_text_vertex_array = FilterTextVertexArray::new(
             &device,
             &filter_text_program,
@@ -255,6 +266,8 @@ where
 
             filter_basic_program,
             filter_basic_vertex_array,
+            filter_opacity_program,
+            filter_opacity_vertex_array,
             filter_text_program,
             filter_text_vertex_array,
             gamma_lut_texture,
@@ -841,6 +854,9 @@ where
             Filter::Composite(composite_op) => {
                 self.composite_render_target(render_target_id, composite_op)
             }
+            Filter::Opacity { opacity, mask, mask_mode } => {
+                self.draw_opacity_render_target(render_target_id, opacity, mask, mask_mode)
+            }
             Filter::Text { fg_color, bg_color, defringing_kernel, gamma_correction } => {
                 self.draw_text_render_target(render_target_id,
                                              fg_color,
@@ -890,6 +906,59 @@ where
         });
     }
 
+    fn draw_opacity_render_target(&self,
+                                  render_target_id: RenderTargetId,
+                                  opacity: f32,
+                                  mask: Option<RenderTargetId>,
+                                  mask_mode: MaskMode) {
+        let clear_color = self.clear_color_for_draw_operation();
+        let source_framebuffer = &self.render_targets[render_target_id.0 as usize].framebuffer;
+        let source_texture = self.device.framebuffer_texture(source_framebuffer);
+        let source_texture_size = self.device.texture_size(source_texture);
+        let main_viewport = self.main_viewport();
+
+        // If there's no mask, the source texture is bound in its place, but it isn't sampled.
+        let (mask_texture, mask_mode) = match mask {
+            None => (source_texture, 0),
+            Some(mask) => {
+                let mask_framebuffer = &self.render_targets[mask.0 as usize].framebuffer;
+                let mask_mode = match mask_mode {
+                    MaskMode::Luminance => 1,
+                    MaskMode::Alpha => 2,
+                    MaskMode::InvertedLuminance => 3,
+                    MaskMode::InvertedAlpha => 4,
+                };
+                (self.device.framebuffer_texture(mask_framebuffer), mask_mode)
+            }
+        };
+
+        let uniforms = vec![
+            (&self.filter_opacity_program.framebuffer_size_uniform,
+             UniformData::Vec2(main_viewport.size().to_f32().0)),
+            (&self.filter_opacity_program.source_uniform, UniformData::TextureUnit(0)),
+            (&self.filter_opacity_program.source_size_uniform,
+             UniformData::Vec2(source_texture_size.0.to_f32x2())),
+            (&self.filter_opacity_program.mask_uniform, UniformData::TextureUnit(1)),
+            (&self.filter_opacity_program.mask_mode_uniform, UniformData::Int(mask_mode)),
+            (&self.filter_opacity_program.opacity_uniform, UniformData::Float(opacity)),
+        ];
+
+        self.device.draw_elements(6, &RenderState {
+            target: &self.draw_render_target(),
+            program: &self.filter_opacity_program.program,
+            vertex_array: &self.filter_opacity_vertex_array.vertex_array,
+            primitive: Primitive::Triangles,
+            textures: &[source_texture, mask_texture],
+            uniforms: &uniforms,
+            viewport: main_viewport,
+            options: RenderOptions {
+                clear_ops: ClearOps { color: clear_color, ..ClearOps::default() },
+                blend: Some(BlendMode::SrcOver.to_blend_state()),
+                ..RenderOptions::default()
+            },
+        });
+    }
+
     fn draw_text_render_target(&self,
                                render_target_id: RenderTargetId,
                                fg_color: ColorF,
@@ -444,6 +444,71 @@ impl<D> FilterBasicVertexArray<D> where D: Device {
     }
 }
 
+pub struct FilterOpacityProgram<D> where D: Device {
+    pub program: D::Program,
+    pub source_uniform: D::Uniform,
+    pub source_size_uniform: D::Uniform,
+    pub framebuffer_size_uniform: D::Uniform,
+    pub mask_uniform: D::Uniform,
+    pub mask_mode_uniform: D::Uniform,
+    pub opacity_uniform: D::Uniform,
+}
+
+impl<D> FilterOpacityProgram<D> where D: Device {
+    pub fn new(device: &D, resources: &dyn ResourceLoader) -> FilterOpacityProgram<D> {
+        let program = device.create_program_from_shader_names(resources,
+                                                              "filter_opacity",
+                                                              "filter",
+                                                              "filter_opacity");
+        let source_uniform = device.get_uniform(&program, "Source");
+        let source_size_uniform = device.get_uniform(&program, "SourceSize");
+        let framebuffer_size_uniform = device.get_uniform(&program, "FramebufferSize");
+        let mask_uniform = device.get_uniform(&program, "Mask");
+        let mask_mode_uniform = device.get_uniform(&program, "MaskMode");
+        let opacity_uniform = device.get_uniform(&program, "Opacity");
+        FilterOpacityProgram {
+            program,
+            source_uniform,
+            source_size_uniform,
+            framebuffer_size_uniform,
+            mask_uniform,
+            mask_mode_uniform,
+            opacity_uniform,
+        }
+    }
+}
+
+pub struct FilterOpacityVertexArray<D> where D: Device {
+    pub vertex_array: D::VertexArray,
+}
+
+impl<D> FilterOpacityVertexArray<D> where D: Device {
+    pub fn new(
+        device: &D,
+        filter_opacity_program: &FilterOpacityProgram<D>,
+        quad_vertex_positions_buffer: &D::Buffer,
+        quad_vertex_indices_buffer: &D::Buffer,
+    ) -> FilterOpacityVertexArray<D> {
+        let vertex_array = device.create_vertex_array();
+        let position_attr = device.get_vertex_attr(&filter_opacity_program.program, "Position")
+                                  .unwrap();
+
+        device.bind_buffer(&vertex_array, quad_vertex_positions_buffer, BufferTarget::Vertex);
+        device.configure_vertex_attr(&vertex_array, &position_attr, &VertexAttrDescriptor {
+            size: 2,
+            class: VertexAttrClass::Int,
+            attr_type: VertexAttrType::I16,
+            stride: 4,
+            offset: 0,
+            divisor: 0,
+            buffer_index: 0,
+        });
+        device.bind_buffer(&vertex_array, quad_vertex_indices_buffer, BufferTarget::Index);
+
+        FilterOpacityVertexArray { vertex_array }
+    }
+}
+
 pub struct FilterTextProgram<D> where D: Device {
     pub program: D::Program,
     pub source_uniform: D::Uniform,
@@ -16,6 +16,7 @@ extern crate bitflags;
 extern crate log;
 
 pub mod concurrent;
+pub mod cpu;
 pub mod gpu;
 pub mod gpu_data;
 pub mod options;
@@ -211,8 +211,8 @@ impl Palette {
         let mut render_target_locations = vec![];
         for (render_target_index, render_target) in self.render_targets.iter().enumerate() {
             let render_target_id = RenderTargetId(render_target_index as u32);
-            render_target_locations.push(allocator.allocate_render_target(render_target.size(),
-                                                                          render_target_id));
+            let size = render_target.size().unwrap_or(view_box_size);
+            render_target_locations.push(allocator.allocate_render_target(size, render_target_id));
         }
 
         // Assign paint locations.
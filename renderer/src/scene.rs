@@ -11,17 +11,20 @@
 //! A set of paths to be rendered.
 
 use crate::builder::SceneBuilder;
-use crate::concurrent::executor::Executor;
+use crate::concurrent::executor::{Executor, SequentialExecutor};
+use crate::cpu::renderer::Renderer as CpuRenderer;
+use crate::gpu::options::RendererOptions;
 use crate::options::{BuildOptions, PreparedBuildOptions};
 use crate::options::{PreparedRenderTransform, RenderCommandListener};
 use crate::paint::{Paint, PaintId, PaintInfo, Palette};
 use pathfinder_content::effects::{BlendMode, Effects};
 use pathfinder_content::fill::FillRule;
 use pathfinder_content::outline::Outline;
-use pathfinder_content::pattern::RenderTargetId;
+use pathfinder_content::pattern::{Image, RenderTargetId};
 use pathfinder_geometry::vector::{Vector2F, Vector2I};
 use pathfinder_geometry::rect::RectF;
 use pathfinder_geometry::transform2d::Transform2F;
+use std::sync::{Arc, Mutex};
 
 #[derive(Clone)]
 pub struct Scene {
@@ -87,7 +90,7 @@ impl Scene {
 
     #[inline]
     pub fn build_paint_info(&self) -> PaintInfo {
-        self.palette.build_paint_info(self.view_box.size().to_i32())
+        self.palette.build_paint_info(self.view_box.size().ceil().to_i32())
     }
 
     #[allow(clippy::trivially_copy_pass_by_ref)]
@@ -190,6 +193,24 @@ impl Scene {
         let prepared_options = options.prepare(self.bounds);
         SceneBuilder::new(self, &prepared_options, listener).build(executor)
     }
+
+    /// Builds the scene and renders it on the CPU to an image of the given size.
+    ///
+    /// This needs no GPU or windowing system, so it's suitable for thumbnailing and for tests.
+    /// The image starts out transparent, and only the area inside the view box is drawn.
+    pub fn render_to_image(&self, options: BuildOptions, size: Vector2I) -> Image {
+        let renderer = Arc::new(Mutex::new(CpuRenderer::new(size, RendererOptions::default())));
+        renderer.lock().unwrap().begin_scene();
+
+        let listener_renderer = renderer.clone();
+        let listener = Box::new(move |command| {
+            listener_renderer.lock().unwrap().render_command(&command)
+        });
+        self.build(options, listener, &SequentialExecutor);
+
+        let renderer = renderer.lock().unwrap();
+        renderer.read_image()
+    }
     
     pub fn paths<'a>(&'a self) -> PathIter {
         PathIter {
@@ -197,6 +218,34 @@ impl Scene {
             pos: 0
         }
     }
+
+    #[inline]
+    pub fn get_paint(&self, paint_id: PaintId) -> &Paint {
+        &self.palette.paints[paint_id.0 as usize]
+    }
+
+    #[inline]
+    pub fn get_clip_path(&self, clip_path_id: ClipPathId) -> &ClipPath {
+        &self.clip_paths[clip_path_id.0 as usize]
+    }
+
+    #[inline]
+    pub fn get_path(&self, path_index: u32) -> &DrawPath {
+        &self.paths[path_index as usize]
+    }
+
+    #[inline]
+    pub fn get_render_target(&self, render_target_id: RenderTargetId) -> &RenderTarget {
+        &self.palette.render_targets[render_target_id.0 as usize]
+    }
+
+    /// Returns the drawing commands, in order, including those that draw into render targets.
+    ///
+    /// Unlike `paths()`, this preserves the layer structure of the scene.
+    #[inline]
+    pub fn display_list(&self) -> &[DisplayItem] {
+        &self.display_list
+    }
 }
 
 pub struct PathIter<'a> {
@@ -205,15 +254,9 @@ pub struct PathIter<'a> {
 }
 
 impl<'a> Iterator for PathIter<'a> {
-    type Item = (&'a Paint, &'a Outline, &'a str);
+    type Item = &'a DrawPath;
     fn next(&mut self) -> Option<Self::Item> {
-        let item = self.scene.paths.get(self.pos).map(|path_object| {
-            (
-                self.scene.palette.paints.get(path_object.paint.0 as usize).unwrap(),
-                &path_object.outline,
-                &*path_object.name
-            )
-        });
+        let item = self.scene.paths.get(self.pos);
         self.pos += 1;
         item
     }
@@ -241,7 +284,7 @@ pub struct ClipPathId(pub u32);
 
 #[derive(Clone, Debug)]
 pub struct RenderTarget {
-    size: Vector2I,
+    size: Option<Vector2I>,
     name: String,
 }
 
@@ -283,24 +326,29 @@ impl DrawPath {
     }
 
     #[inline]
-    pub(crate) fn clip_path(&self) -> Option<ClipPathId> {
+    pub fn clip_path(&self) -> Option<ClipPathId> {
         self.clip_path
     }
 
     #[inline]
-    pub(crate) fn paint(&self) -> PaintId {
+    pub fn paint(&self) -> PaintId {
         self.paint
     }
 
     #[inline]
-    pub(crate) fn fill_rule(&self) -> FillRule {
+    pub fn fill_rule(&self) -> FillRule {
         self.fill_rule
     }
 
     #[inline]
-    pub(crate) fn blend_mode(&self) -> BlendMode {
+    pub fn blend_mode(&self) -> BlendMode {
         self.blend_mode
     }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 }
 
 impl ClipPath {
@@ -315,19 +363,35 @@ impl ClipPath {
     }
 
     #[inline]
-    pub(crate) fn fill_rule(&self) -> FillRule {
+    pub fn fill_rule(&self) -> FillRule {
         self.fill_rule
     }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 }
 
 impl RenderTarget {
     #[inline]
     pub fn new(size: Vector2I, name: String) -> RenderTarget {
-        RenderTarget { size, name }
+        RenderTarget { size: Some(size), name }
+    }
+
+    /// Creates a render target that covers the view box of the scene, at whatever size the view
+    /// box has when the scene is built.
+    ///
+    /// This suits layers that are composited back over the whole scene, since they stay aligned
+    /// with it if the view box is resized after they're created.
+    #[inline]
+    pub fn with_view_box_size(name: String) -> RenderTarget {
+        RenderTarget { size: None, name }
     }
 
+    /// Returns the size of this render target, or `None` if it takes the size of the view box.
     #[inline]
-    pub fn size(&self) -> Vector2I {
+    pub fn size(&self) -> Option<Vector2I> {
         self.size
     }
 }
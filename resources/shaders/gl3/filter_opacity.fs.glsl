@@ -0,0 +1,45 @@
+#version {{version}}
+// Automatically generated from files in pathfinder/shaders/. Do not edit!
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+
+#extension GL_GOOGLE_include_directive : enable
+
+precision highp float;
+
+uniform sampler2D uSource;
+uniform sampler2D uMask;
+uniform vec2 uSourceSize;
+uniform float uOpacity;
+
+uniform int uMaskMode;
+
+in vec2 vTexCoord;
+
+out vec4 oFragColor;
+
+void main(){
+    float coverage = uOpacity;
+    if(uMaskMode != 0){
+
+        vec4 mask = texture(uMask, vTexCoord);
+        float maskValue = uMaskMode == 1 || uMaskMode == 3 ?
+            dot(mask . rgb, vec3(0.2125, 0.7154, 0.0721)):
+            mask . a;
+        coverage *= uMaskMode >= 3 ? 1.0 - maskValue : maskValue;
+    }
+    oFragColor = texture(uSource, vTexCoord) * coverage;
+}
+
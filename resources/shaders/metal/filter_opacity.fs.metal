@@ -0,0 +1,58 @@
+// Automatically generated from files in pathfinder/shaders/. Do not edit!
+#include <metal_stdlib>
+#include <simd/simd.h>
+
+using namespace metal;
+
+struct spvDescriptorSetBuffer0
+{
+    constant float* uOpacity [[id(0)]];
+    constant int* uMaskMode [[id(1)]];
+    texture2d<float> uMask [[id(2)]];
+    sampler uMaskSmplr [[id(3)]];
+    texture2d<float> uSource [[id(4)]];
+    sampler uSourceSmplr [[id(5)]];
+};
+
+struct main0_out
+{
+    float4 oFragColor [[color(0)]];
+};
+
+struct main0_in
+{
+    float2 vTexCoord [[user(locn0)]];
+};
+
+fragment main0_out main0(main0_in in [[stage_in]], constant spvDescriptorSetBuffer0& spvDescriptorSet0 [[buffer(0)]])
+{
+    main0_out out = {};
+    float coverage = (*spvDescriptorSet0.uOpacity);
+    if ((*spvDescriptorSet0.uMaskMode) != 0)
+    {
+        float4 mask = spvDescriptorSet0.uMask.sample(spvDescriptorSet0.uMaskSmplr, in.vTexCoord);
+        float _47;
+        if (((*spvDescriptorSet0.uMaskMode) == 1) || ((*spvDescriptorSet0.uMaskMode) == 3))
+        {
+            _47 = dot(mask.xyz, float3(0.2125, 0.7154, 0.0721));
+        }
+        else
+        {
+            _47 = mask.w;
+        }
+        float maskValue = _47;
+        float _66;
+        if ((*spvDescriptorSet0.uMaskMode) >= 3)
+        {
+            _66 = 1.0 - maskValue;
+        }
+        else
+        {
+            _66 = maskValue;
+        }
+        coverage *= _66;
+    }
+    out.oFragColor = spvDescriptorSet0.uSource.sample(spvDescriptorSet0.uSourceSmplr, in.vTexCoord) * coverage;
+    return out;
+}
+
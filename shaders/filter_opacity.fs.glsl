@@ -0,0 +1,42 @@
+#version 330
+
+// pathfinder/shaders/filter_opacity.fs.glsl
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// TODO: This could be significantly optimized by operating on a
+// sparse per-tile basis.
+
+#extension GL_GOOGLE_include_directive : enable
+
+precision highp float;
+
+uniform sampler2D uSource;
+uniform sampler2D uMask;
+uniform vec2 uSourceSize;
+uniform float uOpacity;
+// 0 = no mask, 1 = luminance, 2 = alpha, 3 = inverted luminance, 4 = inverted alpha.
+uniform int uMaskMode;
+
+in vec2 vTexCoord;
+
+out vec4 oFragColor;
+
+void main() {
+    float coverage = uOpacity;
+    if (uMaskMode != 0) {
+        // The mask is premultiplied, so its luminance is already scaled by its alpha.
+        vec4 mask = texture(uMask, vTexCoord);
+        float maskValue = uMaskMode == 1 || uMaskMode == 3 ?
+            dot(mask.rgb, vec3(0.2125, 0.7154, 0.0721)) :
+            mask.a;
+        coverage *= uMaskMode >= 3 ? 1.0 - maskValue : maskValue;
+    }
+    oFragColor = texture(uSource, vTexCoord) * coverage;
+}
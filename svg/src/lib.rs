@@ -14,37 +14,48 @@
 extern crate bitflags;
 
 use hashbrown::HashMap;
+use image::ImageFormat;
 use pathfinder_color::ColorU;
-use pathfinder_content::effects::BlendMode;
+use pathfinder_content::effects::{BlendMode, Effects, Filter, MaskMode};
 use pathfinder_content::fill::FillRule;
+use pathfinder_content::gradient::{ColorStop, Gradient, SpreadMethod};
 use pathfinder_content::outline::Outline;
+use pathfinder_content::pattern::{self, Image, Pattern, PatternSource, RenderTargetId, Repeat};
 use pathfinder_content::segment::{Segment, SegmentFlags};
 use pathfinder_content::stroke::{LineCap, LineJoin, OutlineStrokeToFill, StrokeStyle};
 use pathfinder_content::transform::Transform2FPathIter;
 use pathfinder_geometry::line_segment::LineSegment2F;
-use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::rect::{RectF, RectI};
 use pathfinder_geometry::transform2d::Transform2F;
-use pathfinder_geometry::vector::Vector2F;
+use pathfinder_geometry::vector::{Vector2F, Vector2I};
 use pathfinder_renderer::paint::Paint;
-use pathfinder_renderer::scene::{ClipPath, ClipPathId, DrawPath, Scene};
+use pathfinder_renderer::scene::{ClipPath, ClipPathId, DrawPath, RenderTarget, Scene};
 use std::fmt::{Display, Formatter, Result as FormatResult};
-use usvg::{Color as SvgColor, FillRule as UsvgFillRule, LineCap as UsvgLineCap};
-use usvg::{LineJoin as UsvgLineJoin, Node, NodeExt, NodeKind, Opacity, Paint as UsvgPaint};
-use usvg::{PathSegment as UsvgPathSegment, Rect as UsvgRect, Transform as UsvgTransform};
-use usvg::{Tree, Visibility};
+use std::fs;
+use usvg::{Align, AspectRatio, BaseGradient, Color as SvgColor, FillRule as UsvgFillRule};
+use usvg::{Image as UsvgImage, ImageData, ImageFormat as UsvgImageFormat, ImageRendering};
+use usvg::{LineCap as UsvgLineCap, LineJoin as UsvgLineJoin, Node, NodeExt, NodeKind, Opacity};
+use usvg::{Paint as UsvgPaint, PathSegment as UsvgPathSegment, Rect as UsvgRect};
+use usvg::{SpreadMethod as UsvgSpreadMethod, Transform as UsvgTransform, Tree, Units, Visibility};
 
 const HAIRLINE_STROKE_WIDTH: f32 = 0.0333;
 
+// The maximum number of pattern tiles drawn along each axis.
+const MAX_PATTERN_TILES: i32 = 64;
+
 pub struct BuiltSVG {
     pub scene: Scene,
     pub result_flags: BuildResultFlags,
     pub clip_paths: HashMap<String, ClipPathId>,
+    gradients: HashMap<String, GradientInfo>,
+    patterns: HashMap<String, Node>,
+    masks: HashMap<String, Node>,
 }
 
 bitflags! {
     // NB: If you change this, make sure to update the `Display`
     // implementation as well.
-    pub struct BuildResultFlags: u16 {
+    pub struct BuildResultFlags: u32 {
         const UNSUPPORTED_CLIP_PATH_NODE       = 0x0001;
         const UNSUPPORTED_DEFS_NODE            = 0x0002;
         const UNSUPPORTED_FILTER_NODE          = 0x0004;
@@ -60,6 +71,7 @@ bitflags! {
         const UNSUPPORTED_FILTER_ATTR          = 0x1000;
         const UNSUPPORTED_MASK_ATTR            = 0x2000;
         const UNSUPPORTED_OPACITY_ATTR         = 0x4000;
+        const TRUNCATED_PATTERN_TILES          = 0x8000;
     }
 }
 
@@ -78,6 +90,9 @@ impl BuiltSVG {
             scene,
             result_flags: BuildResultFlags::empty(),
             clip_paths: HashMap::new(),
+            gradients: HashMap::new(),
+            patterns: HashMap::new(),
+            masks: HashMap::new(),
         };
 
         let root = &tree.root();
@@ -108,10 +123,6 @@ impl BuiltSVG {
                     self.result_flags
                         .insert(BuildResultFlags::UNSUPPORTED_FILTER_ATTR);
                 }
-                if group.mask.is_some() {
-                    self.result_flags
-                        .insert(BuildResultFlags::UNSUPPORTED_MASK_ATTR);
-                }
 
                 if let Some(ref clip_path_name) = group.clip_path {
                     if let Some(clip_path_id) = self.clip_paths.get(clip_path_name) {
@@ -120,6 +131,24 @@ impl BuiltSVG {
                     }
                 }
 
+                let mask = match group.mask {
+                    None => None,
+                    Some(ref mask_name) => {
+                        let mask = self.masks.get(mask_name).cloned();
+                        if mask.is_none() {
+                            self.result_flags.insert(BuildResultFlags::UNSUPPORTED_MASK_ATTR);
+                        }
+                        mask
+                    }
+                };
+
+                let opacity = group.opacity.value() as f32;
+                if state.path_destination == PathDestination::Draw &&
+                        (opacity < 1.0 || mask.is_some()) {
+                    self.draw_group_layer(node, &state, opacity, mask.as_ref());
+                    return;
+                }
+
                 for kid in node.children() {
                     self.process_node(&kid, &state, clip_outline)
                 }
@@ -132,18 +161,25 @@ impl BuiltSVG {
             }
             NodeKind::Path(ref path) if state.path_destination == PathDestination::Draw &&
                     path.visibility == Visibility::Visible => {
+                // Gradients with `objectBoundingBox` units are relative to the untransformed
+                // bounds of the fill geometry, even when they're applied to the stroke.
+                let local_outline =
+                    Outline::from_segments(UsvgPathToSegments::new(path.data.iter().cloned()));
+                let object_bounds = local_outline.bounds();
+
                 if let Some(ref fill) = path.fill {
-                    let path = UsvgPathToSegments::new(path.data.iter().cloned());
-                    let path = Transform2FPathIter::new(path, &state.transform);
-                    let outline = Outline::from_segments(path);
-
-                    let name = format!("Fill({})", node.id());
-                    self.push_draw_path(outline,
-                                        name,
-                                        &state,
-                                        &fill.paint,
-                                        fill.opacity,
-                                        fill.rule);
+                    let mut outline = local_outline.clone();
+                    outline.transform(&state.transform);
+
+                    let paint = self.resolve_paint(&fill.paint,
+                                                   fill.opacity,
+                                                   &state,
+                                                   object_bounds,
+                                                   outline.bounds());
+                    if let Some(paint) = paint {
+                        let name = format!("Fill({})", node.id());
+                        self.push_draw_path(outline, name, &state, &paint, fill.rule);
+                    }
                 }
 
                 if let Some(ref stroke) = path.stroke {
@@ -154,21 +190,21 @@ impl BuiltSVG {
                                                                  stroke.miterlimit.value() as f32),
                     };
 
-                    let path = UsvgPathToSegments::new(path.data.iter().cloned());
-                    let outline = Outline::from_segments(path);
-
-                    let mut stroke_to_fill = OutlineStrokeToFill::new(&outline, stroke_style);
+                    let mut stroke_to_fill = OutlineStrokeToFill::new(&local_outline,
+                                                                      stroke_style);
                     stroke_to_fill.offset();
                     let mut outline = stroke_to_fill.into_outline();
                     outline.transform(&state.transform);
 
-                    let name = format!("Stroke({})", node.id());
-                    self.push_draw_path(outline,
-                                        name,
-                                        &state,
-                                        &stroke.paint,
-                                        stroke.opacity,
-                                        UsvgFillRule::NonZero);
+                    let paint = self.resolve_paint(&stroke.paint,
+                                                   stroke.opacity,
+                                                   &state,
+                                                   object_bounds,
+                                                   outline.bounds());
+                    if let Some(paint) = paint {
+                        let name = format!("Stroke({})", node.id());
+                        self.push_draw_path(outline, name, &state, &paint, UsvgFillRule::NonZero);
+                    }
                 }
             }
             NodeKind::Path(..) => {}
@@ -198,25 +234,39 @@ impl BuiltSVG {
                 self.result_flags
                     .insert(BuildResultFlags::UNSUPPORTED_FILTER_NODE);
             }
-            NodeKind::Image(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_IMAGE_NODE);
+            NodeKind::Image(ref image) if state.path_destination == PathDestination::Draw &&
+                    image.visibility == Visibility::Visible => {
+                match decode_image(image) {
+                    Some(decoded_image) => self.draw_image(node, image, decoded_image, &state),
+                    None => self.result_flags.insert(BuildResultFlags::UNSUPPORTED_IMAGE_NODE),
+                }
             }
-            NodeKind::LinearGradient(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_LINEAR_GRADIENT_NODE);
+            NodeKind::Image(..) => {}
+            NodeKind::LinearGradient(ref linear_gradient) => {
+                let from = Vector2F::new(linear_gradient.x1 as f32, linear_gradient.y1 as f32);
+                let to = Vector2F::new(linear_gradient.x2 as f32, linear_gradient.y2 as f32);
+                let gradient = Gradient::linear(LineSegment2F::new(from, to));
+                self.gradients.insert(node.id().to_owned(),
+                                      GradientInfo::new(gradient, &linear_gradient.base));
             }
             NodeKind::Mask(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_MASK_NODE);
+                // Like patterns, masks depend on the bounds of the element they're applied to.
+                self.masks.insert(node.id().to_owned(), node.clone());
             }
             NodeKind::Pattern(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_PATTERN_NODE);
+                // Pattern contents depend on the bounds of the path they're applied to, so we
+                // draw them lazily when they're referenced.
+                self.patterns.insert(node.id().to_owned(), node.clone());
             }
-            NodeKind::RadialGradient(..) => {
-                self.result_flags
-                    .insert(BuildResultFlags::UNSUPPORTED_RADIAL_GRADIENT_NODE);
+            NodeKind::RadialGradient(ref radial_gradient) => {
+                // The gradient line runs from the focal point to the center of the end circle.
+                let focal = Vector2F::new(radial_gradient.fx as f32, radial_gradient.fy as f32);
+                let center = Vector2F::new(radial_gradient.cx as f32, radial_gradient.cy as f32);
+                let gradient = Gradient::radial(LineSegment2F::new(focal, center),
+                                                0.0,
+                                                radial_gradient.r.value() as f32);
+                self.gradients.insert(node.id().to_owned(),
+                                      GradientInfo::new(gradient, &radial_gradient.base));
             }
             NodeKind::Svg(..) => {
                 self.result_flags
@@ -229,12 +279,9 @@ impl BuiltSVG {
                       outline: Outline,
                       name: String,
                       state: &State,
-                      paint: &UsvgPaint,
-                      opacity: Opacity,
+                      paint: &Paint,
                       fill_rule: UsvgFillRule) {
-        let style = self.scene.push_paint(&Paint::from_svg_paint(paint,
-                                                                 opacity,
-                                                                 &mut self.result_flags));
+        let style = self.scene.push_paint(paint);
         let fill_rule = FillRule::from_usvg_fill_rule(fill_rule);
         self.scene.push_path(DrawPath::new(outline,
                                            style,
@@ -243,6 +290,396 @@ impl BuiltSVG {
                                            BlendMode::SrcOver,
                                            name));
     }
+
+    // Returns `None` if the path should not be drawn at all, as is the case for gradients with no
+    // stops.
+    fn resolve_paint(&mut self,
+                     paint: &UsvgPaint,
+                     opacity: Opacity,
+                     state: &State,
+                     object_bounds: RectF,
+                     device_bounds: RectF)
+                     -> Option<Paint> {
+        match *paint {
+            UsvgPaint::Color(color) => Some(Paint::Color(ColorU::from_svg_color(color, opacity))),
+            UsvgPaint::Link(ref id) => {
+                match self.gradients.get(id) {
+                    Some(gradient_info) => {
+                        gradient_info.to_paint(&state.transform,
+                                               object_bounds,
+                                               device_bounds,
+                                               opacity)
+                    }
+                    None => {
+                        if let Some(pattern_node) = self.patterns.get(id).cloned() {
+                            return self.draw_pattern(&pattern_node,
+                                                     state,
+                                                     object_bounds,
+                                                     device_bounds,
+                                                     opacity);
+                        }
+
+                        self.result_flags.insert(BuildResultFlags::UNSUPPORTED_LINK_PAINT);
+                        Some(Paint::black())
+                    }
+                }
+            }
+        }
+    }
+
+    // Draws the group into a render target and composites it with the group opacity and mask.
+    fn draw_group_layer(&mut self,
+                        node: &Node,
+                        state: &State,
+                        opacity: f32,
+                        mask: Option<&Node>) {
+        let mask = match mask {
+            None => None,
+            Some(mask_node) => {
+                // Per spec, a mask that can't be drawn hides the element entirely.
+                match self.draw_mask(mask_node, state, node_bounds(node)) {
+                    Some(mask) => Some(mask),
+                    None => return,
+                }
+            }
+        };
+
+        let render_target = RenderTarget::with_view_box_size(format!("Group({})", node.id()));
+        let render_target_id = self.scene.push_render_target(render_target);
+        for kid in node.children() {
+            self.process_node(&kid, state, &mut None);
+        }
+        self.scene.pop_render_target();
+
+        let filter = Filter::Opacity { opacity, mask, mask_mode: MaskMode::Luminance };
+        self.scene.draw_render_target(render_target_id, Effects { filter });
+    }
+
+    // Draws the contents of a `<mask>` into a render target, for use as a luminance mask.
+    fn draw_mask(&mut self, node: &Node, state: &State, object_bounds: Option<RectF>)
+                 -> Option<RenderTargetId> {
+        let mask = match *node.borrow() {
+            NodeKind::Mask(ref mask) => (*mask).clone(),
+            _ => unreachable!(),
+        };
+
+        if mask.mask.is_some() {
+            // TODO: Nested masks.
+            self.result_flags.insert(BuildResultFlags::UNSUPPORTED_MASK_ATTR);
+        }
+
+        let mut mask_rect = usvg_rect_to_euclid_rect(&mask.rect);
+        let mut content_transform = Transform2F::default();
+        if mask.units == Units::ObjectBoundingBox ||
+                mask.content_units == Units::ObjectBoundingBox {
+            let object_bounds = object_bounds?;
+            let bounding_box_transform = Transform2F::row_major(object_bounds.width(),
+                                                                0.0,
+                                                                0.0,
+                                                                object_bounds.height(),
+                                                                object_bounds.origin_x(),
+                                                                object_bounds.origin_y());
+            if mask.units == Units::ObjectBoundingBox {
+                mask_rect = RectF::from_points(bounding_box_transform * mask_rect.origin(),
+                                               bounding_box_transform * mask_rect.lower_right());
+            }
+            if mask.content_units == Units::ObjectBoundingBox {
+                content_transform = bounding_box_transform;
+            }
+        }
+
+        let render_target = RenderTarget::with_view_box_size(format!("Mask({})", node.id()));
+        let render_target_id = self.scene.push_render_target(render_target);
+
+        // Mask contents are clipped to the mask rectangle.
+        let clip_outline = rect_outline(mask_rect, &state.transform);
+        let clip_path = ClipPath::new(clip_outline,
+                                      FillRule::Winding,
+                                      format!("MaskClip({})", node.id()));
+        let clip_path_id = self.scene.push_clip_path(clip_path);
+
+        let mask_state = State {
+            path_destination: PathDestination::Draw,
+            transform: state.transform * content_transform,
+            clip_path: Some(clip_path_id),
+        };
+        for kid in node.children() {
+            self.process_node(&kid, &mask_state, &mut None);
+        }
+
+        self.scene.pop_render_target();
+        Some(render_target_id)
+    }
+
+    // The renderer places pixel (0, 0) of a pattern image at the origin of the scene, so we
+    // resample the image into scene space and fill its on-screen rectangle with it.
+    fn draw_image(&mut self, node: &Node, image: &UsvgImage, decoded_image: Image, state: &State) {
+        let image_size = decoded_image.size().to_f32();
+        if image_size.x() == 0.0 || image_size.y() == 0.0 {
+            return;
+        }
+
+        let viewport = usvg_rect_to_euclid_rect(&image.view_box.rect);
+        let image_transform = aspect_ratio_transform(RectF::new(Vector2F::default(), image_size),
+                                                     &image.view_box.aspect,
+                                                     viewport);
+        let image_bounds = RectF::from_points(image_transform * Vector2F::default(),
+                                              image_transform * image_size);
+        let draw_rect = match viewport.intersection(image_bounds) {
+            Some(draw_rect) => draw_rect,
+            None => return,
+        };
+
+        let outline = rect_outline(draw_rect, &state.transform);
+        let pattern_bounds = match pattern::pattern_bounds(outline.bounds()) {
+            Some(pattern_bounds) => pattern_bounds,
+            None => return,
+        };
+
+        let smooth = image.rendering_mode != ImageRendering::OptimizeSpeed;
+        let pattern_image = decoded_image.resample(&(state.transform * image_transform),
+                                                   pattern_bounds,
+                                                   Repeat::empty(),
+                                                   smooth);
+        let pattern = Pattern::new(PatternSource::Image(pattern_image), Repeat::empty());
+
+        let name = format!("Image({})", node.id());
+        self.push_draw_path(outline, name, state, &Paint::Pattern(pattern), UsvgFillRule::NonZero);
+    }
+
+    // Draws the pattern into a render target and returns a paint that samples from it.
+    fn draw_pattern(&mut self,
+                    node: &Node,
+                    state: &State,
+                    object_bounds: RectF,
+                    device_bounds: RectF,
+                    opacity: Opacity)
+                    -> Option<Paint> {
+        let pattern = match *node.borrow() {
+            NodeKind::Pattern(ref pattern) => (*pattern).clone(),
+            _ => unreachable!(),
+        };
+
+        let mut tile_rect = usvg_rect_to_euclid_rect(&pattern.rect);
+        if pattern.units == Units::ObjectBoundingBox {
+            tile_rect = RectF::new(
+                object_bounds.origin() + tile_rect.origin().scale_xy(object_bounds.size()),
+                tile_rect.size().scale_xy(object_bounds.size()));
+        }
+
+        // Per spec, a zero-sized tile disables rendering of the element.
+        if tile_rect.width() <= 0.0 || tile_rect.height() <= 0.0 {
+            return None;
+        }
+
+        let content_transform = match pattern.view_box {
+            Some(ref view_box) => {
+                aspect_ratio_transform(usvg_rect_to_euclid_rect(&view_box.rect),
+                                       &view_box.aspect,
+                                       RectF::new(Vector2F::default(), tile_rect.size()))
+            }
+            None if pattern.content_units == Units::ObjectBoundingBox => {
+                Transform2F::from_scale(object_bounds.size())
+            }
+            None => Transform2F::default(),
+        };
+
+        let pattern_transform = state.transform *
+            usvg_transform_to_transform_2d(&pattern.transform);
+        let render_target_bounds = pattern::pattern_bounds(device_bounds)?;
+
+        let render_target_id = match self.draw_pattern_tile(node,
+                                                            tile_rect,
+                                                            &pattern_transform,
+                                                            &content_transform,
+                                                            render_target_bounds,
+                                                            opacity) {
+            Some(render_target_id) => render_target_id,
+            None => {
+                self.draw_pattern_tiles(node,
+                                        tile_rect,
+                                        &pattern_transform,
+                                        &content_transform,
+                                        device_bounds,
+                                        render_target_bounds,
+                                        opacity)
+            }
+        };
+
+        let source = PatternSource::RenderTarget(render_target_id);
+        Some(Paint::Pattern(Pattern::new(source, Repeat::X | Repeat::Y)))
+    }
+
+    // If the pattern transform only translates and scales, the tiles form a grid in device space,
+    // so we draw a single tile into a render target and let the pattern repeat it. Returns `None`
+    // if the tiles must be drawn one by one instead.
+    fn draw_pattern_tile(&mut self,
+                         node: &Node,
+                         tile_rect: RectF,
+                         pattern_transform: &Transform2F,
+                         content_transform: &Transform2F,
+                         render_target_bounds: RectI,
+                         opacity: Opacity)
+                         -> Option<RenderTargetId> {
+        let matrix = pattern_transform.matrix;
+        if matrix.m12() != 0.0 || matrix.m21() != 0.0 || matrix.m11() <= 0.0 ||
+                matrix.m22() <= 0.0 {
+            return None;
+        }
+
+        // Render targets are a whole number of pixels in size, so the tile is stretched slightly
+        // to fit one. If the tile is larger than the path, there are only a few tiles to draw, and
+        // drawing them one by one needs a smaller render target.
+        let device_tile_size = tile_rect.size()
+                                        .scale_xy(Vector2F::new(matrix.m11(), matrix.m22()))
+                                        .round()
+                                        .to_i32()
+                                        .max(Vector2I::splat(1));
+        let max_tile_size = render_target_bounds.lower_right();
+        if device_tile_size.x() > max_tile_size.x() || device_tile_size.y() > max_tile_size.y() {
+            return None;
+        }
+
+        // The pattern repeats from the scene origin, so shift the tile to the position the tile
+        // grid has there. Up to four copies are needed to fill the render target.
+        let device_tile_size_f = device_tile_size.to_f32();
+        let device_tile_origin = *pattern_transform * tile_rect.origin();
+        let phase = device_tile_origin -
+            (device_tile_origin / device_tile_size_f).floor().scale_xy(device_tile_size_f);
+        let tile_scale = Transform2F::from_scale(device_tile_size_f / tile_rect.size());
+
+        let render_target = RenderTarget::new(device_tile_size, format!("Pattern({})", node.id()));
+        let render_target_id = self.scene.push_render_target(render_target);
+        let opacity_layer = self.push_pattern_opacity_layer(node, device_tile_size, opacity);
+
+        for &tile_y in &[phase.y() - device_tile_size_f.y(), phase.y()] {
+            for &tile_x in &[phase.x() - device_tile_size_f.x(), phase.x()] {
+                let tile_origin = Vector2F::new(tile_x, tile_y);
+                let device_tile_rect = RectF::new(tile_origin, device_tile_size_f);
+                if device_tile_rect.max_x() <= 0.0 || device_tile_rect.max_y() <= 0.0 {
+                    continue;
+                }
+
+                // Pattern contents are clipped to their tile.
+                let clip_outline = rect_outline(device_tile_rect, &Transform2F::default());
+                let name = format!("PatternTile({}, {}, {})", node.id(), tile_x, tile_y);
+                let clip_path = ClipPath::new(clip_outline, FillRule::Winding, name);
+                let clip_path_id = self.scene.push_clip_path(clip_path);
+
+                let tile_state = State {
+                    path_destination: PathDestination::Draw,
+                    transform: Transform2F::from_translation(tile_origin) *
+                        tile_scale *
+                        *content_transform,
+                    clip_path: Some(clip_path_id),
+                };
+                for kid in node.children() {
+                    self.process_node(&kid, &tile_state, &mut None);
+                }
+            }
+        }
+
+        self.pop_pattern_opacity_layer(opacity_layer, opacity);
+        self.scene.pop_render_target();
+        Some(render_target_id)
+    }
+
+    // Draws every tile of the pattern that the path covers into a render target the size of the
+    // path's bounds.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_pattern_tiles(&mut self,
+                          node: &Node,
+                          tile_rect: RectF,
+                          pattern_transform: &Transform2F,
+                          content_transform: &Transform2F,
+                          device_bounds: RectF,
+                          render_target_bounds: RectI,
+                          opacity: Opacity)
+                          -> RenderTargetId {
+        // Find the range of tiles that the path covers in pattern space.
+        let pattern_transform_inv = pattern_transform.inverse();
+        let corners = [
+            device_bounds.origin(),
+            device_bounds.upper_right(),
+            device_bounds.lower_left(),
+            device_bounds.lower_right(),
+        ];
+        let mut covered_rect = RectF::from_points(pattern_transform_inv * corners[0],
+                                                  pattern_transform_inv * corners[0]);
+        for &corner in &corners[1..] {
+            covered_rect = covered_rect.union_point(pattern_transform_inv * corner);
+        }
+        let first_tile = ((covered_rect.origin() - tile_rect.origin()) / tile_rect.size())
+            .floor()
+            .to_i32();
+        let mut last_tile = ((covered_rect.lower_right() - tile_rect.origin()) / tile_rect.size())
+            .ceil()
+            .to_i32();
+        let max_last_tile = first_tile + Vector2I::splat(MAX_PATTERN_TILES);
+        if last_tile.x() > max_last_tile.x() || last_tile.y() > max_last_tile.y() {
+            self.result_flags.insert(BuildResultFlags::TRUNCATED_PATTERN_TILES);
+            last_tile = last_tile.min(max_last_tile);
+        }
+
+        let render_target_size = render_target_bounds.lower_right();
+        let render_target = RenderTarget::new(render_target_size,
+                                              format!("Pattern({})", node.id()));
+        let render_target_id = self.scene.push_render_target(render_target);
+        let opacity_layer = self.push_pattern_opacity_layer(node, render_target_size, opacity);
+
+        for tile_y in first_tile.y()..last_tile.y() {
+            for tile_x in first_tile.x()..last_tile.x() {
+                let tile_offset = Vector2I::new(tile_x, tile_y).to_f32().scale_xy(tile_rect.size());
+                let tile_origin = tile_rect.origin() + tile_offset;
+
+                // Pattern contents are clipped to their tile.
+                let clip_outline = rect_outline(RectF::new(tile_origin, tile_rect.size()),
+                                                pattern_transform);
+                let name = format!("PatternTile({}, {}, {})", node.id(), tile_x, tile_y);
+                let clip_path = ClipPath::new(clip_outline, FillRule::Winding, name);
+                let clip_path_id = self.scene.push_clip_path(clip_path);
+
+                let tile_state = State {
+                    path_destination: PathDestination::Draw,
+                    transform: *pattern_transform *
+                        Transform2F::from_translation(tile_origin) *
+                        *content_transform,
+                    clip_path: Some(clip_path_id),
+                };
+                for kid in node.children() {
+                    self.process_node(&kid, &tile_state, &mut None);
+                }
+            }
+        }
+
+        self.pop_pattern_opacity_layer(opacity_layer, opacity);
+        self.scene.pop_render_target();
+        render_target_id
+    }
+
+    // The opacity of a paint fades the pattern contents as a whole, so that overlapping contents
+    // don't show through each other. If the paint is translucent, this pushes a layer of the same
+    // size as the pattern's render target for the contents to be drawn into.
+    fn push_pattern_opacity_layer(&mut self, node: &Node, size: Vector2I, opacity: Opacity)
+                                  -> Option<RenderTargetId> {
+        if opacity.value() == 1.0 {
+            return None;
+        }
+        let render_target = RenderTarget::new(size, format!("PatternOpacity({})", node.id()));
+        Some(self.scene.push_render_target(render_target))
+    }
+
+    // Composites the layer pushed by `push_pattern_opacity_layer()`, if any, into the pattern.
+    fn pop_pattern_opacity_layer(&mut self,
+                                 opacity_layer: Option<RenderTargetId>,
+                                 opacity: Opacity) {
+        if let Some(render_target_id) = opacity_layer {
+            self.scene.pop_render_target();
+            let opacity = opacity.value() as f32;
+            let filter = Filter::Opacity { opacity, mask: None, mask_mode: MaskMode::Luminance };
+            self.scene.draw_render_target(render_target_id, Effects { filter });
+        }
+    }
 }
 
 impl Display for BuildResultFlags {
@@ -283,31 +720,177 @@ impl Display for BuildResultFlags {
             "filter attribute",
             "mask attribute",
             "opacity attribute",
+            "more than 64 pattern tiles in a row",
         ];
     }
 }
 
-trait PaintExt {
-    fn from_svg_paint(svg_paint: &UsvgPaint, opacity: Opacity, result_flags: &mut BuildResultFlags)
-                      -> Self;
+// A `<linearGradient>` or `<radialGradient>` in its own coordinate space, before it has been
+// positioned relative to the path that references it.
+struct GradientInfo {
+    gradient: Gradient,
+    transform: Transform2F,
+    units: Units,
+    spread_method: SpreadMethod,
 }
 
-impl PaintExt for Paint {
-    #[inline]
-    fn from_svg_paint(svg_paint: &UsvgPaint, opacity: Opacity, result_flags: &mut BuildResultFlags)
-                      -> Paint {
-        // TODO(pcwalton): Support gradients.
-        Paint::Color(match *svg_paint {
-            UsvgPaint::Color(color) => ColorU::from_svg_color(color, opacity),
-            UsvgPaint::Link(_) => {
-                // TODO(pcwalton)
-                result_flags.insert(BuildResultFlags::UNSUPPORTED_LINK_PAINT);
-                ColorU::black()
+impl GradientInfo {
+    fn new(mut gradient: Gradient, base: &BaseGradient) -> GradientInfo {
+        for stop in &base.stops {
+            let color = ColorU::from_svg_color(stop.color, stop.opacity);
+            gradient.add_color_stop(ColorStop::new(color, stop.offset.value() as f32));
+        }
+
+        GradientInfo {
+            gradient,
+            transform: usvg_transform_to_transform_2d(&base.transform),
+            units: base.units,
+            spread_method: match base.spread_method {
+                UsvgSpreadMethod::Pad => SpreadMethod::Pad,
+                UsvgSpreadMethod::Reflect => SpreadMethod::Reflect,
+                UsvgSpreadMethod::Repeat => SpreadMethod::Repeat,
+            },
+        }
+    }
+
+    fn to_paint(&self,
+                transform: &Transform2F,
+                object_bounds: RectF,
+                device_bounds: RectF,
+                opacity: Opacity)
+                -> Option<Paint> {
+        let last_stop = *self.gradient.stops().last()?;
+
+        let mut transform = *transform;
+        if self.units == Units::ObjectBoundingBox {
+            // Per spec, `objectBoundingBox` units on an element with no width or height disable
+            // rendering of that element.
+            if object_bounds.width() == 0.0 || object_bounds.height() == 0.0 {
+                return None;
             }
-        })
+            transform *= Transform2F::row_major(object_bounds.width(),
+                                                0.0,
+                                                0.0,
+                                                object_bounds.height(),
+                                                object_bounds.origin_x(),
+                                                object_bounds.origin_y());
+        }
+        transform *= self.transform;
+
+        let geometry = match self.gradient.geometry().transform(&transform) {
+            Some(geometry) => geometry,
+            None => {
+                // Per spec, a zero-length gradient vector paints the last stop's color.
+                let mut paint = Paint::Color(last_stop.color);
+                paint.set_opacity(opacity.value() as f32);
+                return Some(paint);
+            }
+        };
+
+        let mut gradient = Gradient::new(geometry);
+        for &stop in self.gradient.stops() {
+            gradient.add_color_stop(stop);
+        }
+        gradient.apply_spread_method(self.spread_method, device_bounds);
+
+        let mut paint = Paint::Gradient(gradient);
+        paint.set_opacity(opacity.value() as f32);
+        Some(paint)
     }
 }
 
+fn decode_image(image: &UsvgImage) -> Option<Image> {
+    let format = match image.format {
+        UsvgImageFormat::PNG => ImageFormat::Png,
+        UsvgImageFormat::JPEG => ImageFormat::Jpeg,
+        // TODO: Nested SVG images.
+        UsvgImageFormat::SVG => return None,
+    };
+
+    let image_buffer = match image.data {
+        ImageData::Raw(ref data) => image::load_from_memory_with_format(data, format).ok()?,
+        ImageData::Path(ref path) => {
+            let data = fs::read(path).ok()?;
+            image::load_from_memory_with_format(&data, format).ok()?
+        }
+    };
+    Some(Image::from_image_buffer(image_buffer.to_rgba()))
+}
+
+// Returns the transform that maps `view_box` into `viewport`, per the `preserveAspectRatio`
+// attribute.
+fn aspect_ratio_transform(view_box: RectF, aspect: &AspectRatio, viewport: RectF)
+                          -> Transform2F {
+    if view_box.width() == 0.0 || view_box.height() == 0.0 {
+        return Transform2F::default();
+    }
+
+    let mut scale = viewport.size() / view_box.size();
+    if aspect.align != Align::None {
+        scale = if aspect.slice {
+            Vector2F::splat(f32::max(scale.x(), scale.y()))
+        } else {
+            Vector2F::splat(f32::min(scale.x(), scale.y()))
+        };
+    }
+
+    let alignment = match aspect.align {
+        Align::None | Align::XMinYMin => Vector2F::new(0.0, 0.0),
+        Align::XMidYMin => Vector2F::new(0.5, 0.0),
+        Align::XMaxYMin => Vector2F::new(1.0, 0.0),
+        Align::XMinYMid => Vector2F::new(0.0, 0.5),
+        Align::XMidYMid => Vector2F::new(0.5, 0.5),
+        Align::XMaxYMid => Vector2F::new(1.0, 0.5),
+        Align::XMinYMax => Vector2F::new(0.0, 1.0),
+        Align::XMidYMax => Vector2F::new(0.5, 1.0),
+        Align::XMaxYMax => Vector2F::new(1.0, 1.0),
+    };
+    let slack = viewport.size() - view_box.size().scale_xy(scale);
+    let origin = viewport.origin() + slack.scale_xy(alignment);
+
+    Transform2F::from_translation(origin) *
+        Transform2F::from_scale(scale) *
+        Transform2F::from_translation(-view_box.origin())
+}
+
+// Returns the bounds of the children of the given node, in its local coordinate system.
+fn node_bounds(node: &Node) -> Option<RectF> {
+    let mut bounds: Option<RectF> = None;
+    for kid in node.children() {
+        let transform = usvg_transform_to_transform_2d(&kid.transform());
+        let kid_bounds = match *kid.borrow() {
+            NodeKind::Path(ref path) => {
+                let segments = UsvgPathToSegments::new(path.data.iter().cloned());
+                let mut outline = Outline::from_segments(segments);
+                outline.transform(&transform);
+                outline.bounds()
+            }
+            NodeKind::Image(ref image) => {
+                rect_outline(usvg_rect_to_euclid_rect(&image.view_box.rect), &transform).bounds()
+            }
+            NodeKind::Group(_) => {
+                match node_bounds(&kid) {
+                    Some(kid_bounds) => rect_outline(kid_bounds, &transform).bounds(),
+                    None => continue,
+                }
+            }
+            _ => continue,
+        };
+
+        bounds = Some(match bounds {
+            Some(bounds) => bounds.union_rect(kid_bounds),
+            None => kid_bounds,
+        });
+    }
+    bounds
+}
+
+fn rect_outline(rect: RectF, transform: &Transform2F) -> Outline {
+    let mut outline = Outline::from_rect(rect);
+    outline.transform(transform);
+    outline
+}
+
 fn usvg_rect_to_euclid_rect(rect: &UsvgRect) -> RectF {
     RectF::new(
         Vector2F::new(rect.x() as f32, rect.y() as f32),
@@ -316,10 +899,11 @@ fn usvg_rect_to_euclid_rect(rect: &UsvgRect) -> RectF {
 }
 
 fn usvg_transform_to_transform_2d(transform: &UsvgTransform) -> Transform2F {
+    // SVG lists the matrix column by column.
     Transform2F::row_major(
         transform.a as f32,
-        transform.b as f32,
         transform.c as f32,
+        transform.b as f32,
         transform.d as f32,
         transform.e as f32,
         transform.f as f32,
@@ -497,3 +1081,436 @@ enum PathDestination {
     Defs,
     Clip,
 }
+
+#[cfg(test)]
+mod test {
+    use pathfinder_color::ColorU;
+    use pathfinder_content::effects::Filter;
+    use pathfinder_content::gradient::GradientGeometry;
+    use pathfinder_content::pattern::{Pattern, PatternSource, Repeat};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::{Vector2F, Vector2I};
+    use pathfinder_renderer::options::{BuildOptions, RenderTransform};
+    use pathfinder_renderer::paint::Paint;
+    use pathfinder_renderer::scene::{DisplayItem, Scene};
+    use usvg::{Opacity, Options, Tree};
+    use super::{BuildResultFlags, BuiltSVG};
+
+    // Builds an SVG document, and renders it scaled up by `scale` from its view box size.
+    fn render(svg: &str, scale: f32) -> Vec<ColorU> {
+        let tree = Tree::from_str(svg, &Options::default()).unwrap();
+        let mut built_svg = BuiltSVG::from_tree(&tree);
+        assert!(built_svg.result_flags.is_empty());
+
+        let size = built_svg.scene.view_box().size().scale(scale);
+        built_svg.scene.set_view_box(RectF::new(Vector2F::default(), size));
+        let transform = Transform2F::from_scale(Vector2F::splat(scale));
+        let options = BuildOptions {
+            transform: RenderTransform::Transform2D(transform),
+            ..BuildOptions::default()
+        };
+        let size = size.to_i32();
+        let image = built_svg.scene.render_to_image(options, size);
+        assert_eq!(image.size(), size);
+        image.pixels().to_vec()
+    }
+
+    fn pixel(pixels: &[ColorU], width: i32, position: Vector2I) -> ColorU {
+        pixels[(position.y() * width + position.x()) as usize]
+    }
+
+    // Allows for rounding in premultiplication and compositing.
+    fn approx_eq(actual: ColorU, expected: ColorU) -> bool {
+        let channels = |color: ColorU| [color.r, color.g, color.b, color.a];
+        channels(actual).iter().zip(channels(expected).iter()).all(|(&actual, &expected)| {
+            (actual as i32 - expected as i32).abs() <= 2
+        })
+    }
+
+    #[test]
+    fn test_group_opacity() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20">
+            <g opacity="0.5">
+                <rect width="20" height="20" fill="#ff0000"/>
+                <rect x="10" width="10" height="20" fill="#0000ff"/>
+            </g>
+        </svg>"##;
+
+        // The group is faded as a whole, so the overlapping rectangles don't show through each
+        // other. The layer also covers the scene when it's rendered larger than its view box.
+        for &scale in &[1.0, 3.0] {
+            let pixels = render(svg, scale);
+            let width = (20.0 * scale) as i32;
+            let left = pixel(&pixels, width, Vector2F::new(5.0, 10.0).scale(scale).to_i32());
+            let right = pixel(&pixels, width, Vector2F::new(15.0, 10.0).scale(scale).to_i32());
+            assert!(approx_eq(left, ColorU::new(255, 0, 0, 128)));
+            assert!(approx_eq(right, ColorU::new(0, 0, 255, 128)));
+        }
+    }
+
+    #[test]
+    fn test_luminance_mask() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="30" height="20">
+            <mask id="mask" x="0" y="0" width="30" height="20" maskUnits="userSpaceOnUse">
+                <rect width="10" height="20" fill="#ffffff"/>
+                <rect x="10" width="10" height="20" fill="#808080"/>
+            </mask>
+            <rect width="30" height="20" fill="#00ff00" mask="url(#mask)"/>
+        </svg>"##;
+
+        // White shows the content, gray partly shows it, and outside the mask contents, nothing
+        // is drawn.
+        for &scale in &[1.0, 2.0] {
+            let pixels = render(svg, scale);
+            let width = (30.0 * scale) as i32;
+            let alpha_at = |x: f32| {
+                pixel(&pixels, width, Vector2F::new(x, 10.0).scale(scale).to_i32()).a as i32
+            };
+            assert_eq!(alpha_at(5.0), 255);
+            assert!((alpha_at(15.0) - 128).abs() <= 2);
+            assert_eq!(alpha_at(25.0), 0);
+        }
+    }
+
+    // Builds an SVG document containing the gradient with the ID `g`, and converts the gradient
+    // to a paint for a path with the given transform and bounds in a 100x100 view box.
+    fn gradient_paint(gradient: &str,
+                      transform: Transform2F,
+                      object_bounds: RectF,
+                      opacity: f64)
+                      -> Option<Paint> {
+        let svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\" \
+                           height=\"100\">{}<rect width=\"100\" height=\"100\" \
+                           fill=\"url(#g)\"/></svg>",
+                          gradient);
+        let tree = Tree::from_str(&svg, &Options::default()).unwrap();
+        let built_svg = BuiltSVG::from_tree(&tree);
+        let device_bounds = RectF::new(Vector2F::default(), Vector2F::splat(100.0));
+        built_svg.gradients["g"].to_paint(&transform,
+                                          object_bounds,
+                                          device_bounds,
+                                          Opacity::new(opacity))
+    }
+
+    // Returns the endpoints and stops of a linear gradient paint.
+    fn linear_gradient(paint: Option<Paint>) -> (Vector2F, Vector2F, Vec<(f32, ColorU)>) {
+        let gradient = match paint {
+            Some(Paint::Gradient(gradient)) => gradient,
+            paint => panic!("expected a gradient, got {:?}", paint),
+        };
+        let line = match *gradient.geometry() {
+            GradientGeometry::Linear(line) => line,
+            GradientGeometry::Radial { .. } => panic!("expected a linear gradient"),
+        };
+        let stops = gradient.stops().iter().map(|stop| (stop.offset, stop.color)).collect();
+        (line.from(), line.to(), stops)
+    }
+
+    // Checks that a gradient runs between the expected points, allowing for rounding.
+    fn assert_endpoints(actual: (Vector2F, Vector2F), expected: (Vector2F, Vector2F)) {
+        assert!((actual.0 - expected.0).length() < 0.001 &&
+                (actual.1 - expected.1).length() < 0.001,
+                "{:?} != {:?}", actual, expected);
+    }
+
+    const RED: ColorU = ColorU { r: 255, g: 0, b: 0, a: 255 };
+    const BLUE: ColorU = ColorU { r: 0, g: 0, b: 255, a: 255 };
+
+    #[test]
+    fn test_gradient_object_bounding_box() {
+        let svg = r##"<linearGradient id="g" x2="0.5">
+            <stop offset="0" stop-color="#ff0000"/><stop offset="1" stop-color="#0000ff"/>
+        </linearGradient>"##;
+        let bounds = RectF::new(Vector2F::new(10.0, 20.0), Vector2F::new(40.0, 10.0));
+
+        // The gradient vector is relative to the bounds of the path, before its transform.
+        let paint = gradient_paint(svg, Transform2F::default(), bounds, 1.0);
+        let (from, to, stops) = linear_gradient(paint);
+        assert_endpoints((from, to), (Vector2F::new(10.0, 20.0), Vector2F::new(30.0, 20.0)));
+        assert_eq!(stops, vec![(0.0, RED), (1.0, BLUE)]);
+
+        let transform = Transform2F::from_translation(Vector2F::new(0.0, 5.0));
+        let (from, to, _) = linear_gradient(gradient_paint(svg, transform, bounds, 1.0));
+        assert_endpoints((from, to), (Vector2F::new(10.0, 25.0), Vector2F::new(30.0, 25.0)));
+
+        // Paths without area aren't drawn at all.
+        let line_bounds = RectF::new(Vector2F::new(10.0, 20.0), Vector2F::new(40.0, 0.0));
+        assert!(gradient_paint(svg, Transform2F::default(), line_bounds, 1.0).is_none());
+    }
+
+    #[test]
+    fn test_gradient_transform() {
+        let bounds = RectF::new(Vector2F::new(10.0, 20.0), Vector2F::new(40.0, 10.0));
+
+        // The gradient transform applies before the path's transform.
+        let svg = r##"<linearGradient id="g" x2="10" gradientUnits="userSpaceOnUse"
+                                      gradientTransform="translate(5 0) scale(2)">
+            <stop offset="0" stop-color="#ff0000"/><stop offset="1" stop-color="#0000ff"/>
+        </linearGradient>"##;
+        let transform = Transform2F::from_translation(Vector2F::new(0.0, 3.0));
+        let (from, to, _) = linear_gradient(gradient_paint(svg, transform, bounds, 1.0));
+        assert_endpoints((from, to), (Vector2F::new(5.0, 3.0), Vector2F::new(25.0, 3.0)));
+
+        // With `objectBoundingBox` units, it also applies before mapping to the bounds.
+        let svg = r##"<linearGradient id="g" gradientTransform="rotate(90)">
+            <stop offset="0" stop-color="#ff0000"/><stop offset="1" stop-color="#0000ff"/>
+        </linearGradient>"##;
+        let (from, to, _) = linear_gradient(gradient_paint(svg,
+                                                           Transform2F::default(),
+                                                           bounds,
+                                                           1.0));
+        assert_endpoints((from, to), (Vector2F::new(10.0, 20.0), Vector2F::new(10.0, 30.0)));
+    }
+
+    #[test]
+    fn test_gradient_spread_methods() {
+        let gradient = |spread_method: &str| {
+            let svg = format!("<linearGradient id=\"g\" x2=\"25\" \
+                               gradientUnits=\"userSpaceOnUse\" spreadMethod=\"{}\">\
+                               <stop offset=\"0\" stop-color=\"#ff0000\"/>\
+                               <stop offset=\"1\" stop-color=\"#0000ff\"/></linearGradient>",
+                              spread_method);
+            let bounds = RectF::new(Vector2F::default(), Vector2F::splat(100.0));
+            linear_gradient(gradient_paint(&svg, Transform2F::default(), bounds, 1.0))
+        };
+
+        let (from, to, stops) = gradient("pad");
+        assert_endpoints((from, to), (Vector2F::default(), Vector2F::new(25.0, 0.0)));
+        assert_eq!(stops, vec![(0.0, RED), (1.0, BLUE)]);
+
+        // The other methods stretch the gradient over the view box, one period per quarter. Each
+        // period starts just after the previous one ends.
+        let offsets = [0.0, 0.25, 0.25, 0.5, 0.5, 0.75, 0.75, 1.0];
+        let assert_offsets = |stops: &[(f32, ColorU)]| {
+            assert_eq!(stops.len(), offsets.len());
+            for (stop, &offset) in stops.iter().zip(offsets.iter()) {
+                assert!(stop.0 >= offset && stop.0 - offset < 0.001, "{:?}", stops);
+            }
+        };
+        let (from, to, stops) = gradient("repeat");
+        assert_endpoints((from, to), (Vector2F::default(), Vector2F::new(100.0, 0.0)));
+        assert_offsets(&stops);
+        assert_eq!(stops.iter().map(|stop| stop.1).collect::<Vec<_>>(),
+                   vec![RED, BLUE, RED, BLUE, RED, BLUE, RED, BLUE]);
+
+        let (from, to, stops) = gradient("reflect");
+        assert_endpoints((from, to), (Vector2F::default(), Vector2F::new(100.0, 0.0)));
+        assert_offsets(&stops);
+        assert_eq!(stops.iter().map(|stop| stop.1).collect::<Vec<_>>(),
+                   vec![RED, BLUE, BLUE, RED, RED, BLUE, BLUE, RED]);
+    }
+
+    #[test]
+    fn test_gradient_stop_opacity() {
+        let svg = r##"<linearGradient id="g">
+            <stop offset="0" stop-color="#ff0000"/>
+            <stop offset="1" stop-color="#0000ff" stop-opacity="0.2"/>
+        </linearGradient>"##;
+        let bounds = RectF::new(Vector2F::default(), Vector2F::splat(100.0));
+        let paint = gradient_paint(svg, Transform2F::default(), bounds, 1.0);
+        let (_, _, stops) = linear_gradient(paint);
+        assert_eq!(stops, vec![(0.0, RED), (1.0, ColorU::new(0, 0, 255, 51))]);
+
+        // The opacity of the path multiplies that of the stops.
+        let paint = gradient_paint(svg, Transform2F::default(), bounds, 0.5);
+        let (_, _, stops) = linear_gradient(paint);
+        assert_eq!(stops, vec![(0.0, ColorU::new(255, 0, 0, 128)),
+                               (1.0, ColorU::new(0, 0, 255, 26))]);
+    }
+
+    #[test]
+    fn test_zero_length_gradient() {
+        // A gradient vector of zero length paints the color of the last stop.
+        let svg = r##"<linearGradient id="g" x1="5" x2="5" gradientUnits="userSpaceOnUse">
+            <stop offset="0" stop-color="#ff0000"/><stop offset="1" stop-color="#0000ff"/>
+        </linearGradient>"##;
+        let bounds = RectF::new(Vector2F::default(), Vector2F::splat(100.0));
+        match gradient_paint(svg, Transform2F::default(), bounds, 0.5) {
+            Some(Paint::Color(color)) => assert_eq!(color, ColorU::new(0, 0, 255, 128)),
+            paint => panic!("expected a color, got {:?}", paint),
+        }
+    }
+
+    // Returns the sizes of the render targets that the scene draws into, in order.
+    fn render_target_sizes(scene: &Scene) -> Vec<Vector2I> {
+        scene.display_list().iter().filter_map(|display_item| {
+            match *display_item {
+                DisplayItem::PushRenderTarget(render_target_id) => {
+                    scene.get_render_target(render_target_id).size()
+                }
+                _ => None,
+            }
+        }).collect()
+    }
+
+    // Returns the bounds of the paths drawn into render targets, which for these documents are
+    // the contents of the pattern.
+    fn pattern_content_bounds(scene: &Scene) -> Vec<RectF> {
+        let mut depth = 0;
+        let mut bounds = vec![];
+        for display_item in scene.display_list() {
+            match *display_item {
+                DisplayItem::PushRenderTarget(_) => depth += 1,
+                DisplayItem::PopRenderTarget => depth -= 1,
+                DisplayItem::DrawPaths { start_index, end_index } if depth > 0 => {
+                    for path_index in start_index..end_index {
+                        bounds.push(scene.get_path(path_index).outline().bounds());
+                    }
+                }
+                DisplayItem::DrawPaths { .. } | DisplayItem::DrawRenderTarget { .. } => {}
+            }
+        }
+        bounds
+    }
+
+    fn assert_rect_eq(actual: RectF, expected: RectF) {
+        assert!((actual.origin() - expected.origin()).length() < 0.001 &&
+                (actual.size() - expected.size()).length() < 0.001,
+                "{:?} != {:?}", actual, expected);
+    }
+
+    // Builds a 40x20 document filled with the pattern with the ID `p`.
+    fn build_pattern(pattern: &str, fill_opacity: f32) -> BuiltSVG {
+        let svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"40\" height=\"20\">\
+                           {}<rect width=\"40\" height=\"20\" fill=\"url(#p)\" \
+                           fill-opacity=\"{}\"/></svg>",
+                          pattern,
+                          fill_opacity);
+        let tree = Tree::from_str(&svg, &Options::default()).unwrap();
+        BuiltSVG::from_tree(&tree)
+    }
+
+    #[test]
+    fn test_pattern_single_tile() {
+        // The tile lines up with the pixel grid, so it's drawn once and repeated by the pattern.
+        let built_svg = build_pattern(r##"
+            <pattern id="p" width="10" height="10" patternUnits="userSpaceOnUse">
+                <rect width="5" height="10" fill="#ff0000"/>
+            </pattern>"##, 1.0);
+        assert!(built_svg.result_flags.is_empty());
+        assert_eq!(render_target_sizes(&built_svg.scene), vec![Vector2I::splat(10)]);
+        let content_bounds = pattern_content_bounds(&built_svg.scene);
+        assert_eq!(content_bounds.len(), 1);
+        assert_rect_eq(content_bounds[0],
+                       RectF::new(Vector2F::default(), Vector2F::new(5.0, 10.0)));
+
+        let path = built_svg.scene.get_path(built_svg.scene.path_count() as u32 - 1);
+        match *built_svg.scene.get_paint(path.paint()) {
+            Paint::Pattern(ref pattern) => {
+                assert_eq!(pattern.repeat, Repeat::X | Repeat::Y);
+                assert!(matches!(pattern.source, PatternSource::RenderTarget(_)));
+            }
+            ref paint => panic!("expected a pattern, got {:?}", paint),
+        }
+    }
+
+    #[test]
+    fn test_pattern_per_tile() {
+        // A rotated tile doesn't line up with the pixel grid, so each tile that the path covers
+        // is drawn into a render target the size of the path. Turning the tile sideways turns its
+        // column into a row.
+        let built_svg = build_pattern(r##"
+            <pattern id="p" width="10" height="10" patternUnits="userSpaceOnUse"
+                     patternTransform="rotate(90)">
+                <rect width="5" height="10" fill="#ff0000"/>
+            </pattern>"##, 1.0);
+        assert!(built_svg.result_flags.is_empty());
+        assert_eq!(render_target_sizes(&built_svg.scene), vec![Vector2I::new(40, 20)]);
+        // Two columns of four tiles cover the path, give or take rounding at the edges.
+        let content_bounds = pattern_content_bounds(&built_svg.scene);
+        assert!(content_bounds.len() >= 8, "{:?}", content_bounds);
+        for bounds in content_bounds {
+            assert!((bounds.size() - Vector2F::new(10.0, 5.0)).length() < 0.001, "{:?}", bounds);
+            let row = bounds.origin_y() / 10.0;
+            assert!((row - row.round()).abs() < 0.001, "{:?}", bounds);
+        }
+    }
+
+    #[test]
+    fn test_truncated_pattern_tiles() {
+        let pattern = |size: f32| {
+            build_pattern(&format!("<pattern id=\"p\" width=\"{}\" height=\"{}\" \
+                                    patternUnits=\"userSpaceOnUse\" \
+                                    patternTransform=\"rotate(45)\"><rect width=\"0.1\" \
+                                    height=\"0.1\" fill=\"#ff0000\"/></pattern>",
+                                   size,
+                                   size),
+                          1.0)
+        };
+        assert!(pattern(1.0).result_flags.is_empty());
+        assert_eq!(pattern(0.5).result_flags, BuildResultFlags::TRUNCATED_PATTERN_TILES);
+        assert_eq!(pattern_content_bounds(&pattern(0.5).scene).len(), 64 * 64);
+    }
+
+    #[test]
+    fn test_pattern_preserve_aspect_ratio() {
+        // The view box is twice as tall as the tile, so it's scaled down by half to fit, or
+        // overflows the tile when sliced.
+        let content_bounds = |preserve_aspect_ratio: &str| {
+            let built_svg = build_pattern(&format!("<pattern id=\"p\" width=\"10\" height=\"10\" \
+                                                    patternUnits=\"userSpaceOnUse\" \
+                                                    viewBox=\"0 0 10 20\" \
+                                                    preserveAspectRatio=\"{}\"><rect \
+                                                    width=\"10\" height=\"20\" \
+                                                    fill=\"#ff0000\"/></pattern>",
+                                                   preserve_aspect_ratio),
+                                          1.0);
+            let content_bounds = pattern_content_bounds(&built_svg.scene);
+            assert_eq!(content_bounds.len(), 1);
+            content_bounds[0]
+        };
+        let rect = |x, y, width, height| {
+            RectF::new(Vector2F::new(x, y), Vector2F::new(width, height))
+        };
+
+        assert_rect_eq(content_bounds("xMinYMin meet"), rect(0.0, 0.0, 5.0, 10.0));
+        assert_rect_eq(content_bounds("xMidYMid meet"), rect(2.5, 0.0, 5.0, 10.0));
+        assert_rect_eq(content_bounds("xMaxYMax meet"), rect(5.0, 0.0, 5.0, 10.0));
+        assert_rect_eq(content_bounds("xMidYMid slice"), rect(0.0, -5.0, 10.0, 20.0));
+        assert_rect_eq(content_bounds("none"), rect(0.0, 0.0, 10.0, 10.0));
+    }
+
+    #[test]
+    fn test_pattern_opacity() {
+        // The contents are drawn into a layer that's faded as a whole, so that overlapping
+        // contents don't show through each other, and the pattern paint itself stays opaque.
+        let cases = [("", Vector2I::splat(10)), ("rotate(90)", Vector2I::new(40, 20))];
+        for &(transform, size) in &cases {
+            let built_svg = build_pattern(&format!("<pattern id=\"p\" width=\"10\" height=\"10\" \
+                                                    patternUnits=\"userSpaceOnUse\" \
+                                                    patternTransform=\"{}\"><rect width=\"10\" \
+                                                    height=\"10\" fill=\"#ff0000\"/><rect \
+                                                    width=\"10\" height=\"10\" \
+                                                    fill=\"#0000ff\"/></pattern>",
+                                                   transform),
+                                          0.5);
+            let scene = &built_svg.scene;
+            assert!(built_svg.result_flags.is_empty());
+            assert_eq!(render_target_sizes(scene), vec![size, size]);
+
+            let display_list = scene.display_list();
+            let layer = match display_list[display_list.len() - 3] {
+                DisplayItem::DrawRenderTarget { render_target, effects } => {
+                    match effects.filter {
+                        Filter::Opacity { opacity, mask: None, .. } => assert_eq!(opacity, 0.5),
+                        filter => panic!("expected an opacity filter, got {:?}", filter),
+                    }
+                    render_target
+                }
+                ref display_item => panic!("expected a layer, got {:?}", display_item),
+            };
+            assert!(matches!(display_list[1], DisplayItem::PushRenderTarget(id) if id == layer));
+            assert!(matches!(display_list[display_list.len() - 2], DisplayItem::PopRenderTarget));
+
+            let pattern_render_target = match display_list[0] {
+                DisplayItem::PushRenderTarget(render_target_id) => render_target_id,
+                ref display_item => panic!("expected the pattern, got {:?}", display_item),
+            };
+            let path = scene.get_path(scene.path_count() as u32 - 1);
+            let source = PatternSource::RenderTarget(pattern_render_target);
+            assert_eq!(*scene.get_paint(path.paint()),
+                       Paint::Pattern(Pattern::new(source, Repeat::X | Repeat::Y)));
+        }
+    }
+}
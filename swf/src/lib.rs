@@ -8,23 +8,41 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 use std::ops::Add;
 use pathfinder_color::{ColorF, ColorU};
 use pathfinder_content::effects::BlendMode;
 use pathfinder_content::fill::FillRule;
 use pathfinder_content::outline::{Outline, Contour};
+use pathfinder_content::pattern::Image;
 use pathfinder_content::stroke::{OutlineStrokeToFill, StrokeStyle};
+use pathfinder_geometry::transform2d::Transform2F;
 use pathfinder_geometry::vector::Vector2F;
-use pathfinder_renderer::scene::{DrawPath, Scene};
+use pathfinder_renderer::paint::Paint;
+use pathfinder_renderer::scene::{ClipPath, ClipPathId, DrawPath, Scene};
 
 use swf_types::tags::SetBackgroundColor;
-use swf_types::{Tag, SRgb8, Movie};
+use swf_types::{Matrix, Tag, SRgb8, Movie};
 
-use crate::shapes::{GraphicLayers, PaintOrLine};
+use crate::morph::MorphShape;
+use crate::paint::BitmapFillCache;
+use crate::shapes::{GraphicLayers, PaintOrLine, StyleLayer, SwfFill};
+use crate::text::{Font, Text};
+use crate::timeline::{ColorTransform, PlacementInfo, Timeline, TimelineBuilder};
 
+mod morph;
+mod paint;
 mod shapes;
+mod text;
+mod timeline;
 
-type SymbolId = u16;
+pub type SymbolId = u16;
+
+// The maximum depth that sprites can be nested to when drawing. This guards against sprites that
+// place themselves.
+const MAX_SPRITE_NESTING: u32 = 64;
 
 // In swf, most values are specified in a fixed point format known as "twips" or twentieths of
 // a pixel.  We store twips in their integer form, as if we were to convert them to floating point
@@ -71,14 +89,34 @@ impl Add for Point2<Twips> {
     }
 }
 
+// Scale and skew terms of swf matrices are 16.16 fixed point, while translations are in twips.
+pub(crate) fn matrix_to_transform(matrix: &Matrix) -> Transform2F {
+    Transform2F::row_major(matrix.scale_x.epsilons as f32 / 65536.0,
+                           matrix.rotate_skew1.epsilons as f32 / 65536.0,
+                           matrix.rotate_skew0.epsilons as f32 / 65536.0,
+                           matrix.scale_y.epsilons as f32 / 65536.0,
+                           Twips(matrix.translate_x).as_f32(),
+                           Twips(matrix.translate_y).as_f32())
+}
+
 enum Symbol {
     Graphic(GraphicLayers),
-    // Timeline, // TODO(jon)
+    /// A shape that tweens between a start and an end shape, according to the ratio of each
+    /// placement.
+    MorphShape(MorphShape),
+    /// A bitmap for use in bitmap fills, or `None` if it couldn't be decoded.
+    Bitmap(Option<Image>),
+    /// A sprite (or movie clip) with its own timeline, which advances independently of the
+    /// timeline that it's placed on.
+    Sprite(Timeline),
+    /// An embedded font, whose glyphs are drawn by text symbols.
+    Font(Font),
+    /// Static text, or the initial contents of a text field.
+    Text(Text),
 }
 
 pub struct Stage {
-    // TODO(jon): Support some kind of lazy frames iterator.
-    // frames: Timeline,
+    frames: Timeline,
     background_color: SRgb8,
     width: i32,
     height: i32,
@@ -93,6 +131,11 @@ impl Stage {
         self.height
     }
 
+    /// The number of frames in the main timeline. This is always at least 1.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
     pub fn background_color(&self) -> ColorF {
         ColorU {
             r: self.background_color.r,
@@ -104,107 +147,730 @@ impl Stage {
 }
 
 
-pub struct SymbolLibrary(Vec<Symbol>);
+pub struct SymbolLibrary {
+    // Symbols in the order they were defined.
+    symbols: Vec<Symbol>,
+    indices: HashMap<SymbolId, usize>,
+    bitmap_fills: BitmapFillCache,
+}
 
 impl SymbolLibrary {
-    fn add_symbol(&mut self, symbol: Symbol) {
-        self.0.push(symbol);
+    fn new() -> SymbolLibrary {
+        SymbolLibrary {
+            symbols: vec![],
+            indices: HashMap::new(),
+            bitmap_fills: BitmapFillCache::default(),
+        }
+    }
+
+    fn add_symbol(&mut self, symbol_id: SymbolId, symbol: Symbol) -> Result<(), SwfError> {
+        // Id 0 is reserved, and ids must be unique.
+        if symbol_id == 0 || self.indices.contains_key(&symbol_id) {
+            return Err(SwfError::BadSymbolId(symbol_id));
+        }
+        self.indices.insert(symbol_id, self.symbols.len());
+        self.symbols.push(symbol);
+        Ok(())
     }
 
     fn symbols(&self) -> &Vec<Symbol> {
-        &self.0
+        &self.symbols
     }
+
+    fn symbol(&self, symbol_id: SymbolId) -> Option<&Symbol> {
+        self.indices.get(&symbol_id).map(|&index| &self.symbols[index])
+    }
+
+    /// Returns the outlines of a morph shape at the given ratio, from 0 for the start shape to 1
+    /// for the end shape, along with the paint of each one.
+    ///
+    /// Outlines are in the coordinate space of the shape, and strokes are converted to fills.
+    /// Returns `None` if the symbol isn't a morph shape.
+    pub fn morph_shape_paths(&self, symbol_id: SymbolId, ratio: f32)
+                             -> Option<Vec<(Outline, Paint)>> {
+        match self.symbol(symbol_id) {
+            Some(Symbol::MorphShape(morph_shape)) => {
+                let graphic = morph_shape.shape_at_ratio(ratio, &mut vec![]).ok()?;
+                Some(build_graphic_paths(self,
+                                         &graphic,
+                                         &Transform2F::default(),
+                                         &ColorTransform::identity()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The result of importing a SWF movie.
+pub struct BuiltSWF {
+    pub library: SymbolLibrary,
+    pub stage: Stage,
+    /// Problems that didn't stop the import, but that may make the movie render incorrectly.
+    pub warnings: Vec<SwfWarning>,
+}
+
+/// A problem that stops a SWF movie from being imported.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SwfError {
+    /// A symbol was defined with the reserved id 0, or with an id that's already in use.
+    BadSymbolId(SymbolId),
+    /// A shape selected a fill or line style that it doesn't define.
+    BadStyleId { symbol_id: SymbolId, style_id: usize },
+    /// A shape contains an edge that doesn't start from a known position.
+    MalformedEdges(SymbolId),
 }
 
-pub fn process_swf_tags(movie: &Movie) -> (SymbolLibrary, Stage) {
-    let mut symbol_library = SymbolLibrary(Vec::new());
+/// A problem that was worked around while importing a SWF movie.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SwfWarning {
+    /// A bitmap is in a format that we can't decode. Fills that use it aren't drawn.
+    UnsupportedBitmap(SymbolId),
+    /// A shape uses a style that can only be approximated, such as a line with different start
+    /// and end caps, or a fill with a bitmap that isn't available.
+    UnsupportedStyle(SymbolId),
+    /// Some fills of a shape couldn't be assembled into closed outlines.
+    UnclosedFill(SymbolId),
+    /// A placement refers to a symbol that was never defined, or whose kind isn't supported.
+    UnknownSymbol(SymbolId),
+    /// A text field uses a device font, or an embedded font without layout information, so its
+    /// text isn't drawn.
+    UnsupportedFont(SymbolId),
+}
+
+impl Display for SwfError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            SwfError::BadSymbolId(symbol_id) => write!(formatter, "bad symbol id {}", symbol_id),
+            SwfError::BadStyleId { symbol_id, style_id } => {
+                write!(formatter, "shape {} selects undefined style {}", symbol_id, style_id)
+            }
+            SwfError::MalformedEdges(symbol_id) => {
+                write!(formatter, "shape {} has malformed edges", symbol_id)
+            }
+        }
+    }
+}
+
+impl Error for SwfError {}
+
+impl Display for SwfWarning {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            SwfWarning::UnsupportedBitmap(symbol_id) => {
+                write!(formatter, "bitmap {} has an unsupported format", symbol_id)
+            }
+            SwfWarning::UnsupportedStyle(symbol_id) => {
+                write!(formatter, "shape {} uses unsupported styles", symbol_id)
+            }
+            SwfWarning::UnclosedFill(symbol_id) => {
+                write!(formatter, "shape {} has unclosed fills", symbol_id)
+            }
+            SwfWarning::UnknownSymbol(symbol_id) => {
+                write!(formatter, "unknown symbol {} was placed", symbol_id)
+            }
+            SwfWarning::UnsupportedFont(symbol_id) => {
+                write!(formatter, "text field {} uses an unsupported font", symbol_id)
+            }
+        }
+    }
+}
+
+pub fn process_swf_tags(movie: &Movie) -> Result<BuiltSWF, SwfError> {
+    let mut symbol_library = SymbolLibrary::new();
+    let mut warnings = vec![];
     let stage_width = Twips(movie.header.frame_size.x_max);
     let stage_height = Twips(movie.header.frame_size.y_max);
     // let num_frames = movie.header.frame_count;
+    let mut timeline = TimelineBuilder::new();
+    let mut jpeg_tables = None;
 
-    let mut stage = Stage {
-        // frames: Timeline(Vec::new()), // TODO(jon)
-        background_color: SRgb8 {
-            r: 255,
-            g: 255,
-            b: 255
-        },
-        width: stage_width.as_f32() as i32,
-        height: stage_height.as_f32() as i32,
+    let mut background_color = SRgb8 {
+        r: 255,
+        g: 255,
+        b: 255
     };
 
     for tag in &movie.tags {
         match tag {
             Tag::SetBackgroundColor(SetBackgroundColor { color }) => {
-                stage.background_color = *color;
+                background_color = *color;
             },
             Tag::DefineShape(shape) => {
-                symbol_library.add_symbol(Symbol::Graphic(shapes::decode_shape(shape)));
-                // We will assume that symbol ids just go up, and are 1 based.
-                let symbol_id: SymbolId = shape.id;
-                debug_assert!(symbol_id as usize == symbol_library.0.len());
+                let graphic = shapes::decode_shape(shape, &mut warnings)?;
+                if !has_available_bitmap_fills(&symbol_library, &graphic) {
+                    warnings.push(SwfWarning::UnsupportedStyle(shape.id));
+                }
+                symbol_library.add_symbol(shape.id, Symbol::Graphic(graphic))?;
+            }
+            Tag::DefineMorphShape(shape) => {
+                let morph_shape = morph::decode_morph_shape(shape, &mut warnings)?;
+                symbol_library.add_symbol(shape.id, Symbol::MorphShape(morph_shape))?;
+            }
+            Tag::DefineJpegTables(tables) => jpeg_tables = Some(&tables.data[..]),
+            Tag::DefineBitmap(bitmap) => {
+                let image = paint::decode_bitmap(bitmap, jpeg_tables);
+                if image.is_none() {
+                    warnings.push(SwfWarning::UnsupportedBitmap(bitmap.id));
+                }
+                symbol_library.add_symbol(bitmap.id, Symbol::Bitmap(image))?;
+            }
+            Tag::DefineFont(font) => {
+                let font_symbol = text::decode_font(font, &mut warnings)?;
+                symbol_library.add_symbol(font.id, Symbol::Font(font_symbol))?;
+            }
+            Tag::DefineGlyphFont(font) => {
+                let font_symbol = text::decode_glyph_font(font, &mut warnings)?;
+                symbol_library.add_symbol(font.id, Symbol::Font(font_symbol))?;
+            }
+            Tag::DefineText(text) => {
+                let text_symbol = text::decode_text(text, &symbol_library, &mut warnings);
+                symbol_library.add_symbol(text.id, Symbol::Text(text_symbol))?;
+            }
+            Tag::DefineDynamicText(text) => {
+                let text_symbol = text::decode_edit_text(text, &symbol_library, &mut warnings);
+                symbol_library.add_symbol(text.id, Symbol::Text(text_symbol))?;
+            }
+            Tag::DefineSprite(sprite) => {
+                let mut sprite_timeline = TimelineBuilder::new();
+                for tag in &sprite.tags {
+                    process_control_tag(tag, &symbol_library, &mut sprite_timeline, &mut warnings);
+                }
+                symbol_library.add_symbol(sprite.id, Symbol::Sprite(sprite_timeline.build()))?;
+            }
+            tag => process_control_tag(tag, &symbol_library, &mut timeline, &mut warnings),
+        }
+    }
+
+    let stage = Stage {
+        frames: timeline.build(),
+        background_color,
+        width: stage_width.as_f32() as i32,
+        height: stage_height.as_f32() as i32,
+    };
+    Ok(BuiltSWF { library: symbol_library, stage, warnings })
+}
+
+// Applies a tag that manipulates the display list of a timeline.
+fn process_control_tag(tag: &Tag,
+                       library: &SymbolLibrary,
+                       timeline: &mut TimelineBuilder,
+                       warnings: &mut Vec<SwfWarning>) {
+    match tag {
+        Tag::PlaceObject(place_object) => {
+            if let Some(character_id) = place_object.character_id {
+                let warning = SwfWarning::UnknownSymbol(character_id);
+                if library.symbol(character_id).is_none() && !warnings.contains(&warning) {
+                    warnings.push(warning);
+                }
             }
-            _ => ()
+            timeline.place_object(place_object)
         }
+        Tag::RemoveObject(remove_object) => timeline.remove_object(remove_object),
+        Tag::ShowFrame => timeline.show_frame(),
+        _ => ()
     }
-    (symbol_library, stage)
 }
 
-#[allow(irrefutable_let_patterns)]
+// Returns false if any bitmap fills of the graphic refer to bitmaps that aren't available.
+fn has_available_bitmap_fills(library: &SymbolLibrary, graphic: &GraphicLayers) -> bool {
+    graphic.layers().iter().all(|style_layer| {
+        match *style_layer.fill() {
+            SwfFill::Bitmap(ref bitmap) => {
+                matches!(library.symbol(bitmap.bitmap_id), Some(Symbol::Bitmap(Some(_))))
+            }
+            SwfFill::Color(_) | SwfFill::Gradient(_) => true,
+        }
+    })
+}
+
 pub fn draw_paths_into_scene(library: &SymbolLibrary, scene: &mut Scene) {
     for symbol in library.symbols() {
-        // NOTE: Right now symbols only contain graphics.
         if let Symbol::Graphic(graphic) = symbol {
-            for style_layer in graphic.layers() {
-                let mut path = Outline::new();
-                let paint_id = scene.push_paint(&style_layer.fill());
+            draw_graphic_into_scene(library,
+                                    graphic,
+                                    &Transform2F::default(),
+                                    &ColorTransform::identity(),
+                                    None,
+                                    scene);
+        }
+    }
+}
 
-                for shape in style_layer.shapes() {
-                    let mut contour = Contour::new();
-                    let Point2 { x, y } = shape.outline.first().unwrap().from.as_f32();
-                    contour.push_endpoint(Vector2F::new(x, y));
-                    for segment in &shape.outline {
-                        let Point2 { x, y } = segment.to.as_f32();
-                        match segment.ctrl {
-                            Some(ctrl) => {
-                                let Point2 { x: ctrl_x, y: ctrl_y } = ctrl.as_f32();
-                                contour.push_quadratic(
-                                    Vector2F::new(ctrl_x, ctrl_y),
-                                    Vector2F::new(x, y)
-                                );
-                            }
-                            None => {
-                                contour.push_endpoint(Vector2F::new(x, y));
-                            },
-                        }
-                    }
-                    if shape.is_closed() {
-                        // NOTE: I'm not sure if this really does anything in this context,
-                        // since all our closed shapes already have coincident start and end points.
-                        contour.close();
-                    }
-                    path.push_contour(contour);
+/// Draws the display list of the given frame of the main timeline into the scene.
+///
+/// Frame indices are 0 based. Nothing is drawn if the frame index is out of range.
+pub fn draw_frame_into_scene(library: &SymbolLibrary,
+                             stage: &Stage,
+                             frame_index: usize,
+                             scene: &mut Scene) {
+    if stage.frames.frame(frame_index).is_none() {
+        return;
+    }
+
+    let state = DrawState {
+        transform: Transform2F::default(),
+        color_transform: ColorTransform::identity(),
+        clip_path: None,
+        nesting: 0,
+    };
+    draw_display_list(library, &stage.frames, frame_index, &state, scene);
+}
+
+// The state inherited from the placement of the timeline being drawn.
+struct DrawState {
+    transform: Transform2F,
+    color_transform: ColorTransform,
+    clip_path: Option<ClipPathId>,
+    nesting: u32,
+}
+
+// Draws the placements of the current frame of a timeline that has played `elapsed` frames.
+fn draw_display_list(library: &SymbolLibrary,
+                     timeline: &Timeline,
+                     elapsed: usize,
+                     state: &DrawState,
+                     scene: &mut Scene) {
+    let frame = timeline.looped_frame(elapsed);
+    // Clipping layers, innermost last, along with the depth up to which each one applies.
+    let mut clip_layers: Vec<(u16, ClipPathId)> = vec![];
+
+    for placement in &frame.placements {
+        while let Some(&(clip_depth, _)) = clip_layers.last() {
+            if placement.depth <= clip_depth {
+                break;
+            }
+            clip_layers.pop();
+        }
+
+        let placement_transform = state.transform * placement.transform;
+
+        if let Some(clip_depth) = placement.clip_depth {
+            let mut clip_outline = Outline::new();
+            push_clip_contours(library,
+                               placement,
+                               placement.age(elapsed, timeline.len()),
+                               &placement_transform,
+                               state.nesting,
+                               &mut clip_outline);
+            // FIXME: Nested clipping layers should intersect, but we can only attach one
+            // clip path to each draw path, so the innermost one wins.
+            let clip_path = ClipPath::new(clip_outline, FillRule::EvenOdd, String::new());
+            clip_layers.push((clip_depth, scene.push_clip_path(clip_path)));
+            continue;
+        }
+
+        if !placement.visible {
+            continue;
+        }
+
+        let placement_state = DrawState {
+            transform: placement_transform,
+            color_transform: state.color_transform * placement.color_transform,
+            clip_path: clip_layers.last().map(|&(_, clip_path)| clip_path).or(state.clip_path),
+            nesting: state.nesting + 1,
+        };
+        match library.symbol(placement.symbol_id) {
+            Some(Symbol::Graphic(graphic)) => {
+                draw_graphic_into_scene(library,
+                                        graphic,
+                                        &placement_state.transform,
+                                        &placement_state.color_transform,
+                                        placement_state.clip_path,
+                                        scene);
+            }
+            Some(Symbol::MorphShape(morph_shape)) => {
+                if let Ok(graphic) = morph_shape.shape_at_ratio(placement.morph_ratio(),
+                                                                &mut vec![]) {
+                    draw_graphic_into_scene(library,
+                                            &graphic,
+                                            &placement_state.transform,
+                                            &placement_state.color_transform,
+                                            placement_state.clip_path,
+                                            scene);
+                }
+            }
+            Some(Symbol::Text(text)) => {
+                for (outline, glyph) in text.glyphs(library) {
+                    draw_graphic_into_scene(library,
+                                            outline,
+                                            &(placement_state.transform * glyph.transform),
+                                            &(placement_state.color_transform *
+                                              ColorTransform::fill(glyph.color)),
+                                            placement_state.clip_path,
+                                            scene);
                 }
+            }
+            Some(Symbol::Sprite(sprite_timeline)) if state.nesting < MAX_SPRITE_NESTING => {
+                // Sprites play for as long as they've been placed, even if their parent loops.
+                draw_display_list(library,
+                                  sprite_timeline,
+                                  placement.age(elapsed, timeline.len()),
+                                  &placement_state,
+                                  scene);
+            }
+            Some(Symbol::Sprite(_)) | Some(Symbol::Bitmap(_)) | Some(Symbol::Font(_)) |
+                None => {}
+        }
+    }
+}
 
-                if let PaintOrLine::Line(line) = style_layer.kind() {
-                    let mut stroke_to_fill = OutlineStrokeToFill::new(&path, StrokeStyle {
-                        line_width: line.width.as_f32(),
-                        line_cap: line.cap,
-                        line_join: line.join,
-                    });
-                    stroke_to_fill.offset();
-                    path = stroke_to_fill.into_outline();
+// Adds the filled areas of a clipping layer to the clip outline, in the coordinate space of the
+// stage. `age` is the number of frames that the placement has been on the display list.
+fn push_clip_contours(library: &SymbolLibrary,
+                      placement: &PlacementInfo,
+                      age: usize,
+                      transform: &Transform2F,
+                      nesting: u32,
+                      clip_outline: &mut Outline) {
+    match library.symbol(placement.symbol_id) {
+        Some(Symbol::Graphic(graphic)) => {
+            push_graphic_clip_contours(graphic, transform, clip_outline);
+        }
+        Some(Symbol::MorphShape(morph_shape)) => {
+            if let Ok(graphic) = morph_shape.shape_at_ratio(placement.morph_ratio(), &mut vec![]) {
+                push_graphic_clip_contours(&graphic, transform, clip_outline);
+            }
+        }
+        Some(Symbol::Text(text)) => {
+            for (outline, glyph) in text.glyphs(library) {
+                push_graphic_clip_contours(outline, &(*transform * glyph.transform), clip_outline);
+            }
+        }
+        Some(Symbol::Sprite(timeline)) if nesting < MAX_SPRITE_NESTING => {
+            for child in &timeline.looped_frame(age).placements {
+                if child.clip_depth.is_none() && child.visible {
+                    push_clip_contours(library,
+                                       child,
+                                       child.age(age, timeline.len()),
+                                       &(*transform * child.transform),
+                                       nesting + 1,
+                                       clip_outline);
                 }
+            }
+        }
+        Some(Symbol::Sprite(_)) | Some(Symbol::Bitmap(_)) | Some(Symbol::Font(_)) | None => {}
+    }
+}
+
+fn push_graphic_clip_contours(graphic: &GraphicLayers,
+                              transform: &Transform2F,
+                              clip_outline: &mut Outline) {
+    for style_layer in graphic.layers() {
+        if let PaintOrLine::Paint(_) = style_layer.kind() {
+            let mut outline = build_style_layer_outline(style_layer);
+            outline.transform(transform);
+            for contour in outline.contours() {
+                clip_outline.push_contour(contour.clone());
+            }
+        }
+    }
+}
+
+fn draw_graphic_into_scene(library: &SymbolLibrary,
+                           graphic: &GraphicLayers,
+                           transform: &Transform2F,
+                           color_transform: &ColorTransform,
+                           clip_path: Option<ClipPathId>,
+                           scene: &mut Scene) {
+    for (path, paint) in build_graphic_paths(library, graphic, transform, color_transform) {
+        let paint_id = scene.push_paint(&paint);
+        scene.push_path(DrawPath::new(
+            path,
+            paint_id,
+            clip_path,
+            FillRule::EvenOdd,
+            BlendMode::SrcOver,
+            String::new()
+        ));
+    }
+}
+
+// Builds the filled outline of each style layer of a graphic, transformed into scene space, along
+// with its paint. Layers without a paint are skipped.
+fn build_graphic_paths(library: &SymbolLibrary,
+                       graphic: &GraphicLayers,
+                       transform: &Transform2F,
+                       color_transform: &ColorTransform)
+                       -> Vec<(Outline, Paint)> {
+    let mut paths = vec![];
+    for style_layer in graphic.layers() {
+        let mut path = build_style_layer_outline(style_layer);
+
+        if let PaintOrLine::Line(line) = style_layer.kind() {
+            let mut stroke_to_fill = OutlineStrokeToFill::new(&path, StrokeStyle {
+                line_width: line.width.as_f32(),
+                line_cap: line.cap,
+                line_join: line.join,
+            });
+            stroke_to_fill.offset();
+            path = stroke_to_fill.into_outline();
+        }
+
+        path.transform(transform);
+
+        let paint = match paint::resolve_paint(library,
+                                               style_layer.fill(),
+                                               transform,
+                                               color_transform,
+                                               path.bounds()) {
+            Some(paint) => paint,
+            None => continue,
+        };
+        paths.push((path, paint));
+    }
+    paths
+}
 
-                scene.push_path(DrawPath::new(
-                    path,
-                    paint_id,
-                    None,
-                    FillRule::EvenOdd,
-                    BlendMode::SrcOver,
-                    String::new()
-                ));
+fn build_style_layer_outline(style_layer: &StyleLayer) -> Outline {
+    let mut path = Outline::new();
+    for shape in style_layer.shapes() {
+        let mut contour = Contour::new();
+        let Point2 { x, y } = shape.outline.first().unwrap().from.as_f32();
+        contour.push_endpoint(Vector2F::new(x, y));
+        for segment in &shape.outline {
+            let Point2 { x, y } = segment.to.as_f32();
+            match segment.ctrl {
+                Some(ctrl) => {
+                    let Point2 { x: ctrl_x, y: ctrl_y } = ctrl.as_f32();
+                    contour.push_quadratic(
+                        Vector2F::new(ctrl_x, ctrl_y),
+                        Vector2F::new(x, y)
+                    );
+                }
+                None => {
+                    contour.push_endpoint(Vector2F::new(x, y));
+                },
             }
         }
+        if shape.is_closed() {
+            // NOTE: I'm not sure if this really does anything in this context,
+            // since all our closed shapes already have coincident start and end points.
+            contour.close();
+        }
+        path.push_contour(contour);
+    }
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shapes::{self, GraphicLayers};
+    use crate::timeline::test::place_object;
+    use crate::timeline::{ColorTransform, Timeline, TimelineBuilder};
+    use crate::SymbolId;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_renderer::scene::Scene;
+    use swf_types::{fill_styles, shape_records, FillStyle, Matrix, ShapeRecord, ShapeStyles};
+    use swf_types::fixed::Ufixed8P8;
+    use swf_types::tags::{DefineBitmap, DefineDynamicText, DefineShape};
+    use swf_types::text::TextAlignment;
+    use swf_types::{Header, ImageType, Movie, Rect, Shape, StraightSRgba8, Tag, Vector2D};
+    use super::{DrawState, SwfError, SwfWarning, Symbol, SymbolLibrary};
+
+    fn root_state() -> DrawState {
+        DrawState {
+            transform: Transform2F::default(),
+            color_transform: ColorTransform::identity(),
+            clip_path: None,
+            nesting: 0,
+        }
+    }
+
+    // The edges of a square with its upper left corner at the origin, `size` twips across,
+    // filled with the first fill style.
+    fn square_records(size: i32) -> Vec<ShapeRecord> {
+        let edge = |x, y| {
+            ShapeRecord::Edge(shape_records::Edge { delta: Vector2D { x, y }, control_delta: None })
+        };
+        vec![
+            ShapeRecord::StyleChange(shape_records::StyleChange {
+                move_to: Some(Vector2D { x: 0, y: 0 }),
+                left_fill: None,
+                right_fill: Some(1),
+                line_style: None,
+                new_styles: None,
+            }),
+            edge(size, 0),
+            edge(0, size),
+            edge(-size, 0),
+            edge(0, -size),
+        ]
+    }
+
+    // A red square with its upper left corner at the origin, `size` twips across.
+    fn square(size: i32) -> GraphicLayers {
+        let styles = ShapeStyles {
+            fill: vec![FillStyle::Solid(fill_styles::Solid {
+                color: StraightSRgba8 { r: 255, g: 0, b: 0, a: 255 },
+            })],
+            line: vec![],
+        };
+        shapes::decode_shape_records(1, &styles, &square_records(size), &mut vec![]).unwrap()
+    }
+
+    #[test]
+    fn test_clip_depth_layers() {
+        let mut library = SymbolLibrary::new();
+        library.add_symbol(1, Symbol::Graphic(square(2000))).unwrap();
+        library.add_symbol(2, Symbol::Graphic(square(1000))).unwrap();
+
+        // Depth 1 clips depths 2 to 4, and depth 2 clips depth 3 inside it.
+        let mut timeline = TimelineBuilder::new();
+        for &(depth, symbol_id, clip_depth) in &[(1, 1, Some(4)),
+                                                (2, 1, Some(3)),
+                                                (3, 2, None),
+                                                (4, 2, None),
+                                                (5, 2, None)] {
+            let mut place = place_object(depth, Some(symbol_id), false);
+            place.clip_depth = clip_depth;
+            timeline.place_object(&place);
+        }
+        timeline.show_frame();
+        let timeline = timeline.build();
+
+        let mut scene = Scene::new();
+        super::draw_display_list(&library, &timeline, 0, &root_state(), &mut scene);
+
+        // Clipping layers aren't drawn themselves. Each placement takes the innermost clipping
+        // layer that covers its depth.
+        let clip_paths: Vec<_> = scene.paths()
+                                      .map(|path| path.clip_path().map(|clip_path| clip_path.0))
+                                      .collect();
+        assert_eq!(clip_paths, [Some(1), Some(0), None]);
+    }
+
+    // A sprite that moves a square 100 pixels to the right on each frame.
+    fn moving_sprite(symbol_id: SymbolId, frame_count: i32) -> Timeline {
+        let mut timeline = TimelineBuilder::new();
+        for frame_index in 0..frame_count {
+            let mut place = place_object(1, Some(symbol_id), frame_index > 0);
+            place.matrix = Some(Matrix { translate_x: frame_index * 2000, ..Matrix::default() });
+            timeline.place_object(&place);
+            timeline.show_frame();
+        }
+        timeline.build()
+    }
+
+    #[test]
+    fn test_nested_sprite_keeps_playing_when_parent_loops() {
+        let mut library = SymbolLibrary::new();
+        library.add_symbol(1, Symbol::Graphic(square(100))).unwrap();
+        library.add_symbol(2, Symbol::Sprite(moving_sprite(1, 5))).unwrap();
+
+        // The parent sprite shows the child sprite on both of its frames.
+        let mut parent = TimelineBuilder::new();
+        parent.place_object(&place_object(1, Some(2), false));
+        parent.show_frame();
+        parent.show_frame();
+        library.add_symbol(3, Symbol::Sprite(parent.build())).unwrap();
+
+        let mut stage = TimelineBuilder::new();
+        stage.place_object(&place_object(1, Some(3), false));
+        for _ in 0..8 {
+            stage.show_frame();
+        }
+        let stage = stage.build();
+
+        for frame_index in 0..8 {
+            let mut scene = Scene::new();
+            super::draw_display_list(&library, &stage, frame_index, &root_state(), &mut scene);
+            let bounds = scene.paths().next().unwrap().outline().bounds();
+            assert_eq!(bounds.min_x(), (frame_index % 5) as f32 * 100.0);
+        }
+    }
+
+    fn movie(tags: Vec<Tag>) -> Movie {
+        let frame_size = Rect { x_min: 0, x_max: 2000, y_min: 0, y_max: 2000 };
+        Movie {
+            header: Header {
+                swf_version: 10,
+                frame_size,
+                frame_rate: Ufixed8P8::ONE,
+                frame_count: 1,
+            },
+            tags,
+        }
+    }
+
+    #[test]
+    fn test_import_warnings() {
+        let bitmap_fill = FillStyle::Bitmap(fill_styles::Bitmap {
+            bitmap_id: 1,
+            matrix: Matrix::default(),
+            repeating: false,
+            smoothed: true,
+        });
+        let shape = Shape {
+            initial_styles: ShapeStyles { fill: vec![bitmap_fill], line: vec![] },
+            records: square_records(100),
+        };
+        let text = DefineDynamicText {
+            id: 3,
+            bounds: Rect { x_min: 0, x_max: 2000, y_min: 0, y_max: 400 },
+            word_wrap: false,
+            multiline: false,
+            password: false,
+            readonly: true,
+            auto_size: false,
+            no_select: true,
+            border: false,
+            was_static: false,
+            html: false,
+            use_glyph_font: false,
+            font_id: None,
+            font_class: None,
+            font_size: Some(240),
+            color: None,
+            max_length: None,
+            align: TextAlignment::Left,
+            margin_left: 0,
+            margin_right: 0,
+            indent: 0,
+            leading: 0,
+            variable_name: None,
+            text: Some("Hello".to_owned()),
+        };
+        let tags = vec![
+            Tag::DefineBitmap(DefineBitmap {
+                id: 1,
+                width: 1,
+                height: 1,
+                media_type: ImageType::Png,
+                data: vec![0; 4],
+            }),
+            Tag::DefineShape(DefineShape {
+                id: 2,
+                bounds: Rect { x_min: 0, x_max: 100, y_min: 0, y_max: 100 },
+                edge_bounds: None,
+                has_fill_winding: false,
+                has_non_scaling_strokes: false,
+                has_scaling_strokes: false,
+                shape,
+            }),
+            Tag::DefineDynamicText(text),
+            Tag::PlaceObject(place_object(1, Some(4), false)),
+            Tag::PlaceObject(place_object(2, Some(4), false)),
+        ];
+
+        // Each problem is reported once, even if it comes up again.
+        let built = super::process_swf_tags(&movie(tags)).unwrap();
+        assert_eq!(built.warnings, [SwfWarning::UnsupportedBitmap(1),
+                                    SwfWarning::UnsupportedStyle(2),
+                                    SwfWarning::UnsupportedFont(3),
+                                    SwfWarning::UnknownSymbol(4)]);
+    }
+
+    #[test]
+    fn test_symbol_ids_must_be_unique() {
+        let bitmap = Tag::DefineBitmap(DefineBitmap {
+            id: 1,
+            width: 1,
+            height: 1,
+            media_type: ImageType::Png,
+            data: vec![],
+        });
+        assert!(super::process_swf_tags(&movie(vec![bitmap.clone()])).is_ok());
+        assert_eq!(super::process_swf_tags(&movie(vec![bitmap.clone(), bitmap])).err(),
+                   Some(SwfError::BadSymbolId(1)));
     }
 }
@@ -0,0 +1,309 @@
+// pathfinder/swf/src/morph.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Morph shapes, which tween between a start and an end shape with the same topology.
+
+use crate::shapes::{self, GraphicLayers};
+use crate::{SwfError, SwfWarning, SymbolId};
+
+use swf_types::tags::DefineMorphShape;
+use swf_types::fixed::{Sfixed16P16, Sfixed8P8};
+use swf_types::{fill_styles, shape_records, ColorStop, FillStyle, Gradient, LineStyle, Matrix};
+use swf_types::{MorphFillStyle, MorphGradient, MorphLineStyle, MorphShapeRecord};
+use swf_types::{MorphShapeStyles, ShapeRecord, ShapeStyles, StraightSRgba8, Vector2D};
+
+pub(crate) struct MorphShape {
+    id: SymbolId,
+    initial_styles: MorphShapeStyles,
+    records: Vec<MorphShapeRecord>,
+}
+
+impl MorphShape {
+    /// Returns the shape at the given ratio, where 0 is the start shape and 1 is the end shape.
+    ///
+    /// The shape is decoded again for every ratio, since the fills are only consolidated into
+    /// closed outlines once the edges are known.
+    pub(crate) fn shape_at_ratio(&self, ratio: f32, warnings: &mut Vec<SwfWarning>)
+                                 -> Result<GraphicLayers, SwfError> {
+        let ratio = ratio.clamp(0.0, 1.0);
+        let initial_styles = lerp_styles(&self.initial_styles, ratio);
+        let records = lerp_records(&self.records, ratio);
+        shapes::decode_shape_records(self.id, &initial_styles, &records, warnings)
+    }
+}
+
+pub(crate) fn decode_morph_shape(shape: &DefineMorphShape, warnings: &mut Vec<SwfWarning>)
+                                 -> Result<MorphShape, SwfError> {
+    let morph_shape = MorphShape {
+        id: shape.id,
+        initial_styles: shape.shape.initial_styles.clone(),
+        records: shape.shape.records.clone(),
+    };
+    // The start and end shapes share their styles and topology, so if the start shape decodes,
+    // every ratio does. Decoding it here reports problems up front.
+    morph_shape.shape_at_ratio(0.0, warnings)?;
+    Ok(morph_shape)
+}
+
+fn lerp_records(records: &[MorphShapeRecord], ratio: f32) -> Vec<ShapeRecord> {
+    // Interpolate absolute positions rather than deltas, so that rounding to twips can't open up
+    // gaps in contours that are closed in both the start and the end shape.
+    let (mut start_pos, mut end_pos) = (Vector2D { x: 0, y: 0 }, Vector2D { x: 0, y: 0 });
+    let mut pos = Vector2D { x: 0, y: 0 };
+
+    records.iter().map(|record| {
+        match record {
+            MorphShapeRecord::StyleChange(style_change) => {
+                if let Some(move_to) = style_change.move_to {
+                    start_pos = move_to;
+                }
+                if let Some(morph_move_to) = style_change.morph_move_to {
+                    end_pos = morph_move_to;
+                }
+                let has_move_to = style_change.move_to.is_some() ||
+                    style_change.morph_move_to.is_some();
+                if has_move_to {
+                    pos = lerp_point(start_pos, end_pos, ratio);
+                }
+                ShapeRecord::StyleChange(shape_records::StyleChange {
+                    move_to: if has_move_to { Some(pos) } else { None },
+                    left_fill: style_change.left_fill,
+                    right_fill: style_change.right_fill,
+                    line_style: style_change.line_style,
+                    new_styles: style_change.new_styles.as_ref().map(|new_styles| {
+                        lerp_styles(new_styles, ratio)
+                    }),
+                })
+            }
+            MorphShapeRecord::Edge(edge) => {
+                // If only one of the edges is curved, treat the other as a curve whose control
+                // point is at its midpoint.
+                let control = match (edge.control_delta, edge.morph_control_delta) {
+                    (None, None) => None,
+                    (start_control, end_control) => {
+                        let start_control = start_control.unwrap_or_else(|| midpoint(edge.delta));
+                        let end_control =
+                            end_control.unwrap_or_else(|| midpoint(edge.morph_delta));
+                        Some(lerp_point(add(start_pos, start_control),
+                                        add(end_pos, end_control),
+                                        ratio))
+                    }
+                };
+
+                start_pos = add(start_pos, edge.delta);
+                end_pos = add(end_pos, edge.morph_delta);
+                let from = pos;
+                pos = lerp_point(start_pos, end_pos, ratio);
+
+                ShapeRecord::Edge(shape_records::Edge {
+                    delta: sub(pos, from),
+                    control_delta: control.map(|control| sub(control, from)),
+                })
+            }
+        }
+    }).collect()
+}
+
+fn lerp_styles(styles: &MorphShapeStyles, ratio: f32) -> ShapeStyles {
+    ShapeStyles {
+        fill: styles.fill.iter().map(|fill| lerp_fill(fill, ratio)).collect(),
+        line: styles.line.iter().map(|line| lerp_line(line, ratio)).collect(),
+    }
+}
+
+fn lerp_line(line: &MorphLineStyle, ratio: f32) -> LineStyle {
+    LineStyle {
+        width: lerp(line.width as f32, line.morph_width as f32, ratio).round() as u16,
+        start_cap: line.start_cap,
+        end_cap: line.end_cap,
+        join: line.join,
+        no_h_scale: line.no_h_scale,
+        no_v_scale: line.no_v_scale,
+        no_close: line.no_close,
+        pixel_hinting: line.pixel_hinting,
+        fill: lerp_fill(&line.fill, ratio),
+    }
+}
+
+fn lerp_fill(fill: &MorphFillStyle, ratio: f32) -> FillStyle {
+    match fill {
+        MorphFillStyle::Solid(fill_styles::MorphSolid { color, morph_color }) => {
+            FillStyle::Solid(fill_styles::Solid { color: lerp_color(color, morph_color, ratio) })
+        }
+        MorphFillStyle::LinearGradient(gradient) => {
+            FillStyle::LinearGradient(fill_styles::LinearGradient {
+                matrix: lerp_matrix(&gradient.matrix, &gradient.morph_matrix, ratio),
+                gradient: lerp_gradient(&gradient.gradient, ratio),
+            })
+        }
+        MorphFillStyle::RadialGradient(gradient) => {
+            FillStyle::RadialGradient(fill_styles::RadialGradient {
+                matrix: lerp_matrix(&gradient.matrix, &gradient.morph_matrix, ratio),
+                gradient: lerp_gradient(&gradient.gradient, ratio),
+            })
+        }
+        MorphFillStyle::FocalGradient(gradient) => {
+            let focal_point = lerp(gradient.focal_point.epsilons as f32,
+                                   gradient.morph_focal_point.epsilons as f32,
+                                   ratio);
+            FillStyle::FocalGradient(fill_styles::FocalGradient {
+                matrix: lerp_matrix(&gradient.matrix, &gradient.morph_matrix, ratio),
+                gradient: lerp_gradient(&gradient.gradient, ratio),
+                focal_point: Sfixed8P8 { epsilons: focal_point.round() as i16 },
+            })
+        }
+        MorphFillStyle::Bitmap(bitmap) => {
+            FillStyle::Bitmap(fill_styles::Bitmap {
+                bitmap_id: bitmap.bitmap_id,
+                matrix: lerp_matrix(&bitmap.matrix, &bitmap.morph_matrix, ratio),
+                repeating: bitmap.repeating,
+                smoothed: bitmap.smoothed,
+            })
+        }
+    }
+}
+
+fn lerp_gradient(gradient: &MorphGradient, ratio: f32) -> Gradient {
+    Gradient {
+        spread: gradient.spread,
+        color_space: gradient.color_space,
+        colors: gradient.colors.iter().map(|stop| {
+            ColorStop {
+                ratio: lerp(stop.ratio as f32, stop.morph_ratio as f32, ratio).round() as u8,
+                color: lerp_color(&stop.color, &stop.morph_color, ratio),
+            }
+        }).collect(),
+    }
+}
+
+fn lerp_matrix(start: &Matrix, end: &Matrix, ratio: f32) -> Matrix {
+    let lerp_fixed = |start: Sfixed16P16, end: Sfixed16P16| {
+        Sfixed16P16 { epsilons: lerp_i32(start.epsilons, end.epsilons, ratio) }
+    };
+    Matrix {
+        scale_x: lerp_fixed(start.scale_x, end.scale_x),
+        scale_y: lerp_fixed(start.scale_y, end.scale_y),
+        rotate_skew0: lerp_fixed(start.rotate_skew0, end.rotate_skew0),
+        rotate_skew1: lerp_fixed(start.rotate_skew1, end.rotate_skew1),
+        translate_x: lerp_i32(start.translate_x, end.translate_x, ratio),
+        translate_y: lerp_i32(start.translate_y, end.translate_y, ratio),
+    }
+}
+
+fn lerp_color(start: &StraightSRgba8, end: &StraightSRgba8, ratio: f32) -> StraightSRgba8 {
+    let lerp_channel = |start: u8, end: u8| lerp(start as f32, end as f32, ratio).round() as u8;
+    StraightSRgba8 {
+        r: lerp_channel(start.r, end.r),
+        g: lerp_channel(start.g, end.g),
+        b: lerp_channel(start.b, end.b),
+        a: lerp_channel(start.a, end.a),
+    }
+}
+
+fn lerp_point(start: Vector2D, end: Vector2D, ratio: f32) -> Vector2D {
+    Vector2D { x: lerp_i32(start.x, end.x, ratio), y: lerp_i32(start.y, end.y, ratio) }
+}
+
+fn midpoint(delta: Vector2D) -> Vector2D {
+    Vector2D { x: delta.x / 2, y: delta.y / 2 }
+}
+
+fn add(a: Vector2D, b: Vector2D) -> Vector2D {
+    Vector2D { x: a.x + b.x, y: a.y + b.y }
+}
+
+fn sub(a: Vector2D, b: Vector2D) -> Vector2D {
+    Vector2D { x: a.x - b.x, y: a.y - b.y }
+}
+
+// Fixed point terms can use all 32 bits, which `f32` can't represent exactly.
+#[inline]
+fn lerp_i32(start: i32, end: i32, ratio: f32) -> i32 {
+    (start as f64 + (end as f64 - start as f64) * ratio as f64).round() as i32
+}
+
+#[inline]
+fn lerp(start: f32, end: f32, ratio: f32) -> f32 {
+    start + (end - start) * ratio
+}
+
+#[cfg(test)]
+mod test {
+    use crate::SwfWarning;
+    use swf_types::{fill_styles, shape_records, MorphFillStyle, MorphShapeRecord};
+    use swf_types::{MorphShapeStyles, ShapeRecord, StraightSRgba8, Vector2D};
+    use super::MorphShape;
+
+    // A square that morphs from `start_size` twips across at `start_origin` to `end_size` twips
+    // across at `end_origin`.
+    fn morphing_square(start_origin: Vector2D,
+                       start_size: i32,
+                       end_origin: Vector2D,
+                       end_size: i32)
+                       -> Vec<MorphShapeRecord> {
+        let edge = |dx: i32, dy: i32| {
+            MorphShapeRecord::Edge(shape_records::MorphEdge {
+                delta: Vector2D { x: dx * start_size, y: dy * start_size },
+                morph_delta: Vector2D { x: dx * end_size, y: dy * end_size },
+                control_delta: None,
+                morph_control_delta: None,
+            })
+        };
+        vec![
+            MorphShapeRecord::StyleChange(shape_records::MorphStyleChange {
+                move_to: Some(start_origin),
+                morph_move_to: Some(end_origin),
+                left_fill: None,
+                right_fill: Some(1),
+                line_style: None,
+                new_styles: None,
+            }),
+            edge(1, 0),
+            edge(0, 1),
+            edge(-1, 0),
+            edge(0, -1),
+        ]
+    }
+
+    #[test]
+    fn test_lerped_squares_stay_closed() {
+        // Odd sizes make interpolated deltas round differently on opposite sides of the square.
+        let records = morphing_square(Vector2D { x: 1, y: 0 }, 101, Vector2D { x: 4, y: 3 }, 200);
+
+        let lerped_records = super::lerp_records(&records, 0.5);
+        let (mut x, mut y) = (0, 0);
+        for record in &lerped_records[1..] {
+            match record {
+                ShapeRecord::Edge(edge) => {
+                    x += edge.delta.x;
+                    y += edge.delta.y;
+                }
+                ShapeRecord::StyleChange(_) => panic!("expected an edge"),
+            }
+        }
+        assert_eq!((x, y), (0, 0));
+
+        let color = StraightSRgba8 { r: 255, g: 0, b: 0, a: 255 };
+        let morph_shape = MorphShape {
+            id: 1,
+            initial_styles: MorphShapeStyles {
+                fill: vec![MorphFillStyle::Solid(fill_styles::MorphSolid {
+                    color,
+                    morph_color: color,
+                })],
+                line: vec![],
+            },
+            records,
+        };
+        let mut warnings = vec![];
+        assert!(morph_shape.shape_at_ratio(0.5, &mut warnings).is_ok());
+        assert!(!warnings.contains(&SwfWarning::UnclosedFill(1)));
+    }
+}
@@ -0,0 +1,611 @@
+// pathfinder/swf/src/paint.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Resolves SWF fill styles into paints once they've been placed on the stage.
+
+use crate::shapes::{SwfBitmapFill, SwfFill, SwfGradientFill};
+use crate::timeline::ColorTransform;
+use crate::{Symbol, SymbolId, SymbolLibrary};
+
+use flate2::read::ZlibDecoder;
+use image::ImageFormat;
+use pathfinder_color::{self as color, ColorU};
+use pathfinder_content::gradient::{ColorStop, Gradient, SpreadMethod};
+use pathfinder_content::pattern::{self, Image, Pattern, PatternSource, Repeat};
+use pathfinder_geometry::rect::{RectF, RectI};
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::Vector2I;
+use pathfinder_renderer::paint::Paint;
+use std::cell::RefCell;
+use std::io::Read;
+use swf_types::tags::DefineBitmap;
+use swf_types::{GradientSpread, ImageType};
+
+// The formats of `DefineBitsLossless` and `DefineBitsLossless2` pixel data.
+const LOSSLESS_FORMAT_COLORMAPPED: u8 = 3;
+const LOSSLESS_FORMAT_RGB15: u8 = 4;
+const LOSSLESS_FORMAT_RGB24: u8 = 5;
+
+// The number of resampled bitmap fills that are kept for later frames.
+const MAX_CACHED_BITMAP_FILLS: usize = 64;
+
+/// Bitmap fills that have been resampled for where they're drawn, so that bitmaps that stay in
+/// place from one frame to the next are only resampled once.
+#[derive(Default)]
+pub(crate) struct BitmapFillCache {
+    // Least recently used first.
+    entries: RefCell<Vec<(BitmapFillKey, Image)>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct BitmapFillKey {
+    bitmap_id: SymbolId,
+    transform: Transform2F,
+    color_transform: ColorTransform,
+    bounds: RectI,
+    repeating: bool,
+    smoothed: bool,
+}
+
+/// Returns the paint for a fill drawn with `transform` applied, or `None` if nothing should be
+/// drawn.
+///
+/// `device_bounds` are the bounds of the transformed path, which repeating gradients and bitmaps
+/// need to cover.
+pub(crate) fn resolve_paint(library: &SymbolLibrary,
+                            fill: &SwfFill,
+                            transform: &Transform2F,
+                            color_transform: &ColorTransform,
+                            device_bounds: RectF)
+                            -> Option<Paint> {
+    match *fill {
+        SwfFill::Color(color) => Some(Paint::Color(color_transform.apply(color))),
+        SwfFill::Gradient(ref gradient) => {
+            resolve_gradient(gradient, transform, color_transform, device_bounds)
+        }
+        SwfFill::Bitmap(ref bitmap) => {
+            resolve_bitmap(library, bitmap, transform, color_transform, device_bounds)
+        }
+    }
+}
+
+fn resolve_gradient(fill: &SwfGradientFill,
+                    transform: &Transform2F,
+                    color_transform: &ColorTransform,
+                    device_bounds: RectF)
+                    -> Option<Paint> {
+    let last_stop = *fill.gradient.stops().last()?;
+
+    let transform = *transform * fill.transform;
+    let geometry = match fill.gradient.geometry().transform(&transform) {
+        Some(geometry) => geometry,
+        None => return Some(Paint::Color(color_transform.apply(last_stop.color))),
+    };
+
+    let mut gradient = Gradient::new(geometry);
+    for stop in fill.gradient.stops() {
+        gradient.add_color_stop(ColorStop::new(color_transform.apply(stop.color), stop.offset));
+    }
+    let spread_method = match fill.spread {
+        GradientSpread::Pad => SpreadMethod::Pad,
+        GradientSpread::Reflect => SpreadMethod::Reflect,
+        GradientSpread::Repeat => SpreadMethod::Repeat,
+    };
+    gradient.apply_spread_method(spread_method, device_bounds);
+    Some(Paint::Gradient(gradient))
+}
+
+fn resolve_bitmap(library: &SymbolLibrary,
+                  fill: &SwfBitmapFill,
+                  transform: &Transform2F,
+                  color_transform: &ColorTransform,
+                  device_bounds: RectF)
+                  -> Option<Paint> {
+    let image = match library.symbol(fill.bitmap_id) {
+        Some(Symbol::Bitmap(Some(image))) => image,
+        _ => return None,
+    };
+    if image.size().x() == 0 || image.size().y() == 0 {
+        return None;
+    }
+
+    let transform = *transform * fill.transform;
+    if transform.matrix.det() == 0.0 {
+        return None;
+    }
+    let bounds = pattern::pattern_bounds(device_bounds)?;
+
+    let key = BitmapFillKey {
+        bitmap_id: fill.bitmap_id,
+        transform,
+        color_transform: *color_transform,
+        bounds,
+        repeating: fill.repeating,
+        smoothed: fill.smoothed,
+    };
+    let image = library.bitmap_fills.get_or_insert_with(key, || {
+        // Clipped bitmaps extend their edge pixels outward.
+        let repeat = if fill.repeating { Repeat::X | Repeat::Y } else { Repeat::empty() };
+        let image = image.resample(&transform, bounds, repeat, fill.smoothed);
+        let pixels = image.pixels().iter().map(|&pixel| color_transform.apply(pixel)).collect();
+        Image::new(image.size(), pixels)
+    });
+    Some(Paint::Pattern(Pattern::new(PatternSource::Image(image), Repeat::empty())))
+}
+
+impl BitmapFillCache {
+    fn get_or_insert_with<F>(&self, key: BitmapFillKey, resample: F) -> Image
+                             where F: FnOnce() -> Image {
+        let mut entries = self.entries.borrow_mut();
+        let image = match entries.iter().position(|(entry_key, _)| *entry_key == key) {
+            Some(index) => entries.remove(index).1,
+            None => resample(),
+        };
+        if entries.len() >= MAX_CACHED_BITMAP_FILLS {
+            entries.remove(0);
+        }
+        entries.push((key, image.clone()));
+        image
+    }
+}
+
+/// Decodes the image data of a `DefineBits*` tag, or returns `None` if its format isn't
+/// supported.
+///
+/// `jpeg_tables` is the data of the movie's `JPEGTables` tag, if any, which plain `DefineBits`
+/// JPEGs need in order to be decoded.
+pub(crate) fn decode_bitmap(bitmap: &DefineBitmap, jpeg_tables: Option<&[u8]>) -> Option<Image> {
+    let (format, data) = match bitmap.media_type {
+        ImageType::Jpeg => (ImageFormat::Jpeg, strip_erroneous_jpeg_header(&bitmap.data)),
+        ImageType::Png => (ImageFormat::Png, &bitmap.data[..]),
+        ImageType::Gif => (ImageFormat::Gif, &bitmap.data[..]),
+        ImageType::PartialJpeg => {
+            return decode_partial_jpeg(strip_erroneous_jpeg_header(&bitmap.data), jpeg_tables?);
+        }
+        ImageType::Ajpeg => return decode_jpeg_with_alpha(&bitmap.data, 0),
+        // The deblocking filter parameter comes between the JPEG size and the JPEG.
+        ImageType::Ajpegd => return decode_jpeg_with_alpha(&bitmap.data, 2),
+        ImageType::SwfBmp => return decode_lossless(&bitmap.data, false),
+        ImageType::SwfAbmp => return decode_lossless(&bitmap.data, true),
+    };
+    let image_buffer = image::load_from_memory_with_format(data, format).ok()?;
+    Some(Image::from_image_buffer(image_buffer.to_rgba()))
+}
+
+// Older SWF encoders prefix JPEG data with an extra end of image/start of image marker pair,
+// which decoders choke on.
+fn strip_erroneous_jpeg_header(data: &[u8]) -> &[u8] {
+    if data.starts_with(&[0xff, 0xd9, 0xff, 0xd8]) {
+        &data[4..]
+    } else {
+        data
+    }
+}
+
+// `DefineBits` JPEGs leave out their encoding tables, which all of them share through the
+// `JPEGTables` tag. Splicing the two together, minus the end of image marker of the tables and the
+// start of image marker of the image, makes a complete JPEG.
+fn decode_partial_jpeg(data: &[u8], jpeg_tables: &[u8]) -> Option<Image> {
+    let jpeg_tables = strip_erroneous_jpeg_header(jpeg_tables);
+    let jpeg_tables = if jpeg_tables.ends_with(&[0xff, 0xd9]) {
+        &jpeg_tables[..jpeg_tables.len() - 2]
+    } else {
+        jpeg_tables
+    };
+    let data = if data.starts_with(&[0xff, 0xd8]) { &data[2..] } else { data };
+
+    let mut jpeg = jpeg_tables.to_vec();
+    jpeg.extend_from_slice(data);
+    let image_buffer = image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg).ok()?;
+    Some(Image::from_image_buffer(image_buffer.to_rgba()))
+}
+
+// `DefineBitsJPEG3` data is a 32-bit JPEG size, the JPEG itself, and then the alpha channel as
+// zlib-compressed bytes, one per pixel. `DefineBitsJPEG4` has `header_padding` more bytes between
+// the size and the JPEG.
+fn decode_jpeg_with_alpha(data: &[u8], header_padding: usize) -> Option<Image> {
+    let jpeg_start = 4 + header_padding;
+    let jpeg_end = jpeg_start + read_u32(data, 0)? as usize;
+    let jpeg = strip_erroneous_jpeg_header(data.get(jpeg_start..jpeg_end)?);
+    let image_buffer = image::load_from_memory_with_format(jpeg, ImageFormat::Jpeg).ok()?;
+    let image_buffer = image_buffer.to_rgba();
+
+    let (width, height) = image_buffer.dimensions();
+    let alpha = inflate(&data[jpeg_end..])?;
+    if alpha.len() < width as usize * height as usize {
+        return None;
+    }
+
+    let mut pixels = color::u8_vec_to_color_vec(image_buffer.into_raw());
+    for (pixel, &alpha) in pixels.iter_mut().zip(alpha.iter()) {
+        pixel.a = alpha;
+    }
+    Some(Image::new(Vector2I::new(width as i32, height as i32), pixels))
+}
+
+// Decodes the body of a `DefineBitsLossless` or `DefineBitsLossless2` tag after its character id:
+// a format byte, the width and height, the size of the color table for colormapped images, and
+// then the zlib-compressed pixels. `DefineBitsLossless2` pixels have premultiplied alpha.
+fn decode_lossless(data: &[u8], has_alpha: bool) -> Option<Image> {
+    let format = *data.get(0)?;
+    let width = read_u16(data, 1)? as usize;
+    let height = read_u16(data, 3)? as usize;
+
+    let mut pixels = Vec::with_capacity(width * height);
+    match format {
+        LOSSLESS_FORMAT_COLORMAPPED => {
+            let color_count = *data.get(5)? as usize + 1;
+            let pixel_data = inflate(&data[6..])?;
+            let entry_size = if has_alpha { 4 } else { 3 };
+            let color_table: Vec<ColorU> = pixel_data.get(0..(color_count * entry_size))?
+                .chunks(entry_size)
+                .map(|entry| {
+                    let alpha = if has_alpha { entry[3] } else { 255 };
+                    unpremultiply(ColorU::new(entry[0], entry[1], entry[2], alpha))
+                })
+                .collect();
+            let indices = &pixel_data[(color_count * entry_size)..];
+            let stride = padded_row_length(width);
+            for y in 0..height {
+                let row = indices.get((y * stride)..(y * stride + width))?;
+                for &index in row {
+                    pixels.push(*color_table.get(index as usize)?);
+                }
+            }
+        }
+        LOSSLESS_FORMAT_RGB15 if !has_alpha => {
+            let pixel_data = inflate(&data[5..])?;
+            let stride = padded_row_length(width * 2);
+            for y in 0..height {
+                let row = pixel_data.get((y * stride)..(y * stride + width * 2))?;
+                for pixel in row.chunks(2) {
+                    let pixel = (pixel[0] as u16) << 8 | pixel[1] as u16;
+                    let channel = |shift: u16| {
+                        let value = (pixel >> shift) & 0x1f;
+                        (value << 3 | value >> 2) as u8
+                    };
+                    pixels.push(ColorU::new(channel(10), channel(5), channel(0), 255));
+                }
+            }
+        }
+        LOSSLESS_FORMAT_RGB24 => {
+            // Each pixel is either a padding byte followed by RGB, or premultiplied ARGB.
+            let pixel_data = inflate(&data[5..])?;
+            for pixel in pixel_data.get(0..(width * height * 4))?.chunks(4) {
+                let alpha = if has_alpha { pixel[0] } else { 255 };
+                pixels.push(unpremultiply(ColorU::new(pixel[1], pixel[2], pixel[3], alpha)));
+            }
+        }
+        _ => return None,
+    }
+
+    Some(Image::new(Vector2I::new(width as i32, height as i32), pixels))
+}
+
+// Rows of colormapped and 15-bit lossless bitmaps are padded to a multiple of 4 bytes.
+fn padded_row_length(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+fn unpremultiply(color: ColorU) -> ColorU {
+    if color.a == 0 || color.a == 255 {
+        return color;
+    }
+    let channel = |value: u8| (value as u32 * 255 / color.a as u32).min(255) as u8;
+    ColorU::new(channel(color.r), channel(color.g), channel(color.b), color.a)
+}
+
+fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    let mut inflated = vec![];
+    ZlibDecoder::new(data).read_to_end(&mut inflated).ok()?;
+    Some(inflated)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..(offset + 2))?;
+    Some(bytes[0] as u16 | (bytes[1] as u16) << 8)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..(offset + 4))?;
+    Some(read_u16(bytes, 0)? as u32 | (read_u16(bytes, 2)? as u32) << 16)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shapes::{self, SwfBitmapFill, SwfFill};
+    use crate::timeline::ColorTransform;
+    use crate::{Symbol, SymbolLibrary};
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use image::codecs::jpeg::JpegEncoder;
+    use image::ColorType;
+    use pathfinder_color::ColorU;
+    use pathfinder_content::gradient::GradientGeometry;
+    use pathfinder_content::pattern::{Image, PatternSource};
+    use pathfinder_geometry::rect::RectF;
+    use pathfinder_geometry::transform2d::Transform2F;
+    use pathfinder_geometry::vector::{Vector2F, Vector2I};
+    use pathfinder_renderer::paint::Paint;
+    use std::io::Write;
+    use swf_types::tags::DefineBitmap;
+    use swf_types::{fill_styles, ColorSpace, ColorStop, FillStyle, GradientSpread, ImageType};
+    use swf_types::{Matrix, StraightSRgba8};
+    use super::{MAX_CACHED_BITMAP_FILLS, resolve_paint};
+
+    fn black_to_white(spread: GradientSpread, color_space: ColorSpace) -> SwfFill {
+        let stop = |ratio: u8, value: u8| {
+            ColorStop { ratio, color: StraightSRgba8 { r: value, g: value, b: value, a: 255 } }
+        };
+        shapes::decode_fill(&FillStyle::LinearGradient(fill_styles::LinearGradient {
+            matrix: Matrix::default(),
+            gradient: swf_types::Gradient {
+                spread,
+                color_space,
+                colors: vec![stop(0, 0), stop(255, 255)],
+            },
+        }))
+    }
+
+    fn resolve(library: &SymbolLibrary,
+               fill: &SwfFill,
+               transform: &Transform2F,
+               color_transform: &ColorTransform,
+               device_bounds: RectF)
+               -> Paint {
+        resolve_paint(library, fill, transform, color_transform, device_bounds).unwrap()
+    }
+
+    fn rect(width: f32, height: f32) -> RectF {
+        RectF::new(Vector2F::default(), Vector2F::new(width, height))
+    }
+
+    #[test]
+    fn test_gradient_fill() {
+        let library = SymbolLibrary::new();
+        let fill = black_to_white(GradientSpread::Pad, ColorSpace::SRgb);
+
+        // The gradient square spans -819.2 to 819.2 pixels before the transform.
+        let transform = Transform2F::from_translation(Vector2F::new(10.0, 0.0)) *
+            Transform2F::from_uniform_scale(0.01);
+        let red = ColorTransform::fill(ColorU::new(255, 0, 0, 255));
+        let gradient = match resolve(&library, &fill, &transform, &red, rect(20.0, 1.0)) {
+            Paint::Gradient(gradient) => gradient,
+            paint => panic!("expected a gradient, got {:?}", paint),
+        };
+        match *gradient.geometry() {
+            GradientGeometry::Linear(line) => {
+                assert!((line.from().x() - 1.808).abs() < 0.001);
+                assert!((line.to().x() - 18.192).abs() < 0.001);
+            }
+            ref geometry => panic!("expected a linear gradient, got {:?}", geometry),
+        }
+        let colors: Vec<_> = gradient.stops().iter().map(|stop| stop.color).collect();
+        assert_eq!(colors, [ColorU::new(255, 0, 0, 255), ColorU::new(255, 0, 0, 255)]);
+
+        // Repeating gradients are extended to cover the path.
+        let fill = black_to_white(GradientSpread::Repeat, ColorSpace::SRgb);
+        let identity = ColorTransform::identity();
+        let gradient = match resolve(&library, &fill, &transform, &identity, rect(60.0, 1.0)) {
+            Paint::Gradient(gradient) => gradient,
+            paint => panic!("expected a gradient, got {:?}", paint),
+        };
+        assert!(gradient.stops().len() > 2);
+
+        // Gradients squashed to nothing are drawn in their last color.
+        let transform = Transform2F::from_scale(Vector2F::new(0.0, 1.0));
+        assert_eq!(resolve(&library, &fill, &transform, &identity, rect(20.0, 1.0)),
+                   Paint::Color(ColorU::white()));
+    }
+
+    #[test]
+    fn test_linear_rgb_gradient() {
+        // Interpolating in linear RGB is emulated with extra stops, so the middle of a black to
+        // white gradient is lighter than half gray.
+        let gradient = match black_to_white(GradientSpread::Pad, ColorSpace::LinearRgb) {
+            SwfFill::Gradient(fill) => fill.gradient,
+            _ => panic!("expected a gradient"),
+        };
+        let stops = gradient.stops();
+        assert_eq!(stops.len(), 9);
+        assert_eq!((stops[0].color, stops[0].offset), (ColorU::black(), 0.0));
+        assert_eq!((stops[8].color, stops[8].offset), (ColorU::white(), 1.0));
+        let middle = stops.iter().find(|stop| (stop.offset - 0.5).abs() < 0.001).unwrap();
+        assert!((middle.color.r as i32 - 188).abs() <= 1);
+
+        let gradient = match black_to_white(GradientSpread::Pad, ColorSpace::SRgb) {
+            SwfFill::Gradient(fill) => fill.gradient,
+            _ => panic!("expected a gradient"),
+        };
+        assert_eq!(gradient.stops().len(), 2);
+    }
+
+    fn bitmap_library(image: Image) -> SymbolLibrary {
+        let mut library = SymbolLibrary::new();
+        library.add_symbol(1, Symbol::Bitmap(Some(image))).unwrap();
+        library
+    }
+
+    fn bitmap_fill(repeating: bool) -> SwfFill {
+        SwfFill::Bitmap(SwfBitmapFill {
+            bitmap_id: 1,
+            transform: Transform2F::default(),
+            repeating,
+            smoothed: false,
+        })
+    }
+
+    fn pattern_pixels(paint: &Paint) -> Vec<ColorU> {
+        match *paint {
+            Paint::Pattern(ref pattern) => match pattern.source {
+                PatternSource::Image(ref image) => image.pixels().to_vec(),
+                PatternSource::RenderTarget(_) => panic!("expected an image"),
+            },
+            ref paint => panic!("expected a pattern, got {:?}", paint),
+        }
+    }
+
+    #[test]
+    fn test_bitmap_fill() {
+        let (black, white) = (ColorU::black(), ColorU::white());
+        let library = bitmap_library(Image::new(Vector2I::new(2, 1), vec![black, white]));
+        let identity = ColorTransform::identity();
+        let transform = Transform2F::default();
+
+        // Clipped bitmaps extend their edge pixels, and repeating ones wrap around.
+        let paint = resolve(&library, &bitmap_fill(false), &transform, &identity, rect(4.0, 1.0));
+        assert_eq!(pattern_pixels(&paint), [black, white, white, white]);
+        let paint = resolve(&library, &bitmap_fill(true), &transform, &identity, rect(4.0, 1.0));
+        assert_eq!(pattern_pixels(&paint), [black, white, black, white]);
+
+        // Color transforms apply to the pixels.
+        let red = ColorTransform::fill(ColorU::new(255, 0, 0, 255));
+        let paint = resolve(&library, &bitmap_fill(false), &transform, &red, rect(1.0, 1.0));
+        assert_eq!(pattern_pixels(&paint), [ColorU::new(255, 0, 0, 255)]);
+
+        // Missing bitmaps and degenerate transforms draw nothing.
+        let squashed = Transform2F::from_scale(Vector2F::new(0.0, 1.0));
+        let fill = bitmap_fill(false);
+        assert!(resolve_paint(&library, &fill, &squashed, &identity, rect(1.0, 1.0)).is_none());
+        let library = SymbolLibrary::new();
+        assert!(resolve_paint(&library, &fill, &transform, &identity, rect(1.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn test_bitmap_fills_are_cached() {
+        let library = bitmap_library(Image::new(Vector2I::new(1, 1), vec![ColorU::white()]));
+        let identity = ColorTransform::identity();
+        let fill = bitmap_fill(false);
+        let entry_count = || library.bitmap_fills.entries.borrow().len();
+
+        let first = resolve(&library, &fill, &Transform2F::default(), &identity, rect(2.0, 2.0));
+        let second = resolve(&library, &fill, &Transform2F::default(), &identity, rect(2.0, 2.0));
+        assert_eq!(first, second);
+        assert_eq!(entry_count(), 1);
+
+        // Moving the bitmap resamples it, and the oldest fills are dropped once the cache is
+        // full.
+        for index in 0..(MAX_CACHED_BITMAP_FILLS + 1) {
+            let transform = Transform2F::from_translation(Vector2F::new(index as f32, 0.0));
+            resolve(&library, &fill, &transform, &identity, rect(100.0, 2.0));
+        }
+        assert_eq!(entry_count(), MAX_CACHED_BITMAP_FILLS);
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decode(media_type: ImageType, data: Vec<u8>, jpeg_tables: Option<&[u8]>) -> Option<Image> {
+        let bitmap = DefineBitmap { id: 1, width: 0, height: 0, media_type, data };
+        super::decode_bitmap(&bitmap, jpeg_tables)
+    }
+
+    // The body of a `DefineBitsLossless` tag, with the pixel data compressed.
+    fn lossless(format: u8, width: u16, color_count: Option<u8>, pixel_data: &[u8]) -> Vec<u8> {
+        let mut data = vec![format, width as u8, (width >> 8) as u8, 1, 0];
+        data.extend(color_count.map(|color_count| color_count - 1));
+        data.extend(zlib(pixel_data));
+        data
+    }
+
+    #[test]
+    fn test_lossless_bitmaps() {
+        let red = ColorU::new(255, 0, 0, 255);
+        let blue = ColorU::new(0, 0, 255, 255);
+
+        // Colormapped rows are padded to 4 bytes.
+        let data = lossless(3, 3, Some(2), &[255, 0, 0, 0, 0, 255, 0, 1, 0, 0]);
+        let image = decode(ImageType::SwfBmp, data, None).unwrap();
+        assert_eq!(image.size(), Vector2I::new(3, 1));
+        assert_eq!(image.pixels(), &[red, blue, red]);
+
+        // `DefineBitsLossless2` colors have premultiplied alpha.
+        let data = lossless(3, 1, Some(1), &[128, 0, 0, 128, 0, 0, 0, 0]);
+        let image = decode(ImageType::SwfAbmp, data, None).unwrap();
+        assert_eq!(image.pixels(), &[ColorU::new(255, 0, 0, 128)]);
+
+        // 15-bit pixels are big-endian, and only exist without alpha.
+        let data = lossless(4, 2, None, &[0x7c, 0x00, 0x00, 0x1f]);
+        assert_eq!(decode(ImageType::SwfBmp, data.clone(), None).unwrap().pixels(), &[red, blue]);
+        assert!(decode(ImageType::SwfAbmp, data, None).is_none());
+
+        // 24-bit pixels have a padding byte, or premultiplied alpha, before their color.
+        let data = lossless(5, 2, None, &[0, 255, 0, 0, 64, 0, 0, 64]);
+        let image = decode(ImageType::SwfBmp, data.clone(), None).unwrap();
+        assert_eq!(image.pixels(), &[red, ColorU::new(0, 0, 64, 255)]);
+        let image = decode(ImageType::SwfAbmp, data, None).unwrap();
+        assert_eq!(image.pixels(), &[ColorU::new(255, 0, 0, 0), ColorU::new(0, 0, 255, 64)]);
+
+        // Truncated pixel data and unknown formats aren't decoded.
+        assert!(decode(ImageType::SwfBmp, lossless(5, 2, None, &[0, 255, 0, 0]), None).is_none());
+        assert!(decode(ImageType::SwfBmp, lossless(6, 1, None, &[0; 4]), None).is_none());
+    }
+
+    // An 8×8 red JPEG.
+    fn red_jpeg() -> Vec<u8> {
+        let pixels: Vec<u8> = (0..64).flat_map(|_| vec![255, 0, 0]).collect();
+        let mut jpeg = vec![];
+        JpegEncoder::new_with_quality(&mut jpeg, 100).encode(&pixels, 8, 8, ColorType::Rgb8)
+                                                     .unwrap();
+        jpeg
+    }
+
+    fn is_red(image: &Image) -> bool {
+        image.pixels().iter().all(|pixel| pixel.r > 240 && pixel.g < 16 && pixel.b < 16)
+    }
+
+    #[test]
+    fn test_jpeg_tables() {
+        // Split the JPEG before its frame header, as `DefineBits` and `JPEGTables` do.
+        let jpeg = red_jpeg();
+        let split = jpeg.windows(2).position(|marker| marker == [0xff, 0xc0]).unwrap();
+        let mut jpeg_tables = vec![0xff, 0xd9, 0xff, 0xd8];
+        jpeg_tables.extend_from_slice(&jpeg[..split]);
+        jpeg_tables.extend_from_slice(&[0xff, 0xd9]);
+        let mut data = vec![0xff, 0xd8];
+        data.extend_from_slice(&jpeg[split..]);
+
+        let image = decode(ImageType::PartialJpeg, data.clone(), Some(&jpeg_tables)).unwrap();
+        assert_eq!(image.size(), Vector2I::splat(8));
+        assert!(is_red(&image));
+        assert!(decode(ImageType::PartialJpeg, data, None).is_none());
+
+        // Complete JPEGs may also start with an extra end and start of image marker.
+        let mut data = vec![0xff, 0xd9, 0xff, 0xd8];
+        data.extend_from_slice(&jpeg);
+        assert!(is_red(&decode(ImageType::Jpeg, data, None).unwrap()));
+    }
+
+    #[test]
+    fn test_jpeg_with_alpha() {
+        let jpeg = red_jpeg();
+        let jpeg_with_alpha = |header_padding: usize, alpha: &[u8]| {
+            let mut data = (jpeg.len() as u32).to_le_bytes().to_vec();
+            data.extend(vec![0; header_padding]);
+            data.extend_from_slice(&jpeg);
+            data.extend(zlib(alpha));
+            data
+        };
+
+        let image = decode(ImageType::Ajpeg, jpeg_with_alpha(0, &[100; 64]), None).unwrap();
+        assert!(is_red(&image));
+        assert!(image.pixels().iter().all(|pixel| pixel.a == 100));
+
+        // `DefineBitsJPEG4` has a deblocking filter parameter before the JPEG.
+        let image = decode(ImageType::Ajpegd, jpeg_with_alpha(2, &[50; 64]), None).unwrap();
+        assert!(image.pixels().iter().all(|pixel| pixel.a == 50));
+
+        // There must be an alpha value for every pixel.
+        assert!(decode(ImageType::Ajpeg, jpeg_with_alpha(0, &[100; 63]), None).is_none());
+    }
+}
@@ -8,17 +8,29 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::{Twips, Point2};
+use crate::{matrix_to_transform, SwfError, SwfWarning, SymbolId, Twips, Point2};
 
-use pathfinder_color::ColorU;
+use pathfinder_color::{ColorF, ColorU};
+use pathfinder_content::gradient::{ColorStop, Gradient};
 use pathfinder_content::stroke::{LineJoin, LineCap};
-use pathfinder_renderer::paint::Paint;
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::Vector2F;
 use std::cmp::Ordering;
 use std::mem;
 use swf_types::tags::DefineShape;
-use swf_types::{CapStyle, FillStyle, JoinStyle, LineStyle, ShapeRecord, StraightSRgba8, Vector2D};
+use swf_types::{CapStyle, ColorSpace, FillStyle, GradientSpread, JoinStyle, LineStyle, Matrix};
+use swf_types::{ShapeRecord, ShapeStyles, StraightSRgba8, Vector2D};
 use swf_types::{fill_styles, join_styles, shape_records};
 
+// SWF gradients are defined in a square spanning -16384 to 16384 twips, which their gradient
+// matrices then map into shape space.
+const GRADIENT_SQUARE_HALF_LENGTH: Twips = Twips(16384);
+
+// The number of stops each span between two stops is split into in order to approximate
+// interpolation in linear RGB space, since the renderer only interpolates in sRGB.
+const LINEAR_RGB_SUBDIVISIONS: u32 = 8;
+
 #[derive(Clone, Copy, Debug)]
 pub(crate) struct LineSegment {
     pub(crate) from: Point2<Twips>,
@@ -118,15 +130,44 @@ impl Shape {
     }
 }
 
+/// A fill style, in the coordinate space of the shape that uses it.
+///
+/// Gradients and bitmaps are resolved into paints at draw time, once the placement transform and
+/// the bounds of the path are known.
+#[derive(Clone, Debug)]
+pub(crate) enum SwfFill {
+    Color(ColorU),
+    Gradient(SwfGradientFill),
+    Bitmap(SwfBitmapFill),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct SwfGradientFill {
+    /// The gradient in gradient space, where the gradient square spans -819.2 to 819.2 pixels.
+    pub(crate) gradient: Gradient,
+    /// Maps gradient space to shape space.
+    pub(crate) transform: Transform2F,
+    pub(crate) spread: GradientSpread,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct SwfBitmapFill {
+    pub(crate) bitmap_id: SymbolId,
+    /// Maps bitmap pixels to shape space.
+    pub(crate) transform: Transform2F,
+    pub(crate) repeating: bool,
+    pub(crate) smoothed: bool,
+}
+
 pub(crate) struct SwfLineStyle {
-    color: Paint,
+    fill: SwfFill,
     pub(crate) width: Twips,
     pub(crate) join: LineJoin,
     pub(crate) cap: LineCap,
 }
 
 pub(crate) enum PaintOrLine {
-    Paint(Paint),
+    Paint(SwfFill),
     Line(SwfLineStyle),
 }
 
@@ -149,10 +190,10 @@ impl StyleLayer {
         }
     }
 
-    pub(crate) fn fill(&self) -> &Paint {
+    pub(crate) fn fill(&self) -> &SwfFill {
         match &self.fill {
             PaintOrLine::Paint(ref paint) => paint,
-            PaintOrLine::Line(line) => &line.color,
+            PaintOrLine::Line(line) => &line.fill,
         }
     }
 
@@ -181,7 +222,9 @@ impl StyleLayer {
         self.shapes.last_mut().unwrap()
     }
 
-    fn consolidate_edges(&mut self) {
+    /// Joins shape fragments into outlines. Returns false if some fill fragments couldn't be
+    /// closed.
+    fn consolidate_edges(&mut self) -> bool {
         // Reverse left fill shape fragments in place.
         {
             self.shapes
@@ -219,9 +262,7 @@ impl StyleLayer {
                     if self.is_fill() {
                         // If they didn't match before, they're probably parts of inner shapes
                         // and should be reversed again so they have correct winding
-                        let unclosed = find_matches(0, &mut unmatched_pieces, true);
-                        // If it's a shape we should always be able to close it.
-                        debug_assert!(unclosed.is_none());
+                        find_matches(0, &mut unmatched_pieces, true);
                     }
                     for dropped in &mut unmatched_pieces {
                         dropped.reverse();
@@ -232,6 +273,9 @@ impl StyleLayer {
                 // need to figure out why this happens.
             }
         }
+
+        // If it's a shape we should always be able to close it, even if it's a single fragment.
+        !self.is_fill() || self.shapes.iter().all(|shape| shape.is_closed())
     }
 }
 
@@ -242,22 +286,8 @@ fn get_new_styles<'a>(
 ) -> impl Iterator<Item=PaintOrLine> + 'a {
     // This enforces the order that fills and line groupings are added in.
     // Fills always come first.
-    fills.iter().filter_map(|fill_style| {
-        match fill_style {
-            FillStyle::Solid(
-                fill_styles::Solid {
-                    color: StraightSRgba8 {
-                        r,
-                        g,
-                        b,
-                        a
-                    }
-                }
-            ) => Some(PaintOrLine::Paint(Paint::Color(ColorU { r: *r, g: *g, b: *b, a: *a }))),
-            _ => unimplemented!("Unimplemented fill style")
-        }
-    }).chain(
-        lines.iter().filter_map(|LineStyle {
+    fills.iter().map(|fill_style| PaintOrLine::Paint(decode_fill(fill_style))).chain(
+        lines.iter().map(|LineStyle {
             width,
             fill,
             join,
@@ -272,50 +302,146 @@ fn get_new_styles<'a>(
             */
             ..
         }| {
-            if let FillStyle::Solid(fill_styles::Solid {
-                color: StraightSRgba8 {
-                    r,
-                    g,
-                    b,
-                    a
-                }
-            }) = fill {
-                // NOTE: PathFinder doesn't support different cap styles for start and end of
-                // strokes, so lets assume that they're always the same for the inputs we care about.
-                // Alternately, we split a line in two with a diff cap style for each.
-                // assert_eq!(start_cap, end_cap);
-                Some(PaintOrLine::Line(SwfLineStyle {
-                    width: Twips(*width as i32),
-                    color: Paint::Color(ColorU { r: *r, g: *g, b: *b, a: *a }),
-                    join: match join {
-                        JoinStyle::Bevel => LineJoin::Bevel,
-                        JoinStyle::Round => LineJoin::Round,
-                        JoinStyle::Miter(join_styles::Miter { limit }) => {
-                            LineJoin::Miter(*limit as f32)
-                        },
-                    },
-                    cap: match start_cap {
-                        CapStyle::None => LineCap::Butt,
-                        CapStyle::Square => LineCap::Square,
-                        CapStyle::Round => LineCap::Round,
+            // NOTE: PathFinder doesn't support different cap styles for start and end of
+            // strokes, so lets assume that they're always the same for the inputs we care about.
+            // Alternately, we split a line in two with a diff cap style for each.
+            // assert_eq!(start_cap, end_cap);
+            PaintOrLine::Line(SwfLineStyle {
+                width: Twips(*width as i32),
+                fill: decode_fill(fill),
+                join: match join {
+                    JoinStyle::Bevel => LineJoin::Bevel,
+                    JoinStyle::Round => LineJoin::Round,
+                    JoinStyle::Miter(join_styles::Miter { limit }) => {
+                        LineJoin::Miter(*limit as f32)
                     },
-                }))
-            } else {
-                unimplemented!("unimplemented line fill style");
-            }
+                },
+                cap: match start_cap {
+                    CapStyle::None => LineCap::Butt,
+                    CapStyle::Square => LineCap::Square,
+                    CapStyle::Round => LineCap::Round,
+                },
+            })
         })
     )
 }
 
-pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
+pub(crate) fn decode_fill(fill_style: &FillStyle) -> SwfFill {
+    match fill_style {
+        FillStyle::Solid(fill_styles::Solid { color }) => SwfFill::Color(decode_color(color)),
+        FillStyle::LinearGradient(fill_styles::LinearGradient { matrix, gradient }) => {
+            let half_length = GRADIENT_SQUARE_HALF_LENGTH.as_f32();
+            let line = LineSegment2F::new(Vector2F::new(-half_length, 0.0),
+                                          Vector2F::new(half_length, 0.0));
+            decode_gradient(Gradient::linear(line), matrix, gradient)
+        }
+        FillStyle::RadialGradient(fill_styles::RadialGradient { matrix, gradient }) => {
+            let radius = GRADIENT_SQUARE_HALF_LENGTH.as_f32();
+            let line = LineSegment2F::new(Vector2F::default(), Vector2F::default());
+            decode_gradient(Gradient::radial(line, 0.0, radius), matrix, gradient)
+        }
+        FillStyle::FocalGradient(fill_styles::FocalGradient {
+            matrix,
+            gradient,
+            focal_point,
+        }) => {
+            // The focal point is an 8.8 fixed point fraction of the radius along the x axis.
+            let radius = GRADIENT_SQUARE_HALF_LENGTH.as_f32();
+            let focal_point = Vector2F::new(focal_point.epsilons as f32 / 256.0 * radius, 0.0);
+            let line = LineSegment2F::new(focal_point, Vector2F::default());
+            decode_gradient(Gradient::radial(line, 0.0, radius), matrix, gradient)
+        }
+        FillStyle::Bitmap(fill_styles::Bitmap { bitmap_id, matrix, repeating, smoothed }) => {
+            // Bitmap matrices map bitmap pixels to twips, rather than to pixels.
+            let transform = matrix_to_transform(matrix) *
+                Transform2F::from_uniform_scale(Twips(1).as_f32());
+            SwfFill::Bitmap(SwfBitmapFill {
+                bitmap_id: *bitmap_id,
+                transform,
+                repeating: *repeating,
+                smoothed: *smoothed,
+            })
+        }
+    }
+}
+
+fn decode_gradient(mut gradient: Gradient,
+                   matrix: &Matrix,
+                   swf_gradient: &swf_types::Gradient)
+                   -> SwfFill {
+    let stops = swf_gradient.colors.iter().map(|stop| {
+        ColorStop::new(decode_color(&stop.color), stop.ratio as f32 / 255.0)
+    }).collect::<Vec<_>>();
+
+    match swf_gradient.color_space {
+        ColorSpace::SRgb => {
+            for stop in stops {
+                gradient.add_color_stop(stop);
+            }
+        }
+        ColorSpace::LinearRgb => {
+            for pair in stops.windows(2) {
+                let (from, to) = (pair[0], pair[1]);
+                for step in 0..LINEAR_RGB_SUBDIVISIONS {
+                    let t = step as f32 / LINEAR_RGB_SUBDIVISIONS as f32;
+                    let color = srgb_to_linear(from.color).lerp(srgb_to_linear(to.color), t);
+                    let offset = from.offset + (to.offset - from.offset) * t;
+                    gradient.add_color_stop(ColorStop::new(linear_to_srgb(color), offset));
+                }
+            }
+            if let Some(&last_stop) = stops.last() {
+                gradient.add_color_stop(last_stop);
+            }
+        }
+    }
+
+    SwfFill::Gradient(SwfGradientFill {
+        gradient,
+        transform: matrix_to_transform(matrix),
+        spread: swf_gradient.spread,
+    })
+}
+
+pub(crate) fn decode_color(color: &StraightSRgba8) -> ColorU {
+    let StraightSRgba8 { r, g, b, a } = *color;
+    ColorU { r, g, b, a }
+}
+
+fn srgb_to_linear(color: ColorU) -> ColorF {
+    let convert = |value: f32| {
+        if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+    };
+    let color = color.to_f32();
+    ColorF::new(convert(color.r()), convert(color.g()), convert(color.b()), color.a())
+}
+
+fn linear_to_srgb(color: ColorF) -> ColorU {
+    let convert = |value: f32| {
+        if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 }
+    };
+    ColorF::new(convert(color.r()), convert(color.g()), convert(color.b()), color.a()).to_u8()
+}
+
+pub(crate) fn decode_shape(shape: &DefineShape, warnings: &mut Vec<SwfWarning>)
+                           -> Result<GraphicLayers, SwfError> {
     let DefineShape {
         shape,
-        // id,
+        id,
         // has_fill_winding, NOTE(jon): Could be important for some inputs?
         // has_non_scaling_strokes,
         // has_scaling_strokes,
         ..
     } = shape;
+    decode_shape_records(*id, &shape.initial_styles, &shape.records, warnings)
+}
+
+/// Decodes the edges of a shape, such as a shape symbol or a font glyph, that belongs to the
+/// symbol with the given id.
+pub(crate) fn decode_shape_records(id: SymbolId,
+                                   initial_styles: &ShapeStyles,
+                                   records: &[ShapeRecord],
+                                   warnings: &mut Vec<SwfWarning>)
+                                   -> Result<GraphicLayers, SwfError> {
     let mut graphic = GraphicLayers::new();
     let mut current_line_style = None;
     let mut current_left_fill = None;
@@ -327,15 +453,21 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
     let mut both_fills_same = false;
     let mut both_fills_set_and_same = false;
 
+    let bad_style_id = |style_id: usize| SwfError::BadStyleId { symbol_id: id, style_id };
+
+    if has_unsupported_line_styles(&initial_styles.line) {
+        warnings.push(SwfWarning::UnsupportedStyle(id));
+    }
+
     // Create style groups for initially specified fills and lines.
-    for fills_or_line in get_new_styles(&shape.initial_styles.fill, &shape.initial_styles.line) {
+    for fills_or_line in get_new_styles(&initial_styles.fill, &initial_styles.line) {
         match fills_or_line {
             PaintOrLine::Paint(fill) => graphic.begin_fill_style(fill),
             PaintOrLine::Line(line) => graphic.begin_line_style(line),
         }
     }
 
-    for record in &shape.records {
+    for record in records {
         match record {
             ShapeRecord::StyleChange(
                 shape_records::StyleChange {
@@ -349,8 +481,13 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
                 // Start a whole new style grouping.
                 if let Some(new_style) = new_styles {
                     // Consolidate current style grouping and begin a new one.
-                    graphic.end_style_group();
+                    if !graphic.end_style_group() {
+                        warnings.push(SwfWarning::UnclosedFill(id));
+                    }
                     graphic.begin_style_group();
+                    if has_unsupported_line_styles(&new_style.line) {
+                        warnings.push(SwfWarning::UnsupportedStyle(id));
+                    }
                     for fills_or_line in get_new_styles(&new_style.fill, &new_style.line) {
                         match fills_or_line {
                             PaintOrLine::Paint(fill) => graphic.begin_fill_style(fill),
@@ -367,7 +504,7 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
                         current_right_fill = Some(*fill_id);
                         graphic
                             .with_fill_style_mut(*fill_id)
-                            .unwrap()
+                            .ok_or_else(|| bad_style_id(*fill_id))?
                             .push_new_shape(LineDirection::Right);
                     }
                 }
@@ -379,7 +516,7 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
                         current_left_fill = Some(*fill_id);
                         graphic
                             .with_fill_style_mut(*fill_id)
-                            .unwrap()
+                            .ok_or_else(|| bad_style_id(*fill_id))?
                             .push_new_shape(LineDirection::Left);
                     }
                 }
@@ -397,7 +534,7 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
                         current_line_style = Some(*style_id);
                         graphic
                             .with_line_style_mut(*style_id)
-                            .unwrap()
+                            .ok_or_else(|| bad_style_id(*style_id))?
                             .push_new_shape(LineDirection::Right);
                     }
                 }
@@ -413,13 +550,13 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
                     if let Some(current_right_fill) = current_right_fill {
                         graphic
                             .with_fill_style_mut(current_right_fill)
-                            .unwrap()
+                            .ok_or_else(|| bad_style_id(current_right_fill))?
                             .push_new_shape(LineDirection::Right);
                     }
                     if let Some(current_left_fill) = current_left_fill {
                         graphic
                             .with_fill_style_mut(current_left_fill)
-                            .unwrap()
+                            .ok_or_else(|| bad_style_id(current_left_fill))?
                             .push_new_shape(LineDirection::Left);
                     }
                     if let Some(current_line_style) = current_line_style {
@@ -427,7 +564,7 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
                         // fill directions?
                         graphic
                             .with_line_style_mut(current_line_style)
-                            .unwrap()
+                            .ok_or_else(|| bad_style_id(current_line_style))?
                             .push_new_shape(LineDirection::Right);
                     }
                 }
@@ -438,7 +575,7 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
                     control_delta,
                 }
             ) => {
-                let from = prev_pos.unwrap();
+                let from = prev_pos.ok_or(SwfError::MalformedEdges(id))?;
                 let to = Point2 {
                     x: from.x + Twips(delta.x),
                     y: from.y + Twips(delta.y)
@@ -462,7 +599,7 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
                         if let Some(fill_id) = fill_id {
                             graphic
                                 .with_fill_style_mut(*fill_id)
-                                .unwrap()
+                                .ok_or_else(|| bad_style_id(*fill_id))?
                                 .current_shape_mut()
                                 .add_line_segment(new_segment);
                         }
@@ -480,7 +617,7 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
                         if let Some(fill_id) = fill_id {
                             graphic
                                 .with_fill_style_mut(*fill_id)
-                                .unwrap()
+                                .ok_or_else(|| bad_style_id(*fill_id))?
                                 .push_new_shape(*direction);
                         }
                     }
@@ -488,7 +625,7 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
                 if let Some(current_line_style) = current_line_style {
                     graphic
                         .with_line_style_mut(current_line_style)
-                        .unwrap()
+                        .ok_or_else(|| bad_style_id(current_line_style))?
                         .current_shape_mut()
                         .add_line_segment(new_segment);
                 }
@@ -499,8 +636,18 @@ pub(crate) fn decode_shape(shape: &DefineShape) -> GraphicLayers {
     // possible and forming closed shapes.  In swf, all filled shapes should always be closed,
     // so there will always be a solution for joining shape line segments together so that
     // the start point and end point are coincident.
-    graphic.end_style_group();
-    graphic
+    if !graphic.end_style_group() {
+        warnings.push(SwfWarning::UnclosedFill(id));
+    }
+    Ok(graphic)
+}
+
+// Pathfinder can't draw different caps at either end of a stroke, and always scales strokes with
+// their shapes.
+fn has_unsupported_line_styles(lines: &[LineStyle]) -> bool {
+    lines.iter().any(|line| {
+        line.start_cap != line.end_cap || line.no_h_scale || line.no_v_scale || line.no_close
+    })
 }
 
 fn find_matches(
@@ -569,7 +716,7 @@ impl GraphicLayers {
         self.base_layer_offset = self.style_layers.len();
     }
 
-    fn begin_fill_style(&mut self, fill: Paint) {
+    fn begin_fill_style(&mut self, fill: SwfFill) {
         self.style_layers.push(StyleLayer { fill: PaintOrLine::Paint(fill), shapes: Vec::new() })
     }
 
@@ -581,27 +728,189 @@ impl GraphicLayers {
     }
 
     fn with_fill_style_mut(&mut self, fill_id: usize) -> Option<&mut StyleLayer> {
+        // Fills come before lines in each style group.
+        let fill_count = self.stroke_layer_offset.unwrap_or(self.style_layers.len()) -
+            self.base_layer_offset;
+        if fill_id == 0 || fill_id > fill_count {
+            return None;
+        }
         self.style_layers.get_mut(self.base_layer_offset + fill_id - 1)
     }
 
     fn with_line_style_mut(&mut self, line_id: usize) -> Option<&mut StyleLayer> {
-        self.style_layers.get_mut((self.stroke_layer_offset.unwrap() + line_id) - 1)
+        if line_id == 0 {
+            return None;
+        }
+        self.style_layers.get_mut((self.stroke_layer_offset? + line_id) - 1)
     }
 
     pub(crate) fn layers(&self) -> &Vec<StyleLayer> {
         &self.style_layers
     }
 
-    fn end_style_group(&mut self) {
+    /// Consolidates the edges of the current style group. Returns false if some fills couldn't be
+    /// closed.
+    fn end_style_group(&mut self) -> bool {
+        let mut closed = true;
         for style_layer in &mut self.style_layers[self.base_layer_offset..] {
-            // There can be an unused style group at the end of each layer, which we should remove.
-            if let Some(last) = style_layer.shapes().last() {
-                if last.len() == 0 {
-                    style_layer.shapes_mut().pop();
+            // Style changes that aren't followed by any edges leave unused fragments behind, which
+            // we should remove before trying to join the others up.
+            style_layer.shapes_mut().retain(|shape| shape.len() != 0);
+            closed &= style_layer.consolidate_edges();
+        }
+        closed
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{SwfError, SwfWarning, Twips, Point2};
+    use pathfinder_color::ColorU;
+    use swf_types::{fill_styles, shape_records, CapStyle, FillStyle, JoinStyle, LineStyle};
+    use swf_types::{ShapeRecord, ShapeStyles, StraightSRgba8, Vector2D};
+    use super::{GraphicLayers, LineDirection, LineSegment, Shape, SwfFill};
+
+    fn solid(r: u8) -> FillStyle {
+        FillStyle::Solid(fill_styles::Solid { color: StraightSRgba8 { r, g: 0, b: 0, a: 255 } })
+    }
+
+    fn line(start_cap: CapStyle, end_cap: CapStyle) -> LineStyle {
+        LineStyle {
+            width: 20,
+            start_cap,
+            end_cap,
+            join: JoinStyle::Round,
+            no_h_scale: false,
+            no_v_scale: false,
+            no_close: false,
+            pixel_hinting: false,
+            fill: solid(0),
+        }
+    }
+
+    fn style_change(move_to: Option<(i32, i32)>,
+                    right_fill: Option<usize>,
+                    line_style: Option<usize>)
+                    -> ShapeRecord {
+        ShapeRecord::StyleChange(shape_records::StyleChange {
+            move_to: move_to.map(|(x, y)| Vector2D { x, y }),
+            left_fill: None,
+            right_fill,
+            line_style,
+            new_styles: None,
+        })
+    }
+
+    fn edge(x: i32, y: i32) -> ShapeRecord {
+        ShapeRecord::Edge(shape_records::Edge { delta: Vector2D { x, y }, control_delta: None })
+    }
+
+    fn square_edges() -> Vec<ShapeRecord> {
+        vec![edge(100, 0), edge(0, 100), edge(-100, 0), edge(0, -100)]
+    }
+
+    fn segment(from: (i32, i32), to: (i32, i32)) -> LineSegment {
+        LineSegment {
+            from: Point2 { x: Twips(from.0), y: Twips(from.1) },
+            to: Point2 { x: Twips(to.0), y: Twips(to.1) },
+            ctrl: None,
+        }
+    }
+
+    fn decode(styles: &ShapeStyles, records: &[ShapeRecord])
+              -> (Result<GraphicLayers, SwfError>, Vec<SwfWarning>) {
+        let mut warnings = vec![];
+        let result = super::decode_shape_records(1, styles, records, &mut warnings);
+        (result, warnings)
+    }
+
+    #[test]
+    fn test_consecutive_style_changes_leave_no_empty_fragments() {
+        let styles = ShapeStyles { fill: vec![solid(255), solid(0)], line: vec![] };
+        let mut records = vec![style_change(Some((0, 0)), Some(1), None),
+                               style_change(Some((0, 0)), Some(2), None),
+                               style_change(Some((0, 0)), Some(1), None)];
+        records.extend(square_edges());
+
+        let (graphic, warnings) = decode(&styles, &records);
+        let graphic = graphic.unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(graphic.layers()[0].shapes().len(), 1);
+        assert!(graphic.layers()[0].shapes()[0].is_closed());
+        assert!(graphic.layers()[1].shapes().is_empty());
+    }
+
+    #[test]
+    fn test_empty_fragments_are_dropped_before_joining() {
+        let mut graphic = GraphicLayers::new();
+        graphic.begin_fill_style(SwfFill::Color(ColorU::black()));
+        let fragments = [None,
+                         Some(vec![segment((0, 0), (100, 0)), segment((100, 0), (100, 100))]),
+                         None,
+                         Some(vec![segment((100, 100), (0, 100)), segment((0, 100), (0, 0))]),
+                         None];
+        {
+            let style_layer = graphic.with_fill_style_mut(1).unwrap();
+            for fragment in &fragments {
+                let mut shape = Shape::new_with_direction(LineDirection::Right);
+                for &segment in fragment.iter().flatten() {
+                    shape.add_line_segment(segment);
                 }
+                style_layer.shapes_mut().push(shape);
             }
-            style_layer.consolidate_edges();
         }
+
+        assert!(graphic.end_style_group());
+        let shapes = graphic.layers()[0].shapes();
+        assert_eq!(shapes.len(), 1);
+        assert_eq!(shapes[0].len(), 4);
+        assert!(shapes[0].is_closed());
+    }
+
+    #[test]
+    fn test_undefined_styles_are_errors() {
+        let styles = ShapeStyles { fill: vec![solid(255)], line: vec![] };
+        let (result, _) = decode(&styles, &[style_change(Some((0, 0)), Some(2), None)]);
+        assert_eq!(result.err(), Some(SwfError::BadStyleId { symbol_id: 1, style_id: 2 }));
+        let (result, _) = decode(&styles, &[style_change(Some((0, 0)), None, Some(1))]);
+        assert_eq!(result.err(), Some(SwfError::BadStyleId { symbol_id: 1, style_id: 1 }));
     }
-}
 
+    #[test]
+    fn test_edge_before_move_to_is_malformed() {
+        let styles = ShapeStyles { fill: vec![solid(255)], line: vec![] };
+        let (result, _) = decode(&styles, &square_edges());
+        assert_eq!(result.err(), Some(SwfError::MalformedEdges(1)));
+    }
+
+    #[test]
+    fn test_unclosed_fill_warns() {
+        let styles = ShapeStyles { fill: vec![solid(255)], line: vec![] };
+        let mut records = vec![style_change(Some((0, 0)), Some(1), None)];
+        records.extend(square_edges().into_iter().take(3));
+        let (result, warnings) = decode(&styles, &records);
+        assert!(result.is_ok());
+        assert_eq!(warnings, [SwfWarning::UnclosedFill(1)]);
+    }
+
+    #[test]
+    fn test_mismatched_line_caps_warn() {
+        let styles = ShapeStyles {
+            fill: vec![],
+            line: vec![line(CapStyle::Round, CapStyle::Square)],
+        };
+        let mut records = vec![style_change(Some((0, 0)), None, Some(1))];
+        records.extend(square_edges());
+        let (result, warnings) = decode(&styles, &records);
+        assert!(result.is_ok());
+        assert_eq!(warnings, [SwfWarning::UnsupportedStyle(1)]);
+
+        let styles = ShapeStyles {
+            fill: vec![],
+            line: vec![line(CapStyle::Round, CapStyle::Round)],
+        };
+        let (_, warnings) = decode(&styles, &records);
+        assert!(warnings.is_empty());
+    }
+}
@@ -0,0 +1,542 @@
+// pathfinder/swf/src/text.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Embedded fonts, and static and dynamic text laid out with them.
+
+use crate::shapes::{self, GraphicLayers};
+use crate::{matrix_to_transform, SwfError, SwfWarning, Symbol, SymbolId, SymbolLibrary, Twips};
+
+use pathfinder_color::ColorU;
+use pathfinder_geometry::transform2d::Transform2F;
+use pathfinder_geometry::vector::Vector2F;
+use swf_types::tags::{DefineDynamicText, DefineFont, DefineGlyphFont, DefineText};
+use swf_types::text::{EmSquareSize, FontLayout, TextAlignment};
+use swf_types::{fill_styles, FillStyle, Glyph, ShapeStyles, StraightSRgba8};
+
+// The gap that Flash leaves between the bounds of a text field and its text.
+const TEXT_FIELD_GUTTER: Twips = Twips(40);
+
+// The font size of text fields that don't specify one.
+const DEFAULT_FONT_SIZE: Twips = Twips(240);
+
+pub(crate) struct Font {
+    glyphs: Vec<GraphicLayers>,
+    // The size of the EM square that glyph coordinates are given in, in twips.
+    em_square_size: f32,
+    // The UTF-16 code unit of each glyph, for text fields.
+    code_units: Vec<u16>,
+    layout: Option<FontLayout>,
+}
+
+impl Font {
+    #[inline]
+    pub(crate) fn glyph(&self, index: usize) -> Option<&GraphicLayers> {
+        self.glyphs.get(index)
+    }
+
+    // Returns the index of the glyph for the given character, if the font has one.
+    fn glyph_index(&self, character: char) -> Option<usize> {
+        let mut code_units = [0; 2];
+        match *character.encode_utf16(&mut code_units) {
+            [code_unit] => self.code_units.iter().position(|&other| other == code_unit),
+            _ => None,
+        }
+    }
+}
+
+/// Text that has been laid out into glyphs.
+pub(crate) struct Text {
+    glyphs: Vec<TextGlyph>,
+}
+
+pub(crate) struct TextGlyph {
+    font_id: SymbolId,
+    glyph_index: usize,
+    /// Maps glyph coordinates into the coordinate space of the text.
+    pub(crate) transform: Transform2F,
+    pub(crate) color: ColorU,
+}
+
+impl Text {
+    /// Returns the outline of each glyph that can be drawn, along with where to draw it.
+    pub(crate) fn glyphs<'a>(&'a self, library: &'a SymbolLibrary)
+                             -> impl Iterator<Item = (&'a GraphicLayers, &'a TextGlyph)> + 'a {
+        self.glyphs.iter().filter_map(move |glyph| {
+            match library.symbol(glyph.font_id) {
+                Some(Symbol::Font(font)) => font.glyph(glyph.glyph_index).map(|outline| {
+                    (outline, glyph)
+                }),
+                _ => None,
+            }
+        })
+    }
+}
+
+pub(crate) fn decode_font(font: &DefineFont, warnings: &mut Vec<SwfWarning>)
+                          -> Result<Font, SwfError> {
+    let em_square_size = match font.em_square_size {
+        EmSquareSize::EmSquareSize1024 => 1024.0,
+        EmSquareSize::EmSquareSize20480 => 20480.0,
+    };
+    // Fonts without glyphs are device fonts, which we have no outlines for.
+    let glyphs = match font.glyphs {
+        Some(ref glyphs) => decode_glyphs(font.id, glyphs, warnings)?,
+        None => vec![],
+    };
+    Ok(Font {
+        glyphs,
+        em_square_size,
+        code_units: font.code_units.clone().unwrap_or_default(),
+        layout: font.layout.clone(),
+    })
+}
+
+pub(crate) fn decode_glyph_font(font: &DefineGlyphFont, warnings: &mut Vec<SwfWarning>)
+                                -> Result<Font, SwfError> {
+    Ok(Font {
+        glyphs: decode_glyphs(font.id, &font.glyphs, warnings)?,
+        em_square_size: 1024.0,
+        code_units: vec![],
+        layout: None,
+    })
+}
+
+fn decode_glyphs(font_id: SymbolId, glyphs: &[Glyph], warnings: &mut Vec<SwfWarning>)
+                 -> Result<Vec<GraphicLayers>, SwfError> {
+    // Glyphs are filled with fill style 1, which is implicitly defined. We fill them with black
+    // and recolor them when drawing.
+    let styles = ShapeStyles {
+        fill: vec![FillStyle::Solid(fill_styles::Solid {
+            color: StraightSRgba8 { r: 0, g: 0, b: 0, a: 255 },
+        })],
+        line: vec![],
+    };
+    glyphs.iter().map(|glyph| {
+        shapes::decode_shape_records(font_id, &styles, &glyph.records, warnings)
+    }).collect()
+}
+
+/// Lays out the glyph records of a `DefineText` tag.
+pub(crate) fn decode_text(text: &DefineText,
+                          library: &SymbolLibrary,
+                          warnings: &mut Vec<SwfWarning>)
+                          -> Text {
+    let text_transform = matrix_to_transform(&text.matrix);
+    let mut glyphs = vec![];
+
+    let (mut font_id, mut font_size, mut color) = (None, 0, ColorU::black());
+    let (mut x, mut y) = (0, 0);
+    for record in &text.records {
+        if record.font_id.is_some() {
+            font_id = record.font_id;
+        }
+        if let Some(size) = record.font_size {
+            font_size = size;
+        }
+        if let Some(ref record_color) = record.color {
+            color = shapes::decode_color(record_color);
+        }
+
+        // NOTE: Offsets are optional in the file but not in `swf_types`, where missing
+        // offsets are zero. A vertical offset starts a new line, and otherwise a zero offset
+        // continues from the end of the previous record.
+        //
+        // FIXME: A record that only moves the baseline, such as for superscript, can't be told
+        // apart from one that starts a line at an X offset of 0, so it restarts at the left edge
+        // too. Telling them apart needs the flags of the record, which `swf_parser` drops.
+        if record.offset_y != 0 {
+            x = record.offset_x as i32;
+            y = record.offset_y as i32;
+        } else if record.offset_x != 0 {
+            x = record.offset_x as i32;
+        }
+
+        let font_id = match font_id {
+            Some(font_id) => font_id,
+            None => continue,
+        };
+        let font = match library.symbol(font_id) {
+            Some(Symbol::Font(font)) => font,
+            _ => {
+                let warning = SwfWarning::UnknownSymbol(font_id);
+                if !warnings.contains(&warning) {
+                    warnings.push(warning);
+                }
+                continue;
+            }
+        };
+
+        let scale = font_size as f32 / font.em_square_size;
+        for entry in &record.entries {
+            let origin = Vector2F::new(Twips(x).as_f32(), Twips(y).as_f32());
+            glyphs.push(TextGlyph {
+                font_id,
+                glyph_index: entry.index,
+                transform: text_transform * Transform2F::from_translation(origin) *
+                    Transform2F::from_uniform_scale(scale),
+                color,
+            });
+            x += entry.advance;
+        }
+    }
+
+    Text { glyphs }
+}
+
+/// Lays out the initial text of a `DefineEditText` tag.
+///
+/// Only text fields that use embedded fonts with layout information can be drawn.
+pub(crate) fn decode_edit_text(text: &DefineDynamicText,
+                               library: &SymbolLibrary,
+                               warnings: &mut Vec<SwfWarning>)
+                               -> Text {
+    let mut glyphs = vec![];
+    let string = match text.text {
+        Some(ref string) if !string.is_empty() => string,
+        _ => return Text { glyphs },
+    };
+
+    let (font_id, font) = match text.font_id.map(|font_id| (font_id, library.symbol(font_id))) {
+        Some((font_id, Some(Symbol::Font(font)))) if text.use_glyph_font => (font_id, font),
+        _ => {
+            warnings.push(SwfWarning::UnsupportedFont(text.id));
+            return Text { glyphs };
+        }
+    };
+    let layout = match font.layout {
+        Some(ref layout) if !font.glyphs.is_empty() => layout,
+        _ => {
+            warnings.push(SwfWarning::UnsupportedFont(text.id));
+            return Text { glyphs };
+        }
+    };
+
+    let mut string = if text.html { html_to_plain_text(string) } else { string.clone() };
+    if text.password {
+        string = string.chars().map(|character| {
+            if character == '\n' { character } else { '*' }
+        }).collect();
+    }
+
+    let font_size = text.font_size.map_or(DEFAULT_FONT_SIZE, |size| Twips(size as i32));
+    let scale = font_size.0 as f32 / font.em_square_size;
+    let color = text.color.as_ref().map_or(ColorU::black(), shapes::decode_color);
+    let advance = |character: char| {
+        font.glyph_index(character)
+            .and_then(|index| layout.advances.get(index))
+            .map_or(0.0, |&advance| advance as f32 * scale)
+    };
+
+    // Measure everything in twips, as floats since glyph advances are scaled.
+    let left = (text.bounds.x_min + TEXT_FIELD_GUTTER.0) as f32 + text.margin_left as f32;
+    let right = (text.bounds.x_max - TEXT_FIELD_GUTTER.0) as f32 - text.margin_right as f32;
+    let line_height = (layout.ascent as f32 + layout.descent as f32) * scale + text.leading as f32;
+    let mut baseline = (text.bounds.y_min + TEXT_FIELD_GUTTER.0) as f32 +
+        layout.ascent as f32 * scale;
+
+    for paragraph in string.split('\n') {
+        let mut lines = vec![];
+        let mut line = String::new();
+        let mut line_width = text.indent as f32;
+        for word in paragraph.split(' ') {
+            let word_width: f32 = word.chars().map(advance).sum();
+            let space_width = if line.is_empty() { 0.0 } else { advance(' ') };
+            if text.word_wrap && !line.is_empty() &&
+                    left + line_width + space_width + word_width > right {
+                lines.push((line, line_width));
+                line = String::new();
+                line_width = 0.0;
+            } else if !line.is_empty() {
+                line.push(' ');
+                line_width += space_width;
+            }
+            line.push_str(word);
+            line_width += word_width;
+        }
+        lines.push((line, line_width));
+
+        for (line_index, (line, line_width)) in lines.into_iter().enumerate() {
+            let mut x = match text.align {
+                // TODO: Justify text.
+                TextAlignment::Left | TextAlignment::Justify => left,
+                TextAlignment::Right => right - line_width,
+                TextAlignment::Center => left + (right - left - line_width) * 0.5,
+            };
+            if line_index == 0 {
+                x += text.indent as f32;
+            }
+
+            // TODO: Apply kerning.
+            for character in line.chars() {
+                if let Some(glyph_index) = font.glyph_index(character) {
+                    let origin = Vector2F::new(x, baseline).scale(Twips(1).as_f32());
+                    glyphs.push(TextGlyph {
+                        font_id,
+                        glyph_index,
+                        transform: Transform2F::from_translation(origin) *
+                            Transform2F::from_uniform_scale(scale),
+                        color,
+                    });
+                }
+                x += advance(character);
+            }
+            baseline += line_height;
+        }
+
+        if !text.multiline {
+            break;
+        }
+    }
+
+    Text { glyphs }
+}
+
+// Reduces the HTML subset that Flash text fields support to plain text with line breaks.
+fn html_to_plain_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut chars = html.chars();
+    while let Some(character) = chars.next() {
+        match character {
+            '<' => {
+                let tag: String = chars.by_ref()
+                                       .take_while(|&character| character != '>')
+                                       .collect();
+                let name = tag.trim_end_matches('/').trim().to_ascii_lowercase();
+                if name == "br" || name == "/p" || name == "/li" {
+                    text.push('\n');
+                }
+            }
+            '&' => {
+                let entity: String = chars.by_ref()
+                                          .take_while(|&character| character != ';')
+                                          .collect();
+                match &*entity {
+                    "lt" => text.push('<'),
+                    "gt" => text.push('>'),
+                    "amp" => text.push('&'),
+                    "quot" => text.push('"'),
+                    "apos" => text.push('\''),
+                    "nbsp" => text.push(' '),
+                    _ => {}
+                }
+            }
+            '\r' => text.push('\n'),
+            character => text.push(character),
+        }
+    }
+    // Closing the last paragraph doesn't start a new line.
+    if text.ends_with('\n') {
+        text.pop();
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{SwfWarning, Symbol, SymbolLibrary};
+    use pathfinder_color::ColorU;
+    use pathfinder_geometry::vector::Vector2F;
+    use swf_types::tags::{DefineDynamicText, DefineText};
+    use swf_types::text::{FontLayout, GlyphEntry, TextAlignment, TextRecord};
+    use swf_types::{Glyph, Matrix, Rect, StraightSRgba8};
+    use super::{Font, Text, decode_edit_text, decode_glyphs, decode_text, html_to_plain_text};
+
+    // A font with an empty glyph for each character, 1024 units to the EM square.
+    fn font(characters: &str, advances: &[u16]) -> Font {
+        let glyphs: Vec<Glyph> = characters.chars().map(|_| Glyph { records: vec![] }).collect();
+        Font {
+            glyphs: decode_glyphs(1, &glyphs, &mut vec![]).unwrap(),
+            em_square_size: 1024.0,
+            code_units: characters.encode_utf16().collect(),
+            layout: Some(FontLayout {
+                ascent: 800,
+                descent: 224,
+                leading: 0,
+                advances: advances.to_vec(),
+                bounds: vec![],
+                kerning: vec![],
+            }),
+        }
+    }
+
+    fn library(font: Font) -> SymbolLibrary {
+        let mut library = SymbolLibrary::new();
+        library.add_symbol(1, Symbol::Font(font)).unwrap();
+        library
+    }
+
+    fn record(font_id: Option<u16>,
+              color: Option<ColorU>,
+              offset: (i16, i16),
+              entries: &[(usize, i32)])
+              -> TextRecord {
+        TextRecord {
+            font_id,
+            color: color.map(|ColorU { r, g, b, a }| StraightSRgba8 { r, g, b, a }),
+            offset_x: offset.0,
+            offset_y: offset.1,
+            font_size: font_id.map(|_| 512),
+            entries: entries.iter().map(|&(index, advance)| {
+                GlyphEntry { index, advance }
+            }).collect(),
+        }
+    }
+
+    // The position of each glyph in twips, along with its index and color.
+    fn layout(text: &Text) -> Vec<(Vector2F, usize, ColorU)> {
+        text.glyphs.iter().map(|glyph| {
+            let position = (glyph.transform.vector * Vector2F::splat(20.0)).round();
+            (position, glyph.glyph_index, glyph.color)
+        }).collect()
+    }
+
+    #[test]
+    fn test_text_records() {
+        let library = library(font("ab", &[512, 512]));
+        let (red, blue) = (ColorU::new(255, 0, 0, 255), ColorU::new(0, 0, 255, 255));
+        let text = DefineText {
+            id: 2,
+            bounds: Rect { x_min: 0, x_max: 2000, y_min: 0, y_max: 2000 },
+            matrix: Matrix::default(),
+            records: vec![
+                record(Some(1), Some(red), (100, 200), &[(0, 100), (1, 150)]),
+                // Records without offsets continue where the last one left off, and keep its
+                // font and color unless they change them.
+                record(None, Some(blue), (0, 0), &[(0, 50)]),
+                record(None, None, (0, 500), &[(1, 10)]),
+                record(None, None, (40, 0), &[(0, 10)]),
+            ],
+        };
+        let mut warnings = vec![];
+        let text = decode_text(&text, &library, &mut warnings);
+        assert!(warnings.is_empty());
+        assert_eq!(layout(&text), [
+            (Vector2F::new(100.0, 200.0), 0, red),
+            (Vector2F::new(200.0, 200.0), 1, red),
+            (Vector2F::new(350.0, 200.0), 0, blue),
+            (Vector2F::new(0.0, 500.0), 1, blue),
+            (Vector2F::new(40.0, 500.0), 0, blue),
+        ]);
+
+        // Glyphs are scaled from the EM square to the font size.
+        assert_eq!(text.glyphs[0].transform.matrix.m11(), 0.5);
+    }
+
+    #[test]
+    fn test_text_with_unknown_font() {
+        let library = SymbolLibrary::new();
+        let text = DefineText {
+            id: 2,
+            bounds: Rect { x_min: 0, x_max: 2000, y_min: 0, y_max: 2000 },
+            matrix: Matrix::default(),
+            records: vec![
+                record(Some(1), None, (0, 0), &[(0, 100)]),
+                record(None, None, (0, 0), &[(0, 100)]),
+            ],
+        };
+        let mut warnings = vec![];
+        let text = decode_text(&text, &library, &mut warnings);
+        assert!(text.glyphs.is_empty());
+        assert_eq!(warnings, [SwfWarning::UnknownSymbol(1)]);
+    }
+
+    // A text field 2000 twips wide inside its gutters, drawn at the size of the EM square.
+    fn edit_text(string: &str, align: TextAlignment) -> DefineDynamicText {
+        DefineDynamicText {
+            id: 2,
+            bounds: Rect { x_min: 0, x_max: 2080, y_min: 0, y_max: 4000 },
+            word_wrap: true,
+            multiline: true,
+            password: false,
+            readonly: true,
+            auto_size: false,
+            no_select: true,
+            border: false,
+            was_static: false,
+            html: false,
+            use_glyph_font: true,
+            font_id: Some(1),
+            font_class: None,
+            font_size: Some(1024),
+            color: Some(StraightSRgba8 { r: 255, g: 0, b: 0, a: 255 }),
+            max_length: None,
+            align,
+            margin_left: 0,
+            margin_right: 0,
+            indent: 0,
+            leading: 0,
+            variable_name: None,
+            text: Some(string.to_owned()),
+        }
+    }
+
+    // The position of each glyph in twips.
+    fn positions(text: &Text) -> Vec<(f32, f32)> {
+        layout(text).iter().map(|(position, _, _)| (position.x(), position.y())).collect()
+    }
+
+    #[test]
+    fn test_edit_text_wrapping() {
+        let library = library(font("ab ", &[256, 512, 128]));
+
+        // The third word doesn't fit on the first line. Lines start inside the gutter, a line
+        // height apart.
+        let text = decode_edit_text(&edit_text("aa bb aa", TextAlignment::Left),
+                                    &library,
+                                    &mut vec![]);
+        assert_eq!(positions(&text), [
+            (40.0, 840.0), (296.0, 840.0), (552.0, 840.0), (680.0, 840.0), (1192.0, 840.0),
+            (40.0, 1864.0), (296.0, 1864.0),
+        ]);
+        assert!(text.glyphs.iter().all(|glyph| glyph.color == ColorU::new(255, 0, 0, 255)));
+
+        // Line breaks start new paragraphs, unless the field is a single line.
+        let mut field = edit_text("a\nb", TextAlignment::Left);
+        let text = decode_edit_text(&field, &library, &mut vec![]);
+        assert_eq!(positions(&text), [(40.0, 840.0), (40.0, 1864.0)]);
+        field.multiline = false;
+        let text = decode_edit_text(&field, &library, &mut vec![]);
+        assert_eq!(positions(&text), [(40.0, 840.0)]);
+    }
+
+    #[test]
+    fn test_edit_text_alignment() {
+        let library = library(font("ab ", &[256, 512, 128]));
+        let first_x = |align: TextAlignment| {
+            let text = decode_edit_text(&edit_text("ab", align), &library, &mut vec![]);
+            positions(&text)[0].0
+        };
+        assert_eq!(first_x(TextAlignment::Left), 40.0);
+        assert_eq!(first_x(TextAlignment::Right), 2040.0 - 768.0);
+        assert_eq!(first_x(TextAlignment::Center), 40.0 + (2000.0 - 768.0) * 0.5);
+    }
+
+    #[test]
+    fn test_edit_text_needs_font_layout() {
+        let mut font = font("a", &[256]);
+        font.layout = None;
+        let library = library(font);
+        let mut warnings = vec![];
+        let text = decode_edit_text(&edit_text("a", TextAlignment::Left),
+                                    &library,
+                                    &mut warnings);
+        assert!(text.glyphs.is_empty());
+        assert_eq!(warnings, [SwfWarning::UnsupportedFont(2)]);
+    }
+
+    #[test]
+    fn test_html_to_plain_text() {
+        assert_eq!(html_to_plain_text("<p align=\"left\">Hello&nbsp;<b>world</b></p>\
+                                       <p>a &lt; b<br/>c &amp; d&unknown;</p>"),
+                   "Hello world\na < b\nc & d");
+        assert_eq!(html_to_plain_text("<LI>one</LI><li>two</li>"), "one\ntwo");
+        assert_eq!(html_to_plain_text("one\rtwo"), "one\ntwo");
+    }
+}
@@ -8,35 +8,397 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-struct PlacementInfo {
-    symbol_id: u32,
-    translate_x: Twips,
-    translate_y: Twips,
+use crate::{matrix_to_transform, SymbolId};
+
+use pathfinder_color::ColorU;
+use pathfinder_geometry::transform2d::Transform2F;
+use std::collections::BTreeMap;
+use std::ops::Mul;
+use swf_types::tags::{PlaceObject, RemoveObject};
+use swf_types::ColorTransformWithAlpha;
+
+/// A color transform as specified by a `PlaceObject` tag.
+///
+/// Each channel is multiplied by `mult` and then offset by `add`, where `add` is in 0-255 units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ColorTransform {
+    mult: [f32; 4],
+    add: [f32; 4],
+}
+
+impl ColorTransform {
+    pub(crate) fn identity() -> ColorTransform {
+        ColorTransform { mult: [1.0; 4], add: [0.0; 4] }
+    }
+
+    /// Returns a transform that recolors opaque black to `color`, as used for text glyphs.
+    pub(crate) fn fill(color: ColorU) -> ColorTransform {
+        ColorTransform {
+            mult: [0.0, 0.0, 0.0, color.a as f32 / 255.0],
+            add: [color.r as f32, color.g as f32, color.b as f32, 0.0],
+        }
+    }
+
+    fn from_swf(color_transform: &ColorTransformWithAlpha) -> ColorTransform {
+        // Multiplication terms are 8.8 fixed point.
+        ColorTransform {
+            mult: [
+                color_transform.red_mult.epsilons as f32 / 256.0,
+                color_transform.green_mult.epsilons as f32 / 256.0,
+                color_transform.blue_mult.epsilons as f32 / 256.0,
+                color_transform.alpha_mult.epsilons as f32 / 256.0,
+            ],
+            add: [
+                color_transform.red_add as f32,
+                color_transform.green_add as f32,
+                color_transform.blue_add as f32,
+                color_transform.alpha_add as f32,
+            ],
+        }
+    }
+
+    pub(crate) fn apply(&self, color: ColorU) -> ColorU {
+        let channels = [color.r, color.g, color.b, color.a];
+        let mut result = [0; 4];
+        for (index, channel) in channels.iter().enumerate() {
+            let value = *channel as f32 * self.mult[index] + self.add[index];
+            result[index] = value.clamp(0.0, 255.0) as u8;
+        }
+        ColorU::new(result[0], result[1], result[2], result[3])
+    }
+}
+
+impl Mul<ColorTransform> for ColorTransform {
+    type Output = ColorTransform;
+
+    /// Combines two color transforms, such that `other` is applied first.
+    fn mul(self, other: ColorTransform) -> ColorTransform {
+        let mut result = ColorTransform::identity();
+        for index in 0..4 {
+            result.mult[index] = self.mult[index] * other.mult[index];
+            result.add[index] = self.mult[index] * other.add[index] + self.add[index];
+        }
+        result
+    }
+}
+
+/// Where and how a symbol is placed on a single depth of the display list.
+#[derive(Clone, Debug)]
+pub(crate) struct PlacementInfo {
+    pub(crate) symbol_id: SymbolId,
+    pub(crate) depth: u16,
+    pub(crate) transform: Transform2F,
+    pub(crate) color_transform: ColorTransform,
+    pub(crate) ratio: u16,
+    /// If set, this placement isn't drawn; instead it masks all placements with depths up to and
+    /// including this one.
+    pub(crate) clip_depth: Option<u16>,
+    pub(crate) visible: bool,
+    /// The frame of the containing timeline that this instance was placed on. Sprite timelines
+    /// start playing from this frame.
+    first_frame: usize,
+    /// Whether this instance is on the display list from the first frame of the containing
+    /// timeline to the last, so that it stays the same instance when the timeline loops.
+    survives_loop: bool,
 }
 
-struct Timeline(Vec<Frame>);
+impl PlacementInfo {
+    fn new(symbol_id: SymbolId, depth: u16, first_frame: usize) -> PlacementInfo {
+        PlacementInfo {
+            symbol_id,
+            depth,
+            first_frame,
+            transform: Transform2F::default(),
+            color_transform: ColorTransform::identity(),
+            ratio: 0,
+            clip_depth: None,
+            visible: true,
+            survives_loop: false,
+        }
+    }
+
+    /// Returns the number of frames that this instance has been on the display list, when the
+    /// containing timeline has played `elapsed` frames in total.
+    ///
+    /// This is also the number of frames that a sprite placed here has played.
+    pub(crate) fn age(&self, elapsed: usize, frame_count: usize) -> usize {
+        if self.survives_loop {
+            elapsed
+        } else {
+            (elapsed % frame_count).saturating_sub(self.first_frame)
+        }
+    }
+
+    /// Returns the ratio that a morph shape placed here is shown at, from 0 for the start shape
+    /// to 1 for the end shape.
+    pub(crate) fn morph_ratio(&self) -> f32 {
+        self.ratio as f32 / 65535.0
+    }
+
+    fn update(&mut self, place_object: &PlaceObject) {
+        if let Some(ref matrix) = place_object.matrix {
+            self.transform = matrix_to_transform(matrix);
+        }
+        if let Some(ref color_transform) = place_object.color_transform {
+            self.color_transform = ColorTransform::from_swf(color_transform);
+        }
+        if let Some(ratio) = place_object.ratio {
+            self.ratio = ratio;
+        }
+        if let Some(clip_depth) = place_object.clip_depth {
+            self.clip_depth = Some(clip_depth);
+        }
+        if let Some(visible) = place_object.visible {
+            self.visible = visible;
+        }
+    }
+}
+
+/// The contents of the display list at the time of a `ShowFrame` tag, sorted back to front.
+#[derive(Clone, Debug)]
+pub(crate) struct Frame {
+    pub(crate) placements: Vec<PlacementInfo>,
+}
+
+pub(crate) struct Timeline(Vec<Frame>);
 
 impl Timeline {
-    fn first(&self) -> &Frame {
-        &self.0[0]
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub(crate) fn frame(&self, index: usize) -> Option<&Frame> {
+        self.0.get(index)
+    }
+
+    /// Returns the frame shown once the timeline has played `elapsed` frames, looping back to the
+    /// first frame after the last one.
+    #[inline]
+    pub(crate) fn looped_frame(&self, elapsed: usize) -> &Frame {
+        &self.0[elapsed % self.0.len()]
+    }
+}
+
+/// Builds a timeline by replaying display list tags in order.
+pub(crate) struct TimelineBuilder {
+    display_list: BTreeMap<u16, PlacementInfo>,
+    frames: Vec<Frame>,
+}
+
+impl TimelineBuilder {
+    pub(crate) fn new() -> TimelineBuilder {
+        TimelineBuilder { display_list: BTreeMap::new(), frames: vec![] }
     }
 
-    fn last(&self) -> &Frame {
-        &self.0[self.0.len() - 1]
+    pub(crate) fn place_object(&mut self, place_object: &PlaceObject) {
+        let depth = place_object.depth;
+        let frame_index = self.frames.len();
+        match (place_object.is_update, place_object.character_id) {
+            (true, character_id) => {
+                // Modify the existing placement, swapping out its symbol if a new one was given.
+                // A new symbol is a new instance, so its timeline starts over.
+                if let Some(placement) = self.display_list.get_mut(&depth) {
+                    if let Some(character_id) = character_id {
+                        if placement.symbol_id != character_id {
+                            placement.symbol_id = character_id;
+                            placement.first_frame = frame_index;
+                        }
+                    }
+                    placement.update(place_object);
+                }
+            }
+            (false, Some(character_id)) => {
+                let mut placement = PlacementInfo::new(character_id, depth, frame_index);
+                placement.update(place_object);
+                self.display_list.insert(depth, placement);
+            }
+            (false, None) => {
+                // A new placement without a symbol has nothing to draw.
+            }
+        }
     }
 
-    fn first_mut(&mut self) -> &mut Frame {
-        &mut self.0[0]
+    pub(crate) fn remove_object(&mut self, remove_object: &RemoveObject) {
+        self.display_list.remove(&remove_object.depth);
     }
 
-    fn last_mut(&mut self) -> &mut Frame {
-        let last = self.0.len() - 1;
-        &mut self.0[last]
+    pub(crate) fn show_frame(&mut self) {
+        let placements = self.display_list.values().cloned().collect();
+        self.frames.push(Frame { placements });
+    }
+
+    pub(crate) fn build(mut self) -> Timeline {
+        // Make sure there's always at least one frame to show.
+        if self.frames.is_empty() {
+            self.show_frame();
+        }
+
+        // Instances that were placed on the first frame and are still there on the last one
+        // aren't placed again when the timeline loops, so their sprites keep playing.
+        let last_frame = self.frames.last().unwrap();
+        let surviving_depths: Vec<u16> = last_frame.placements
+                                                   .iter()
+                                                   .filter(|placement| placement.first_frame == 0)
+                                                   .map(|placement| placement.depth)
+                                                   .collect();
+        for frame in &mut self.frames {
+            for placement in &mut frame.placements {
+                if placement.first_frame == 0 && surviving_depths.contains(&placement.depth) {
+                    placement.survives_loop = true;
+                }
+            }
+        }
+
+        Timeline(self.frames)
     }
 }
 
-struct Frame {
-    duration_frames_initial: u16,
-    duration_remaining_frames: u16,
-    placements: Vec<PlacementInfo>
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::SymbolId;
+    use pathfinder_color::ColorU;
+    use swf_types::tags::{PlaceObject, RemoveObject};
+    use super::{ColorTransform, PlacementInfo, TimelineBuilder};
+
+    /// Returns a `PlaceObject` tag that places or updates the given depth and changes nothing
+    /// else.
+    pub(crate) fn place_object(depth: u16, character_id: Option<SymbolId>, is_update: bool)
+                               -> PlaceObject {
+        PlaceObject {
+            is_update,
+            depth,
+            character_id,
+            class_name: None,
+            matrix: None,
+            color_transform: None,
+            ratio: None,
+            name: None,
+            clip_depth: None,
+            filters: None,
+            blend_mode: None,
+            bitmap_cache: None,
+            visible: None,
+            background_color: None,
+            clip_actions: None,
+        }
+    }
+
+    fn symbol_ids(timeline: &TimelineBuilder, frame_index: usize) -> Vec<SymbolId> {
+        let frame = &timeline.frames[frame_index];
+        frame.placements.iter().map(|placement| placement.symbol_id).collect()
+    }
+
+    #[test]
+    fn test_place_update_and_remove() {
+        let mut timeline = TimelineBuilder::new();
+        timeline.place_object(&place_object(2, Some(20), false));
+        timeline.place_object(&place_object(1, Some(10), false));
+        timeline.show_frame();
+
+        let mut update = place_object(1, None, true);
+        update.ratio = Some(100);
+        timeline.place_object(&update);
+        timeline.show_frame();
+
+        timeline.remove_object(&RemoveObject { depth: 2, character_id: None });
+        timeline.show_frame();
+
+        // Placements are sorted by depth, and frames keep the display list as it was.
+        assert_eq!(symbol_ids(&timeline, 0), [10, 20]);
+        assert_eq!(timeline.frames[0].placements[0].ratio, 0);
+        assert_eq!(timeline.frames[1].placements[0].ratio, 100);
+        assert_eq!(symbol_ids(&timeline, 2), [10]);
+        assert_eq!(timeline.build().len(), 3);
+    }
+
+    #[test]
+    fn test_update_with_new_character_restarts_instance() {
+        let mut timeline = TimelineBuilder::new();
+        timeline.place_object(&place_object(1, Some(10), false));
+        timeline.show_frame();
+        timeline.place_object(&place_object(1, Some(10), true));
+        timeline.show_frame();
+        timeline.place_object(&place_object(1, Some(20), true));
+        timeline.show_frame();
+
+        assert_eq!(timeline.frames[1].placements[0].first_frame, 0);
+        assert_eq!(symbol_ids(&timeline, 2), [20]);
+        assert_eq!(timeline.frames[2].placements[0].first_frame, 2);
+    }
+
+    #[test]
+    fn test_update_of_empty_depth_does_nothing() {
+        let mut timeline = TimelineBuilder::new();
+        timeline.place_object(&place_object(1, Some(10), true));
+        timeline.place_object(&place_object(2, None, false));
+        timeline.show_frame();
+        assert!(timeline.frames[0].placements.is_empty());
+    }
+
+    #[test]
+    fn test_empty_timeline_has_one_frame() {
+        let timeline = TimelineBuilder::new().build();
+        assert_eq!(timeline.len(), 1);
+        assert!(timeline.frame(0).unwrap().placements.is_empty());
+        assert!(timeline.frame(1).is_none());
+    }
+
+    #[test]
+    fn test_age_restarts_when_timeline_loops() {
+        let placement = PlacementInfo::new(10, 1, 3);
+        assert_eq!(placement.age(3, 8), 0);
+        assert_eq!(placement.age(6, 8), 3);
+        assert_eq!(placement.age(11, 8), 0);
+        assert_eq!(placement.age(14, 8), 3);
+    }
+
+    #[test]
+    fn test_age_of_instance_that_survives_loop() {
+        let mut timeline = TimelineBuilder::new();
+        timeline.place_object(&place_object(1, Some(10), false));
+        timeline.place_object(&place_object(2, Some(20), false));
+        timeline.show_frame();
+        timeline.remove_object(&RemoveObject { depth: 2, character_id: None });
+        timeline.show_frame();
+        timeline.place_object(&place_object(2, Some(20), false));
+        timeline.show_frame();
+        let timeline = timeline.build();
+
+        // Depth 1 is never removed, so it keeps playing across loops. Depth 2 is placed again on
+        // every loop.
+        let first_frame = timeline.frame(0).unwrap();
+        assert_eq!(first_frame.placements[0].age(7, 3), 7);
+        assert_eq!(first_frame.placements[1].age(6, 3), 0);
+        assert_eq!(timeline.frame(2).unwrap().placements[1].age(8, 3), 0);
+    }
+
+    #[test]
+    fn test_color_transform_apply_clamps() {
+        let color_transform = ColorTransform {
+            mult: [0.5, 2.0, 1.0, 1.0],
+            add: [10.0, 0.0, -20.0, 0.0],
+        };
+        assert_eq!(color_transform.apply(ColorU::new(100, 200, 10, 255)),
+                   ColorU::new(60, 255, 0, 255));
+        assert_eq!(ColorTransform::identity().apply(ColorU::new(1, 2, 3, 4)),
+                   ColorU::new(1, 2, 3, 4));
+    }
+
+    #[test]
+    fn test_color_transform_mul_applies_right_hand_side_first() {
+        let darken = ColorTransform { mult: [0.5; 4], add: [0.0; 4] };
+        let brighten = ColorTransform { mult: [1.0; 4], add: [20.0, 20.0, 20.0, 0.0] };
+        let color = ColorU::new(100, 100, 100, 200);
+        assert_eq!((darken * brighten).apply(color), darken.apply(brighten.apply(color)));
+        assert_eq!((darken * brighten).apply(color), ColorU::new(60, 60, 60, 100));
+        assert_eq!((brighten * darken).apply(color), ColorU::new(70, 70, 70, 100));
+    }
+
+    #[test]
+    fn test_fill_color_transform_recolors_black() {
+        let color = ColorU::new(10, 20, 30, 255);
+        assert_eq!(ColorTransform::fill(color).apply(ColorU::black()), color);
+    }
 }
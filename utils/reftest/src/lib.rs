@@ -0,0 +1,327 @@
+// pathfinder/utils/reftest/src/lib.rs
+//
+// Copyright © 2020 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Golden-image regression tests for the SVG and SWF files in `resources/`.
+//!
+//! Each file is built into a scene, rendered on the CPU, and compared against a reference PNG in
+//! `resources/reftests/`. Run the tests with `PATHFINDER_UPDATE_REFTESTS=1` to (re)generate the
+//! references. When a comparison fails, the rendered image and a diff image are written to
+//! `target/reftests/`.
+
+use image::{Rgba, RgbaImage};
+use pathfinder_color::ColorU;
+use pathfinder_geometry::rect::RectF;
+use pathfinder_geometry::vector::Vector2F;
+use pathfinder_renderer::options::BuildOptions;
+use pathfinder_renderer::scene::Scene;
+use pathfinder_svg::BuiltSVG;
+use pathfinder_swf::{draw_frame_into_scene, process_swf_tags, BuiltSWF};
+use std::env;
+use std::fmt::{Display, Formatter, Result as FormatResult};
+use std::fs;
+use std::path::{Path, PathBuf};
+use usvg::{Options as UsvgOptions, Tree};
+
+/// Set this environment variable to write new reference images instead of comparing.
+pub const UPDATE_ENV_VAR: &str = "PATHFINDER_UPDATE_REFTESTS";
+
+// The largest possible value of `color_delta()`.
+const MAX_YIQ_DELTA: f32 = 35215.0;
+
+/// How different a rendering may be from its reference before the test fails.
+#[derive(Clone, Copy, Debug)]
+pub struct Tolerance {
+    /// The largest perceptual difference between two pixels, from 0.0 to 1.0, for them to be
+    /// considered equal.
+    pub threshold: f32,
+    /// The fraction of pixels that may differ.
+    pub max_mismatched_fraction: f32,
+}
+
+impl Default for Tolerance {
+    #[inline]
+    fn default() -> Tolerance {
+        Tolerance { threshold: 0.1, max_mismatched_fraction: 0.001 }
+    }
+}
+
+/// The kind of file a reftest renders.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReftestKind {
+    SVG,
+    SWF,
+}
+
+impl ReftestKind {
+    fn directory_name(self) -> &'static str {
+        match self {
+            ReftestKind::SVG => "svg",
+            ReftestKind::SWF => "swf",
+        }
+    }
+}
+
+/// The result of comparing a rendering with its reference.
+pub struct Comparison {
+    pub mismatched_pixel_count: usize,
+    pub pixel_count: usize,
+    /// The reference in faded grayscale, with mismatched pixels in red.
+    pub diff: RgbaImage,
+}
+
+impl Comparison {
+    #[inline]
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        self.mismatched_pixel_count as f32 <=
+            self.pixel_count as f32 * tolerance.max_mismatched_fraction
+    }
+}
+
+#[derive(Debug)]
+pub enum ReftestFailure {
+    MissingReference { path: PathBuf, reference_path: PathBuf },
+    SizeMismatch { path: PathBuf, actual: (u32, u32), expected: (u32, u32) },
+    PixelMismatch { path: PathBuf, mismatched_pixel_count: usize, pixel_count: usize },
+}
+
+impl Display for ReftestFailure {
+    fn fmt(&self, formatter: &mut Formatter) -> FormatResult {
+        match *self {
+            ReftestFailure::MissingReference { ref path, ref reference_path } => {
+                write!(formatter,
+                       "{}: no reference image at {} (set {} to create one)",
+                       path.display(),
+                       reference_path.display(),
+                       UPDATE_ENV_VAR)
+            }
+            ReftestFailure::SizeMismatch { ref path, actual, expected } => {
+                write!(formatter,
+                       "{}: rendered at {}x{} but the reference is {}x{}",
+                       path.display(),
+                       actual.0,
+                       actual.1,
+                       expected.0,
+                       expected.1)
+            }
+            ReftestFailure::PixelMismatch { ref path, mismatched_pixel_count, pixel_count } => {
+                write!(formatter,
+                       "{}: {} of {} pixels differ from the reference",
+                       path.display(),
+                       mismatched_pixel_count,
+                       pixel_count)
+            }
+        }
+    }
+}
+
+/// Returns the files of the given kind in `resources/`, sorted by name.
+pub fn corpus(kind: ReftestKind) -> Vec<PathBuf> {
+    let directory = resources_dir().join(kind.directory_name());
+    let mut paths: Vec<PathBuf> = fs::read_dir(&directory).unwrap().map(|entry| {
+        entry.unwrap().path()
+    }).filter(|path| {
+        path.extension().and_then(|extension| extension.to_str()) ==
+            Some(kind.directory_name())
+    }).collect();
+    paths.sort();
+    paths
+}
+
+/// Renders the file at `path` and compares it against its reference image.
+///
+/// A missing reference is a failure. If `PATHFINDER_UPDATE_REFTESTS` is set, the rendering becomes
+/// the new reference instead.
+pub fn run_reftest(kind: ReftestKind, path: &Path, tolerance: &Tolerance)
+                   -> Result<(), ReftestFailure> {
+    let actual = match kind {
+        ReftestKind::SVG => render_svg(path),
+        ReftestKind::SWF => render_swf(path),
+    };
+
+    let file_name = format!("{}.png", path.file_stem().unwrap().to_str().unwrap());
+    let reference_path = resources_dir().join("reftests")
+                                        .join(kind.directory_name())
+                                        .join(&file_name);
+
+    if env::var_os(UPDATE_ENV_VAR).is_some() {
+        fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+        actual.save(&reference_path).unwrap();
+        return Ok(());
+    }
+
+    let expected = match image::open(&reference_path) {
+        Ok(expected) => expected.to_rgba(),
+        Err(_) => {
+            return Err(ReftestFailure::MissingReference { path: path.to_owned(), reference_path });
+        }
+    };
+
+    if actual.dimensions() != expected.dimensions() {
+        return Err(ReftestFailure::SizeMismatch {
+            path: path.to_owned(),
+            actual: actual.dimensions(),
+            expected: expected.dimensions(),
+        });
+    }
+
+    let comparison = compare_images(&actual, &expected, tolerance.threshold);
+    if comparison.passes(tolerance) {
+        return Ok(());
+    }
+
+    let output_dir = output_dir().join(kind.directory_name());
+    fs::create_dir_all(&output_dir).unwrap();
+    let stem = path.file_stem().unwrap().to_str().unwrap();
+    actual.save(output_dir.join(&file_name)).unwrap();
+    comparison.diff.save(output_dir.join(format!("{}-diff.png", stem))).unwrap();
+
+    Err(ReftestFailure::PixelMismatch {
+        path: path.to_owned(),
+        mismatched_pixel_count: comparison.mismatched_pixel_count,
+        pixel_count: comparison.pixel_count,
+    })
+}
+
+/// Builds an SVG file with `BuiltSVG` and renders it.
+pub fn render_svg(path: &Path) -> RgbaImage {
+    let data = fs::read(path).unwrap();
+    let tree = Tree::from_data(&data, &UsvgOptions::default()).unwrap();
+    let built_svg = BuiltSVG::from_tree(&tree);
+    render_scene(&built_svg.scene, None)
+}
+
+/// Builds the first frame of a SWF file with `pathfinder_swf` and renders it over the stage
+/// background color.
+pub fn render_swf(path: &Path) -> RgbaImage {
+    let data = fs::read(path).unwrap();
+    let (_, movie) = swf_parser::streaming::movie::parse_movie(&data[..]).unwrap();
+    let BuiltSWF { library, stage, .. } = process_swf_tags(&movie).unwrap();
+
+    let mut scene = Scene::new();
+    scene.set_view_box(RectF::new(Vector2F::default(),
+                                  Vector2F::new(stage.width() as f32, stage.height() as f32)));
+    draw_frame_into_scene(&library, &stage, 0, &mut scene);
+    render_scene(&scene, Some(stage.background_color().to_u8()))
+}
+
+/// Compares two images of the same size pixel by pixel.
+///
+/// Pixels are compared in YIQ space after blending them over white, following the metric from
+/// Kotsarenko and Ramos, "Measuring perceived color difference using YIQ NTSC transmission color
+/// space in mobile applications".
+pub fn compare_images(actual: &RgbaImage, expected: &RgbaImage, threshold: f32) -> Comparison {
+    debug_assert_eq!(actual.dimensions(), expected.dimensions());
+
+    let max_delta = MAX_YIQ_DELTA * threshold * threshold;
+    let mut diff = RgbaImage::new(expected.width(), expected.height());
+    let mut mismatched_pixel_count = 0;
+
+    for ((actual_pixel, expected_pixel), diff_pixel) in actual.pixels()
+                                                              .zip(expected.pixels())
+                                                              .zip(diff.pixels_mut()) {
+        if color_delta(*actual_pixel, *expected_pixel) > max_delta {
+            mismatched_pixel_count += 1;
+            *diff_pixel = Rgba([255, 0, 0, 255]);
+        } else {
+            let (y, _, _) = rgb_to_yiq(blend_with_white(*expected_pixel));
+            let faded = (255.0 - (255.0 - y) * 0.1) as u8;
+            *diff_pixel = Rgba([faded, faded, faded, 255]);
+        }
+    }
+
+    Comparison {
+        mismatched_pixel_count,
+        pixel_count: (expected.width() * expected.height()) as usize,
+        diff,
+    }
+}
+
+fn render_scene(scene: &Scene, background_color: Option<ColorU>) -> RgbaImage {
+    // Render at the native size of the view box, so that render targets such as opacity and mask
+    // layers line up with the scene. All the view boxes in `resources/` start at the origin.
+    let size = scene.view_box().size().ceil().to_i32();
+    let image = scene.render_to_image(BuildOptions::default(), size);
+
+    let mut buffer = RgbaImage::new(size.x() as u32, size.y() as u32);
+    for (pixel, color) in buffer.pixels_mut().zip(image.pixels()) {
+        *pixel = match background_color {
+            None => Rgba([color.r, color.g, color.b, color.a]),
+            Some(background_color) => Rgba(blend_over(*color, background_color)),
+        };
+    }
+    buffer
+}
+
+fn blend_over(color: ColorU, background_color: ColorU) -> [u8; 4] {
+    let alpha = color.a as f32 / 255.0;
+    let blend = |src: u8, dest: u8| {
+        (src as f32 * alpha + dest as f32 * (1.0 - alpha)).round() as u8
+    };
+    [
+        blend(color.r, background_color.r),
+        blend(color.g, background_color.g),
+        blend(color.b, background_color.b),
+        blend(255, background_color.a),
+    ]
+}
+
+fn color_delta(a: Rgba<u8>, b: Rgba<u8>) -> f32 {
+    if a == b {
+        return 0.0;
+    }
+    let (a_y, a_i, a_q) = rgb_to_yiq(blend_with_white(a));
+    let (b_y, b_i, b_q) = rgb_to_yiq(blend_with_white(b));
+    let (y, i, q) = (a_y - b_y, a_i - b_i, a_q - b_q);
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+fn blend_with_white(pixel: Rgba<u8>) -> [f32; 3] {
+    let alpha = pixel[3] as f32 / 255.0;
+    let blend = |channel: u8| 255.0 + (channel as f32 - 255.0) * alpha;
+    [blend(pixel[0]), blend(pixel[1]), blend(pixel[2])]
+}
+
+fn rgb_to_yiq(rgb: [f32; 3]) -> (f32, f32, f32) {
+    let [r, g, b] = rgb;
+    (r * 0.2988953 + g * 0.5866225 + b * 0.1144822,
+     r * 0.5959780 - g * 0.2741761 - b * 0.3218019,
+     r * 0.2114702 - g * 0.5226171 + b * 0.3111469)
+}
+
+fn resources_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../resources")
+}
+
+fn output_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../target/reftests")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ReftestKind, Tolerance, corpus, run_reftest};
+
+    fn run_corpus(kind: ReftestKind) {
+        let tolerance = Tolerance::default();
+        let failures: Vec<String> = corpus(kind).iter().filter_map(|path| {
+            run_reftest(kind, path, &tolerance).err()
+        }).map(|failure| failure.to_string()).collect();
+        assert!(failures.is_empty(), "reftests failed:\n{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_svg_reftests() {
+        run_corpus(ReftestKind::SVG);
+    }
+
+    #[test]
+    fn test_swf_reftests() {
+        run_corpus(ReftestKind::SWF);
+    }
+}